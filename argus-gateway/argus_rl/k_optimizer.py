@@ -165,7 +165,7 @@ class KOptimizer:
         request = json.dumps(
             {
                 "jsonrpc": "2.0",
-                "method": "get_health",
+                "method": "get_network_metrics",
                 "id": 1,
             }
         )
@@ -182,10 +182,9 @@ class KOptimizer:
 
             return NetworkMetrics(
                 current_k=result.get("current_k", 3),
-                orphan_rate=result.get("red_count", 0)
-                / max(result.get("total_blocks", 1), 1),
-                tip_regression_velocity=0.0,  # Computed from tip count delta
-                network_latency=50.0,  # Estimated from recent block timestamps
+                orphan_rate=result.get("red_rate", 0.0),
+                tip_regression_velocity=result.get("tip_velocity", 0.0),
+                network_latency=result.get("estimated_propagation_delay_ms", 0.0),
             )
         except (ConnectionRefusedError, socket.timeout, json.JSONDecodeError) as e:
             print(f"[KOptimizer] RPC error: {e}")
@@ -197,7 +196,12 @@ class KOptimizer:
             {
                 "jsonrpc": "2.0",
                 "method": "update_k",
-                "params": {"new_k": new_k},
+                "params": {
+                    "new_k": new_k,
+                    "source": "rl",
+                    "confidence": self.confidence,
+                    "reason": f"PPO action from k={self.current_k}",
+                },
                 "id": 2,
             }
         )
@@ -2,11 +2,20 @@
 ///
 /// Defines the command and event enums sent between the orchestrator
 /// and the `GhostDagAgent` via `tokio::sync::mpsc` channels.
-use argus_ghostdag::BlockHash;
+///
+/// Most commands are fire-and-forget.  Query commands carry a
+/// `tokio::sync::oneshot` reply channel; they are process-local and
+/// therefore skipped by serde.  `AgentHandle` wraps them in async methods.
+use argus_ghostdag::{BlockHash, BlockHeader};
 use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::governance::KChangeSource;
+use crate::healing::ChainDiff;
+use crate::transitions::{TransitionReason, TransitionRecord};
 
 /// Commands sent *to* the GhostDagAgent.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum AgentCommand {
     /// Check whether the local selected-parent chain diverges
     /// from the network's blue set.
@@ -21,6 +30,15 @@ pub enum AgentCommand {
         lca: BlockHash,
         /// Block hashes that exist on the network chain but are missing locally.
         missing_blocks: Vec<BlockHash>,
+        /// The `RecoveryLoop` session and attempt this belongs to, if any.
+        #[serde(default)]
+        session: Option<RecoveryAttempt>,
+    },
+
+    /// Blocks received from peers, in any order.  Blocks with missing
+    /// parents wait in the orphan pool until their parents arrive.
+    SubmitBlocks {
+        blocks: Vec<BlockHeader>,
     },
 
     /// Inform the agent of an updated network tip.
@@ -28,13 +46,99 @@ pub enum AgentCommand {
         tip: BlockHash,
     },
 
+    /// Run a headers-first initial block download from the agent's
+    /// `BlockFetcher`.
+    StartIbd {
+        /// Headers per batch (default: `ibd::DEFAULT_IBD_BATCH_SIZE`).
+        #[serde(default)]
+        batch_size: Option<usize>,
+    },
+
     /// Update the k parameter (from the RL optimizer).
+    ///
+    /// The change applies from `activation_blue_score` on (default: one
+    /// above the local tip), so already-colored blocks keep their colors.
+    /// If the agent has a `KGovernor`, the change must pass its policy.
     UpdateK {
         new_k: u64,
+        #[serde(default)]
+        activation_blue_score: Option<u64>,
+        #[serde(default)]
+        source: KChangeSource,
+        #[serde(default)]
+        reason: String,
+        #[serde(default)]
+        confidence: Option<f64>,
     },
 
     /// Request a graceful shutdown.
     Shutdown,
+
+    /// Query: the current state label.
+    #[serde(skip)]
+    GetState {
+        reply: oneshot::Sender<AgentStateLabel>,
+    },
+
+    /// Query: the local tip.
+    #[serde(skip)]
+    GetLocalTip {
+        reply: oneshot::Sender<BlockHash>,
+    },
+
+    /// Query: the most recent network tip, if any.
+    #[serde(skip)]
+    GetNetworkTip {
+        reply: oneshot::Sender<Option<BlockHash>>,
+    },
+
+    /// Query: the k in force at the local tip.
+    #[serde(skip)]
+    GetK {
+        reply: oneshot::Sender<u64>,
+    },
+
+    /// Query: the most recent `limit` state transitions (all if `None`).
+    #[serde(skip)]
+    GetHistory {
+        limit: Option<usize>,
+        reply: oneshot::Sender<Vec<TransitionRecord>>,
+    },
+
+    /// Query: a consistent snapshot of the agent's status.
+    #[serde(skip)]
+    GetStatus {
+        reply: oneshot::Sender<AgentStatus>,
+    },
+
+    /// Like `CheckDivergence`, but replies with the status after the check.
+    #[serde(skip)]
+    QueryDivergence {
+        network_tip: BlockHash,
+        reply: oneshot::Sender<AgentStatus>,
+    },
+}
+
+/// A point-in-time view of the agent, returned by status queries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentStatus {
+    pub state: AgentStateLabel,
+    pub local_tip: BlockHash,
+    pub network_tip: Option<BlockHash>,
+    /// The k in force at the local tip's blue score.
+    pub k: u64,
+    /// The latest scheduled k; differs from `k` while a change is pending.
+    pub scheduled_k: u64,
+    /// Blocks waiting in the orphan pool.
+    pub orphans: usize,
+}
+
+/// Identifies one attempt of a `RecoveryLoop` session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryAttempt {
+    pub session_id: u64,
+    /// 1-based attempt number within the session.
+    pub attempt: u32,
 }
 
 /// Events emitted *by* the GhostDagAgent.
@@ -44,6 +148,7 @@ pub enum AgentEvent {
     StateChanged {
         from: AgentStateLabel,
         to: AgentStateLabel,
+        reason: TransitionReason,
     },
 
     /// Recovery completed — the agent is back in sync.
@@ -51,6 +156,70 @@ pub enum AgentEvent {
         blocks_recovered: u64,
     },
 
+    /// A recovery attempt fetched and ingested blocks.
+    RecoveryProgress {
+        session: Option<RecoveryAttempt>,
+        /// Blocks the attempt set out to recover.
+        missing: u64,
+        /// Headers received from the fetcher.
+        fetched: u64,
+        /// Blocks added to the DAG.
+        ingested: u64,
+    },
+
+    /// A recovery session gave up after `attempts` attempts.
+    RecoveryFailed {
+        session_id: u64,
+        attempts: u32,
+        reason: String,
+    },
+
+    /// Both sides of a partition were merged into one DAG and recolored.
+    /// `*_red` count each side's blocks that are red after the merge; the
+    /// total ordering may have changed between the two affected scores.
+    PartitionHealed {
+        new_tip: BlockHash,
+        local_blocks: u64,
+        local_red: u64,
+        network_blocks: u64,
+        network_red: u64,
+        chain_diff: ChainDiff,
+        affected_from_score: u64,
+        affected_to_score: u64,
+    },
+
+    /// A k change was accepted into the agent's schedule.
+    KScheduled {
+        new_k: u64,
+        activation_blue_score: u64,
+    },
+
+    /// An initial-block-download batch was validated and ingested.
+    IbdProgress {
+        batch: u64,
+        headers_received: u64,
+        blocks_ingested: u64,
+        local_blue_score: u64,
+    },
+
+    /// Initial block download finished.
+    IbdComplete {
+        blocks_ingested: u64,
+    },
+
+    /// Submitted blocks were added to the DAG; `local_tip` is the tip
+    /// after ingestion.
+    BlocksIngested {
+        ingested: u64,
+        local_tip: BlockHash,
+    },
+
+    /// Orphan blocks are waiting on these ancestors; they should be
+    /// requested from peers.
+    RequestBlocks {
+        hashes: Vec<BlockHash>,
+    },
+
     /// The agent detected divergence.
     DivergenceDetected {
         local_tip: BlockHash,
@@ -58,6 +227,20 @@ pub enum AgentEvent {
         divergence_depth: u64,
     },
 
+    /// Peers' tips are further apart than `TipAggregatorConfig::max_score_spread`;
+    /// `outliers` were left out of the network tip choice.
+    PeerTipDisagreement {
+        median_blue_score: u64,
+        outliers: Vec<String>,
+    },
+
+    /// Every peer reporting tips shares one source: the node may be
+    /// eclipsed.
+    EclipseSuspected {
+        source: String,
+        peers: usize,
+    },
+
     /// An error occurred inside the agent.
     Error {
         message: String,
@@ -0,0 +1,191 @@
+/// Argus Agent — Injectable time source.
+///
+/// Everything that reads the time or sleeps — the agent's transition and
+/// recorder timestamps and orphan ages, the k governor's step window, the
+/// recovery loop's ticks, backoff and session deadlines, the k tuner and
+/// tip aggregator polls, and the linearizer server — goes through a
+/// `Clock`:
+///
+/// - `SystemClock` uses the OS clock and tokio timers.
+/// - `ManualClock` only moves when `advance` is called, so tests and
+///   simulations can step through timeouts deterministically.
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use tokio::sync::watch;
+
+/// A clock shared by several components.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// A source of monotonic time, wall-clock time and sleeps.
+#[async_trait]
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Monotonic time, for intervals and deadlines.
+    fn now(&self) -> Instant;
+
+    /// Milliseconds since the Unix epoch, for timestamps.
+    fn unix_millis(&self) -> u64;
+
+    /// Wait until `duration` has passed on this clock.
+    async fn sleep(&self, duration: Duration);
+}
+
+/// The default clock shared by components that were not given one.
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Run `future` until it completes or `duration` passes on `clock`.
+/// Returns `None` on timeout.
+pub async fn timeout<F: Future>(
+    clock: &dyn Clock,
+    duration: Duration,
+    future: F,
+) -> Option<F::Output> {
+    tokio::select! {
+        output = future => Some(output),
+        _ = clock.sleep(duration) => None,
+    }
+}
+
+/// The OS clock and tokio timers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_millis(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Sleepers wake as soon as `advance` (or `set_elapsed`) carries the clock
+/// past their deadline; a zero-length sleep returns immediately.
+#[derive(Debug)]
+pub struct ManualClock {
+    origin: Instant,
+    start_unix_millis: u64,
+    /// Time elapsed since `origin`.
+    elapsed: watch::Sender<Duration>,
+}
+
+impl ManualClock {
+    /// A clock whose wall time starts at `start_unix_millis`.
+    pub fn new(start_unix_millis: u64) -> Self {
+        let (elapsed, _) = watch::channel(Duration::ZERO);
+        Self {
+            origin: Instant::now(),
+            start_unix_millis,
+            elapsed,
+        }
+    }
+
+    /// Wrap the clock for sharing; keep a clone of the `Arc` to advance it.
+    pub fn shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    /// Time elapsed since the clock was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.borrow()
+    }
+
+    /// Move the clock forward by `duration`, waking due sleepers.
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.send_modify(|elapsed| *elapsed += duration);
+    }
+
+    /// Move the clock to `elapsed` since creation.  Never moves backward.
+    pub fn set_elapsed(&self, elapsed: Duration) {
+        self.elapsed.send_modify(|current| *current = (*current).max(elapsed));
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.origin + self.elapsed()
+    }
+
+    fn unix_millis(&self) -> u64 {
+        self.start_unix_millis + self.elapsed().as_millis() as u64
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        let mut rx = self.elapsed.subscribe();
+        let deadline = *rx.borrow_and_update() + duration;
+        // `changed` only fails once the clock itself is gone.
+        while *rx.borrow_and_update() < deadline {
+            if rx.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_manual_clock_wakes_sleepers_on_advance() {
+        let clock = ManualClock::new(1_000).shared();
+        let start = clock.now();
+
+        let sleeper = {
+            let clock = clock.clone();
+            tokio::spawn(async move { clock.sleep(Duration::from_secs(10)).await })
+        };
+        tokio::task::yield_now().await;
+        clock.advance(Duration::from_secs(4));
+        tokio::task::yield_now().await;
+        assert!(!sleeper.is_finished());
+
+        clock.advance(Duration::from_secs(6));
+        sleeper.await.unwrap();
+        assert_eq!(clock.now() - start, Duration::from_secs(10));
+        assert_eq!(clock.unix_millis(), 11_000);
+
+        // Going backward is ignored; zero-length sleeps do not block.
+        clock.set_elapsed(Duration::from_secs(1));
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+        clock.sleep(Duration::ZERO).await;
+    }
+
+    #[tokio::test]
+    async fn test_timeout_follows_clock() {
+        let clock = ManualClock::default().shared();
+        assert_eq!(timeout(clock.as_ref(), Duration::from_secs(1), async { 7 }).await, Some(7));
+
+        let pending = {
+            let clock = clock.clone();
+            let never = std::future::pending::<()>();
+            tokio::spawn(async move { timeout(&*clock, Duration::from_secs(1), never).await })
+        };
+        tokio::task::yield_now().await;
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(pending.await.unwrap(), None);
+    }
+}
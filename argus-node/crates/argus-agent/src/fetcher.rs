@@ -0,0 +1,445 @@
+/// Argus Agent — Block fetching for recovery.
+///
+/// Recovery knows *which* blocks are missing (from the LCA computation)
+/// but needs a way to obtain them.  `BlockFetcher` abstracts that source:
+///
+/// - **`JsonRpcBlockFetcher`** — asks a peer's Argus JSON-RPC endpoint via
+///   the `get_headers` method.
+/// - **`MockBlockFetcher`** — serves headers from memory (tests, simulations).
+///
+/// `fetch_with_ancestors` chases missing parents of fetched blocks so the
+/// result can be inserted into the local DAG.
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+
+use argus_ghostdag::block::{BlockHash, BlockHeader};
+use argus_ghostdag::dag::DagStore;
+
+use crate::ibd;
+
+/// Errors from fetching blocks.
+#[derive(Debug, Error, Clone)]
+pub enum FetchError {
+    #[error("transport error: {0}")]
+    Transport(String),
+
+    #[error("peer returned JSON-RPC error {code}: {message}")]
+    Rpc { code: i64, message: String },
+
+    #[error("malformed response: {0}")]
+    Decode(String),
+
+    #[error("fetch timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+/// The consensus-relevant part of a header, as exchanged between nodes.
+/// Blue score, blue work and the selected parent are recomputed locally.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderRecord {
+    /// Block hash (hex-encoded).
+    pub hash: String,
+    /// Parent hashes (hex-encoded).
+    pub parents: Vec<String>,
+    /// Timestamp in milliseconds since epoch.
+    pub timestamp: u64,
+}
+
+impl HeaderRecord {
+    /// Wire form of a local header.
+    pub fn from_header(header: &BlockHeader) -> Self {
+        Self {
+            hash: header.hash.to_hex(),
+            parents: header.parents.iter().map(|p| p.to_hex()).collect(),
+            timestamp: header.timestamp,
+        }
+    }
+
+    /// Decode into an uncolored `BlockHeader`.
+    pub fn into_header(self) -> Result<BlockHeader, FetchError> {
+        let decode = |s: &str| BlockHash::from_hex(s).map_err(|e| FetchError::Decode(e.to_string()));
+        let hash = decode(&self.hash)?;
+        let parents = self
+            .parents
+            .iter()
+            .map(|p| decode(p))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(if parents.is_empty() {
+            BlockHeader::genesis(hash, self.timestamp)
+        } else {
+            BlockHeader::new(hash, parents, self.timestamp)
+        })
+    }
+}
+
+/// A source of block headers, looked up by hash.
+#[async_trait]
+pub trait BlockFetcher: Send + Sync {
+    /// Fetch the headers for `hashes`.  Unknown hashes are omitted from
+    /// the result rather than treated as errors.
+    async fn fetch_headers(&self, hashes: &[BlockHash]) -> Result<Vec<BlockHeader>, FetchError>;
+
+    /// Initial block download: up to `max` headers the requester lacks,
+    /// in sync order, resuming after `cursor` (see `ibd::headers_after`).
+    async fn fetch_headers_after(
+        &self,
+        locator: &[BlockHash],
+        cursor: Option<BlockHash>,
+        max: usize,
+    ) -> Result<Vec<BlockHeader>, FetchError>;
+}
+
+/// Fetch `hashes` plus any of their ancestors missing from `dag`, stopping
+/// after `limit` headers.  Returned headers are in no particular order.
+///
+/// The DAG lock is only held between requests, never across a fetch.
+pub async fn fetch_with_ancestors(
+    fetcher: &dyn BlockFetcher,
+    dag: &RwLock<DagStore>,
+    hashes: &[BlockHash],
+    limit: usize,
+) -> Result<Vec<BlockHeader>, FetchError> {
+    let mut fetched: HashMap<BlockHash, BlockHeader> = HashMap::new();
+    let mut requested: HashSet<BlockHash> = HashSet::new();
+    let mut wanted: Vec<BlockHash> = {
+        let dag = dag.read().await;
+        hashes.iter().filter(|h| !dag.contains(h)).copied().collect()
+    };
+
+    while !wanted.is_empty() && fetched.len() < limit {
+        wanted.truncate(limit - fetched.len());
+        requested.extend(wanted.iter().copied());
+        let headers = fetcher.fetch_headers(&wanted).await?;
+        if headers.is_empty() {
+            break;
+        }
+
+        let dag = dag.read().await;
+        let mut next = Vec::new();
+        for header in headers {
+            for p in &header.parents {
+                if !dag.contains(p) && !fetched.contains_key(p) && !requested.contains(p) {
+                    next.push(*p);
+                }
+            }
+            fetched.insert(header.hash, header);
+        }
+        next.sort();
+        next.dedup();
+        wanted = next;
+    }
+
+    Ok(fetched.into_values().collect())
+}
+
+// ---------------------------------------------------------------------------
+// JSON-RPC implementation
+// ---------------------------------------------------------------------------
+
+/// Fetches headers from a peer's Argus JSON-RPC endpoint (`get_headers`).
+#[derive(Debug, Clone)]
+pub struct JsonRpcBlockFetcher {
+    addr: SocketAddr,
+    timeout: Duration,
+    batch_size: usize,
+}
+
+impl JsonRpcBlockFetcher {
+    /// Hashes per request; keeps requests within the server's read buffer.
+    pub const DEFAULT_BATCH_SIZE: usize = 32;
+
+    /// Create a fetcher for the peer at `addr` with a 5s timeout.
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            timeout: Duration::from_secs(5),
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Set the per-request timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send one JSON-RPC call and decode a list of `HeaderRecord`s.
+    async fn call(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<Vec<BlockHeader>, FetchError> {
+        let result = rpc_call(self.addr, self.timeout, method, params).await?;
+        let records: Vec<HeaderRecord> =
+            serde_json::from_value(result).map_err(|e| FetchError::Decode(e.to_string()))?;
+        records.into_iter().map(HeaderRecord::into_header).collect()
+    }
+}
+
+/// Send one JSON-RPC call to the Argus node at `addr` and return its
+/// `result`.
+pub(crate) async fn rpc_call(
+    addr: SocketAddr,
+    timeout: Duration,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, FetchError> {
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+        "id": 1,
+    });
+
+    let exchange = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(request.to_string().as_bytes()).await?;
+        // Half-close so the server sees where the request ends.
+        stream.shutdown().await?;
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await?;
+        Ok::<_, std::io::Error>(buf)
+    };
+    let buf = tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| FetchError::Timeout(timeout))?
+        .map_err(|e| FetchError::Transport(e.to_string()))?;
+
+    let response: serde_json::Value =
+        serde_json::from_slice(&buf).map_err(|e| FetchError::Decode(e.to_string()))?;
+    if let Some(err) = response.get("error").filter(|e| !e.is_null()) {
+        return Err(FetchError::Rpc {
+            code: err.get("code").and_then(|c| c.as_i64()).unwrap_or(0),
+            message: err
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        });
+    }
+    Ok(response.get("result").cloned().unwrap_or_default())
+}
+
+#[async_trait]
+impl BlockFetcher for JsonRpcBlockFetcher {
+    async fn fetch_headers(&self, hashes: &[BlockHash]) -> Result<Vec<BlockHeader>, FetchError> {
+        let mut out = Vec::with_capacity(hashes.len());
+        for chunk in hashes.chunks(self.batch_size.max(1)) {
+            let params = serde_json::json!({
+                "hashes": chunk.iter().map(|h| h.to_hex()).collect::<Vec<_>>(),
+            });
+            out.extend(self.call("get_headers", params).await?);
+        }
+        Ok(out)
+    }
+
+    async fn fetch_headers_after(
+        &self,
+        locator: &[BlockHash],
+        cursor: Option<BlockHash>,
+        max: usize,
+    ) -> Result<Vec<BlockHeader>, FetchError> {
+        let params = serde_json::json!({
+            "locator": locator.iter().map(|h| h.to_hex()).collect::<Vec<_>>(),
+            "cursor": cursor.map(|h| h.to_hex()),
+            "max": max,
+        });
+        self.call("get_headers_after", params).await
+    }
+}
+
+// ---------------------------------------------------------------------------
+// In-memory mock
+// ---------------------------------------------------------------------------
+
+/// Serves headers from an in-memory map and counts requests.
+#[derive(Debug, Default)]
+pub struct MockBlockFetcher {
+    headers: Mutex<HashMap<BlockHash, BlockHeader>>,
+    requests: AtomicU64,
+    max_batch: Option<usize>,
+}
+
+impl MockBlockFetcher {
+    /// An empty mock.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A mock serving every block of `dag`.
+    pub fn from_dag(dag: &DagStore) -> Self {
+        let mock = Self::new();
+        for h in dag.headers() {
+            mock.insert(h.clone());
+        }
+        mock
+    }
+
+    /// Serve at most `max` headers per `fetch_headers_after`, however many
+    /// are requested (as the RPC server clamps to `MAX_GET_HEADERS`).
+    pub fn with_max_batch(mut self, max: usize) -> Self {
+        self.max_batch = Some(max);
+        self
+    }
+
+    /// Make a header available.
+    pub fn insert(&self, header: BlockHeader) {
+        self.headers.lock().unwrap().insert(header.hash, header);
+    }
+
+    /// Assemble the served headers into a `DagStore` (headers whose
+    /// ancestry is incomplete are left out).
+    fn to_dag(&self) -> DagStore {
+        let headers = self.headers.lock().unwrap();
+        let mut dag = DagStore::new();
+        let mut pending: Vec<&BlockHeader> = headers.values().collect();
+        loop {
+            let before = pending.len();
+            pending.retain(|h| {
+                let result = if h.parents.is_empty() {
+                    dag.add_genesis(BlockHeader::genesis(h.hash, h.timestamp))
+                } else if h.parents.iter().all(|p| dag.contains(p)) {
+                    dag.add_block(BlockHeader::new(h.hash, h.parents.clone(), h.timestamp))
+                } else {
+                    return true;
+                };
+                result.is_err()
+            });
+            if pending.is_empty() || pending.len() == before {
+                break;
+            }
+        }
+        dag
+    }
+
+    /// Number of requests served so far.
+    pub fn request_count(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl BlockFetcher for MockBlockFetcher {
+    async fn fetch_headers(&self, hashes: &[BlockHash]) -> Result<Vec<BlockHeader>, FetchError> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let headers = self.headers.lock().unwrap();
+        Ok(hashes
+            .iter()
+            .filter_map(|h| headers.get(h))
+            .map(|h| BlockHeader::new(h.hash, h.parents.clone(), h.timestamp))
+            .collect())
+    }
+
+    async fn fetch_headers_after(
+        &self,
+        locator: &[BlockHash],
+        cursor: Option<BlockHash>,
+        max: usize,
+    ) -> Result<Vec<BlockHeader>, FetchError> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let dag = self.to_dag();
+        let max = self.max_batch.map_or(max, |limit| max.min(limit));
+        ibd::headers_after(&dag, locator, cursor.as_ref(), max)
+            .map_err(|e| FetchError::Rpc {
+                code: -32603,
+                message: e.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Remote chain G → A → B → C; local DAG only has G.
+    fn remote_and_local() -> (DagStore, DagStore) {
+        let g = BlockHash::from_byte(0);
+        let mut remote = DagStore::new();
+        remote.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        for i in 1u8..=3 {
+            remote
+                .add_block(BlockHeader::new(
+                    BlockHash::from_byte(i),
+                    vec![BlockHash::from_byte(i - 1)],
+                    i as u64,
+                ))
+                .unwrap();
+        }
+        let mut local = DagStore::new();
+        local.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        (remote, local)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_with_ancestors_chases_parents() {
+        let (remote, local) = remote_and_local();
+        let mock = MockBlockFetcher::from_dag(&remote);
+        let local = RwLock::new(local);
+
+        let headers = fetch_with_ancestors(&mock, &local, &[BlockHash::from_byte(3)], 100)
+            .await
+            .unwrap();
+        let mut got: Vec<BlockHash> = headers.iter().map(|h| h.hash).collect();
+        got.sort();
+        assert_eq!(
+            got,
+            vec![BlockHash::from_byte(1), BlockHash::from_byte(2), BlockHash::from_byte(3)]
+        );
+        assert_eq!(mock.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_with_ancestors_respects_limit() {
+        let (remote, local) = remote_and_local();
+        let mock = MockBlockFetcher::from_dag(&remote);
+        let local = RwLock::new(local);
+        let headers = fetch_with_ancestors(&mock, &local, &[BlockHash::from_byte(3)], 2)
+            .await
+            .unwrap();
+        assert_eq!(headers.len(), 2);
+    }
+
+    #[test]
+    fn test_header_record_roundtrip() {
+        let h = BlockHeader::new(BlockHash::from_byte(2), vec![BlockHash::from_byte(1)], 7);
+        let decoded = HeaderRecord::from_header(&h).into_header().unwrap();
+        assert_eq!(decoded.hash, h.hash);
+        assert_eq!(decoded.parents, h.parents);
+        assert_eq!(decoded.timestamp, 7);
+    }
+
+    #[tokio::test]
+    async fn test_json_rpc_fetcher() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let served = HeaderRecord::from_header(&BlockHeader::new(
+            BlockHash::from_byte(1),
+            vec![BlockHash::from_byte(0)],
+            1,
+        ));
+        let body = serde_json::json!({"jsonrpc": "2.0", "result": [served], "id": 1}).to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(String::from_utf8_lossy(&buf[..n]).contains("get_headers"));
+            stream.write_all(body.as_bytes()).await.unwrap();
+        });
+
+        let fetcher = JsonRpcBlockFetcher::new(addr).with_timeout(Duration::from_secs(2));
+        let headers = fetcher.fetch_headers(&[BlockHash::from_byte(1)]).await.unwrap();
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].parents, vec![BlockHash::from_byte(0)]);
+    }
+}
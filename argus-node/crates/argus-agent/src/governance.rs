@@ -0,0 +1,450 @@
+/// Argus Agent — k governance.
+///
+/// k decides how much of the DAG is blue, so a bad value is a consensus
+/// problem, not a tuning one.  Every k change — from JSON-RPC, the RL
+/// optimizer or the native `KTuner` — is checked against a `KPolicy`:
+///
+/// - the new k must lie in `min_k..=max_k`,
+/// - the total |Δk| within any `step_interval` may not exceed `max_step`,
+/// - proposals that carry a confidence must reach `min_confidence`.
+///
+/// Accepted changes go into a bounded audit log with their source, reason
+/// and timestamp.  `revert_proposal` rolls back the most recent change that
+/// has not been reverted yet; reverts only have to respect the range, so a
+/// bad change can always be undone.
+///
+/// The server and the agent share one `KGovernor` (`SharedKGovernor`), so
+/// a change is checked and recorded once, by whichever side sees it first.
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+use crate::clock::{system_clock, SharedClock};
+
+/// Default number of k changes kept in the audit log.
+pub const DEFAULT_AUDIT_CAPACITY: usize = 256;
+
+/// A `KGovernor` shared between the server and the agent.
+pub type SharedKGovernor = Arc<RwLock<KGovernor>>;
+
+/// Who asked for a k change.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KChangeSource {
+    /// An operator or client over JSON-RPC.
+    #[default]
+    Rpc,
+    /// The RL optimizer.
+    Rl,
+    /// The agent's native `KTuner`.
+    Tuner,
+    /// A `revert_k` rollback.
+    Revert,
+}
+
+impl fmt::Display for KChangeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            KChangeSource::Rpc => "rpc",
+            KChangeSource::Rl => "rl",
+            KChangeSource::Tuner => "tuner",
+            KChangeSource::Revert => "revert",
+        };
+        f.write_str(s)
+    }
+}
+
+impl std::str::FromStr for KChangeSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rpc" => Ok(KChangeSource::Rpc),
+            "rl" => Ok(KChangeSource::Rl),
+            "tuner" => Ok(KChangeSource::Tuner),
+            "revert" => Ok(KChangeSource::Revert),
+            other => Err(format!("unknown k change source: {other}")),
+        }
+    }
+}
+
+/// Limits every k change must respect.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KPolicy {
+    pub min_k: u64,
+    pub max_k: u64,
+    /// Largest total |Δk| within one `step_interval`.
+    pub max_step: u64,
+    pub step_interval: Duration,
+    /// Minimum confidence for proposals that carry one.
+    pub min_confidence: f64,
+}
+
+impl Default for KPolicy {
+    fn default() -> Self {
+        Self {
+            min_k: 1,
+            max_k: 64,
+            max_step: 8,
+            step_interval: Duration::from_secs(60),
+            min_confidence: 0.5,
+        }
+    }
+}
+
+/// A requested k change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KProposal {
+    pub new_k: u64,
+    pub source: KChangeSource,
+    pub reason: String,
+    /// Confidence of the proposer (e.g. `rl_confidence`), if it has one.
+    pub confidence: Option<f64>,
+    /// Sequence number of the change this proposal rolls back.
+    pub reverts: Option<u64>,
+}
+
+impl KProposal {
+    pub fn new(new_k: u64, source: KChangeSource) -> Self {
+        Self {
+            new_k,
+            source,
+            reason: String::new(),
+            confidence: None,
+            reverts: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = reason.into();
+        self
+    }
+
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        self.confidence = Some(confidence);
+        self
+    }
+}
+
+/// Why a k change was refused.
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum KGovernanceError {
+    #[error("k {k} is outside the allowed range {min_k}..={max_k}")]
+    OutOfRange { k: u64, min_k: u64, max_k: u64 },
+
+    #[error(
+        "k step {step} exceeds the limit of {max_step} per {interval_secs}s \
+         ({used} already used)"
+    )]
+    StepTooLarge {
+        step: u64,
+        used: u64,
+        max_step: u64,
+        interval_secs: u64,
+    },
+
+    #[error("confidence {confidence} is below the required {min_confidence}")]
+    LowConfidence { confidence: f64, min_confidence: f64 },
+
+    #[error("no k change left to revert")]
+    NothingToRevert,
+}
+
+/// An accepted k change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KAuditRecord {
+    /// Increasing sequence number, unique per governor.
+    pub seq: u64,
+    pub from_k: u64,
+    pub to_k: u64,
+    pub activation_blue_score: u64,
+    pub source: KChangeSource,
+    pub reason: String,
+    pub confidence: Option<f64>,
+    /// Sequence number of the change this one rolled back.
+    pub reverts: Option<u64>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+}
+
+/// A governor's policy and audit log, e.g. as recorded by `EventRecorder`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KGovernorSnapshot {
+    pub policy: KPolicy,
+    /// Oldest first.
+    pub audit: Vec<KAuditRecord>,
+}
+
+/// Checks k changes against a `KPolicy` and keeps the audit log.
+#[derive(Debug, Clone)]
+pub struct KGovernor {
+    policy: KPolicy,
+    records: VecDeque<KAuditRecord>,
+    capacity: usize,
+    next_seq: u64,
+    /// Timestamps records and measures the step window.
+    clock: SharedClock,
+}
+
+impl KGovernor {
+    /// A governor with an empty audit log of `DEFAULT_AUDIT_CAPACITY`.
+    pub fn new(policy: KPolicy) -> Self {
+        Self {
+            policy,
+            records: VecDeque::new(),
+            capacity: DEFAULT_AUDIT_CAPACITY,
+            next_seq: 1,
+            clock: system_clock(),
+        }
+    }
+
+    /// Read the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Keep at most `capacity` audit records.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// A governor with `snapshot`'s policy and audit log; new records
+    /// continue its sequence numbers.
+    pub fn restore(snapshot: KGovernorSnapshot) -> Self {
+        let mut governor = Self::new(snapshot.policy);
+        governor.capacity = governor.capacity.max(snapshot.audit.len());
+        governor.next_seq = snapshot.audit.last().map_or(1, |r| r.seq + 1);
+        governor.records = snapshot.audit.into();
+        governor
+    }
+
+    /// The policy and the full audit log.
+    pub fn snapshot(&self) -> KGovernorSnapshot {
+        KGovernorSnapshot {
+            policy: self.policy.clone(),
+            audit: self.audit(None),
+        }
+    }
+
+    /// Wrap the governor for sharing between the server and the agent.
+    pub fn shared(self) -> SharedKGovernor {
+        Arc::new(RwLock::new(self))
+    }
+
+    pub fn policy(&self) -> &KPolicy {
+        &self.policy
+    }
+
+    /// Check `proposal` against the policy, given the latest scheduled k.
+    pub fn check(&self, current_k: u64, proposal: &KProposal) -> Result<(), KGovernanceError> {
+        self.check_at(current_k, proposal, self.clock.unix_millis())
+    }
+
+    fn check_at(
+        &self,
+        current_k: u64,
+        proposal: &KProposal,
+        now_ms: u64,
+    ) -> Result<(), KGovernanceError> {
+        let policy = &self.policy;
+        if proposal.new_k < policy.min_k || proposal.new_k > policy.max_k {
+            return Err(KGovernanceError::OutOfRange {
+                k: proposal.new_k,
+                min_k: policy.min_k,
+                max_k: policy.max_k,
+            });
+        }
+        if proposal.source == KChangeSource::Revert {
+            return Ok(());
+        }
+        if let Some(confidence) = proposal.confidence {
+            if confidence < policy.min_confidence {
+                return Err(KGovernanceError::LowConfidence {
+                    confidence,
+                    min_confidence: policy.min_confidence,
+                });
+            }
+        }
+
+        let window_ms = policy.step_interval.as_millis() as u64;
+        let used: u64 = self
+            .records
+            .iter()
+            .filter(|r| r.source != KChangeSource::Revert)
+            .filter(|r| r.timestamp_ms + window_ms > now_ms)
+            .map(|r| r.from_k.abs_diff(r.to_k))
+            .sum();
+        let step = current_k.abs_diff(proposal.new_k);
+        if used + step > policy.max_step {
+            return Err(KGovernanceError::StepTooLarge {
+                step,
+                used,
+                max_step: policy.max_step,
+                interval_secs: policy.step_interval.as_secs(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Record an accepted change from `from_k`.
+    pub fn record(
+        &mut self,
+        from_k: u64,
+        proposal: KProposal,
+        activation_blue_score: u64,
+    ) -> KAuditRecord {
+        let now_ms = self.clock.unix_millis();
+        self.record_at(from_k, proposal, activation_blue_score, now_ms)
+    }
+
+    fn record_at(
+        &mut self,
+        from_k: u64,
+        proposal: KProposal,
+        activation_blue_score: u64,
+        timestamp_ms: u64,
+    ) -> KAuditRecord {
+        let record = KAuditRecord {
+            seq: self.next_seq,
+            from_k,
+            to_k: proposal.new_k,
+            activation_blue_score,
+            source: proposal.source,
+            reason: proposal.reason,
+            confidence: proposal.confidence,
+            reverts: proposal.reverts,
+            timestamp_ms,
+        };
+        self.next_seq += 1;
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record.clone());
+        record
+    }
+
+    /// Returns `true` if the latest record is exactly this change, i.e. it
+    /// was already approved by the other side of the shared governor.
+    pub fn is_recorded(&self, new_k: u64, activation_blue_score: u64) -> bool {
+        self.records
+            .back()
+            .is_some_and(|r| r.to_k == new_k && r.activation_blue_score == activation_blue_score)
+    }
+
+    /// A proposal rolling back the most recent change that is neither a
+    /// revert nor reverted already.
+    pub fn revert_proposal(
+        &self,
+        reason: impl Into<String>,
+    ) -> Result<KProposal, KGovernanceError> {
+        let reverted: HashSet<u64> = self.records.iter().filter_map(|r| r.reverts).collect();
+        let target = self
+            .records
+            .iter()
+            .rev()
+            .find(|r| r.source != KChangeSource::Revert && !reverted.contains(&r.seq))
+            .ok_or(KGovernanceError::NothingToRevert)?;
+        let mut proposal = KProposal::new(target.from_k, KChangeSource::Revert).with_reason(reason);
+        proposal.reverts = Some(target.seq);
+        Ok(proposal)
+    }
+
+    /// The most recent `limit` records (all if `None`), oldest first.
+    pub fn audit(&self, limit: Option<usize>) -> Vec<KAuditRecord> {
+        let skip = limit.map_or(0, |l| self.records.len().saturating_sub(l));
+        self.records.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Default for KGovernor {
+    fn default() -> Self {
+        Self::new(KPolicy::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn governor() -> KGovernor {
+        KGovernor::new(KPolicy {
+            min_k: 2,
+            max_k: 20,
+            max_step: 4,
+            step_interval: Duration::from_secs(60),
+            min_confidence: 0.7,
+        })
+    }
+
+    fn rpc(new_k: u64) -> KProposal {
+        KProposal::new(new_k, KChangeSource::Rpc)
+    }
+
+    #[test]
+    fn test_range_and_confidence() {
+        let gov = governor();
+        assert!(matches!(
+            gov.check_at(5, &rpc(1), 0),
+            Err(KGovernanceError::OutOfRange { k: 1, .. })
+        ));
+        assert!(gov.check_at(5, &rpc(21), 0).is_err());
+        assert!(matches!(
+            gov.check_at(5, &rpc(6).with_confidence(0.5), 0),
+            Err(KGovernanceError::LowConfidence { .. })
+        ));
+        assert!(gov.check_at(5, &rpc(6).with_confidence(0.9), 0).is_ok());
+        // Proposals without a confidence (e.g. the tuner's) skip that check.
+        assert!(gov.check_at(5, &rpc(6), 0).is_ok());
+    }
+
+    #[test]
+    fn test_step_budget_per_interval() {
+        let mut gov = governor();
+        assert!(gov.check_at(5, &rpc(8), 0).is_ok());
+        gov.record_at(5, rpc(8), 10, 0);
+
+        // 3 of 4 used: one more step is fine, two are not.
+        assert!(gov.check_at(8, &rpc(9), 1_000).is_ok());
+        assert!(matches!(
+            gov.check_at(8, &rpc(10), 1_000),
+            Err(KGovernanceError::StepTooLarge { step: 2, used: 3, .. })
+        ));
+        // Once the interval has passed the budget is back.
+        assert!(gov.check_at(8, &rpc(12), 60_000).is_ok());
+    }
+
+    #[test]
+    fn test_revert_walks_back() {
+        let mut gov = governor();
+        assert_eq!(gov.revert_proposal("x"), Err(KGovernanceError::NothingToRevert));
+
+        gov.record_at(5, rpc(7).with_reason("first"), 10, 0);
+        gov.record_at(7, rpc(9).with_reason("second"), 20, 0);
+        assert!(gov.is_recorded(9, 20));
+        assert!(!gov.is_recorded(9, 21));
+
+        let revert = gov.revert_proposal("bad change").unwrap();
+        assert_eq!((revert.new_k, revert.reverts), (7, Some(2)));
+        // Reverts ignore the exhausted step budget.
+        assert!(gov.check_at(9, &revert, 0).is_ok());
+        gov.record_at(9, revert, 30, 0);
+
+        let revert = gov.revert_proposal("again").unwrap();
+        assert_eq!((revert.new_k, revert.reverts), (5, Some(1)));
+        gov.record_at(7, revert, 40, 0);
+        assert_eq!(gov.revert_proposal("x"), Err(KGovernanceError::NothingToRevert));
+
+        let audit = gov.audit(None);
+        assert_eq!(audit.len(), 4);
+        assert_eq!(audit[2].source, KChangeSource::Revert);
+        assert_eq!(audit[1].reason, "second");
+        assert_eq!(gov.audit(Some(1))[0].seq, 4);
+    }
+}
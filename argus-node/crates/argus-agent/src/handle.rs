@@ -0,0 +1,289 @@
+/// Argus Agent — AgentHandle.
+///
+/// A cloneable front-end to a running `GhostDagAgent`.  Fire-and-forget
+/// commands are sent as-is; queries attach a `oneshot` reply channel and
+/// await the answer, so callers never have to infer state from events.
+///
+/// The agent answers queries between commands, so a query can wait behind
+/// a long recovery or IBD.  Callers that must stay responsive (the RPC
+/// server) set a query timeout with `with_query_timeout`.
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use argus_ghostdag::block::{BlockHash, BlockHeader};
+
+use crate::channels::{AgentCommand, AgentStateLabel, AgentStatus, CommandTx};
+use crate::clock::{self, system_clock, SharedClock};
+use crate::governance::{KChangeSource, KProposal};
+use crate::transitions::TransitionRecord;
+
+/// Errors from talking to the agent.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum AgentHandleError {
+    #[error("agent is not running")]
+    Stopped,
+    #[error("agent did not answer within {0:?}")]
+    Timeout(Duration),
+}
+
+/// Default query timeout for callers serving remote clients.
+pub const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cloneable handle for sending commands and queries to a `GhostDagAgent`.
+#[derive(Debug, Clone)]
+pub struct AgentHandle {
+    cmd_tx: CommandTx,
+    /// How long a query waits for its answer; forever if `None`.
+    query_timeout: Option<Duration>,
+    clock: SharedClock,
+}
+
+impl AgentHandle {
+    /// Wrap the sending half of the agent's command channel.
+    pub fn new(cmd_tx: CommandTx) -> Self {
+        Self {
+            cmd_tx,
+            query_timeout: None,
+            clock: system_clock(),
+        }
+    }
+
+    /// Give up on queries the agent has not answered within `timeout`.
+    /// Commands still wait for room in the channel.
+    pub fn with_query_timeout(mut self, timeout: Duration) -> Self {
+        self.query_timeout = Some(timeout);
+        self
+    }
+
+    /// Measure query timeouts on `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The underlying command sender.
+    pub fn sender(&self) -> &CommandTx {
+        &self.cmd_tx
+    }
+
+    /// Send a fire-and-forget command.
+    pub async fn send(&self, cmd: AgentCommand) -> Result<(), AgentHandleError> {
+        self.cmd_tx
+            .send(cmd)
+            .await
+            .map_err(|_| AgentHandleError::Stopped)
+    }
+
+    async fn query<T>(
+        &self,
+        make: impl FnOnce(oneshot::Sender<T>) -> AgentCommand,
+    ) -> Result<T, AgentHandleError> {
+        let (reply, rx) = oneshot::channel();
+        self.send(make(reply)).await?;
+        let answer = match self.query_timeout {
+            Some(timeout) => clock::timeout(&*self.clock, timeout, rx)
+                .await
+                .ok_or(AgentHandleError::Timeout(timeout))?,
+            None => rx.await,
+        };
+        answer.map_err(|_| AgentHandleError::Stopped)
+    }
+
+    /// The agent's current state.
+    pub async fn state(&self) -> Result<AgentStateLabel, AgentHandleError> {
+        self.query(|reply| AgentCommand::GetState { reply }).await
+    }
+
+    /// The agent's local tip.
+    pub async fn local_tip(&self) -> Result<BlockHash, AgentHandleError> {
+        self.query(|reply| AgentCommand::GetLocalTip { reply }).await
+    }
+
+    /// The most recent network tip the agent knows about.
+    pub async fn network_tip(&self) -> Result<Option<BlockHash>, AgentHandleError> {
+        self.query(|reply| AgentCommand::GetNetworkTip { reply }).await
+    }
+
+    /// The k in force at the local tip.
+    pub async fn k(&self) -> Result<u64, AgentHandleError> {
+        self.query(|reply| AgentCommand::GetK { reply }).await
+    }
+
+    /// The most recent `limit` state transitions (all if `None`), oldest first.
+    pub async fn history(
+        &self,
+        limit: Option<usize>,
+    ) -> Result<Vec<TransitionRecord>, AgentHandleError> {
+        self.query(|reply| AgentCommand::GetHistory { limit, reply })
+            .await
+    }
+
+    /// A consistent snapshot of state, tips, k and orphan count.
+    pub async fn status(&self) -> Result<AgentStatus, AgentHandleError> {
+        self.query(|reply| AgentCommand::GetStatus { reply }).await
+    }
+
+    /// Run a divergence check against `network_tip` and return the
+    /// resulting status.
+    pub async fn check_divergence(
+        &self,
+        network_tip: BlockHash,
+    ) -> Result<AgentStatus, AgentHandleError> {
+        self.query(|reply| AgentCommand::QueryDivergence { network_tip, reply })
+            .await
+    }
+
+    /// Inform the agent of a new network tip.
+    pub async fn update_network_tip(&self, tip: BlockHash) -> Result<(), AgentHandleError> {
+        self.send(AgentCommand::UpdateNetworkTip { tip }).await
+    }
+
+    /// Schedule a k change (see `AgentCommand::UpdateK`).
+    pub async fn update_k(
+        &self,
+        new_k: u64,
+        activation_blue_score: Option<u64>,
+    ) -> Result<(), AgentHandleError> {
+        self.propose_k(KProposal::new(new_k, KChangeSource::Rpc), activation_blue_score)
+            .await
+    }
+
+    /// Schedule a k change with its source, reason and confidence, for the
+    /// agent's `KGovernor` to check and record.
+    pub async fn propose_k(
+        &self,
+        proposal: KProposal,
+        activation_blue_score: Option<u64>,
+    ) -> Result<(), AgentHandleError> {
+        self.send(AgentCommand::UpdateK {
+            new_k: proposal.new_k,
+            activation_blue_score,
+            source: proposal.source,
+            reason: proposal.reason,
+            confidence: proposal.confidence,
+        })
+        .await
+    }
+
+    /// Hand blocks received from peers to the agent.
+    pub async fn submit_blocks(&self, blocks: Vec<BlockHeader>) -> Result<(), AgentHandleError> {
+        self.send(AgentCommand::SubmitBlocks { blocks }).await
+    }
+
+    /// Start a headers-first initial block download.
+    pub async fn start_ibd(&self, batch_size: Option<usize>) -> Result<(), AgentHandleError> {
+        self.send(AgentCommand::StartIbd { batch_size }).await
+    }
+
+    /// Ask the agent to shut down.
+    pub async fn shutdown(&self) -> Result<(), AgentHandleError> {
+        self.send(AgentCommand::Shutdown).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::channels::{command_channel, event_channel};
+    use crate::clock::ManualClock;
+    use crate::state_machine::GhostDagAgent;
+    use argus_ghostdag::coloring::color_dag;
+    use argus_ghostdag::dag::DagStore;
+    use argus_ghostdag::params::ConsensusParams;
+
+    fn spawn_agent() -> (AgentHandle, tokio::task::JoinHandle<()>) {
+        let mut dag = DagStore::new();
+        let g = BlockHash::from_byte(0);
+        let a = BlockHash::from_byte(1);
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        dag.add_block(BlockHeader::new(a, vec![g], 1)).unwrap();
+        color_dag(&mut dag, 3).unwrap();
+
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, _event_rx) = event_channel(32);
+        let agent = GhostDagAgent::new(
+            Arc::new(RwLock::new(dag)),
+            a,
+            ConsensusParams::devnet(),
+            cmd_rx,
+            event_tx,
+        );
+        (AgentHandle::new(cmd_tx), tokio::spawn(agent.run()))
+    }
+
+    #[tokio::test]
+    async fn test_queries() {
+        let (handle, join) = spawn_agent();
+
+        assert_eq!(handle.state().await.unwrap(), AgentStateLabel::Synced);
+        assert_eq!(handle.local_tip().await.unwrap(), BlockHash::from_byte(1));
+        assert_eq!(handle.network_tip().await.unwrap(), None);
+        assert_eq!(handle.k().await.unwrap(), 3);
+
+        // The change activates above the tip, so k stays put for now.
+        handle.update_k(5, None).await.unwrap();
+        assert_eq!(handle.k().await.unwrap(), 3);
+        assert_eq!(handle.status().await.unwrap().scheduled_k, 5);
+
+        handle.shutdown().await.unwrap();
+        join.await.unwrap();
+        assert_eq!(handle.state().await, Err(AgentHandleError::Stopped));
+    }
+
+    #[tokio::test]
+    async fn test_query_times_out_on_busy_agent() {
+        // Nothing reads the commands, like an agent stuck in a long sync.
+        let (cmd_tx, _cmd_rx) = command_channel(32);
+        let clock = ManualClock::new(0).shared();
+        let handle = AgentHandle::new(cmd_tx)
+            .with_clock(clock.clone())
+            .with_query_timeout(Duration::from_secs(2));
+
+        let query = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.state().await }
+        });
+        while !query.is_finished() {
+            tokio::task::yield_now().await;
+            clock.advance(Duration::from_secs(1));
+        }
+        assert_eq!(
+            query.await.unwrap(),
+            Err(AgentHandleError::Timeout(Duration::from_secs(2)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_divergence() {
+        let (handle, join) = spawn_agent();
+
+        let status = handle
+            .check_divergence(BlockHash::from_byte(99))
+            .await
+            .unwrap();
+        assert_eq!(status.state, AgentStateLabel::Drifting);
+        assert_eq!(status.network_tip, Some(BlockHash::from_byte(99)));
+
+        let status = handle
+            .clone()
+            .check_divergence(BlockHash::from_byte(1))
+            .await
+            .unwrap();
+        assert_eq!(status.state, AgentStateLabel::Synced);
+
+        let history = handle.history(None).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].to, AgentStateLabel::Drifting);
+        assert_eq!(history[1].to, AgentStateLabel::Synced);
+        assert_eq!(handle.history(Some(1)).await.unwrap().len(), 1);
+
+        handle.shutdown().await.unwrap();
+        join.await.unwrap();
+    }
+}
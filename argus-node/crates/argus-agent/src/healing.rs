@@ -0,0 +1,265 @@
+/// Argus Agent — Partition healing.
+///
+/// When the divergence depth exceeds the partition threshold the agent
+/// enters PARTITIONED: both sides kept building on their own branch.  A
+/// DAG needs no fork choice to merge them — every block from both sides
+/// simply goes into one `DagStore`, and the k-cluster coloring decides
+/// which side's blocks stay blue.  Healing therefore:
+///
+/// 1. gathers the other side's blocks (already local, or fetched),
+/// 2. ingests them into the shared DAG and recolors with the k schedule,
+/// 3. splits the merged DAG into the blocks only the local side had and
+///    the blocks only the network side had, and counts how many of each
+///    became red,
+/// 4. diffs the old and new selected-parent chains, which bounds the
+///    range of the total ordering that changed.  The new chain ends at the
+///    best tip that agrees with the trusted checkpoints (`best_tip`).
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use argus_ghostdag::block::{BlockHash, BlockHeader};
+use argus_ghostdag::checkpoints::{best_tip, Checkpoint};
+use argus_ghostdag::coloring::selected_parent_chain;
+use argus_ghostdag::dag::DagStore;
+use argus_ghostdag::errors::GhostDagError;
+use argus_ghostdag::schedule::KSchedule;
+
+use crate::orphans::OrphanPool;
+use crate::recovery::ingest_and_recolor;
+
+/// Blocks one side of a partition contributed to the merged DAG.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SideReport {
+    /// Blocks only this side had.
+    pub blocks: u64,
+    /// How many of those are red after the merge.
+    pub red: u64,
+}
+
+/// Difference between the selected-parent chains before and after a merge.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainDiff {
+    /// Last block both chains share.
+    pub fork_point: BlockHash,
+    /// Blocks that left the selected chain, fork-point-exclusive, oldest first.
+    pub removed: Vec<BlockHash>,
+    /// Blocks that joined the selected chain, fork-point-exclusive, oldest first.
+    pub added: Vec<BlockHash>,
+}
+
+/// Result of `heal_partition`.
+#[derive(Debug, Clone)]
+pub struct HealingReport {
+    /// Bluest tip of the merged DAG that passes the checkpoints; the
+    /// agent's new local tip.
+    pub new_tip: BlockHash,
+    /// Blocks that only the local side had.
+    pub local_side: SideReport,
+    /// Blocks that only the network side had.
+    pub network_side: SideReport,
+    /// Selected-chain change caused by the merge.
+    pub chain_diff: ChainDiff,
+    /// First blue score whose ordering may have changed.
+    pub affected_from_score: u64,
+    /// Last blue score whose ordering may have changed.
+    pub affected_to_score: u64,
+    /// Blocks added to the DAG while healing.
+    pub ingested: u64,
+    /// Ancestors still missing after ingestion (to be requested from peers).
+    pub missing_ancestors: Vec<BlockHash>,
+}
+
+/// Merge the two sides of a partition and report the effect.
+///
+/// `blocks` are the network side's blocks that were not yet local (empty
+/// if they already are); `network_tip` must be local once they are
+/// ingested.  `now` is passed on to `ingest_and_recolor`.
+// Both tips plus everything ingestion needs; a config struct would only move them.
+#[allow(clippy::too_many_arguments)]
+pub async fn heal_partition(
+    dag: &Arc<RwLock<DagStore>>,
+    blocks: Vec<BlockHeader>,
+    schedule: &KSchedule,
+    orphans: &mut OrphanPool,
+    now: Instant,
+    checkpoints: &[Checkpoint],
+    local_tip: BlockHash,
+    network_tip: BlockHash,
+) -> Result<HealingReport, GhostDagError> {
+    let old_chain = selected_parent_chain(&*dag.read().await, &local_tip)?;
+
+    let outcome = ingest_and_recolor(dag, blocks, schedule, orphans, now).await?;
+    let red = &outcome.coloring.red_set;
+
+    let mut dag = dag.write().await;
+    if !dag.contains(&network_tip) {
+        return Err(GhostDagError::BlockNotFound(network_tip));
+    }
+
+    let mut local_past = dag.past(&local_tip)?;
+    local_past.insert(local_tip);
+    let mut network_past = dag.past(&network_tip)?;
+    network_past.insert(network_tip);
+    let side = |only: &HashSet<BlockHash>, other: &HashSet<BlockHash>| {
+        let blocks: Vec<&BlockHash> = only.difference(other).collect();
+        SideReport {
+            blocks: blocks.len() as u64,
+            red: blocks.iter().filter(|h| red.contains(h)).count() as u64,
+        }
+    };
+    let local_side = side(&local_past, &network_past);
+    let network_side = side(&network_past, &local_past);
+
+    let best = best_tip(&dag, checkpoints);
+    let new_tip = match (best.tip, best.refused) {
+        (Some(tip), _) => tip,
+        (None, Some((_, e))) => return Err(e),
+        (None, None) => return Err(GhostDagError::NoGenesis),
+    };
+    let new_chain = selected_parent_chain(&dag, &new_tip)?;
+    let chain_diff = diff_chains(&old_chain, &new_chain)?;
+
+    let fork_score = dag.get(&chain_diff.fork_point)?.blue_score;
+    let affected_to_score = [new_tip, local_tip]
+        .iter()
+        .map(|h| dag.get(h).map(|h| h.blue_score))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .max()
+        .unwrap_or(fork_score);
+
+    Ok(HealingReport {
+        new_tip,
+        local_side,
+        network_side,
+        chain_diff,
+        affected_from_score: fork_score + 1,
+        affected_to_score,
+        ingested: outcome.ingested,
+        missing_ancestors: outcome.missing_ancestors,
+    })
+}
+
+/// Diff two genesis-first selected-parent chains.
+pub fn diff_chains(old: &[BlockHash], new: &[BlockHash]) -> Result<ChainDiff, GhostDagError> {
+    let shared = old
+        .iter()
+        .zip(new)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if shared == 0 {
+        return Err(GhostDagError::Internal(
+            "selected chains share no genesis".to_string(),
+        ));
+    }
+    Ok(ChainDiff {
+        fork_point: old[shared - 1],
+        removed: old[shared..].to_vec(),
+        added: new[shared..].to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use argus_ghostdag::coloring::color_dag;
+
+    fn h(v: u64) -> BlockHash {
+        BlockHash::from_u64(v)
+    }
+
+    /// G → A, then two branches off A: 200.. (length `local`) and
+    /// 100.. (length `network`).  Returns the DAG and both tips.  The
+    /// network branch is inserted first, so it wins the coloring and stays
+    /// blue.
+    fn partitioned_dag(local: u64, network: u64) -> (DagStore, BlockHash, BlockHash) {
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        dag.add_block(BlockHeader::new(h(1), vec![h(0)], 1)).unwrap();
+        for (base, len) in [(100, network), (200, local)] {
+            let mut parent = h(1);
+            for i in 1..=len {
+                dag.add_block(BlockHeader::new(h(base + i), vec![parent], 1 + i))
+                    .unwrap();
+                parent = h(base + i);
+            }
+        }
+        color_dag(&mut dag, 3).unwrap();
+        (dag, h(200 + local), h(100 + network))
+    }
+
+    #[test]
+    fn test_diff_chains() {
+        let diff = diff_chains(&[h(0), h(1), h(2)], &[h(0), h(1), h(3), h(4)]).unwrap();
+        assert_eq!(diff.fork_point, h(1));
+        assert_eq!(diff.removed, vec![h(2)]);
+        assert_eq!(diff.added, vec![h(3), h(4)]);
+        assert!(diff_chains(&[h(0)], &[h(9)]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_heal_merges_both_sides() {
+        let (dag, local_tip, network_tip) = partitioned_dag(12, 15);
+        let dag = Arc::new(RwLock::new(dag));
+
+        let report = heal_partition(
+            &dag,
+            Vec::new(),
+            &KSchedule::new(3),
+            &mut OrphanPool::default(),
+            Instant::now(),
+            &[],
+            local_tip,
+            network_tip,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.local_side.blocks, 12);
+        assert_eq!(report.network_side.blocks, 15);
+        // With k = 3 the two long branches cannot both stay blue.
+        assert_eq!(report.network_side.red, 0);
+        assert!(report.local_side.red > 0);
+
+        // The local branch left the selected chain.
+        assert_eq!(report.new_tip, network_tip);
+        assert_eq!(report.chain_diff.fork_point, h(1));
+        assert_eq!(report.chain_diff.removed.len(), 12);
+        assert_eq!(report.chain_diff.added.len(), 15);
+        assert_eq!(report.affected_from_score, 2);
+        assert!(report.affected_to_score >= 16);
+    }
+
+    #[tokio::test]
+    async fn test_heal_ingests_fetched_side() {
+        // Only the network's first three blocks are local; the rest arrive
+        // with the heal.
+        let (dag, local_tip, _) = partitioned_dag(10, 3);
+        let fetched: Vec<BlockHeader> = (4..=6)
+            .rev()
+            .map(|i| BlockHeader::new(h(100 + i), vec![h(99 + i)], 1 + i))
+            .collect();
+        let network_tip = h(106);
+        let dag = Arc::new(RwLock::new(dag));
+
+        let report = heal_partition(
+            &dag,
+            fetched,
+            &KSchedule::new(3),
+            &mut OrphanPool::default(),
+            Instant::now(),
+            &[],
+            local_tip,
+            network_tip,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.ingested, 3);
+        assert_eq!(report.network_side.blocks, 6);
+        assert!(report.missing_ancestors.is_empty());
+    }
+}
@@ -0,0 +1,730 @@
+/// Argus Agent — Headers-first initial block download (IBD).
+///
+/// A fresh (or far-behind) node catches up with a peer in three steps:
+///
+/// 1. **Locator** — the node describes its selected chain with an
+///    exponentially spaced list of hashes (`build_locator`): the ten most
+///    recent chain blocks, then every 2nd, 4th, 8th … back to genesis.
+/// 2. **Serve** — the peer finds the highest locator entry it knows
+///    (`find_shared_block`) and returns the headers outside that block's
+///    past in *sync order*: ascending `(height, hash)`, where height is the
+///    longest parent path from genesis.  Sync order is topological and
+///    stable as the DAG grows, so the last header of a batch is a valid
+///    cursor for the next request.
+/// 3. **Ingest** — each batch is validated (`validate_batch`) before it is
+///    merged and re-colored, and a progress event is emitted per batch.
+///    With trusted checkpoints, the batch's highest block must have every
+///    checkpoint it may have reached in its past; otherwise the peer is
+///    rejected before anything reaches the live DAG.  The locator is
+///    built from the best tip that agrees with the checkpoints.
+///
+/// Unlike `greedy_path_intersection`, none of this requires the network
+/// chain to be present locally.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use argus_ghostdag::block::{BlockHash, BlockHeader};
+use argus_ghostdag::checkpoints::{best_tip, Checkpoint};
+use argus_ghostdag::coloring::selected_parent_chain;
+use argus_ghostdag::dag::DagStore;
+use argus_ghostdag::errors::{GhostDagError, GhostDagResult};
+use argus_ghostdag::schedule::KSchedule;
+
+use crate::channels::{AgentEvent, EventTx};
+use crate::clock::Clock;
+use crate::fetcher::{BlockFetcher, FetchError};
+use crate::orphans::OrphanPool;
+use crate::recovery::ingest_and_recolor;
+
+/// Number of most-recent chain blocks listed individually in a locator.
+const LOCATOR_DENSE_PREFIX: usize = 10;
+
+/// Default number of headers requested per IBD batch.
+pub const DEFAULT_IBD_BATCH_SIZE: usize = 256;
+
+/// Errors that abort an initial block download.
+#[derive(Debug, Error)]
+pub enum IbdError {
+    #[error("fetch failed: {0}")]
+    Fetch(#[from] FetchError),
+
+    #[error("invalid header batch: {0}")]
+    InvalidBatch(String),
+
+    #[error("peer chain violates a checkpoint: {0}")]
+    Checkpoint(GhostDagError),
+
+    #[error("consensus error: {0}")]
+    Consensus(#[from] GhostDagError),
+}
+
+/// Summary of a completed IBD.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IbdReport {
+    /// Number of batches received.
+    pub batches: u64,
+    /// Headers received from the peer.
+    pub headers_received: u64,
+    /// Headers actually added to the local DAG.
+    pub blocks_ingested: u64,
+}
+
+// ---------------------------------------------------------------------------
+// Requesting side
+// ---------------------------------------------------------------------------
+
+/// Build a block locator for the selected chain ending at `tip`.
+///
+/// The result starts at `tip`, lists the first `LOCATOR_DENSE_PREFIX`
+/// chain blocks one by one, then doubles the step each time, and always
+/// ends at genesis.  Its length is O(log chain length).
+pub fn build_locator(dag: &DagStore, tip: &BlockHash) -> GhostDagResult<Vec<BlockHash>> {
+    let mut chain = selected_parent_chain(dag, tip)?;
+    chain.reverse(); // tip first
+
+    let mut locator = Vec::new();
+    let mut index = 0usize;
+    let mut step = 1usize;
+    while index < chain.len() {
+        locator.push(chain[index]);
+        if locator.len() >= LOCATOR_DENSE_PREFIX {
+            step *= 2;
+        }
+        index += step;
+    }
+    if let Some(genesis) = chain.last() {
+        if locator.last() != Some(genesis) {
+            locator.push(*genesis);
+        }
+    }
+    Ok(locator)
+}
+
+/// Check that `batch` can be inserted into `dag` as-is: no duplicates,
+/// no extra genesis blocks, parent limits respected, and every parent
+/// either already local or earlier in the batch.
+pub fn validate_batch(dag: &DagStore, batch: &[BlockHeader]) -> Result<(), IbdError> {
+    let mut seen: HashSet<BlockHash> = HashSet::with_capacity(batch.len());
+    for header in batch {
+        if !seen.insert(header.hash) {
+            return Err(IbdError::InvalidBatch(format!(
+                "duplicate header {}",
+                header.hash
+            )));
+        }
+        if header.parents.is_empty() {
+            if dag.genesis().is_some_and(|g| g != header.hash) {
+                return Err(IbdError::InvalidBatch(format!(
+                    "header {} claims to be a second genesis",
+                    header.hash
+                )));
+            }
+            continue;
+        }
+        if let Some(max) = dag.max_parents() {
+            if header.parents.len() > max {
+                return Err(IbdError::InvalidBatch(format!(
+                    "header {} has {} parents (max {max})",
+                    header.hash,
+                    header.parents.len()
+                )));
+            }
+        }
+        let distinct: HashSet<&BlockHash> = header.parents.iter().collect();
+        if distinct.len() != header.parents.len() {
+            return Err(IbdError::InvalidBatch(format!(
+                "header {} lists a parent twice",
+                header.hash
+            )));
+        }
+        for p in &header.parents {
+            if !dag.contains(p) && !seen.contains(p) {
+                return Err(IbdError::InvalidBatch(format!(
+                    "header {} references unknown parent {p}",
+                    header.hash
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Download every header the peer behind `fetcher` has and we lack.
+///
+/// Batches of up to `batch_size` headers are requested until the peer
+/// returns an empty one; each is validated, ingested and re-colored with
+/// `schedule`; inserting them also releases any waiting `orphans`, aged
+/// by `clock`.  A batch that would leave the best chain contradicting one
+/// of `checkpoints` aborts with `IbdError::Checkpoint` before it is
+/// ingested, so the DAG keeps only the batches accepted so far.  A batch
+/// with nothing new, or one that does not move the cursor, aborts with
+/// `IbdError::InvalidBatch`.  An `IbdProgress` event is sent on
+/// `progress` (if given) after each batch.
+// The download needs every one of these; a config struct would only move them.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_ibd(
+    dag: &Arc<RwLock<DagStore>>,
+    fetcher: &dyn BlockFetcher,
+    schedule: &KSchedule,
+    orphans: &mut OrphanPool,
+    clock: &dyn Clock,
+    checkpoints: &[Checkpoint],
+    batch_size: usize,
+    progress: Option<&EventTx>,
+) -> Result<IbdReport, IbdError> {
+    let batch_size = batch_size.max(1);
+    let mut report = IbdReport::default();
+    let mut cursor: Option<BlockHash> = None;
+
+    loop {
+        let locator = {
+            let dag = dag.read().await;
+            match best_tip(&dag, checkpoints).tip {
+                Some(tip) => build_locator(&dag, &tip)?,
+                None => Vec::new(),
+            }
+        };
+
+        let batch = fetcher
+            .fetch_headers_after(&locator, cursor, batch_size)
+            .await?;
+        // Peers may serve fewer headers than requested (the RPC server
+        // clamps to `MAX_GET_HEADERS`), so only an empty batch means done.
+        if batch.is_empty() {
+            break;
+        }
+        if batch.len() > batch_size {
+            return Err(IbdError::InvalidBatch(format!(
+                "peer sent {} headers, requested at most {batch_size}",
+                batch.len()
+            )));
+        }
+
+        {
+            let dag = dag.read().await;
+            validate_batch(&dag, &batch)?;
+            // A peer that keeps serving what we have would be asked forever.
+            if batch.iter().all(|h| dag.contains(&h.hash)) {
+                return Err(IbdError::InvalidBatch(format!(
+                    "peer sent {} headers we already have",
+                    batch.len()
+                )));
+            }
+            if !checkpoints.is_empty() {
+                check_checkpoints(&dag, &batch, checkpoints)?;
+            }
+        }
+        let received = batch.len();
+        let last = batch.last().map(|h| h.hash);
+        if let Some(stuck) = last.filter(|l| Some(*l) == cursor) {
+            return Err(IbdError::InvalidBatch(format!(
+                "batch ends at the cursor {stuck}; the download would not advance"
+            )));
+        }
+        cursor = last;
+
+        let outcome = ingest_and_recolor(dag, batch, schedule, orphans, clock.now()).await?;
+        if !outcome.orphaned.is_empty() {
+            // validate_batch guarantees parents; this would be a store bug.
+            warn!(count = outcome.orphaned.len(), "IBD batch left orphaned blocks");
+        }
+
+        report.batches += 1;
+        report.headers_received += received as u64;
+        report.blocks_ingested += outcome.ingested;
+
+        let local_blue_score = {
+            let dag = dag.read().await;
+            let Some(tip) = best_tip(&dag, checkpoints).tip else {
+                return Err(IbdError::Consensus(GhostDagError::NoGenesis));
+            };
+            dag.get(&tip)?.blue_score
+        };
+        info!(
+            batch = report.batches,
+            received,
+            ingested = outcome.ingested,
+            local_blue_score,
+            "IBD batch ingested"
+        );
+        if let Some(tx) = progress {
+            let _ = tx
+                .send(AgentEvent::IbdProgress {
+                    batch: report.batches,
+                    headers_received: report.headers_received,
+                    blocks_ingested: report.blocks_ingested,
+                    local_blue_score,
+                })
+                .await;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Check `batch` against `checkpoints` without coloring it.  The batch's
+/// last header is its highest block (sync order); a blue score is at most
+/// the size of the past, so that block may only have reached checkpoints
+/// whose blue score its past can hold, and each of those must be in it.
+fn check_checkpoints(
+    dag: &DagStore,
+    batch: &[BlockHeader],
+    checkpoints: &[Checkpoint],
+) -> Result<(), IbdError> {
+    let Some(best) = batch.last() else {
+        return Ok(());
+    };
+    let in_batch: HashMap<BlockHash, &BlockHeader> = batch.iter().map(|h| (h.hash, h)).collect();
+    let mut past: HashSet<BlockHash> = HashSet::new();
+    let mut queue: VecDeque<BlockHash> = best.parents.iter().copied().collect();
+    while let Some(hash) = queue.pop_front() {
+        if !past.insert(hash) {
+            continue;
+        }
+        let parents = match in_batch.get(&hash) {
+            Some(header) => header.parents.as_slice(),
+            None => dag.parents(&hash)?,
+        };
+        queue.extend(parents.iter().filter(|p| !past.contains(p)));
+    }
+
+    let reachable = past.len() as u64;
+    for checkpoint in checkpoints.iter().take_while(|c| c.blue_score <= reachable) {
+        if checkpoint.hash != best.hash && !past.contains(&checkpoint.hash) {
+            return Err(IbdError::Checkpoint(GhostDagError::CheckpointMismatch {
+                blue_score: checkpoint.blue_score,
+                expected: checkpoint.hash,
+                found: best.hash,
+            }));
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Serving side
+// ---------------------------------------------------------------------------
+
+/// The first (i.e. highest) locator entry present in `dag`.
+pub fn find_shared_block(dag: &DagStore, locator: &[BlockHash]) -> Option<BlockHash> {
+    locator.iter().find(|h| dag.contains(h)).copied()
+}
+
+/// Longest-path height of every block (genesis = 0).
+fn heights(dag: &DagStore) -> GhostDagResult<HashMap<BlockHash, u64>> {
+    let mut heights = HashMap::with_capacity(dag.len());
+    for hash in dag.topological_order()? {
+        let height = dag
+            .parents(&hash)?
+            .iter()
+            .filter_map(|p| heights.get(p))
+            .max()
+            .map_or(0, |h| h + 1);
+        heights.insert(hash, height);
+    }
+    Ok(heights)
+}
+
+/// `hash` together with all of its ancestors.
+fn past_inclusive(dag: &DagStore, hash: &BlockHash) -> GhostDagResult<HashSet<BlockHash>> {
+    let mut visited = HashSet::from([*hash]);
+    let mut queue = VecDeque::from([*hash]);
+    while let Some(cur) = queue.pop_front() {
+        for p in dag.parents(&cur)? {
+            if visited.insert(*p) {
+                queue.push_back(*p);
+            }
+        }
+    }
+    Ok(visited)
+}
+
+/// Serve an IBD request: up to `max` headers outside the past of the
+/// highest shared locator block, in sync order, strictly after `cursor`.
+///
+/// An empty locator means the requester has nothing, so everything
+/// (including genesis) is served.  A non-empty locator with no known
+/// entry means the requester is on a different network.
+pub fn headers_after(
+    dag: &DagStore,
+    locator: &[BlockHash],
+    cursor: Option<&BlockHash>,
+    max: usize,
+) -> GhostDagResult<Vec<BlockHeader>> {
+    let known = if locator.is_empty() {
+        HashSet::new()
+    } else {
+        let shared = find_shared_block(dag, locator).ok_or_else(|| {
+            GhostDagError::InvalidParams("no locator block is known to this node".to_string())
+        })?;
+        past_inclusive(dag, &shared)?
+    };
+
+    let heights = heights(dag)?;
+    let after = match cursor {
+        Some(c) => Some((
+            *heights.get(c).ok_or(GhostDagError::BlockNotFound(*c))?,
+            *c,
+        )),
+        None => None,
+    };
+
+    let mut order: Vec<(u64, BlockHash)> = heights
+        .iter()
+        .filter(|(h, _)| !known.contains(h))
+        .map(|(h, height)| (*height, *h))
+        .filter(|key| after.is_none_or(|a| *key > a))
+        .collect();
+    order.sort_unstable();
+    order.truncate(max);
+
+    // Strip local coloring; the requester recomputes it.
+    order
+        .into_iter()
+        .map(|(_, h)| {
+            let hdr = dag.get(&h)?;
+            Ok(if hdr.parents.is_empty() {
+                BlockHeader::genesis(hdr.hash, hdr.timestamp)
+            } else {
+                BlockHeader::new(hdr.hash, hdr.parents.clone(), hdr.timestamp)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::event_channel;
+    use crate::clock::SystemClock;
+    use crate::fetcher::MockBlockFetcher;
+    use argus_ghostdag::coloring::color_dag;
+
+    /// A chain of `len` blocks after genesis, with a side block hanging
+    /// off every fifth chain block and merged by the next one.
+    fn remote_dag(len: u64) -> DagStore {
+        let mut dag = DagStore::new();
+        let g = BlockHash::from_u64(0);
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        let mut prev = g;
+        let mut side: Option<BlockHash> = None;
+        for i in 1..=len {
+            let h = BlockHash::from_u64(i);
+            let mut parents = vec![prev];
+            parents.extend(side.take());
+            dag.add_block(BlockHeader::new(h, parents, i)).unwrap();
+            if i % 5 == 0 {
+                let s = BlockHash::from_u64(10_000 + i);
+                dag.add_block(BlockHeader::new(s, vec![prev], i)).unwrap();
+                side = Some(s);
+            }
+            prev = h;
+        }
+        color_dag(&mut dag, 3).unwrap();
+        dag
+    }
+
+    fn genesis_only() -> DagStore {
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(BlockHash::from_u64(0), 0))
+            .unwrap();
+        color_dag(&mut dag, 3).unwrap();
+        dag
+    }
+
+    #[test]
+    fn test_locator_is_exponential() {
+        let dag = remote_dag(100);
+        let tip = BlockHash::from_u64(100);
+        let locator = build_locator(&dag, &tip).unwrap();
+
+        assert_eq!(locator[0], tip);
+        assert_eq!(*locator.last().unwrap(), BlockHash::from_u64(0));
+        assert_eq!(locator[9], BlockHash::from_u64(91));
+        assert!(locator.len() < 20, "locator too long: {}", locator.len());
+    }
+
+    #[test]
+    fn test_headers_after_shared_block() {
+        let dag = remote_dag(20);
+        // Requester knows up to chain block 12.
+        let locator = vec![BlockHash::from_u64(12), BlockHash::from_u64(0)];
+        let headers = headers_after(&dag, &locator, None, 1_000).unwrap();
+
+        let hashes: HashSet<BlockHash> = headers.iter().map(|h| h.hash).collect();
+        assert!(!hashes.contains(&BlockHash::from_u64(12)));
+        assert!(hashes.contains(&BlockHash::from_u64(13)));
+        assert!(hashes.contains(&BlockHash::from_u64(10_015)));
+        // Side block 10_010 hangs off block 9, which is in past(12).
+        assert!(!hashes.contains(&BlockHash::from_u64(10_010)));
+
+        // Every parent is already known to the requester or precedes its child.
+        let mut seen = past_inclusive(&dag, &BlockHash::from_u64(12)).unwrap();
+        for h in &headers {
+            assert!(h.parents.iter().all(|p| seen.contains(p)));
+            seen.insert(h.hash);
+        }
+
+        // Resuming from a cursor continues exactly where the batch ended.
+        let first = headers_after(&dag, &locator, None, 3).unwrap();
+        let rest = headers_after(&dag, &locator, Some(&first[2].hash), 1_000).unwrap();
+        assert_eq!(first.len() + rest.len(), headers.len());
+        assert_eq!(rest[0].hash, headers[3].hash);
+    }
+
+    #[test]
+    fn test_headers_after_unknown_locator() {
+        let dag = remote_dag(5);
+        assert!(headers_after(&dag, &[BlockHash::from_byte(0xEE)], None, 10).is_err());
+    }
+
+    #[test]
+    fn test_validate_batch_rejects_unknown_parent() {
+        let dag = genesis_only();
+        let bad = vec![BlockHeader::new(
+            BlockHash::from_u64(2),
+            vec![BlockHash::from_u64(1)],
+            2,
+        )];
+        assert!(matches!(
+            validate_batch(&dag, &bad),
+            Err(IbdError::InvalidBatch(_))
+        ));
+
+        let good = vec![
+            BlockHeader::new(BlockHash::from_u64(1), vec![BlockHash::from_u64(0)], 1),
+            BlockHeader::new(BlockHash::from_u64(2), vec![BlockHash::from_u64(1)], 2),
+        ];
+        validate_batch(&dag, &good).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ibd_catches_up_in_batches() {
+        let remote = remote_dag(40);
+        let fetcher = MockBlockFetcher::from_dag(&remote);
+        let local = Arc::new(RwLock::new(genesis_only()));
+        let (event_tx, mut event_rx) = event_channel(64);
+
+        let report = run_ibd(
+            &local,
+            &fetcher,
+            &KSchedule::new(3),
+            &mut OrphanPool::default(),
+            &SystemClock,
+            &[],
+            8,
+            Some(&event_tx),
+        )
+            .await
+            .unwrap();
+
+        let expected = (remote.len() - 1) as u64;
+        assert_eq!(report.blocks_ingested, expected);
+        assert_eq!(report.headers_received, expected);
+        assert_eq!(report.batches, expected.div_ceil(8));
+
+        let local = local.read().await;
+        assert_eq!(local.len(), remote.len());
+        let remote_tip = remote.headers().max_by_key(|h| h.blue_score).unwrap();
+        assert_eq!(local.get(&remote_tip.hash).unwrap().blue_score, remote_tip.blue_score);
+
+        let mut progress = 0;
+        while let Ok(event) = event_rx.try_recv() {
+            assert!(matches!(event, AgentEvent::IbdProgress { .. }));
+            progress += 1;
+        }
+        assert_eq!(progress, report.batches);
+    }
+
+    #[tokio::test]
+    async fn test_ibd_continues_past_clamped_batches() {
+        // The peer serves at most 5 headers however many are asked for.
+        let remote = remote_dag(40);
+        let fetcher = MockBlockFetcher::from_dag(&remote).with_max_batch(5);
+        let local = Arc::new(RwLock::new(genesis_only()));
+
+        let report = run_ibd(
+            &local,
+            &fetcher,
+            &KSchedule::new(3),
+            &mut OrphanPool::default(),
+            &SystemClock,
+            &[],
+            8,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let expected = (remote.len() - 1) as u64;
+        assert_eq!(report.blocks_ingested, expected);
+        assert_eq!(report.batches, expected.div_ceil(5));
+        assert_eq!(local.read().await.len(), remote.len());
+    }
+
+    #[tokio::test]
+    async fn test_ibd_rejects_peer_off_checkpoint() {
+        let remote = remote_dag(40);
+        let fetcher = MockBlockFetcher::from_dag(&remote);
+        let local = Arc::new(RwLock::new(genesis_only()));
+        let at_score = |score: u64| {
+            let chain = selected_parent_chain(&remote, &BlockHash::from_u64(40)).unwrap();
+            let hash = chain
+                .into_iter()
+                .find(|h| remote.get(h).unwrap().blue_score == score)
+                .unwrap();
+            Checkpoint::new(score, hash)
+        };
+
+        // The peer's chain passes through the checkpoint: accepted.
+        let good = [at_score(5)];
+        let mut orphans = OrphanPool::default();
+        run_ibd(&local, &fetcher, &KSchedule::new(3), &mut orphans, &SystemClock, &good, 8, None)
+            .await
+            .unwrap();
+
+        // A checkpoint the peer's chain misses: the first batch that would
+        // reach it is rejected, and the peer leaves no trace.
+        let local = Arc::new(RwLock::new(genesis_only()));
+        let before: Vec<(BlockHash, u64)> = {
+            let dag = local.read().await;
+            dag.headers().map(|h| (h.hash, h.blue_score)).collect()
+        };
+        let bad = [Checkpoint::new(5, BlockHash::from_u64(99_999))];
+        let schedule = KSchedule::new(3);
+        let result =
+            run_ibd(&local, &fetcher, &schedule, &mut orphans, &SystemClock, &bad, 8, None).await;
+        assert!(matches!(result, Err(IbdError::Checkpoint(_))));
+        let after: Vec<(BlockHash, u64)> = {
+            let dag = local.read().await;
+            dag.headers().map(|h| (h.hash, h.blue_score)).collect()
+        };
+        assert_eq!(after, before);
+    }
+
+    #[tokio::test]
+    async fn test_ibd_locates_from_checkpointed_tip() {
+        let h = BlockHash::from_u64;
+        // Local: G → 1 → 2, plus a heavier fork G → 301 … 310 that skips
+        // the checkpoint at block 1.
+        let mut local = genesis_only();
+        local.add_block(BlockHeader::new(h(1), vec![h(0)], 1)).unwrap();
+        local.add_block(BlockHeader::new(h(2), vec![h(1)], 2)).unwrap();
+        let mut parent = h(0);
+        for i in 301..=310 {
+            local.add_block(BlockHeader::new(h(i), vec![parent], i)).unwrap();
+            parent = h(i);
+        }
+        color_dag(&mut local, 3).unwrap();
+        let local = Arc::new(RwLock::new(local));
+
+        // The peer extends the checkpointed chain to block 20.
+        let mut remote = genesis_only();
+        for i in 1..=20 {
+            remote.add_block(BlockHeader::new(h(i), vec![h(i - 1)], i)).unwrap();
+        }
+        let fetcher = MockBlockFetcher::from_dag(&remote);
+        let (event_tx, mut event_rx) = event_channel(64);
+
+        let checkpoints = [Checkpoint::new(1, h(1))];
+        let report = run_ibd(
+            &local,
+            &fetcher,
+            &KSchedule::new(3),
+            &mut OrphanPool::default(),
+            &SystemClock,
+            &checkpoints,
+            8,
+            Some(&event_tx),
+        )
+        .await
+        .unwrap();
+
+        // The locator named block 2, so blocks 1 and 2 were not resent.
+        assert_eq!(report.headers_received, 18);
+        assert_eq!(report.blocks_ingested, 18);
+        let mut scores = Vec::new();
+        while let Ok(AgentEvent::IbdProgress { local_blue_score, .. }) = event_rx.try_recv() {
+            scores.push(local_blue_score);
+        }
+        assert_eq!(scores, vec![10, 18, 20]);
+    }
+
+    /// Serves scripted batches, repeating the last one forever.
+    struct ScriptedFetcher(std::sync::Mutex<VecDeque<Vec<BlockHeader>>>);
+
+    #[async_trait::async_trait]
+    impl BlockFetcher for ScriptedFetcher {
+        async fn fetch_headers(&self, _: &[BlockHash]) -> Result<Vec<BlockHeader>, FetchError> {
+            Ok(Vec::new())
+        }
+
+        async fn fetch_headers_after(
+            &self,
+            _: &[BlockHash],
+            _: Option<BlockHash>,
+            _: usize,
+        ) -> Result<Vec<BlockHeader>, FetchError> {
+            let mut batches = self.0.lock().unwrap();
+            if batches.len() > 1 {
+                Ok(batches.pop_front().unwrap())
+            } else {
+                Ok(batches.front().cloned().unwrap_or_default())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ibd_fails_on_peer_that_does_not_advance() {
+        let h = BlockHash::from_u64;
+        let b1 = BlockHeader::new(h(1), vec![h(0)], 1);
+        let b2 = BlockHeader::new(h(2), vec![h(1)], 2);
+        let schedule = KSchedule::new(3);
+
+        // The same batch over and over.
+        let fetcher = ScriptedFetcher(std::sync::Mutex::new(VecDeque::from([vec![b1.clone()]])));
+        let local = Arc::new(RwLock::new(genesis_only()));
+        let mut orphans = OrphanPool::default();
+        let result =
+            run_ibd(&local, &fetcher, &schedule, &mut orphans, &SystemClock, &[], 8, None).await;
+        assert!(matches!(result, Err(IbdError::InvalidBatch(_))));
+        assert!(local.read().await.contains(&h(1)));
+
+        // New headers, but ending at the cursor again.
+        let fetcher = ScriptedFetcher(std::sync::Mutex::new(VecDeque::from([
+            vec![b1.clone()],
+            vec![b2, b1],
+        ])));
+        let local = Arc::new(RwLock::new(genesis_only()));
+        let result =
+            run_ibd(&local, &fetcher, &schedule, &mut orphans, &SystemClock, &[], 8, None).await;
+        assert!(matches!(result, Err(IbdError::InvalidBatch(m)) if m.contains("cursor")));
+        assert!(!local.read().await.contains(&h(2)));
+    }
+
+    #[tokio::test]
+    async fn test_ibd_when_already_synced_is_noop() {
+        // Length 12 so every side block is merged into the selected chain.
+        let remote = remote_dag(12);
+        let fetcher = MockBlockFetcher::from_dag(&remote);
+        let local = Arc::new(RwLock::new(remote.clone()));
+
+        let report = run_ibd(
+            &local,
+            &fetcher,
+            &KSchedule::new(3),
+            &mut OrphanPool::default(),
+            &SystemClock,
+            &[],
+            8,
+            None,
+        )
+            .await
+            .unwrap();
+        assert_eq!(report, IbdReport::default());
+    }
+}
@@ -0,0 +1,558 @@
+/// Argus Agent — Native adaptive-k controllers.
+///
+/// A `KController` looks at the red (orphan) rate of recent blocks and
+/// proposes a new k.  Two are built in:
+///
+/// - **`PidController`** — steers the red rate towards a target: too many
+///   reds means honest blocks are being excluded, so k goes up; too few
+///   means k can come down for faster confirmation.
+/// - **`BanditController`** — UCB1 over a k range, rewarding each k by how
+///   close the red rate it produced was to the target.
+///
+/// `KTuner` samples the DAG, feeds the controller, and applies `KLimits`
+/// (bounds, step size, minimum interval between changes) before sending
+/// `AgentCommand::UpdateK`.  Changes always activate above the current
+/// tip, so tuning never recolors history.
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use argus_ghostdag::coloring::is_red;
+use argus_ghostdag::dag::DagStore;
+
+use crate::clock::{system_clock, SharedClock};
+use crate::governance::{KChangeSource, KProposal};
+use crate::handle::AgentHandle;
+
+/// Default red-rate target.
+pub const DEFAULT_TARGET_RED_RATE: f64 = 0.05;
+
+/// Block and red counts of a DAG at one point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DagSample {
+    pub blocks: u64,
+    pub red_blocks: u64,
+}
+
+impl DagSample {
+    /// Count the blocks of a colored DAG.
+    pub fn from_dag(dag: &DagStore) -> Self {
+        let red_blocks = dag
+            .headers()
+            .filter(|h| is_red(dag, &h.hash).unwrap_or(false))
+            .count() as u64;
+        Self {
+            blocks: dag.len() as u64,
+            red_blocks,
+        }
+    }
+}
+
+/// What a controller sees: the blocks added since its last decision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KObservation {
+    /// The k in force while these blocks arrived.
+    pub k: u64,
+    /// Blocks added in the window.
+    pub blocks: u64,
+    /// Of those, how many are red.
+    pub red_blocks: u64,
+}
+
+impl KObservation {
+    /// Fraction of the window's blocks that are red.
+    pub fn red_rate(&self) -> f64 {
+        if self.blocks == 0 {
+            0.0
+        } else {
+            (self.red_blocks.min(self.blocks)) as f64 / self.blocks as f64
+        }
+    }
+}
+
+/// Proposes k from observed red rates.
+pub trait KController: Send {
+    /// Short name for logs.
+    fn name(&self) -> &'static str;
+
+    /// The k to use next, or `None` to keep the current one.  Proposals
+    /// are bounded and rate-limited by `KTuner`, not by the controller.
+    fn propose(&mut self, obs: &KObservation) -> Option<u64>;
+}
+
+/// PID loop on the red rate.
+#[derive(Debug, Clone)]
+pub struct PidController {
+    target_red_rate: f64,
+    kp: f64,
+    ki: f64,
+    kd: f64,
+    /// Bound on the integral term (anti-windup).
+    integral_limit: f64,
+    integral: f64,
+    prev_error: Option<f64>,
+}
+
+impl PidController {
+    /// A controller steering towards `target_red_rate` with default gains.
+    pub fn new(target_red_rate: f64) -> Self {
+        Self {
+            target_red_rate,
+            kp: 20.0,
+            ki: 4.0,
+            kd: 2.0,
+            integral_limit: 1.0,
+            integral: 0.0,
+            prev_error: None,
+        }
+    }
+
+    /// Override the proportional, integral and derivative gains.
+    pub fn with_gains(mut self, kp: f64, ki: f64, kd: f64) -> Self {
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+        self
+    }
+}
+
+impl KController for PidController {
+    fn name(&self) -> &'static str {
+        "pid"
+    }
+
+    fn propose(&mut self, obs: &KObservation) -> Option<u64> {
+        let error = obs.red_rate() - self.target_red_rate;
+        self.integral = (self.integral + error).clamp(-self.integral_limit, self.integral_limit);
+        let derivative = self.prev_error.map_or(0.0, |prev| error - prev);
+        self.prev_error = Some(error);
+
+        let output = self.kp * error + self.ki * self.integral + self.kd * derivative;
+        let proposed = (obs.k as f64 + output).round().max(1.0) as u64;
+        (proposed != obs.k).then_some(proposed)
+    }
+}
+
+/// UCB1 multi-armed bandit over `min_k..=max_k`.
+#[derive(Debug, Clone)]
+pub struct BanditController {
+    min_k: u64,
+    target_red_rate: f64,
+    /// Exploration weight.
+    exploration: f64,
+    pulls: Vec<u64>,
+    rewards: Vec<f64>,
+}
+
+impl BanditController {
+    /// A bandit over `min_k..=max_k` rewarding red rates near the target.
+    pub fn new(min_k: u64, max_k: u64, target_red_rate: f64) -> Self {
+        let min_k = min_k.max(1);
+        let arms = max_k.max(min_k) - min_k + 1;
+        Self {
+            min_k,
+            target_red_rate,
+            exploration: 2.0_f64.sqrt(),
+            pulls: vec![0; arms as usize],
+            rewards: vec![0.0; arms as usize],
+        }
+    }
+
+    /// Reward in `[0, 1]`: 1 at the target, falling off linearly.
+    fn reward(&self, red_rate: f64) -> f64 {
+        let span = self.target_red_rate.max(1.0 - self.target_red_rate);
+        1.0 - ((red_rate - self.target_red_rate).abs() / span).min(1.0)
+    }
+
+    /// Mean reward observed for `k`, if it was tried.
+    pub fn mean_reward(&self, k: u64) -> Option<f64> {
+        let arm = k.checked_sub(self.min_k)? as usize;
+        let pulls = *self.pulls.get(arm)?;
+        (pulls > 0).then(|| self.rewards[arm] / pulls as f64)
+    }
+}
+
+impl KController for BanditController {
+    fn name(&self) -> &'static str {
+        "bandit"
+    }
+
+    fn propose(&mut self, obs: &KObservation) -> Option<u64> {
+        if let Some(arm) = obs
+            .k
+            .checked_sub(self.min_k)
+            .map(|a| a as usize)
+            .filter(|a| *a < self.pulls.len())
+        {
+            self.pulls[arm] += 1;
+            self.rewards[arm] += self.reward(obs.red_rate());
+        }
+
+        let total: u64 = self.pulls.iter().sum();
+        let best = match self.pulls.iter().position(|&n| n == 0) {
+            Some(untried) => untried,
+            None => {
+                let ucb = |arm: usize| {
+                    let n = self.pulls[arm] as f64;
+                    self.rewards[arm] / n + self.exploration * ((total as f64).ln() / n).sqrt()
+                };
+                (0..self.pulls.len())
+                    .max_by(|&a, &b| ucb(a).total_cmp(&ucb(b)))
+                    .unwrap_or(0)
+            }
+        };
+        let proposed = self.min_k + best as u64;
+        (proposed != obs.k).then_some(proposed)
+    }
+}
+
+/// Which built-in controller to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KControllerKind {
+    Pid,
+    Bandit,
+}
+
+impl std::str::FromStr for KControllerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pid" => Ok(Self::Pid),
+            "bandit" => Ok(Self::Bandit),
+            other => Err(format!("unknown k controller '{other}' (expected pid or bandit)")),
+        }
+    }
+}
+
+/// Bounds and rate limits applied to every proposal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KLimits {
+    pub min_k: u64,
+    pub max_k: u64,
+    /// Largest change per update.
+    pub max_step: u64,
+    /// Minimum time between two updates.
+    pub min_interval: Duration,
+    /// Blocks that must arrive before the controller is consulted.
+    pub min_blocks: u64,
+}
+
+impl Default for KLimits {
+    fn default() -> Self {
+        Self {
+            min_k: 1,
+            max_k: 64,
+            max_step: 2,
+            min_interval: Duration::from_secs(60),
+            min_blocks: 100,
+        }
+    }
+}
+
+impl KLimits {
+    /// Clamp `proposed` into bounds and within `max_step` of `current`.
+    pub fn clamp(&self, current: u64, proposed: u64) -> u64 {
+        let lo = current.saturating_sub(self.max_step).max(self.min_k);
+        let hi = current.saturating_add(self.max_step).min(self.max_k);
+        proposed.clamp(lo.min(hi), hi.max(lo))
+    }
+}
+
+/// Configuration for running a `KTuner` inside a node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KTunerConfig {
+    pub controller: KControllerKind,
+    pub target_red_rate: f64,
+    pub limits: KLimits,
+    /// How often the DAG is sampled.
+    pub interval: Duration,
+}
+
+impl Default for KTunerConfig {
+    fn default() -> Self {
+        Self {
+            controller: KControllerKind::Pid,
+            target_red_rate: DEFAULT_TARGET_RED_RATE,
+            limits: KLimits::default(),
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl KTunerConfig {
+    /// Instantiate the configured controller and wrap it in a tuner.
+    pub fn build(&self) -> KTuner {
+        let controller: Box<dyn KController> = match self.controller {
+            KControllerKind::Pid => Box::new(PidController::new(self.target_red_rate)),
+            KControllerKind::Bandit => Box::new(BanditController::new(
+                self.limits.min_k,
+                self.limits.max_k,
+                self.target_red_rate,
+            )),
+        };
+        KTuner::new(controller, self.limits.clone())
+    }
+}
+
+/// Drives a `KController` from DAG samples and applies `KLimits`.
+pub struct KTuner {
+    controller: Box<dyn KController>,
+    limits: KLimits,
+    /// Sample at the start of the current observation window.
+    window_start: Option<DagSample>,
+    last_change: Option<Instant>,
+    clock: SharedClock,
+}
+
+impl KTuner {
+    pub fn new(controller: Box<dyn KController>, limits: KLimits) -> Self {
+        Self {
+            controller,
+            limits,
+            window_start: None,
+            last_change: None,
+            clock: system_clock(),
+        }
+    }
+
+    /// Sample and rate-limit on `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Feed a DAG sample taken at `now` while `k` was in force.  Returns
+    /// the new k to schedule, if any.
+    pub fn step(&mut self, k: u64, sample: DagSample, now: Instant) -> Option<u64> {
+        let Some(start) = self.window_start else {
+            self.window_start = Some(sample);
+            return None;
+        };
+        let blocks = sample.blocks.saturating_sub(start.blocks);
+        if blocks < self.limits.min_blocks.max(1) {
+            return None;
+        }
+        self.window_start = Some(sample);
+
+        let obs = KObservation {
+            k,
+            blocks,
+            red_blocks: sample.red_blocks.saturating_sub(start.red_blocks),
+        };
+        let proposed = self.controller.propose(&obs)?;
+        let new_k = self.limits.clamp(k, proposed);
+        debug!(
+            controller = self.controller.name(),
+            red_rate = obs.red_rate(),
+            proposed,
+            new_k,
+            "k controller proposal"
+        );
+        if new_k == k {
+            return None;
+        }
+        if self
+            .last_change
+            .is_some_and(|at| now.saturating_duration_since(at) < self.limits.min_interval)
+        {
+            debug!("k change rate-limited");
+            return None;
+        }
+        self.last_change = Some(now);
+        Some(new_k)
+    }
+
+    /// Sample `dag` every `interval` and send accepted proposals to the
+    /// agent as `UpdateK`, until shutdown.
+    pub async fn run(
+        mut self,
+        dag: Arc<RwLock<DagStore>>,
+        agent: AgentHandle,
+        interval: Duration,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+    ) {
+        info!(controller = self.controller.name(), "k tuner started");
+        loop {
+            tokio::select! {
+                _ = self.clock.sleep(interval) => {}
+                Ok(()) = shutdown.changed() => {
+                    if *shutdown.borrow() {
+                        info!("k tuner shutting down");
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            let Ok(k) = agent.k().await else {
+                break;
+            };
+            let sample = DagSample::from_dag(&*dag.read().await);
+            if let Some(new_k) = self.step(k, sample, self.clock.now()) {
+                info!(old_k = k, new_k, "k tuner scheduling a k change");
+                let proposal = KProposal::new(new_k, KChangeSource::Tuner).with_reason(format!(
+                    "{} controller at {} blocks, {} red",
+                    self.controller.name(),
+                    sample.blocks,
+                    sample.red_blocks
+                ));
+                if let Err(e) = agent.propose_k(proposal, None).await {
+                    warn!("k tuner could not reach the agent: {e}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use argus_ghostdag::block::{BlockHash, BlockHeader};
+    use argus_ghostdag::coloring::color_dag;
+
+    fn obs(k: u64, red_blocks: u64) -> KObservation {
+        KObservation {
+            k,
+            blocks: 100,
+            red_blocks,
+        }
+    }
+
+    #[test]
+    fn test_sample_counts_red_blocks() {
+        // G with five parallel children: with k = 1 at most two are blue.
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(BlockHash::from_byte(0), 0))
+            .unwrap();
+        for i in 1..=5 {
+            dag.add_block(BlockHeader::new(
+                BlockHash::from_byte(i),
+                vec![BlockHash::from_byte(0)],
+                1,
+            ))
+            .unwrap();
+        }
+        let coloring = color_dag(&mut dag, 1).unwrap();
+        let sample = DagSample::from_dag(&dag);
+        assert_eq!(sample.blocks, 6);
+        assert_eq!(sample.red_blocks, coloring.red_set.len() as u64);
+        assert!(sample.red_blocks > 0);
+    }
+
+    #[test]
+    fn test_pid_moves_k_towards_target() {
+        let mut pid = PidController::new(0.05);
+        // 30% red: k must rise.
+        assert!(pid.propose(&obs(4, 30)).unwrap() > 4);
+        // No reds at all: k comes down.
+        let mut pid = PidController::new(0.05);
+        assert!(pid.propose(&obs(10, 0)).unwrap() < 10);
+        // On target: no change.
+        let mut pid = PidController::new(0.05);
+        assert_eq!(pid.propose(&obs(6, 5)), None);
+    }
+
+    #[test]
+    fn test_bandit_explores_then_exploits() {
+        let mut bandit = BanditController::new(2, 4, 0.05);
+        // Every arm is tried once before any is repeated.
+        let mut k = 2;
+        let mut tried = vec![k];
+        for _ in 0..2 {
+            k = bandit.propose(&obs(k, if k == 3 { 5 } else { 40 })).unwrap();
+            tried.push(k);
+        }
+        tried.sort();
+        assert_eq!(tried, vec![2, 3, 4]);
+
+        // k = 3 hits the target; the bandit settles on it.
+        let mut picks = Vec::new();
+        for _ in 0..30 {
+            let red = if k == 3 { 5 } else { 40 };
+            k = bandit.propose(&obs(k, red)).unwrap_or(k);
+            picks.push(k);
+        }
+        let count = |k| picks.iter().filter(|&&p| p == k).count();
+        assert!(count(3) > count(2) + count(4), "picks: {picks:?}");
+        assert!(bandit.mean_reward(3).unwrap() > bandit.mean_reward(4).unwrap());
+    }
+
+    #[test]
+    fn test_tuner_applies_limits() {
+        let limits = KLimits {
+            min_k: 2,
+            max_k: 8,
+            max_step: 1,
+            min_interval: Duration::from_secs(60),
+            min_blocks: 10,
+        };
+        let mut tuner = KTuner::new(Box::new(PidController::new(0.05)), limits);
+        let t0 = Instant::now();
+        let sample = |blocks, red_blocks| DagSample { blocks, red_blocks };
+
+        // The first sample only opens the window; then too few blocks.
+        assert_eq!(tuner.step(4, sample(100, 0), t0), None);
+        assert_eq!(tuner.step(4, sample(105, 5), t0), None);
+        // 50% red over 20 blocks: PID wants a big jump, limited to +1.
+        assert_eq!(tuner.step(4, sample(120, 10), t0), Some(5));
+        // Rate limit: nothing within a minute of the last change.
+        assert_eq!(
+            tuner.step(5, sample(140, 20), t0 + Duration::from_secs(30)),
+            None
+        );
+        assert_eq!(
+            tuner.step(5, sample(160, 30), t0 + Duration::from_secs(61)),
+            Some(6)
+        );
+        // Upper bound.
+        assert_eq!(KLimits::default().clamp(64, 80), 64);
+        assert_eq!(
+            KLimits {
+                min_k: 2,
+                ..Default::default()
+            }
+            .clamp(2, 1),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tuner_samples_on_its_clock() {
+        use crate::channels::{command_channel, AgentCommand};
+        use crate::clock::ManualClock;
+
+        let clock = ManualClock::default().shared();
+        let tuner = KTuner::new(Box::new(PidController::new(0.05)), KLimits::default())
+            .with_clock(clock.clone());
+        let (cmd_tx, mut cmd_rx) = command_channel(8);
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let task = tokio::spawn(tuner.run(
+            Arc::new(RwLock::new(DagStore::new())),
+            AgentHandle::new(cmd_tx),
+            Duration::from_secs(60),
+            shutdown_rx,
+        ));
+
+        // Real time passing does not wake the tuner; its clock does.
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(cmd_rx.try_recv().is_err());
+        clock.advance(Duration::from_secs(60));
+        let command = tokio::time::timeout(Duration::from_secs(1), cmd_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        match command {
+            AgentCommand::GetK { reply } => reply.send(3).unwrap(),
+            other => panic!("Expected GetK, got {other:?}"),
+        }
+
+        shutdown_tx.send(true).unwrap();
+        task.await.unwrap();
+    }
+}
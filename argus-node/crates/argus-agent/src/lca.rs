@@ -10,9 +10,14 @@
 /// 3. The first hash found in both visited sets is the LCA.
 /// 4. All blocks on the network chain between the LCA and `network_tip`
 ///    that are NOT in the local DAG are the "missing blocks" to recover.
+///
+/// With trusted checkpoints the walk stops at the newest checkpoint both
+/// chains contain (`shared_checkpoint`): that block is common by
+/// definition, so nothing below it needs to be looked at.
 use std::collections::HashSet;
 
 use argus_ghostdag::block::BlockHash;
+use argus_ghostdag::checkpoints::{checkpoint_floor, Checkpoint};
 use argus_ghostdag::dag::DagStore;
 use argus_ghostdag::errors::{GhostDagError, GhostDagResult};
 
@@ -44,6 +49,17 @@ pub fn greedy_path_intersection(
     local_dag: &DagStore,
     local_tip: &BlockHash,
     network_chain: &[BlockHash],
+) -> GhostDagResult<LcaResult> {
+    greedy_path_intersection_bounded(local_dag, local_tip, network_chain, None)
+}
+
+/// `greedy_path_intersection` that never walks below `floor`, a block
+/// both chains are known to contain (see `shared_checkpoint`).
+pub fn greedy_path_intersection_bounded(
+    local_dag: &DagStore,
+    local_tip: &BlockHash,
+    network_chain: &[BlockHash],
+    floor: Option<&Checkpoint>,
 ) -> GhostDagResult<LcaResult> {
     if network_chain.is_empty() {
         return Err(GhostDagError::Internal(
@@ -52,14 +68,24 @@ pub fn greedy_path_intersection(
     }
 
     // Build the local selected-parent chain.
-    let local_chain = build_selected_parent_chain(local_dag, local_tip)?;
+    let local_chain = build_selected_parent_chain(local_dag, local_tip, floor)?;
     let local_set: HashSet<BlockHash> = local_chain.iter().copied().collect();
 
+    let network_start = match floor {
+        Some(cp) => network_chain.iter().position(|h| *h == cp.hash).ok_or_else(|| {
+            GhostDagError::Internal(format!(
+                "network chain does not contain checkpoint {}",
+                cp.hash
+            ))
+        })?,
+        None => 0,
+    };
+
     // Walk the network chain from tip backward to find the LCA.
     let mut lca: Option<BlockHash> = None;
     let mut lca_network_idx: Option<usize> = None;
 
-    for (idx, hash) in network_chain.iter().enumerate().rev() {
+    for (idx, hash) in network_chain.iter().enumerate().skip(network_start).rev() {
         if local_set.contains(hash) {
             lca = Some(*hash);
             lca_network_idx = Some(idx);
@@ -94,17 +120,30 @@ pub fn greedy_path_intersection(
     })
 }
 
-/// Build the selected-parent chain from `tip` back to genesis.
-/// Returns the chain ordered genesis-first.
+/// Build the selected-parent chain from `tip` back to genesis, or back to
+/// `floor` if given.  Returns the chain ordered oldest-first.
 fn build_selected_parent_chain(
     dag: &DagStore,
     tip: &BlockHash,
+    floor: Option<&Checkpoint>,
 ) -> GhostDagResult<Vec<BlockHash>> {
     let mut chain = vec![*tip];
     let mut current = *tip;
 
     loop {
         let hdr = dag.get(&current)?;
+        if let Some(cp) = floor {
+            if hdr.blue_score <= cp.blue_score {
+                if hdr.hash != cp.hash {
+                    return Err(GhostDagError::CheckpointMismatch {
+                        blue_score: cp.blue_score,
+                        expected: cp.hash,
+                        found: hdr.hash,
+                    });
+                }
+                break;
+            }
+        }
         match hdr.selected_parent {
             Some(sp) => {
                 chain.push(sp);
@@ -118,6 +157,23 @@ fn build_selected_parent_chain(
     Ok(chain)
 }
 
+/// The newest checkpoint on both the local and the network selected
+/// chain, to bound the LCA search.  Fails with `CheckpointMismatch` if
+/// either chain contradicts a checkpoint.
+pub fn shared_checkpoint(
+    dag: &DagStore,
+    local_tip: &BlockHash,
+    network_tip: &BlockHash,
+    checkpoints: &[Checkpoint],
+) -> GhostDagResult<Option<Checkpoint>> {
+    let local = checkpoint_floor(dag, local_tip, checkpoints)?;
+    let network = checkpoint_floor(dag, network_tip, checkpoints)?;
+    Ok(match (local, network) {
+        (Some(a), Some(b)) => Some(if a.blue_score <= b.blue_score { a } else { b }),
+        _ => None,
+    })
+}
+
 /// Check whether the local tip diverges from the network chain.
 /// Returns `true` if the local tip is NOT on the network chain.
 pub fn has_diverged(
@@ -195,6 +251,39 @@ mod tests {
         assert_eq!(result.divergence_depth, 0);
     }
 
+    #[test]
+    fn test_lca_bounded_by_checkpoint() {
+        // G → 1 → … → 8, forking at 6 into 107 → 108.
+        let h = BlockHash::from_u64;
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        for i in 1..=8 {
+            dag.add_block(BlockHeader::new(h(i), vec![h(i - 1)], i)).unwrap();
+        }
+        dag.add_block(BlockHeader::new(h(107), vec![h(6)], 7)).unwrap();
+        dag.add_block(BlockHeader::new(h(108), vec![h(107)], 8)).unwrap();
+        color_dag(&mut dag, 3).unwrap();
+
+        let checkpoints = [Checkpoint::new(2, h(2)), Checkpoint::new(5, h(5))];
+        let floor = shared_checkpoint(&dag, &h(8), &h(108), &checkpoints)
+            .unwrap()
+            .unwrap();
+        assert_eq!(floor.hash, h(5));
+
+        // Only the part of the network chain above the checkpoint is needed.
+        let network_chain = vec![h(5), h(6), h(107), h(108)];
+        let bounded =
+            greedy_path_intersection_bounded(&dag, &h(8), &network_chain, Some(&floor)).unwrap();
+        assert_eq!(bounded.lca, h(6));
+        assert_eq!(bounded.divergence_depth, 2);
+
+        // A network chain that skips the checkpoint is rejected.
+        assert!(matches!(
+            shared_checkpoint(&dag, &h(8), &h(108), &[Checkpoint::new(5, h(99))]),
+            Err(GhostDagError::CheckpointMismatch { .. })
+        ));
+    }
+
     #[test]
     fn test_has_diverged() {
         let a = BlockHash::from_byte(1);
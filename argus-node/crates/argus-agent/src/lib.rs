@@ -1,22 +1,79 @@
-/// Argus Agent — Autonomous Self-Healing for GhostDAG nodes.
-///
-/// This crate provides:
-///
-/// - **`state_machine`** — The `GhostDagAgent` state machine (SYNCED / DRIFTING / RECOVERING / PARTITIONED).
-/// - **`lca`** — Greedy Path Intersection for computing the Lowest Common Ancestor.
-/// - **`recovery`** — Async recovery loops for automatic DAG repair.
-/// - **`channels`** — Typed mpsc channel definitions for agent communication.
+//! Argus Agent — Autonomous Self-Healing for GhostDAG nodes.
+//!
+//! This crate provides:
+//!
+//! - **`state_machine`** — The `GhostDagAgent` state machine (SYNCED / DRIFTING / RECOVERING / PARTITIONED).
+//! - **`transitions`** — Explicit transition table with guards, reasons and a bounded history.
+//! - **`k_controller`** — Native adaptive-k controllers (PID, bandit) feeding `UpdateK`.
+//! - **`governance`** — k policy (range, step budget, confidence), audit log and revert.
+//! - **`lca`** — Greedy Path Intersection for computing the Lowest Common Ancestor.
+//! - **`recovery`** — Async recovery loops for automatic DAG repair.
+//! - **`healing`** — Merges both sides of a partition and reports what turned red.
+//! - **`ibd`** — Headers-first initial block download with block locators.
+//! - **`network_tip`** — Aggregates tips from several peers; flags disagreement and eclipses.
+//! - **`orphans`** — Bounded pool of blocks waiting for missing parents.
+//! - **`fetcher`** — `BlockFetcher` sources (JSON-RPC peer, in-memory mock) for missing blocks.
+//! - **`recorder`** — JSON Lines log of agent commands and events, and a deterministic replayer.
+//! - **`clock`** — `Clock` trait with system and manual implementations for deterministic tests.
+//! - **`handle`** — Cloneable `AgentHandle` with async queries to a running agent.
+//! - **`channels`** — Typed mpsc channel definitions for agent communication.
 
 pub mod channels;
+pub mod clock;
+pub mod fetcher;
+pub mod governance;
+pub mod handle;
+pub mod healing;
+pub mod ibd;
+pub mod k_controller;
 pub mod lca;
+pub mod network_tip;
+pub mod orphans;
+pub mod recorder;
 pub mod recovery;
 pub mod state_machine;
+pub mod transitions;
 
 // Re-exports.
 pub use channels::{
-    command_channel, event_channel, AgentCommand, AgentEvent, AgentStateLabel,
+    command_channel, event_channel, AgentCommand, AgentEvent, AgentStateLabel, AgentStatus,
     CommandRx, CommandTx, EventRx, EventTx,
 };
-pub use lca::{greedy_path_intersection, has_diverged, LcaResult};
-pub use recovery::{ingest_and_recolor, RecoveryConfig, RecoveryLoop};
+pub use clock::{system_clock, Clock, ManualClock, SharedClock, SystemClock};
+pub use fetcher::{
+    fetch_with_ancestors, BlockFetcher, FetchError, HeaderRecord, JsonRpcBlockFetcher,
+    MockBlockFetcher,
+};
+pub use governance::{
+    KAuditRecord, KChangeSource, KGovernanceError, KGovernor, KGovernorSnapshot, KPolicy,
+    KProposal, SharedKGovernor, DEFAULT_AUDIT_CAPACITY,
+};
+pub use handle::{AgentHandle, AgentHandleError};
+pub use healing::{diff_chains, heal_partition, ChainDiff, HealingReport, SideReport};
+pub use ibd::{
+    build_locator, find_shared_block, headers_after, run_ibd, validate_batch, IbdError,
+    IbdReport, DEFAULT_IBD_BATCH_SIZE,
+};
+pub use k_controller::{
+    BanditController, DagSample, KController, KControllerKind, KLimits, KObservation, KTuner,
+    KTunerConfig, PidController, DEFAULT_TARGET_RED_RATE,
+};
+pub use lca::{
+    greedy_path_intersection, greedy_path_intersection_bounded, has_diverged, shared_checkpoint,
+    LcaResult,
+};
+pub use network_tip::{
+    JsonRpcTipProvider, MockTipProvider, NetworkTipProvider, PeerInfo, TipAggregator,
+    TipAggregatorConfig, TipAssessment, TipReport,
+};
+pub use orphans::{OrphanPool, DEFAULT_MAX_ORPHANS, DEFAULT_ORPHAN_TTL};
+pub use recorder::{
+    read_recording, replay, AgentStart, EventRecorder, RecordEntry, RecordKind, RecorderError,
+    ReplayReport,
+};
+pub use recovery::{ingest_and_recolor, IngestOutcome, RecoveryConfig, RecoveryLoop};
 pub use state_machine::GhostDagAgent;
+pub use transitions::{
+    check_transition, Guard, TransitionContext, TransitionError, TransitionHistory,
+    TransitionReason, TransitionRecord, TransitionRule, TRANSITIONS,
+};
@@ -0,0 +1,620 @@
+/// Argus Agent — Multi-peer network tip aggregation.
+///
+/// A single `UpdateNetworkTip` source can push the agent into DRIFTING or
+/// PARTITIONED on its own.  `TipAggregator` instead polls several
+/// `NetworkTipProvider`s and derives the network tip from all of them:
+///
+/// 1. Reports older than `max_age` are ignored.
+/// 2. Reports whose blue score is more than `max_score_spread` away from
+///    the median are *outliers*: they take no part in the choice and raise
+///    `AgentEvent::PeerTipDisagreement`.
+/// 3. Among the remaining tips, the one reported by the most peers wins,
+///    provided at least `min_support` peers (by default a majority of the
+///    fresh reports) agree on it.  Self-reported blue work only breaks
+///    ties, so a single peer cannot pick the tip by inflating it.
+///
+/// Each time the chosen tip changes the agent gets a `CheckDivergence`
+/// for it, so a deep fork is declared a partition and healed.
+///
+/// `JsonRpcTipProvider` reads a peer Argus node's bluest tip over its
+/// `get_tips` RPC.
+///
+/// If every fresh report comes from the same `source` (e.g. one operator
+/// or one subnet) the node may be eclipsed, and
+/// `AgentEvent::EclipseSuspected` is raised.  Both alerts fire when the
+/// condition appears, not on every poll.
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use argus_ghostdag::block::BlockHash;
+
+use crate::channels::{AgentCommand, AgentEvent, EventTx};
+use crate::clock::{system_clock, SharedClock};
+use crate::fetcher::{rpc_call, FetchError};
+use crate::handle::AgentHandle;
+
+/// Who reports a tip.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PeerInfo {
+    /// Unique peer identifier.
+    pub id: String,
+    /// Grouping used for eclipse detection (operator, subnet, ASN, …).
+    pub source: String,
+}
+
+impl PeerInfo {
+    pub fn new(id: impl Into<String>, source: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            source: source.into(),
+        }
+    }
+}
+
+/// A peer's view of the network tip.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TipReport {
+    pub tip: BlockHash,
+    pub blue_score: u64,
+    pub blue_work: BigUint,
+}
+
+/// A source of network tip reports.
+#[async_trait]
+pub trait NetworkTipProvider: Send + Sync {
+    /// The peer behind this provider.
+    fn peer(&self) -> &PeerInfo;
+
+    /// The peer's current tip.
+    async fn latest_tip(&self) -> Result<TipReport, FetchError>;
+}
+
+/// Serves a settable tip from memory (tests, simulations).
+#[derive(Debug)]
+pub struct MockTipProvider {
+    peer: PeerInfo,
+    report: Mutex<Option<TipReport>>,
+}
+
+impl MockTipProvider {
+    /// A provider for `peer` with no tip yet.
+    pub fn new(peer: PeerInfo) -> Self {
+        Self {
+            peer,
+            report: Mutex::new(None),
+        }
+    }
+
+    /// Set the reported tip.
+    pub fn set_tip(&self, report: TipReport) {
+        *self.report.lock().unwrap() = Some(report);
+    }
+
+    /// Make the provider fail until a tip is set again.
+    pub fn clear(&self) {
+        *self.report.lock().unwrap() = None;
+    }
+}
+
+#[async_trait]
+impl NetworkTipProvider for MockTipProvider {
+    fn peer(&self) -> &PeerInfo {
+        &self.peer
+    }
+
+    async fn latest_tip(&self) -> Result<TipReport, FetchError> {
+        self.report
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| FetchError::Transport(format!("{} has no tip", self.peer.id)))
+    }
+}
+
+/// Reads the bluest tip of another Argus node over JSON-RPC (`get_tips`).
+#[derive(Debug, Clone)]
+pub struct JsonRpcTipProvider {
+    peer: PeerInfo,
+    addr: SocketAddr,
+    timeout: Duration,
+}
+
+impl JsonRpcTipProvider {
+    /// A provider for the node at `addr`, reported as `peer`, with a 5s
+    /// timeout.
+    pub fn new(peer: PeerInfo, addr: SocketAddr) -> Self {
+        Self {
+            peer,
+            addr,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Set the per-request timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// One entry of a `get_tips` result.
+#[derive(Debug, Deserialize)]
+struct TipRecord {
+    hash: String,
+    blue_score: u64,
+    /// Decimal; absent from older peers.
+    #[serde(default)]
+    blue_work: Option<String>,
+}
+
+impl TipRecord {
+    fn into_report(self) -> Result<TipReport, FetchError> {
+        let decode = |e: String| FetchError::Decode(e);
+        Ok(TipReport {
+            tip: BlockHash::from_hex(&self.hash).map_err(|e| decode(e.to_string()))?,
+            blue_score: self.blue_score,
+            blue_work: match self.blue_work {
+                Some(work) => work.parse().map_err(|e| decode(format!("blue_work: {e}")))?,
+                None => BigUint::ZERO,
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl NetworkTipProvider for JsonRpcTipProvider {
+    fn peer(&self) -> &PeerInfo {
+        &self.peer
+    }
+
+    async fn latest_tip(&self) -> Result<TipReport, FetchError> {
+        let result = rpc_call(self.addr, self.timeout, "get_tips", serde_json::json!({})).await?;
+        let tips: Vec<TipRecord> =
+            serde_json::from_value(result).map_err(|e| FetchError::Decode(e.to_string()))?;
+        let mut best: Option<TipReport> = None;
+        for tip in tips {
+            let report = tip.into_report()?;
+            let better = best.as_ref().is_none_or(|b| {
+                (&report.blue_work, report.blue_score) > (&b.blue_work, b.blue_score)
+            });
+            if better {
+                best = Some(report);
+            }
+        }
+        best.ok_or_else(|| FetchError::Decode(format!("{} reported no tips", self.peer.id)))
+    }
+}
+
+/// Configuration for `TipAggregator`.
+#[derive(Debug, Clone)]
+pub struct TipAggregatorConfig {
+    /// How often `run` polls the providers.
+    pub poll_interval: Duration,
+    /// Reports older than this are ignored.
+    pub max_age: Duration,
+    /// Largest blue-score distance from the median still counted as
+    /// agreement.
+    pub max_score_spread: u64,
+    /// Peers that must report a tip before it can be chosen; `None`
+    /// requires a strict majority of the peers with a fresh report.
+    pub min_support: Option<usize>,
+}
+
+impl Default for TipAggregatorConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            max_age: Duration::from_secs(30),
+            max_score_spread: 10,
+            min_support: None,
+        }
+    }
+}
+
+/// The aggregator's view after a poll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TipAssessment {
+    /// The chosen network tip, if any tip qualified.
+    pub network_tip: Option<BlockHash>,
+    /// Peers that reported the chosen tip.
+    pub supporters: usize,
+    /// Peers with a fresh report.
+    pub peers: usize,
+    /// Median blue score of the fresh reports.
+    pub median_blue_score: u64,
+    /// Peers too far from the median, sorted.
+    pub outliers: Vec<String>,
+    /// Distinct sources among the fresh reports, sorted.
+    pub sources: Vec<String>,
+}
+
+impl TipAssessment {
+    /// Every fresh report comes from a single source.
+    pub fn single_source(&self) -> bool {
+        self.sources.len() == 1
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PeerEntry {
+    peer: PeerInfo,
+    report: TipReport,
+    received_at: Instant,
+}
+
+/// Combines tip reports from several peers into one network tip.
+pub struct TipAggregator {
+    config: TipAggregatorConfig,
+    providers: Vec<Arc<dyn NetworkTipProvider>>,
+    reports: HashMap<String, PeerEntry>,
+    disagreement_alerted: bool,
+    eclipse_alerted: bool,
+    clock: SharedClock,
+}
+
+impl TipAggregator {
+    /// An aggregator with no providers.
+    pub fn new(config: TipAggregatorConfig) -> Self {
+        Self {
+            config,
+            providers: Vec::new(),
+            reports: HashMap::new(),
+            disagreement_alerted: false,
+            eclipse_alerted: false,
+            clock: system_clock(),
+        }
+    }
+
+    /// Poll and age reports on `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Poll `provider` as well.
+    pub fn with_provider(mut self, provider: Arc<dyn NetworkTipProvider>) -> Self {
+        self.providers.push(provider);
+        self
+    }
+
+    /// Record a report from `peer` received at `now`, replacing the
+    /// peer's previous one.
+    pub fn record(&mut self, peer: PeerInfo, report: TipReport, now: Instant) {
+        self.reports.insert(
+            peer.id.clone(),
+            PeerEntry {
+                peer,
+                report,
+                received_at: now,
+            },
+        );
+    }
+
+    /// Drop everything `peer_id` reported.
+    pub fn forget(&mut self, peer_id: &str) {
+        self.reports.remove(peer_id);
+    }
+
+    /// Ask every provider for its tip and record the answers.
+    pub async fn poll(&mut self) {
+        for provider in self.providers.clone() {
+            match provider.latest_tip().await {
+                Ok(report) => self.record(provider.peer().clone(), report, self.clock.now()),
+                Err(e) => debug!(peer = %provider.peer().id, "Tip provider failed: {e}"),
+            }
+        }
+    }
+
+    /// Derive the network tip from the reports fresh at `now`.
+    pub fn assess(&self, now: Instant) -> TipAssessment {
+        let fresh: Vec<&PeerEntry> = self
+            .reports
+            .values()
+            .filter(|e| now.saturating_duration_since(e.received_at) <= self.config.max_age)
+            .collect();
+
+        let mut scores: Vec<u64> = fresh.iter().map(|e| e.report.blue_score).collect();
+        scores.sort_unstable();
+        let median_blue_score = scores.get(scores.len() / 2).copied().unwrap_or(0);
+
+        let (agreeing, outlying): (Vec<&PeerEntry>, Vec<&PeerEntry>) =
+            fresh.iter().partition(|e| {
+                e.report.blue_score.abs_diff(median_blue_score) <= self.config.max_score_spread
+            });
+        let mut outliers: Vec<String> = outlying.iter().map(|e| e.peer.id.clone()).collect();
+        outliers.sort();
+
+        let mut support: HashMap<BlockHash, (usize, &BigUint)> = HashMap::new();
+        for e in &agreeing {
+            let entry = support
+                .entry(e.report.tip)
+                .or_insert((0, &e.report.blue_work));
+            entry.0 += 1;
+            entry.1 = entry.1.max(&e.report.blue_work);
+        }
+        let min_support = self.config.min_support.unwrap_or(fresh.len() / 2 + 1);
+        let chosen = support
+            .into_iter()
+            .filter(|(_, (count, _))| *count >= min_support)
+            .max_by(|(ha, (ca, wa)), (hb, (cb, wb))| {
+                ca.cmp(cb).then(wa.cmp(wb)).then(hb.cmp(ha))
+            });
+
+        let sources: BTreeSet<String> = fresh.iter().map(|e| e.peer.source.clone()).collect();
+
+        TipAssessment {
+            network_tip: chosen.map(|(tip, _)| tip),
+            supporters: chosen.map_or(0, |(_, (count, _))| count),
+            peers: fresh.len(),
+            median_blue_score,
+            outliers,
+            sources: sources.into_iter().collect(),
+        }
+    }
+
+    /// Alerts for `assessment` that were not already raised.
+    pub fn alerts(&mut self, assessment: &TipAssessment) -> Vec<AgentEvent> {
+        let mut events = Vec::new();
+
+        let disagreement = !assessment.outliers.is_empty();
+        if disagreement && !self.disagreement_alerted {
+            warn!(outliers = ?assessment.outliers, "Peers disagree on the network tip");
+            events.push(AgentEvent::PeerTipDisagreement {
+                median_blue_score: assessment.median_blue_score,
+                outliers: assessment.outliers.clone(),
+            });
+        }
+        self.disagreement_alerted = disagreement;
+
+        let eclipse = assessment.single_source();
+        if eclipse && !self.eclipse_alerted {
+            warn!(source = %assessment.sources[0], "All tip reports come from one source");
+            events.push(AgentEvent::EclipseSuspected {
+                source: assessment.sources[0].clone(),
+                peers: assessment.peers,
+            });
+        }
+        self.eclipse_alerted = eclipse;
+
+        events
+    }
+
+    /// Poll the providers every `poll_interval` until shutdown, writing
+    /// the chosen tip to the recovery loop's `network_tip`, asking the
+    /// agent for a divergence check whenever it changes, and emitting
+    /// alerts on `event_tx`.
+    pub async fn run(
+        mut self,
+        agent: AgentHandle,
+        network_tip: Arc<RwLock<Option<BlockHash>>>,
+        event_tx: EventTx,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+    ) {
+        info!(providers = self.providers.len(), "Tip aggregator started");
+        loop {
+            tokio::select! {
+                _ = self.clock.sleep(self.config.poll_interval) => {}
+                Ok(()) = shutdown.changed() => {
+                    if *shutdown.borrow() {
+                        info!("Tip aggregator shutting down");
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            self.poll().await;
+            let assessment = self.assess(self.clock.now());
+            for event in self.alerts(&assessment) {
+                let _ = event_tx.send(event).await;
+            }
+
+            let Some(tip) = assessment.network_tip else {
+                continue;
+            };
+            let changed = network_tip.write().await.replace(tip) != Some(tip);
+            // A new tip is checked at once rather than at the recovery
+            // loop's next tick, so a partition is declared without delay.
+            if changed
+                && agent
+                    .send(AgentCommand::CheckDivergence { network_tip: tip })
+                    .await
+                    .is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(tip: u8, blue_score: u64) -> TipReport {
+        TipReport {
+            tip: BlockHash::from_byte(tip),
+            blue_score,
+            blue_work: BigUint::from(blue_score + 1),
+        }
+    }
+
+    #[test]
+    fn test_choose_by_agreement() {
+        let now = Instant::now();
+        let mut agg = TipAggregator::new(TipAggregatorConfig::default());
+        agg.record(PeerInfo::new("a", "net1"), report(1, 100), now);
+        agg.record(PeerInfo::new("b", "net2"), report(2, 101), now);
+        agg.record(PeerInfo::new("c", "net3"), report(2, 101), now);
+        agg.record(PeerInfo::new("d", "net4"), report(2, 101), now);
+        agg.record(PeerInfo::new("e", "net4"), report(2, 101), now);
+        // A lone peer claiming far more work than everyone else is ignored.
+        agg.record(PeerInfo::new("evil", "net5"), report(9, 5_000), now);
+
+        let assessment = agg.assess(now);
+        assert_eq!(assessment.network_tip, Some(BlockHash::from_byte(2)));
+        assert_eq!(assessment.supporters, 4);
+        assert_eq!(assessment.outliers, vec!["evil".to_string()]);
+        assert!(!assessment.single_source());
+
+        // So is one at the median score with inflated blue work.
+        let mut liar = report(8, 101);
+        liar.blue_work = BigUint::from(u64::MAX);
+        agg.record(PeerInfo::new("liar", "net6"), liar, now);
+        let assessment = agg.assess(now);
+        assert_eq!(assessment.network_tip, Some(BlockHash::from_byte(2)));
+        assert_eq!(assessment.outliers.len(), 1);
+
+        // Without a majority, no tip is chosen.
+        agg.forget("c");
+        agg.forget("d");
+        agg.forget("e");
+        assert_eq!(agg.assess(now).network_tip, None);
+
+        // Requiring two supporters rules out tip 1 even without tip 2.
+        let mut strict = TipAggregator::new(TipAggregatorConfig {
+            min_support: Some(2),
+            ..Default::default()
+        });
+        strict.record(PeerInfo::new("a", "net1"), report(1, 100), now);
+        strict.record(PeerInfo::new("b", "net2"), report(2, 101), now);
+        assert_eq!(strict.assess(now).network_tip, None);
+    }
+
+    #[test]
+    fn test_alerts_fire_once_per_condition() {
+        let now = Instant::now();
+        let mut agg = TipAggregator::new(TipAggregatorConfig::default());
+        agg.record(PeerInfo::new("a", "same"), report(1, 100), now);
+        agg.record(PeerInfo::new("b", "same"), report(1, 100), now);
+        agg.record(PeerInfo::new("c", "same"), report(7, 500), now);
+
+        let assessment = agg.assess(now);
+        let events = agg.alerts(&assessment);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[0],
+            AgentEvent::PeerTipDisagreement { outliers, .. } if outliers == &vec!["c".to_string()]
+        ));
+        assert!(matches!(
+            &events[1],
+            AgentEvent::EclipseSuspected { source, peers: 3 } if source == "same"
+        ));
+        assert!(agg.alerts(&assessment).is_empty());
+
+        // A second source clears the eclipse alert; losing it re-raises it.
+        agg.record(PeerInfo::new("d", "other"), report(1, 100), now);
+        assert!(agg.alerts(&agg.assess(now)).is_empty());
+        agg.forget("d");
+        let events = agg.alerts(&agg.assess(now));
+        assert!(matches!(&events[..], [AgentEvent::EclipseSuspected { .. }]));
+    }
+
+    #[test]
+    fn test_stale_reports_ignored() {
+        let start = Instant::now();
+        let mut agg = TipAggregator::new(TipAggregatorConfig {
+            max_age: Duration::from_secs(5),
+            ..Default::default()
+        });
+        agg.record(PeerInfo::new("old", "net1"), report(1, 100), start);
+        agg.record(
+            PeerInfo::new("new", "net2"),
+            report(2, 90),
+            start + Duration::from_secs(4),
+        );
+        let later = start + Duration::from_secs(8);
+        let assessment = agg.assess(later);
+        assert_eq!(assessment.peers, 1);
+        assert_eq!(assessment.network_tip, Some(BlockHash::from_byte(2)));
+    }
+
+    #[tokio::test]
+    async fn test_run_polls_on_its_clock() {
+        use crate::channels::{command_channel, event_channel, AgentCommand};
+        use crate::clock::ManualClock;
+
+        let clock = ManualClock::default().shared();
+        let p1 = Arc::new(MockTipProvider::new(PeerInfo::new("p1", "net1")));
+        p1.set_tip(report(3, 50));
+        let agg = TipAggregator::new(TipAggregatorConfig {
+            poll_interval: Duration::from_secs(60),
+            ..Default::default()
+        })
+        .with_provider(p1)
+        .with_clock(clock.clone());
+
+        let (cmd_tx, mut cmd_rx) = command_channel(8);
+        let (event_tx, _event_rx) = event_channel(8);
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let task = tokio::spawn(agg.run(
+            AgentHandle::new(cmd_tx),
+            Arc::new(RwLock::new(None)),
+            event_tx,
+            shutdown_rx,
+        ));
+
+        // Real time passing does not trigger a poll; the clock does.
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(cmd_rx.try_recv().is_err());
+        clock.advance(Duration::from_secs(60));
+        let command = tokio::time::timeout(Duration::from_secs(1), cmd_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(command, AgentCommand::CheckDivergence { .. }));
+
+        shutdown_tx.send(true).unwrap();
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_feeds_agent_from_mock_providers() {
+        use crate::channels::{command_channel, event_channel, AgentCommand};
+
+        let p1 = Arc::new(MockTipProvider::new(PeerInfo::new("p1", "net1")));
+        let p2 = Arc::new(MockTipProvider::new(PeerInfo::new("p2", "net1")));
+        p1.set_tip(report(3, 50));
+        p2.set_tip(report(3, 50));
+        let agg = TipAggregator::new(TipAggregatorConfig {
+            poll_interval: Duration::from_millis(5),
+            ..Default::default()
+        })
+        .with_provider(p1)
+        .with_provider(p2);
+
+        let (cmd_tx, mut cmd_rx) = command_channel(8);
+        let (event_tx, mut event_rx) = event_channel(8);
+        let network_tip = Arc::new(RwLock::new(None));
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let task = tokio::spawn(agg.run(
+            AgentHandle::new(cmd_tx),
+            network_tip.clone(),
+            event_tx,
+            shutdown_rx,
+        ));
+
+        match cmd_rx.recv().await.unwrap() {
+            AgentCommand::CheckDivergence { network_tip } => {
+                assert_eq!(network_tip, BlockHash::from_byte(3))
+            }
+            other => panic!("Expected CheckDivergence, got {other:?}"),
+        }
+        assert_eq!(*network_tip.read().await, Some(BlockHash::from_byte(3)));
+        assert!(matches!(
+            event_rx.recv().await.unwrap(),
+            AgentEvent::EclipseSuspected { peers: 2, .. }
+        ));
+
+        shutdown_tx.send(true).unwrap();
+        task.await.unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+/// Argus Agent — Orphan block pool.
+///
+/// Peers deliver blocks out of order; a block whose parents are not yet
+/// local is an *orphan*, not an error.  Orphans wait here, indexed by each
+/// missing parent, until that parent is inserted, at which point
+/// `take_ready` hands back the children that have become insertable.
+///
+/// The pool is bounded: it holds at most `max_orphans` blocks (evicting the
+/// oldest first) and drops entries older than `max_age` on `expire`.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use argus_ghostdag::block::{BlockHash, BlockHeader};
+use argus_ghostdag::dag::DagStore;
+
+/// Default maximum number of orphans held.
+pub const DEFAULT_MAX_ORPHANS: usize = 1024;
+
+/// Default orphan lifetime.
+pub const DEFAULT_ORPHAN_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone)]
+struct OrphanEntry {
+    header: BlockHeader,
+    received_at: Instant,
+    /// Parents that were missing when the orphan was added.
+    missing: HashSet<BlockHash>,
+}
+
+/// Blocks waiting for missing parents.
+#[derive(Debug, Clone)]
+pub struct OrphanPool {
+    orphans: HashMap<BlockHash, OrphanEntry>,
+    /// Missing parent → orphans waiting on it.
+    waiting_on: HashMap<BlockHash, HashSet<BlockHash>>,
+    /// Insertion order, for eviction and expiry.  May hold stale entries
+    /// for orphans that were already released, but never more than
+    /// `max_orphans` of them (see `prune_arrival`).
+    arrival: VecDeque<(BlockHash, Instant)>,
+    max_orphans: usize,
+    max_age: Duration,
+}
+
+impl OrphanPool {
+    /// Create a pool holding at most `max_orphans` blocks for `max_age`.
+    pub fn new(max_orphans: usize, max_age: Duration) -> Self {
+        Self {
+            orphans: HashMap::new(),
+            waiting_on: HashMap::new(),
+            arrival: VecDeque::new(),
+            max_orphans: max_orphans.max(1),
+            max_age,
+        }
+    }
+
+    /// Number of orphans held.
+    pub fn len(&self) -> usize {
+        self.orphans.len()
+    }
+
+    /// Returns `true` if no orphans are held.
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty()
+    }
+
+    /// Returns `true` if `hash` is waiting in the pool.
+    pub fn contains(&self, hash: &BlockHash) -> bool {
+        self.orphans.contains_key(hash)
+    }
+
+    /// Add an orphan received at `now`, waiting on `missing` parents.
+    /// Evicts the oldest orphan if the pool is full.  Returns the hashes
+    /// of any evicted orphans.
+    pub fn add(
+        &mut self,
+        header: BlockHeader,
+        missing: impl IntoIterator<Item = BlockHash>,
+        now: Instant,
+    ) -> Vec<BlockHash> {
+        let hash = header.hash;
+        if self.orphans.contains_key(&hash) {
+            return Vec::new();
+        }
+
+        let mut evicted = Vec::new();
+        while self.orphans.len() >= self.max_orphans {
+            match self.pop_oldest() {
+                Some(old) => evicted.push(old),
+                None => break,
+            }
+        }
+
+        let missing: HashSet<BlockHash> = missing.into_iter().collect();
+        for p in &missing {
+            self.waiting_on.entry(*p).or_default().insert(hash);
+        }
+        self.orphans.insert(
+            hash,
+            OrphanEntry {
+                header,
+                received_at: now,
+                missing,
+            },
+        );
+        self.arrival.push_back((hash, now));
+        evicted
+    }
+
+    /// `parent` has been inserted into `dag`: remove and return the orphans
+    /// that waited on it and now have all parents present.  Orphans still
+    /// missing other parents stay in the pool.
+    pub fn take_ready(&mut self, parent: &BlockHash, dag: &DagStore) -> Vec<BlockHeader> {
+        let Some(children) = self.waiting_on.remove(parent) else {
+            return Vec::new();
+        };
+        let mut ready = Vec::new();
+        for child in children {
+            let Some(entry) = self.orphans.get_mut(&child) else {
+                continue;
+            };
+            entry.missing.remove(parent);
+            if entry.header.parents.iter().all(|p| dag.contains(p)) {
+                if let Some(entry) = self.remove(&child) {
+                    ready.push(entry.header);
+                }
+            }
+        }
+        ready
+    }
+
+    /// Drop orphans received more than `max_age` before `now`.  Returns
+    /// the number of expired orphans.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let mut expired = 0;
+        while let Some(&(hash, at)) = self.arrival.front() {
+            if now.saturating_duration_since(at) <= self.max_age {
+                break;
+            }
+            self.arrival.pop_front();
+            if self.orphans.get(&hash).is_some_and(|e| e.received_at == at) {
+                self.remove(&hash);
+                expired += 1;
+            }
+        }
+        if expired > 0 {
+            debug!(expired, remaining = self.len(), "Expired orphan blocks");
+        }
+        expired
+    }
+
+    /// The missing ancestors to request: parents some orphan waits on that
+    /// are neither local nor themselves orphans.  Sorted for determinism.
+    pub fn missing_ancestors(&self, dag: &DagStore) -> Vec<BlockHash> {
+        let mut out: Vec<BlockHash> = self
+            .waiting_on
+            .keys()
+            .filter(|p| !dag.contains(p) && !self.orphans.contains_key(p))
+            .copied()
+            .collect();
+        out.sort();
+        out
+    }
+
+    fn remove(&mut self, hash: &BlockHash) -> Option<OrphanEntry> {
+        let entry = self.orphans.remove(hash)?;
+        for p in &entry.missing {
+            if let Some(set) = self.waiting_on.get_mut(p) {
+                set.remove(hash);
+                if set.is_empty() {
+                    self.waiting_on.remove(p);
+                }
+            }
+        }
+        self.prune_arrival();
+        Some(entry)
+    }
+
+    /// Drop stale `arrival` entries from the front, and compact the queue
+    /// once stale entries in the middle outnumber the live ones.
+    fn prune_arrival(&mut self) {
+        fn is_live(
+            orphans: &HashMap<BlockHash, OrphanEntry>,
+            (hash, at): &(BlockHash, Instant),
+        ) -> bool {
+            orphans.get(hash).is_some_and(|e| e.received_at == *at)
+        }
+        while let Some(front) = self.arrival.front() {
+            if is_live(&self.orphans, front) {
+                break;
+            }
+            self.arrival.pop_front();
+        }
+        if self.arrival.len() > 2 * self.max_orphans {
+            let orphans = &self.orphans;
+            self.arrival.retain(|entry| is_live(orphans, entry));
+        }
+    }
+
+    fn pop_oldest(&mut self) -> Option<BlockHash> {
+        while let Some((hash, at)) = self.arrival.pop_front() {
+            if self.orphans.get(&hash).is_some_and(|e| e.received_at == at) {
+                self.remove(&hash);
+                return Some(hash);
+            }
+        }
+        None
+    }
+}
+
+impl Default for OrphanPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ORPHANS, DEFAULT_ORPHAN_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h(b: u8) -> BlockHash {
+        BlockHash::from_byte(b)
+    }
+
+    #[test]
+    fn test_children_released_when_parents_arrive() {
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        let now = Instant::now();
+
+        // C has parents A and B; both missing.
+        let mut pool = OrphanPool::default();
+        pool.add(BlockHeader::new(h(3), vec![h(1), h(2)], 3), [h(1), h(2)], now);
+        assert_eq!(pool.missing_ancestors(&dag), vec![h(1), h(2)]);
+
+        dag.add_block(BlockHeader::new(h(1), vec![h(0)], 1)).unwrap();
+        assert!(pool.take_ready(&h(1), &dag).is_empty());
+        assert!(pool.contains(&h(3)));
+
+        dag.add_block(BlockHeader::new(h(2), vec![h(0)], 1)).unwrap();
+        let ready = pool.take_ready(&h(2), &dag);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].hash, h(3));
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_missing_ancestors_skip_orphans() {
+        let dag = DagStore::new();
+        let now = Instant::now();
+        let mut pool = OrphanPool::default();
+        // B waits on A, A waits on X: only X needs requesting.
+        pool.add(BlockHeader::new(h(2), vec![h(1)], 2), [h(1)], now);
+        pool.add(BlockHeader::new(h(1), vec![h(9)], 1), [h(9)], now);
+        assert_eq!(pool.missing_ancestors(&dag), vec![h(9)]);
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let now = Instant::now();
+        let mut pool = OrphanPool::new(2, DEFAULT_ORPHAN_TTL);
+        pool.add(BlockHeader::new(h(1), vec![h(9)], 1), [h(9)], now);
+        pool.add(BlockHeader::new(h(2), vec![h(9)], 2), [h(9)], now);
+        let evicted = pool.add(BlockHeader::new(h(3), vec![h(9)], 3), [h(9)], now);
+        assert_eq!(evicted, vec![h(1)]);
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&h(1)));
+    }
+
+    #[test]
+    fn test_expire_by_age() {
+        let start = Instant::now();
+        let mut pool = OrphanPool::new(16, Duration::from_secs(10));
+        pool.add(BlockHeader::new(h(1), vec![h(9)], 1), [h(9)], start);
+        pool.add(
+            BlockHeader::new(h(2), vec![h(9)], 2),
+            [h(9)],
+            start + Duration::from_secs(5),
+        );
+
+        assert_eq!(pool.expire(start + Duration::from_secs(10)), 0);
+        assert_eq!(pool.expire(start + Duration::from_secs(11)), 1);
+        assert!(pool.contains(&h(2)));
+        assert_eq!(pool.expire(start + Duration::from_secs(16)), 1);
+        assert!(pool.is_empty());
+        assert!(pool.missing_ancestors(&DagStore::new()).is_empty());
+    }
+
+    #[test]
+    fn test_released_orphans_do_not_accumulate() {
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        let now = Instant::now();
+        let mut pool = OrphanPool::new(4, DEFAULT_ORPHAN_TTL);
+        // One orphan stays put while many others come and go behind it.
+        pool.add(BlockHeader::new(h(255), vec![h(254)], 9), [h(254)], now);
+        for i in 1..=100u8 {
+            let parent = BlockHash::from_u64(1_000 + i as u64);
+            pool.add(BlockHeader::new(h(i), vec![parent], 2), [parent], now);
+            dag.add_block(BlockHeader::new(parent, vec![h(0)], 1)).unwrap();
+            assert_eq!(pool.take_ready(&parent, &dag).len(), 1);
+            assert!(pool.arrival.len() <= 2 * pool.max_orphans);
+        }
+        assert_eq!(pool.len(), 1);
+        assert!(pool.contains(&h(255)));
+    }
+}
@@ -0,0 +1,537 @@
+/// Argus Agent — Event recorder and replayer.
+///
+/// An agent built `with_recorder` appends to a JSON Lines file:
+///
+/// 1. one `start` entry with its state, tips, consensus parameters, k
+///    schedule, governor policy and audit log, and the DAG it started from
+///    (headers in `DagStore::insertion_order`),
+/// 2. every command it processes, in processing order,
+/// 3. every event it emits.
+///
+/// ```text
+/// {"seq":1,"timestamp_ms":…,"start":{"state":"Synced",…,"dag":[…]}}
+/// {"seq":2,"timestamp_ms":…,"command":{"CheckDivergence":{…}}}
+/// {"seq":3,"timestamp_ms":…,"event":{"StateChanged":{…}}}
+/// ```
+///
+/// Queries are read-only and not recorded, except `QueryDivergence`, which
+/// runs a divergence check and is recorded as `CheckDivergence`.
+///
+/// `replay` rebuilds the recorded DAG, k schedule and governor, feeds the
+/// recorded commands to a fresh `GhostDagAgent` one at a time on a
+/// `ManualClock` set to each command's `timestamp_ms`, and compares what
+/// it emits with the recorded events.  Blocks a fetcher returned during
+/// recovery are not part of the trace; pass a fetcher to `replay` if the
+/// incident depends on them.
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use argus_ghostdag::block::BlockHash;
+use argus_ghostdag::coloring::color_dag_scheduled;
+use argus_ghostdag::dag::DagStore;
+use argus_ghostdag::errors::GhostDagError;
+use argus_ghostdag::params::ConsensusParams;
+use argus_ghostdag::schedule::KSchedule;
+
+use crate::channels::{command_channel, event_channel, AgentCommand, AgentEvent, AgentStateLabel};
+use crate::clock::{system_clock, ManualClock, SharedClock};
+use crate::fetcher::{BlockFetcher, HeaderRecord};
+use crate::governance::{KGovernor, KGovernorSnapshot};
+use crate::handle::AgentHandle;
+use crate::state_machine::GhostDagAgent;
+
+/// Errors from reading or replaying a recording.
+#[derive(Debug, Error)]
+pub enum RecorderError {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("line {line}: {source}")]
+    Parse {
+        line: usize,
+        source: serde_json::Error,
+    },
+
+    #[error("recording has no start entry")]
+    MissingStart,
+
+    #[error("cannot rebuild the recorded DAG: {0}")]
+    Dag(#[from] GhostDagError),
+
+    #[error("cannot decode recorded header: {0}")]
+    Header(String),
+}
+
+/// What the agent looked like when recording began.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStart {
+    pub state: AgentStateLabel,
+    pub local_tip: BlockHash,
+    pub network_tip: Option<BlockHash>,
+    pub params: ConsensusParams,
+    /// Every scheduled k change; absent from older recordings, which
+    /// replay with `params.k` throughout.
+    #[serde(default)]
+    pub k_schedule: Option<KSchedule>,
+    /// The governor's policy and audit log, if the agent had one.
+    #[serde(default)]
+    pub governor: Option<KGovernorSnapshot>,
+    /// The DAG, in an order that reproduces it exactly when re-inserted.
+    pub dag: Vec<HeaderRecord>,
+}
+
+/// Payload of one recorded line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Start(AgentStart),
+    Command(AgentCommand),
+    Event(AgentEvent),
+}
+
+/// One line of a recording.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordEntry {
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    #[serde(flatten)]
+    pub kind: RecordKind,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum RecordRef<'a> {
+    Start(&'a AgentStart),
+    Command(&'a AgentCommand),
+    Event(&'a AgentEvent),
+}
+
+#[derive(Serialize)]
+struct EntryRef<'a> {
+    seq: u64,
+    timestamp_ms: u64,
+    #[serde(flatten)]
+    kind: RecordRef<'a>,
+}
+
+struct RecorderInner {
+    writer: Box<dyn Write + Send>,
+    next_seq: u64,
+}
+
+/// Appends agent commands and events to a JSON Lines sink.  Cloning shares
+/// the sink.  Write failures are logged, never propagated to the agent.
+#[derive(Clone)]
+pub struct EventRecorder {
+    inner: Arc<Mutex<RecorderInner>>,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for EventRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventRecorder").finish_non_exhaustive()
+    }
+}
+
+impl EventRecorder {
+    /// Append to the file at `path`, creating it if needed.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::from_writer(BufWriter::new(file)))
+    }
+
+    /// Record into any writer.
+    pub fn from_writer(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(RecorderInner {
+                writer: Box::new(writer),
+                next_seq: 1,
+            })),
+            clock: system_clock(),
+        }
+    }
+
+    /// Timestamp entries with `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record the agent's starting point.
+    pub fn record_start(&self, start: &AgentStart) {
+        self.write(RecordRef::Start(start));
+    }
+
+    /// Record a command the agent is about to process.
+    pub fn record_command(&self, cmd: &AgentCommand) {
+        match cmd {
+            AgentCommand::QueryDivergence { network_tip, .. } => {
+                let check = AgentCommand::CheckDivergence {
+                    network_tip: *network_tip,
+                };
+                self.write(RecordRef::Command(&check));
+            }
+            AgentCommand::GetState { .. }
+            | AgentCommand::GetLocalTip { .. }
+            | AgentCommand::GetNetworkTip { .. }
+            | AgentCommand::GetK { .. }
+            | AgentCommand::GetHistory { .. }
+            | AgentCommand::GetStatus { .. } => {}
+            other => self.write(RecordRef::Command(other)),
+        }
+    }
+
+    /// Record an event the agent emitted.
+    pub fn record_event(&self, event: &AgentEvent) {
+        self.write(RecordRef::Event(event));
+    }
+
+    fn write(&self, kind: RecordRef<'_>) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        let entry = EntryRef {
+            seq: inner.next_seq,
+            timestamp_ms: self.clock.unix_millis(),
+            kind,
+        };
+        inner.next_seq += 1;
+        let result = serde_json::to_writer(&mut inner.writer, &entry)
+            .map_err(io::Error::from)
+            .and_then(|()| inner.writer.write_all(b"\n"))
+            .and_then(|()| inner.writer.flush());
+        if let Err(e) = result {
+            warn!("Failed to record agent entry: {e}");
+        }
+    }
+}
+
+/// Read a recording written by `EventRecorder`.
+pub fn read_recording(path: impl AsRef<Path>) -> Result<Vec<RecordEntry>, RecorderError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .map_err(|source| RecorderError::Parse { line: i + 1, source })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Outcome of `replay`.
+#[derive(Debug)]
+pub struct ReplayReport {
+    /// Commands fed to the agent.
+    pub commands: usize,
+    /// Events in the recording.
+    pub expected: Vec<AgentEvent>,
+    /// Events the replayed agent emitted.
+    pub actual: Vec<AgentEvent>,
+    /// Index of the first event that differs (or is missing on one side).
+    pub first_mismatch: Option<usize>,
+}
+
+impl ReplayReport {
+    /// Returns `true` if the replay emitted exactly the recorded events.
+    pub fn is_faithful(&self) -> bool {
+        self.first_mismatch.is_none()
+    }
+}
+
+/// Replay the first recorded session in `entries` (up to the next `start`)
+/// against a fresh agent and compare the events it emits.
+pub async fn replay(
+    entries: Vec<RecordEntry>,
+    fetcher: Option<Arc<dyn BlockFetcher>>,
+) -> Result<ReplayReport, RecorderError> {
+    let mut entries = entries.into_iter();
+    let (started_ms, start) = entries
+        .by_ref()
+        .find_map(|e| match e.kind {
+            RecordKind::Start(start) => Some((e.timestamp_ms, start)),
+            _ => None,
+        })
+        .ok_or(RecorderError::MissingStart)?;
+
+    let mut commands = Vec::new();
+    let mut expected = Vec::new();
+    for entry in entries {
+        match entry.kind {
+            RecordKind::Start(_) => break,
+            RecordKind::Command(cmd) => commands.push((entry.timestamp_ms, cmd)),
+            RecordKind::Event(event) => expected.push(event),
+        }
+    }
+
+    let mut dag = DagStore::new();
+    for record in start.dag {
+        let header = record
+            .into_header()
+            .map_err(|e| RecorderError::Header(e.to_string()))?;
+        if header.is_genesis() {
+            dag.add_genesis(header)?;
+        } else {
+            dag.add_block(header)?;
+        }
+    }
+    let schedule = start
+        .k_schedule
+        .unwrap_or_else(|| KSchedule::new(start.params.k));
+    color_dag_scheduled(&mut dag, &schedule)?;
+
+    let clock = ManualClock::new(started_ms).shared();
+    let (cmd_tx, cmd_rx) = command_channel(commands.len() + 1);
+    let (event_tx, mut event_rx) = event_channel(expected.len().max(16));
+    let mut agent = GhostDagAgent::new(
+        Arc::new(RwLock::new(dag)),
+        start.local_tip,
+        start.params,
+        cmd_rx,
+        event_tx,
+    )
+    .with_k_schedule(schedule)
+    .with_initial_state(start.state, start.network_tip)
+    .with_clock(clock.clone());
+    if let Some(fetcher) = fetcher {
+        agent = agent.with_fetcher(fetcher);
+    }
+    if let Some(snapshot) = start.governor {
+        let governor = KGovernor::restore(snapshot).with_clock(clock.clone());
+        agent = agent.with_governor(governor.shared());
+    }
+    let collector = tokio::spawn(async move {
+        let mut events = Vec::new();
+        while let Some(event) = event_rx.recv().await {
+            events.push(event);
+        }
+        events
+    });
+
+    let agent = tokio::spawn(agent.run());
+    let handle = AgentHandle::new(cmd_tx);
+    let count = commands.len();
+    for (timestamp_ms, cmd) in commands {
+        clock.set_elapsed(Duration::from_millis(timestamp_ms.saturating_sub(started_ms)));
+        if handle.send(cmd).await.is_err() {
+            break;
+        }
+        // Commands run in order: once this is answered, `cmd` has been
+        // handled and the clock may move on.  It fails after `Shutdown`.
+        if handle.state().await.is_err() {
+            break;
+        }
+    }
+    let _ = handle.shutdown().await;
+    drop(handle);
+    let _ = agent.await;
+    let actual = collector.await.unwrap_or_default();
+
+    let as_json = |e: &AgentEvent| serde_json::to_value(e).ok();
+    let first_mismatch = (0..expected.len().max(actual.len())).find(|&i| {
+        expected.get(i).map(as_json) != actual.get(i).map(as_json)
+    });
+    Ok(ReplayReport {
+        commands: count,
+        expected,
+        actual,
+        first_mismatch,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::EventRx;
+    use argus_ghostdag::block::BlockHeader;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("argus-{name}-{}-{nanos}.jsonl", std::process::id()))
+    }
+
+    /// G → A → B on the local side; the network has C and D off A.
+    fn dag() -> DagStore {
+        let h = BlockHash::from_byte;
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        dag.add_block(BlockHeader::new(h(1), vec![h(0)], 1)).unwrap();
+        dag.add_block(BlockHeader::new(h(2), vec![h(1)], 2)).unwrap();
+        color_dag_scheduled(&mut dag, &KSchedule::new(3)).unwrap();
+        dag
+    }
+
+    async fn drain(mut rx: EventRx) -> usize {
+        let mut n = 0;
+        while rx.recv().await.is_some() {
+            n += 1;
+        }
+        n
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let h = BlockHash::from_byte;
+        let path = temp_path("replay");
+        let recorder = EventRecorder::create(&path).unwrap();
+
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, event_rx) = event_channel(32);
+        let agent = GhostDagAgent::new(
+            Arc::new(RwLock::new(dag())),
+            h(2),
+            ConsensusParams::devnet(),
+            cmd_rx,
+            event_tx,
+        )
+        .with_recorder(recorder);
+        let join = tokio::spawn(agent.run());
+        let drained = tokio::spawn(drain(event_rx));
+
+        let handle = AgentHandle::new(cmd_tx);
+        handle.check_divergence(h(9)).await.unwrap();
+        assert_eq!(handle.k().await.unwrap(), 3);
+        handle
+            .submit_blocks(vec![
+                BlockHeader::new(h(4), vec![h(3)], 3),
+                BlockHeader::new(h(3), vec![h(1)], 2),
+            ])
+            .await
+            .unwrap();
+        handle.update_k(5, None).await.unwrap();
+        handle.check_divergence(h(2)).await.unwrap();
+        handle.shutdown().await.unwrap();
+        join.await.unwrap();
+        let emitted = drained.await.unwrap();
+
+        let entries = read_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(entries[0].kind, RecordKind::Start(_)));
+        assert!(entries.windows(2).all(|w| w[0].seq < w[1].seq));
+        let recorded_events = entries
+            .iter()
+            .filter(|e| matches!(e.kind, RecordKind::Event(_)))
+            .count();
+        assert_eq!(recorded_events, emitted);
+
+        let report = replay(entries, None).await.unwrap();
+        // Two divergence checks, the blocks, the k update and shutdown;
+        // the `GetK` query is not recorded.
+        assert_eq!(report.commands, 5);
+        assert!(!report.expected.is_empty());
+        assert!(report.is_faithful(), "{report:?}");
+    }
+
+    #[tokio::test]
+    async fn test_replay_restores_schedule_governor_and_time() {
+        use crate::governance::{KChangeSource, KPolicy, KProposal};
+
+        let h = BlockHash::from_byte;
+        let path = temp_path("governed");
+        let clock = ManualClock::new(1_000_000).shared();
+        let recorder = EventRecorder::create(&path).unwrap().with_clock(clock.clone());
+
+        // Before recording: k went 3 → 4, using the whole step budget.
+        let mut schedule = KSchedule::new(3);
+        schedule.schedule(10, 4).unwrap();
+        let mut governor = KGovernor::new(KPolicy {
+            max_step: 1,
+            step_interval: Duration::from_secs(60),
+            ..Default::default()
+        })
+        .with_clock(clock.clone());
+        governor.record(3, KProposal::new(4, KChangeSource::Rpc), 10);
+
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, event_rx) = event_channel(32);
+        let agent = GhostDagAgent::new(
+            Arc::new(RwLock::new(dag())),
+            h(2),
+            ConsensusParams::devnet(),
+            cmd_rx,
+            event_tx,
+        )
+        .with_k_schedule(schedule.clone())
+        .with_governor(governor.shared())
+        .with_clock(clock.clone())
+        .with_recorder(recorder);
+        let join = tokio::spawn(agent.run());
+        let drained = tokio::spawn(drain(event_rx));
+
+        // Refused inside the step window, accepted once it has passed.
+        let handle = AgentHandle::new(cmd_tx);
+        handle.update_k(5, Some(20)).await.unwrap();
+        assert_eq!(handle.status().await.unwrap().scheduled_k, 4);
+        clock.advance(Duration::from_secs(61));
+        handle.update_k(5, Some(20)).await.unwrap();
+        assert_eq!(handle.status().await.unwrap().scheduled_k, 5);
+        handle.shutdown().await.unwrap();
+        join.await.unwrap();
+        drained.await.unwrap();
+
+        let entries = read_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let RecordKind::Start(start) = &entries[0].kind else {
+            panic!("recording does not begin with a start entry");
+        };
+        assert_eq!(start.k_schedule.as_ref(), Some(&schedule));
+        assert_eq!(start.governor.as_ref().unwrap().audit.len(), 1);
+
+        let report = replay(entries, None).await.unwrap();
+        assert!(matches!(report.actual[0], AgentEvent::Error { .. }));
+        assert!(matches!(report.actual[1], AgentEvent::KScheduled { new_k: 5, .. }));
+        assert!(report.is_faithful(), "{report:?}");
+    }
+
+    #[tokio::test]
+    async fn test_replay_detects_divergence() {
+        let h = BlockHash::from_byte;
+        let recorder_path = temp_path("mismatch");
+        let recorder = EventRecorder::create(&recorder_path).unwrap();
+        let start = AgentStart {
+            state: AgentStateLabel::Synced,
+            local_tip: h(2),
+            network_tip: None,
+            params: ConsensusParams::devnet(),
+            k_schedule: None,
+            governor: None,
+            dag: {
+                let dag = dag();
+                dag.insertion_order()
+                    .unwrap()
+                    .iter()
+                    .map(|hash| HeaderRecord::from_header(dag.get(hash).unwrap()))
+                    .collect()
+            },
+        };
+        recorder.record_start(&start);
+        recorder.record_command(&AgentCommand::CheckDivergence { network_tip: h(9) });
+        // Claim the agent stayed quiet: the replay will drift and say so.
+        let entries = read_recording(&recorder_path).unwrap();
+        std::fs::remove_file(&recorder_path).unwrap();
+
+        let report = replay(entries, None).await.unwrap();
+        assert_eq!(report.first_mismatch, Some(0));
+        assert!(!report.is_faithful());
+
+        assert!(matches!(
+            replay(Vec::new(), None).await,
+            Err(RecorderError::MissingStart)
+        ));
+    }
+}
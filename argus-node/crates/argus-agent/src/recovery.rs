@@ -6,45 +6,121 @@
 /// The recovery loop:
 /// 1. Periodically checks local tip vs. network tip.
 /// 2. If divergence is detected, computes the LCA.
-/// 3. Fetches missing blocks within the anticone(B) threshold k.
+/// 3. Asks the agent to fetch the missing blocks (via its `BlockFetcher`).
 /// 4. Re-colors the DAG and transitions back to SYNCED.
+///
+/// Divergence deeper than the partition threshold is not recovered here:
+/// the loop sends the agent a `CheckDivergence`, and the agent declares
+/// the partition and heals it.
+///
+/// Attempts are grouped into *sessions*.  A session starts when divergence
+/// is first seen and ends when the agent is SYNCED again, after
+/// `max_attempts` failed attempts, or at its deadline — whichever comes
+/// first.  Failed attempts are retried with exponential backoff and
+/// jitter, and only one session runs at a time: the loop waits for each
+/// attempt's outcome before doing anything else.  Ticks, backoff and
+/// deadlines all run on the loop's `Clock` (see `with_clock`).
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
 use argus_ghostdag::block::{BlockHash, BlockHeader};
-use argus_ghostdag::coloring::{color_dag, selected_parent_chain};
+use argus_ghostdag::coloring::{color_dag_scheduled, selected_parent_chain};
 use argus_ghostdag::dag::DagStore;
+use argus_ghostdag::params::ConsensusParams;
+use argus_ghostdag::schedule::KSchedule;
 
-use crate::channels::{AgentCommand, AgentEvent, AgentStateLabel, CommandTx, EventTx};
-use crate::lca::{greedy_path_intersection, has_diverged, LcaResult};
+use crate::channels::{
+    AgentCommand, AgentEvent, AgentStateLabel, CommandTx, EventTx, RecoveryAttempt,
+};
+use crate::clock::{system_clock, timeout, SharedClock};
+use crate::handle::AgentHandle;
+use crate::lca::{greedy_path_intersection_bounded, has_diverged, shared_checkpoint, LcaResult};
+use crate::orphans::OrphanPool;
 
 /// Configuration for the recovery loop.
 #[derive(Debug, Clone)]
 pub struct RecoveryConfig {
     /// How often to check for divergence (in milliseconds).
     pub check_interval_ms: u64,
-    /// The k parameter for anticone threshold.
-    pub k: u64,
+    /// Consensus parameters (k and the partition threshold).
+    pub params: ConsensusParams,
     /// Maximum blocks to recover in a single pass.
     pub max_recovery_batch: usize,
-    /// Divergence depth multiplier for partition detection (default: 3).
-    pub partition_threshold_multiplier: u64,
+    /// Attempts per session before it is reported as failed.
+    pub max_attempts: u32,
+    /// Delay before the first retry (in milliseconds); doubles per attempt.
+    pub initial_backoff_ms: u64,
+    /// Upper bound on the retry delay (in milliseconds).  Also the pause
+    /// after a failed session before a new one may start.
+    pub max_backoff_ms: u64,
+    /// Fraction of each delay that is randomized, in `[0, 1]`.
+    pub backoff_jitter: f64,
+    /// Wall-clock budget for a whole session (in milliseconds).
+    pub session_timeout_ms: u64,
 }
 
 impl Default for RecoveryConfig {
     fn default() -> Self {
         Self {
             check_interval_ms: 1000,
-            k: 3,
+            params: ConsensusParams::default(),
             max_recovery_batch: 256,
-            partition_threshold_multiplier: 3,
+            max_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            backoff_jitter: 0.2,
+            session_timeout_ms: 120_000,
         }
     }
 }
 
+impl RecoveryConfig {
+    /// Delay after failed attempt number `attempt` (1-based).  `sample` in
+    /// `[0, 1)` picks where in the jitter window the delay falls.
+    pub fn backoff(&self, attempt: u32, sample: f64) -> Duration {
+        let exp = attempt.saturating_sub(1).min(32);
+        let base = self
+            .initial_backoff_ms
+            .saturating_mul(1u64 << exp)
+            .min(self.max_backoff_ms);
+        let jitter = self.backoff_jitter.clamp(0.0, 1.0) * sample.clamp(0.0, 1.0);
+        Duration::from_millis((base as f64 * (1.0 - jitter)) as u64)
+    }
+}
+
+/// A uniformly distributed sample in `[0, 1)` for backoff jitter.
+fn jitter_sample() -> f64 {
+    (RandomState::new().hash_one(Instant::now()) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// The recovery session currently in progress.
+#[derive(Debug, Clone)]
+struct RecoverySession {
+    id: u64,
+    /// Attempts made so far.
+    attempts: u32,
+    deadline: Instant,
+    next_attempt_at: Instant,
+}
+
+/// What a tick found.
+enum Assessment {
+    /// Nothing to act on (no network tip yet, tip unknown, partition).
+    Undecided,
+    /// The local tip is on the network chain.
+    InSync,
+    /// Recovery is needed.
+    Diverged {
+        lca: BlockHash,
+        missing_blocks: Vec<BlockHash>,
+    },
+}
+
 /// A standalone recovery loop that runs alongside the `GhostDagAgent`.
 ///
 /// This component monitors the DAG and sends commands to the agent
@@ -52,12 +128,18 @@ impl Default for RecoveryConfig {
 pub struct RecoveryLoop {
     dag: Arc<RwLock<DagStore>>,
     config: RecoveryConfig,
-    cmd_tx: CommandTx,
+    agent: AgentHandle,
     event_tx: EventTx,
     /// The latest known network tip (updated externally).
     network_tip: Arc<RwLock<Option<BlockHash>>>,
     /// Shutdown signal.
     shutdown: tokio::sync::watch::Receiver<bool>,
+    session: Option<RecoverySession>,
+    next_session_id: u64,
+    /// No new session starts before this instant (set after a failure).
+    cooldown_until: Option<Instant>,
+    /// Drives ticks, backoff and session deadlines.
+    clock: SharedClock,
 }
 
 impl RecoveryLoop {
@@ -73,62 +155,95 @@ impl RecoveryLoop {
         Self {
             dag,
             config,
-            cmd_tx,
+            agent: AgentHandle::new(cmd_tx),
             event_tx,
             network_tip,
             shutdown,
+            session: None,
+            next_session_id: 1,
+            cooldown_until: None,
+            clock: system_clock(),
         }
     }
 
+    /// Tick, back off and time out on `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Run the recovery monitoring loop until shutdown is signaled.
     pub async fn run(mut self) {
         info!(
             interval_ms = self.config.check_interval_ms,
-            k = self.config.k,
+            k = self.config.params.k,
             "Recovery loop started"
         );
 
         let interval = Duration::from_millis(self.config.check_interval_ms);
+        let mut shutdown = self.shutdown.clone();
 
         loop {
+            // A tick may wait on an attempt for the whole session timeout,
+            // so shutdown is raced against the tick too, not just the sleep.
+            let tick = async {
+                self.clock.sleep(interval).await;
+                self.tick().await;
+            };
             tokio::select! {
-                _ = tokio::time::sleep(interval) => {
-                    self.tick().await;
-                }
-                Ok(()) = self.shutdown.changed() => {
-                    if *self.shutdown.borrow() {
-                        info!("Recovery loop shutting down");
-                        break;
-                    }
+                _ = tick => {}
+                _ = shutdown_signaled(&mut shutdown) => {
+                    info!("Recovery loop shutting down");
+                    break;
                 }
             }
         }
     }
 
     /// A single tick of the monitoring loop.
-    async fn tick(&self) {
+    async fn tick(&mut self) {
+        match self.assess().await {
+            Assessment::Undecided => {}
+            Assessment::InSync => {
+                if let Some(session) = self.session.take() {
+                    info!(
+                        session_id = session.id,
+                        "Local tip converged — recovery session closed"
+                    );
+                }
+            }
+            Assessment::Diverged {
+                lca,
+                missing_blocks,
+            } => self.attempt(lca, missing_blocks).await,
+        }
+    }
+
+    /// Look at the DAG and the network tip.
+    async fn assess(&self) -> Assessment {
         let network_tip = {
             let guard = self.network_tip.read().await;
             match *guard {
                 Some(tip) => tip,
                 None => {
                     debug!("No network tip known yet — skipping divergence check");
-                    return;
+                    return Assessment::Undecided;
                 }
             }
         };
 
-        let dag = self.dag.read().await;
-
-        // Find our local tip (highest blue-score block).
-        let local_tip = match dag.headers().max_by_key(|h| h.blue_score).map(|h| h.hash) {
-            Some(tip) => tip,
-            None => {
-                debug!("DAG is empty — skipping divergence check");
-                return;
+        // The agent's tip, not the heaviest block: a heavier fork the agent
+        // refused (it contradicts a checkpoint) is not where we are.
+        let local_tip = match self.agent.local_tip().await {
+            Ok(tip) => tip,
+            Err(e) => {
+                debug!("Cannot ask the agent for its tip ({e}) — skipping divergence check");
+                return Assessment::Undecided;
             }
         };
 
+        let dag = self.dag.read().await;
+
         // Quick check: is the network tip in our DAG?
         if !dag.contains(&network_tip) {
             warn!(
@@ -138,10 +253,10 @@ impl RecoveryLoop {
             );
             drop(dag);
             let _ = self
-                .cmd_tx
+                .agent
                 .send(AgentCommand::CheckDivergence { network_tip })
                 .await;
-            return;
+            return Assessment::Undecided;
         }
 
         // Build network chain and check divergence.
@@ -149,102 +264,296 @@ impl RecoveryLoop {
             Ok(chain) => chain,
             Err(e) => {
                 error!("Failed to build network selected-parent chain: {e}");
-                return;
+                return Assessment::Undecided;
             }
         };
 
         if !has_diverged(&local_tip, &network_chain) {
             debug!("No divergence — local tip is on network chain");
-            return;
+            return Assessment::InSync;
         }
 
+        // The newest shared checkpoint bounds the LCA search.
+        let checkpoints = &self.config.params.checkpoints;
+        let floor = match shared_checkpoint(&dag, &local_tip, &network_tip, checkpoints) {
+            Ok(floor) => floor,
+            Err(e) => {
+                warn!(network = %network_tip, "Network chain rejected: {e}");
+                return Assessment::Undecided;
+            }
+        };
+
         // Compute LCA.
-        let lca_result: LcaResult =
-            match greedy_path_intersection(&dag, &local_tip, &network_chain) {
-                Ok(r) => r,
-                Err(e) => {
-                    error!("LCA computation failed: {e}");
-                    return;
-                }
-            };
+        let lca_result: LcaResult = match greedy_path_intersection_bounded(
+            &dag,
+            &local_tip,
+            &network_chain,
+            floor.as_ref(),
+        ) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("LCA computation failed: {e}");
+                return Assessment::Undecided;
+            }
+        };
 
-        let partition_threshold = self.config.partition_threshold_multiplier * self.config.k;
+        let partition_threshold = self.config.params.partition_threshold();
 
         if lca_result.divergence_depth > partition_threshold {
             warn!(
                 depth = lca_result.divergence_depth,
                 threshold = partition_threshold,
-                "Divergence exceeds partition threshold"
+                "Divergence exceeds partition threshold — sending divergence check"
             );
+            // The agent declares the partition and heals it.
+            drop(dag);
             let _ = self
-                .event_tx
-                .send(AgentEvent::DivergenceDetected {
-                    local_tip,
-                    network_tip,
-                    divergence_depth: lca_result.divergence_depth,
-                })
+                .agent
+                .send(AgentCommand::CheckDivergence { network_tip })
                 .await;
+            return Assessment::Undecided;
+        }
+
+        let mut missing_blocks = lca_result.missing_blocks;
+        missing_blocks.truncate(self.config.max_recovery_batch);
+        Assessment::Diverged {
+            lca: lca_result.lca,
+            missing_blocks,
+        }
+    }
+
+    /// Run the next attempt of the current session, starting one if none
+    /// is in progress, unless backoff or the failure cooldown says wait.
+    async fn attempt(&mut self, lca: BlockHash, missing_blocks: Vec<BlockHash>) {
+        let now = self.clock.now();
+        let session = match &mut self.session {
+            Some(session) if now < session.next_attempt_at => {
+                debug!(session_id = session.id, "Recovery session backing off");
+                return;
+            }
+            Some(session) => session,
+            None => {
+                if self.cooldown_until.is_some_and(|until| now < until) {
+                    debug!("Recovery cooling down after a failed session");
+                    return;
+                }
+                let id = self.next_session_id;
+                self.next_session_id += 1;
+                info!(
+                    session_id = id,
+                    lca = %lca,
+                    missing = missing_blocks.len(),
+                    "Divergence detected — starting recovery session"
+                );
+                self.session.insert(RecoverySession {
+                    id,
+                    attempts: 0,
+                    deadline: now + Duration::from_millis(self.config.session_timeout_ms),
+                    next_attempt_at: now,
+                })
+            }
+        };
+        if now >= session.deadline {
+            self.give_up("session deadline exceeded".to_string()).await;
             return;
         }
 
-        // Trigger recovery.
+        session.attempts += 1;
+        let attempt = RecoveryAttempt {
+            session_id: session.id,
+            attempt: session.attempts,
+        };
+        let remaining = session.deadline - now;
         info!(
-            lca = %lca_result.lca,
-            missing = lca_result.missing_blocks.len(),
-            "Divergence detected — triggering recovery"
+            session_id = attempt.session_id,
+            attempt = attempt.attempt,
+            "Triggering recovery attempt"
         );
 
-        let missing = if lca_result.missing_blocks.len() > self.config.max_recovery_batch {
-            lca_result.missing_blocks[..self.config.max_recovery_batch].to_vec()
-        } else {
-            lca_result.missing_blocks
+        // Commands are handled in order, so the state query is answered
+        // once the agent has finished this attempt.
+        let sent = self
+            .agent
+            .send(AgentCommand::StartRecovery {
+                lca,
+                missing_blocks,
+                session: Some(attempt),
+            })
+            .await;
+        let outcome = match sent {
+            Ok(()) => timeout(self.clock.as_ref(), remaining, self.agent.state()).await,
+            Err(e) => Some(Err(e)),
         };
 
-        drop(dag);
+        match outcome {
+            Some(Ok(AgentStateLabel::Synced)) => {
+                info!(session_id = attempt.session_id, "Recovery session succeeded");
+                self.session = None;
+            }
+            Some(Ok(state)) => {
+                self.retry_later(format!("agent is {state} after attempt {}", attempt.attempt))
+                    .await;
+            }
+            Some(Err(e)) => {
+                warn!("Recovery attempt not delivered: {e}");
+                self.session = None;
+            }
+            None => {
+                self.give_up("session deadline exceeded".to_string()).await;
+            }
+        }
+    }
+
+    /// Schedule the next attempt after a failed one, or give up once the
+    /// session is out of attempts.
+    async fn retry_later(&mut self, reason: String) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        if session.attempts >= self.config.max_attempts {
+            let reason = format!("{reason}; no attempts left");
+            self.give_up(reason).await;
+            return;
+        }
+        let delay = self.config.backoff(session.attempts, jitter_sample());
+        session.next_attempt_at = self.clock.now() + delay;
+        warn!(
+            session_id = session.id,
+            attempt = session.attempts,
+            retry_in_ms = delay.as_millis() as u64,
+            "Recovery attempt failed: {reason}"
+        );
+    }
 
+    /// End the current session as failed.
+    async fn give_up(&mut self, reason: String) {
+        let Some(session) = self.session.take() else {
+            return;
+        };
+        error!(
+            session_id = session.id,
+            attempts = session.attempts,
+            "Recovery session failed: {reason}"
+        );
+        let cooldown = Duration::from_millis(self.config.max_backoff_ms);
+        self.cooldown_until = Some(self.clock.now() + cooldown);
         let _ = self
-            .cmd_tx
-            .send(AgentCommand::StartRecovery {
-                lca: lca_result.lca,
-                missing_blocks: missing,
+            .event_tx
+            .send(AgentEvent::RecoveryFailed {
+                session_id: session.id,
+                attempts: session.attempts,
+                reason,
             })
             .await;
     }
 }
 
-/// Ingest a batch of new blocks into the DAG, re-color, and return
-/// the updated coloring.
+/// Resolves once `shutdown` is set to `true`; never if its sender is gone.
+async fn shutdown_signaled(shutdown: &mut tokio::sync::watch::Receiver<bool>) {
+    while !*shutdown.borrow_and_update() {
+        if shutdown.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Result of `ingest_and_recolor`.
+#[derive(Debug, Clone)]
+pub struct IngestOutcome {
+    /// Coloring of the DAG after ingestion.
+    pub coloring: argus_ghostdag::ColoringOutput,
+    /// Number of blocks actually added to the DAG, including orphans
+    /// released by this batch.
+    pub ingested: u64,
+    /// Blocks from this batch that are now waiting in the orphan pool.
+    pub orphaned: Vec<BlockHash>,
+    /// Ancestors the orphan pool is waiting for (to be requested from peers).
+    pub missing_ancestors: Vec<BlockHash>,
+    /// Blocks the DAG refused (e.g. too many parents), with the reason.
+    pub rejected: Vec<(BlockHash, String)>,
+}
+
+/// Ingest a batch of new blocks into the DAG, re-color with the k
+/// schedule, and report how many blocks were added.
 ///
-/// This is used by the recovery path to merge missing blocks.
+/// Blocks may arrive in any order.  A block whose parents are not all
+/// local is parked in `orphans`; inserting a block releases any orphans
+/// that were waiting on it.  A block the DAG refuses is skipped and
+/// reported in `IngestOutcome::rejected`; the rest of the batch is still
+/// ingested and re-colored.  `now` (from the caller's clock) ages out
+/// stale orphans and stamps new ones.  This is used by the recovery path
+/// to merge missing blocks.
 pub async fn ingest_and_recolor(
     dag: &Arc<RwLock<DagStore>>,
     blocks: Vec<BlockHeader>,
-    k: u64,
-) -> Result<argus_ghostdag::ColoringOutput, argus_ghostdag::GhostDagError> {
+    schedule: &KSchedule,
+    orphans: &mut OrphanPool,
+    now: Instant,
+) -> Result<IngestOutcome, argus_ghostdag::GhostDagError> {
     let mut dag = dag.write().await;
+    orphans.expire(now);
+
+    let batch: Vec<BlockHash> = blocks.iter().map(|b| b.hash).collect();
+    let mut ingested = 0u64;
+    let mut rejected = Vec::new();
 
     for block in blocks {
         // Skip blocks already present (idempotent ingestion).
-        if dag.contains(&block.hash) {
+        if dag.contains(&block.hash) || orphans.contains(&block.hash) {
+            continue;
+        }
+        let missing: Vec<BlockHash> = block
+            .parents
+            .iter()
+            .filter(|p| !dag.contains(p))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            debug!(block = %block.hash, missing = missing.len(), "Parking orphan block");
+            orphans.add(block, missing, now);
             continue;
         }
-        // Only add if all parents are present.
-        let parents_present = block.parents.iter().all(|p| dag.contains(p));
-        if parents_present {
-            dag.add_block(block)?;
-        } else {
-            warn!("Skipping block with missing parents during recovery");
+
+        // Insert, then cascade through any orphans this unblocks.
+        let mut ready = vec![block];
+        while let Some(block) = ready.pop() {
+            let hash = block.hash;
+            let result = if block.parents.is_empty() && dag.genesis().is_none() {
+                dag.add_genesis(block)
+            } else {
+                dag.add_block(block)
+            };
+            if let Err(e) = result {
+                warn!(block = %hash, "Rejecting invalid block: {e}");
+                rejected.push((hash, e.to_string()));
+                continue;
+            }
+            ingested += 1;
+            ready.extend(orphans.take_ready(&hash, &dag));
         }
     }
 
-    color_dag(&mut dag, k)
+    let orphaned: Vec<BlockHash> = batch
+        .into_iter()
+        .filter(|h| orphans.contains(h))
+        .collect();
+    let missing_ancestors = orphans.missing_ancestors(&dag);
+    let coloring = color_dag_scheduled(&mut dag, schedule)?;
+    Ok(IngestOutcome {
+        coloring,
+        ingested,
+        orphaned,
+        missing_ancestors,
+        rejected,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::channels::{command_channel, event_channel};
-    use argus_ghostdag::block::{BlockHash, BlockHeader};
+    use crate::clock::ManualClock;
+    use argus_ghostdag::checkpoints::Checkpoint;
     use argus_ghostdag::coloring::color_dag;
 
     #[tokio::test]
@@ -261,10 +570,370 @@ mod tests {
         // Ingest a new block.
         let b = BlockHash::from_byte(2);
         let new_block = BlockHeader::new(b, vec![a], 2);
-        let coloring = ingest_and_recolor(&dag, vec![new_block], 3).await.unwrap();
+        let outcome = ingest_and_recolor(
+            &dag,
+            vec![new_block],
+            &KSchedule::new(3),
+            &mut OrphanPool::default(),
+            Instant::now(),
+        )
+        .await
+        .unwrap();
 
         // All 3 blocks should be blue with k=3.
-        assert_eq!(coloring.blue_set.len(), 3);
+        assert_eq!(outcome.coloring.blue_set.len(), 3);
+        assert_eq!(outcome.ingested, 1);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_out_of_order_and_orphans() {
+        let mut dag = DagStore::new();
+        let g = BlockHash::from_byte(0);
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        let dag = Arc::new(RwLock::new(dag));
+        let schedule = KSchedule::new(3);
+        let mut orphans = OrphanPool::default();
+
+        let a = BlockHash::from_byte(1);
+        let b = BlockHash::from_byte(2);
+        let x = BlockHash::from_byte(8);
+        let orphan = BlockHash::from_byte(9);
+        let blocks = vec![
+            BlockHeader::new(b, vec![a], 2),
+            BlockHeader::new(orphan, vec![x], 3),
+            BlockHeader::new(a, vec![g], 1),
+        ];
+        let outcome = ingest_and_recolor(&dag, blocks, &schedule, &mut orphans, Instant::now())
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.ingested, 2);
+        assert_eq!(outcome.orphaned, vec![orphan]);
+        assert_eq!(outcome.missing_ancestors, vec![x]);
+        assert!(dag.read().await.contains(&b));
+
+        // The missing parent arrives later: the orphan is inserted with it.
+        let outcome = ingest_and_recolor(
+            &dag,
+            vec![BlockHeader::new(x, vec![g], 1)],
+            &schedule,
+            &mut orphans,
+            Instant::now(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome.ingested, 2);
+        assert!(outcome.missing_ancestors.is_empty());
+        assert!(orphans.is_empty());
+        assert!(dag.read().await.contains(&orphan));
+    }
+
+    #[tokio::test]
+    async fn test_ingest_skips_invalid_blocks() {
+        let mut dag = DagStore::new();
+        let g = BlockHash::from_byte(0);
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        let dag = Arc::new(RwLock::new(dag));
+
+        let a = BlockHash::from_byte(1);
+        let bogus = BlockHash::from_byte(7);
+        let b = BlockHash::from_byte(2);
+        // A parentless block that is not the genesis sits between two
+        // valid ones.
+        let blocks = vec![
+            BlockHeader::new(a, vec![g], 1),
+            BlockHeader::genesis(bogus, 1),
+            BlockHeader::new(b, vec![a], 2),
+        ];
+        let mut orphans = OrphanPool::default();
+        let schedule = KSchedule::new(3);
+        let outcome = ingest_and_recolor(&dag, blocks, &schedule, &mut orphans, Instant::now())
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.ingested, 2);
+        assert_eq!(outcome.rejected.len(), 1);
+        assert_eq!(outcome.rejected[0].0, bogus);
+        let dag = dag.read().await;
+        assert!(!dag.contains(&bogus));
+        // Everything accepted was re-colored.
+        assert_eq!(dag.get(&b).unwrap().blue_score, 2);
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let config = RecoveryConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            backoff_jitter: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(config.backoff(1, 0.0), Duration::from_millis(100));
+        assert_eq!(config.backoff(2, 0.0), Duration::from_millis(200));
+        assert_eq!(config.backoff(4, 0.0), Duration::from_millis(800));
+        assert_eq!(config.backoff(5, 0.0), Duration::from_millis(1_000));
+        assert_eq!(config.backoff(u32::MAX, 0.0), Duration::from_millis(1_000));
+        // Jitter shortens the delay by at most `backoff_jitter`.
+        assert_eq!(config.backoff(2, 1.0), Duration::from_millis(100));
+        let sample = jitter_sample();
+        assert!((0.0..1.0).contains(&sample));
+    }
+
+    /// G → A is the network chain; our bluest tip Y sits on G → X → Y.
+    async fn diverged_loop(
+        config: RecoveryConfig,
+    ) -> (
+        RecoveryLoop,
+        crate::channels::CommandRx,
+        crate::channels::EventRx,
+        tokio::sync::watch::Sender<bool>,
+    ) {
+        let h = BlockHash::from_byte;
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        dag.add_block(BlockHeader::new(h(1), vec![h(0)], 1)).unwrap();
+        dag.add_block(BlockHeader::new(h(2), vec![h(0)], 1)).unwrap();
+        dag.add_block(BlockHeader::new(h(3), vec![h(2)], 2)).unwrap();
+        color_dag(&mut dag, 3).unwrap();
+
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, event_rx) = event_channel(32);
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let recovery = RecoveryLoop::new(
+            Arc::new(RwLock::new(dag)),
+            config,
+            cmd_tx,
+            event_tx,
+            Arc::new(RwLock::new(Some(h(1)))),
+            shutdown_rx,
+        );
+        (recovery, cmd_rx, event_rx, shutdown_tx)
+    }
+
+    #[tokio::test]
+    async fn test_assess_uses_agent_tip() {
+        // G → 1 → 4 is the checkpointed chain; G → 2 → 3 → 5 is heavier but
+        // skips the checkpoint, so the agent stays on 1.
+        let h = BlockHash::from_byte;
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        dag.add_block(BlockHeader::new(h(1), vec![h(0)], 1)).unwrap();
+        dag.add_block(BlockHeader::new(h(2), vec![h(0)], 1)).unwrap();
+        dag.add_block(BlockHeader::new(h(3), vec![h(2)], 2)).unwrap();
+        dag.add_block(BlockHeader::new(h(4), vec![h(1)], 2)).unwrap();
+        dag.add_block(BlockHeader::new(h(5), vec![h(3)], 3)).unwrap();
+        color_dag(&mut dag, 3).unwrap();
+
+        let mut config = RecoveryConfig::default();
+        config.params.checkpoints = vec![Checkpoint::new(1, h(1))];
+        let (cmd_tx, mut cmd_rx) = command_channel(32);
+        let (event_tx, _event_rx) = event_channel(32);
+        let (_shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let recovery = RecoveryLoop::new(
+            Arc::new(RwLock::new(dag)),
+            config,
+            cmd_tx,
+            event_tx,
+            Arc::new(RwLock::new(Some(h(4)))),
+            shutdown_rx,
+        );
+        let agent = tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                match cmd {
+                    AgentCommand::GetLocalTip { reply } => {
+                        let _ = reply.send(h(1));
+                    }
+                    other => panic!("unexpected command {other:?}"),
+                }
+            }
+        });
+
+        assert!(matches!(recovery.assess().await, Assessment::InSync));
+        drop(recovery);
+        agent.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_retries_then_fails() {
+        let config = RecoveryConfig {
+            check_interval_ms: 5,
+            max_attempts: 3,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 60_000,
+            ..Default::default()
+        };
+        let (recovery, mut cmd_rx, mut event_rx, shutdown_tx) = diverged_loop(config).await;
+        let handle = tokio::spawn(recovery.run());
+
+        // A fake agent whose recoveries never succeed.
+        let mut attempts = Vec::new();
+        let failed = loop {
+            tokio::select! {
+                Some(cmd) = cmd_rx.recv() => match cmd {
+                    AgentCommand::StartRecovery { session, .. } => attempts.push(session.unwrap()),
+                    AgentCommand::GetState { reply } => {
+                        let _ = reply.send(AgentStateLabel::Drifting);
+                    }
+                    AgentCommand::GetLocalTip { reply } => {
+                        let _ = reply.send(BlockHash::from_byte(3));
+                    }
+                    other => panic!("unexpected command {other:?}"),
+                },
+                Some(event) = event_rx.recv() => break event,
+            }
+        };
+
+        match failed {
+            AgentEvent::RecoveryFailed {
+                session_id,
+                attempts: n,
+                ..
+            } => {
+                assert_eq!(session_id, 1);
+                assert_eq!(n, 3);
+            }
+            other => panic!("Expected RecoveryFailed, got {other:?}"),
+        }
+        let numbers: Vec<u32> = attempts.iter().map(|a| a.attempt).collect();
+        assert_eq!(numbers, vec![1, 2, 3]);
+        assert!(attempts.iter().all(|a| a.session_id == 1));
+
+        // The failed session is followed by a cooldown, not a new session.
+        let cooldown = tokio::time::sleep(Duration::from_millis(50));
+        tokio::pin!(cooldown);
+        loop {
+            tokio::select! {
+                Some(cmd) = cmd_rx.recv() => match cmd {
+                    AgentCommand::GetLocalTip { reply } => {
+                        let _ = reply.send(BlockHash::from_byte(3));
+                    }
+                    other => panic!("unexpected command during cooldown: {other:?}"),
+                },
+                _ = &mut cooldown => break,
+            }
+        }
+
+        shutdown_tx.send(true).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_deadline() {
+        let config = RecoveryConfig {
+            check_interval_ms: 5,
+            session_timeout_ms: 50,
+            ..Default::default()
+        };
+        let (recovery, mut cmd_rx, mut event_rx, shutdown_tx) = diverged_loop(config).await;
+        let handle = tokio::spawn(recovery.run());
+
+        // A fake agent stuck in its first recovery: it never answers.
+        let mut starts = 0;
+        let mut pending = Vec::new();
+        let failed = loop {
+            tokio::select! {
+                Some(cmd) = cmd_rx.recv() => match cmd {
+                    AgentCommand::StartRecovery { .. } => starts += 1,
+                    AgentCommand::GetState { reply } => pending.push(reply),
+                    AgentCommand::GetLocalTip { reply } => {
+                        let _ = reply.send(BlockHash::from_byte(3));
+                    }
+                    other => panic!("unexpected command {other:?}"),
+                },
+                Some(event) = event_rx.recv() => break event,
+            }
+        };
+
+        // No second session or attempt while the first was running.
+        assert_eq!(starts, 1);
+        match failed {
+            AgentEvent::RecoveryFailed {
+                session_id,
+                attempts,
+                reason,
+            } => {
+                assert_eq!((session_id, attempts), (1, 1));
+                assert!(reason.contains("deadline"));
+            }
+            other => panic!("Expected RecoveryFailed, got {other:?}"),
+        }
+
+        shutdown_tx.send(true).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_interrupts_attempt() {
+        let config = RecoveryConfig {
+            check_interval_ms: 5,
+            session_timeout_ms: 600_000,
+            ..Default::default()
+        };
+        let (recovery, mut cmd_rx, _event_rx, shutdown_tx) = diverged_loop(config).await;
+        let handle = tokio::spawn(recovery.run());
+
+        // A fake agent that never answers: the attempt waits on it.
+        let mut pending = Vec::new();
+        loop {
+            match cmd_rx.recv().await.unwrap() {
+                AgentCommand::GetState { reply } => {
+                    pending.push(reply);
+                    break;
+                }
+                AgentCommand::StartRecovery { .. } => {}
+                AgentCommand::GetLocalTip { reply } => {
+                    let _ = reply.send(BlockHash::from_byte(3));
+                }
+                other => panic!("unexpected command {other:?}"),
+            }
+        }
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("recovery loop ignored shutdown during an attempt")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_deadline_on_manual_clock() {
+        let config = RecoveryConfig {
+            check_interval_ms: 1_000,
+            session_timeout_ms: 60_000,
+            ..Default::default()
+        };
+        let clock = ManualClock::default().shared();
+        let (recovery, mut cmd_rx, mut event_rx, shutdown_tx) = diverged_loop(config).await;
+        let handle = tokio::spawn(recovery.with_clock(clock.clone()).run());
+
+        // A fake agent that never answers; only the manual clock moves.
+        let mut starts = 0;
+        let mut pending = Vec::new();
+        let real_start = Instant::now();
+        let failed = loop {
+            tokio::select! {
+                Some(cmd) = cmd_rx.recv() => match cmd {
+                    AgentCommand::StartRecovery { .. } => starts += 1,
+                    AgentCommand::GetState { reply } => pending.push(reply),
+                    AgentCommand::GetLocalTip { reply } => {
+                        let _ = reply.send(BlockHash::from_byte(3));
+                    }
+                    other => panic!("unexpected command {other:?}"),
+                },
+                Some(event) = event_rx.recv() => break event,
+                _ = tokio::task::yield_now() => clock.advance(Duration::from_millis(500)),
+            }
+        };
+
+        assert_eq!(starts, 1);
+        assert!(matches!(failed, AgentEvent::RecoveryFailed { attempts: 1, .. }));
+        // One tick plus the whole session timeout passed on the clock…
+        assert!(clock.elapsed() >= Duration::from_secs(61));
+        // …but not in real time.
+        assert!(real_start.elapsed() < Duration::from_secs(10));
+
+        shutdown_tx.send(true).unwrap();
+        handle.await.unwrap();
     }
 
     #[tokio::test]
@@ -5,23 +5,47 @@
 /// - **SYNCED**: Local DAG matches network blue set.
 /// - **DRIFTING**: Local selected-parent diverges from network.
 /// - **RECOVERING**: Actively fetching missing anticone blocks.
-/// - **PARTITIONED**: Divergence exceeds 3k threshold — network partition assumed.
+/// - **PARTITIONED**: Divergence exceeds the partition threshold
+///   (`ConsensusParams::partition_threshold`, 3k by default) — network partition assumed.
+///   The agent then merges both sides (see `healing`) and returns to SYNCED.
 ///
 /// Transitions are driven by `AgentCommand` messages received on an mpsc channel.
-/// State changes emit `AgentEvent` messages.
+/// Every state change is checked against the `transitions::TRANSITIONS`
+/// table, recorded in a bounded history, and emitted as an `AgentEvent`.
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
 
-use argus_ghostdag::block::BlockHash;
-use argus_ghostdag::coloring::{color_dag, selected_parent_chain};
+use argus_ghostdag::block::{BlockHash, BlockHeader};
+use argus_ghostdag::checkpoints::best_tip;
+use argus_ghostdag::coloring::selected_parent_chain;
 use argus_ghostdag::dag::DagStore;
+use argus_ghostdag::errors::GhostDagError;
+use argus_ghostdag::params::ConsensusParams;
+use argus_ghostdag::schedule::KSchedule;
 
 use crate::channels::{
-    AgentCommand, AgentEvent, AgentStateLabel, CommandRx, EventTx,
+    event_channel, AgentCommand, AgentEvent, AgentStateLabel, AgentStatus, CommandRx, EventTx,
+    RecoveryAttempt,
 };
-use crate::lca::{greedy_path_intersection, has_diverged};
+use crate::clock::{system_clock, SharedClock};
+use crate::fetcher::{fetch_with_ancestors, BlockFetcher, HeaderRecord};
+use crate::governance::{KProposal, SharedKGovernor};
+use crate::healing::heal_partition;
+use crate::ibd::{run_ibd, DEFAULT_IBD_BATCH_SIZE};
+use crate::lca::{greedy_path_intersection_bounded, has_diverged, shared_checkpoint};
+use crate::orphans::OrphanPool;
+use crate::recorder::{AgentStart, EventRecorder};
+use crate::recovery::ingest_and_recolor;
+use crate::transitions::{
+    check_transition, TransitionContext, TransitionHistory, TransitionReason, TransitionRecord,
+};
+
+/// Upper bound on headers fetched (including ancestors) per recovery.
+pub const RECOVERY_FETCH_LIMIT: usize = 4096;
 
 /// The GhostDagAgent — an autonomous state machine that monitors
 /// DAG health and orchestrates recovery.
@@ -34,8 +58,24 @@ pub struct GhostDagAgent {
     local_tip: BlockHash,
     /// The most recent network tip we know about.
     network_tip: Option<BlockHash>,
-    /// The GhostDAG k parameter (may be updated by RL optimizer).
-    k: u64,
+    /// Where `network_tip` is mirrored for the `RecoveryLoop`, if anywhere.
+    shared_network_tip: Option<Arc<RwLock<Option<BlockHash>>>>,
+    /// Consensus parameters; `params.k` tracks the latest scheduled k.
+    params: ConsensusParams,
+    /// Blue-score-activated k changes used when re-coloring.
+    k_schedule: KSchedule,
+    /// Source of missing blocks during recovery.
+    fetcher: Option<Arc<dyn BlockFetcher>>,
+    /// Blocks waiting for missing parents.
+    orphans: OrphanPool,
+    /// Bounded log of accepted state transitions.
+    history: TransitionHistory,
+    /// Policy and audit log for k changes, shared with the server.
+    governor: Option<SharedKGovernor>,
+    /// Appends processed commands and emitted events to a log.
+    recorder: Option<EventRecorder>,
+    /// Source of transition timestamps.
+    clock: SharedClock,
     /// Channel to receive commands from the orchestrator.
     cmd_rx: CommandRx,
     /// Channel to emit events to the orchestrator.
@@ -47,7 +87,7 @@ impl GhostDagAgent {
     pub fn new(
         dag: Arc<RwLock<DagStore>>,
         local_tip: BlockHash,
-        k: u64,
+        params: ConsensusParams,
         cmd_rx: CommandRx,
         event_tx: EventTx,
     ) -> Self {
@@ -56,68 +96,349 @@ impl GhostDagAgent {
             dag,
             local_tip,
             network_tip: None,
-            k,
+            shared_network_tip: None,
+            k_schedule: KSchedule::new(params.k),
+            params,
+            fetcher: None,
+            orphans: OrphanPool::default(),
+            history: TransitionHistory::default(),
+            governor: None,
+            recorder: None,
+            clock: system_clock(),
             cmd_rx,
             event_tx,
         }
     }
 
+    /// Use `fetcher` to obtain missing blocks during recovery.  Without a
+    /// fetcher, recovery can only succeed if the blocks are already local.
+    pub fn with_fetcher(mut self, fetcher: Arc<dyn BlockFetcher>) -> Self {
+        self.fetcher = Some(fetcher);
+        self
+    }
+
+    /// Replace the default orphan pool (e.g. to change its limits).
+    pub fn with_orphan_pool(mut self, orphans: OrphanPool) -> Self {
+        self.orphans = orphans;
+        self
+    }
+
+    /// Check and record every k change with `governor`.  Without one, any
+    /// k of 1 or more is accepted.
+    pub fn with_governor(mut self, governor: SharedKGovernor) -> Self {
+        self.governor = Some(governor);
+        self
+    }
+
+    /// Record the starting point, every processed command and every
+    /// emitted event with `recorder` (see `recorder::replay`).
+    pub fn with_recorder(mut self, recorder: EventRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Timestamp transitions and age orphans with `clock` instead of the
+    /// system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Write every network tip the agent learns (`UpdateNetworkTip`,
+    /// `CheckDivergence`, `QueryDivergence`) to `tip`, the cell a
+    /// `RecoveryLoop` watches.
+    pub fn with_shared_network_tip(mut self, tip: Arc<RwLock<Option<BlockHash>>>) -> Self {
+        self.shared_network_tip = Some(tip);
+        self
+    }
+
+    /// Start from `schedule` instead of a single k from `params` (used to
+    /// resume a recorded session).
+    pub fn with_k_schedule(mut self, schedule: KSchedule) -> Self {
+        self.params.k = schedule.latest_k();
+        self.k_schedule = schedule;
+        self
+    }
+
+    /// Start in `state` with a known network tip instead of SYNCED (used
+    /// to resume a recorded session).
+    pub fn with_initial_state(
+        mut self,
+        state: AgentStateLabel,
+        network_tip: Option<BlockHash>,
+    ) -> Self {
+        self.state = state;
+        self.network_tip = network_tip;
+        self
+    }
+
     /// Returns the current state label.
     pub fn state(&self) -> AgentStateLabel {
         self.state
     }
 
+    /// A snapshot of the agent's status.
+    pub async fn status(&self) -> AgentStatus {
+        AgentStatus {
+            state: self.state,
+            local_tip: self.local_tip,
+            network_tip: self.network_tip,
+            k: self.active_k().await,
+            scheduled_k: self.k_schedule.latest_k(),
+            orphans: self.orphans.len(),
+        }
+    }
+
+    /// The k in force at the local tip: scheduled changes only count once
+    /// the tip's blue score reaches their activation.
+    pub async fn active_k(&self) -> u64 {
+        let dag = self.dag.read().await;
+        let tip_score = dag.get(&self.local_tip).map(|h| h.blue_score).unwrap_or(0);
+        self.k_schedule.k_at(tip_score)
+    }
+
+    /// The most recent `limit` state transitions (all if `None`), oldest first.
+    pub fn history(&self, limit: Option<usize>) -> Vec<TransitionRecord> {
+        self.history.recent(limit)
+    }
+
     /// Run the agent event loop.  This consumes `self` and runs until
     /// a `Shutdown` command is received or the command channel closes.
     pub async fn run(mut self) {
         info!(state = %self.state, "GhostDagAgent started");
+        let recording = match self.recorder.clone() {
+            Some(recorder) => Some(self.start_recording(recorder).await),
+            None => None,
+        };
 
         while let Some(cmd) = self.cmd_rx.recv().await {
+            if let Some(recorder) = &self.recorder {
+                recorder.record_command(&cmd);
+            }
             match cmd {
                 AgentCommand::Shutdown => {
                     info!("GhostDagAgent shutting down");
                     break;
                 }
                 AgentCommand::UpdateNetworkTip { tip } => {
-                    self.network_tip = Some(tip);
+                    self.set_network_tip(tip).await;
                     info!(?tip, "Network tip updated");
                 }
-                AgentCommand::UpdateK { new_k } => {
-                    info!(old_k = self.k, new_k, "k parameter updated");
-                    self.k = new_k;
+                AgentCommand::UpdateK {
+                    new_k,
+                    activation_blue_score,
+                    source,
+                    reason,
+                    confidence,
+                } => {
+                    let proposal = KProposal {
+                        new_k,
+                        source,
+                        reason,
+                        confidence,
+                        reverts: None,
+                    };
+                    self.handle_update_k(proposal, activation_blue_score).await;
                 }
                 AgentCommand::CheckDivergence { network_tip } => {
-                    self.network_tip = Some(network_tip);
+                    self.set_network_tip(network_tip).await;
+                    self.handle_divergence_check(network_tip).await;
+                }
+                AgentCommand::StartRecovery {
+                    lca,
+                    missing_blocks,
+                    session,
+                } => {
+                    self.handle_start_recovery(lca, missing_blocks, session)
+                        .await;
+                }
+                AgentCommand::SubmitBlocks { blocks } => {
+                    self.handle_submit_blocks(blocks).await;
+                }
+                AgentCommand::GetState { reply } => {
+                    let _ = reply.send(self.state);
+                }
+                AgentCommand::GetLocalTip { reply } => {
+                    let _ = reply.send(self.local_tip);
+                }
+                AgentCommand::GetNetworkTip { reply } => {
+                    let _ = reply.send(self.network_tip);
+                }
+                AgentCommand::GetK { reply } => {
+                    let _ = reply.send(self.active_k().await);
+                }
+                AgentCommand::GetHistory { limit, reply } => {
+                    let _ = reply.send(self.history(limit));
+                }
+                AgentCommand::GetStatus { reply } => {
+                    let _ = reply.send(self.status().await);
+                }
+                AgentCommand::QueryDivergence { network_tip, reply } => {
+                    self.set_network_tip(network_tip).await;
                     self.handle_divergence_check(network_tip).await;
+                    let _ = reply.send(self.status().await);
                 }
-                AgentCommand::StartRecovery { lca, missing_blocks } => {
-                    self.handle_start_recovery(lca, missing_blocks).await;
+                AgentCommand::StartIbd { batch_size } => {
+                    self.handle_start_ibd(batch_size.unwrap_or(DEFAULT_IBD_BATCH_SIZE))
+                        .await;
                 }
             }
         }
 
         info!("GhostDagAgent event loop exited");
+        if let Some(recording) = recording {
+            // Dropping the agent closes the tap; wait until it has recorded
+            // and forwarded every event.
+            drop(self);
+            let _ = recording.await;
+        }
     }
 
-    /// Transition to a new state, emitting a `StateChanged` event.
-    async fn transition_to(&mut self, new_state: AgentStateLabel) {
+    /// Remember `tip` as the network tip, and publish it to the shared cell.
+    async fn set_network_tip(&mut self, tip: BlockHash) {
+        self.network_tip = Some(tip);
+        if let Some(shared) = &self.shared_network_tip {
+            *shared.write().await = Some(tip);
+        }
+    }
+
+    /// Record the starting point and route events through a task that
+    /// records each one before forwarding it to the real event channel.
+    async fn start_recording(&mut self, recorder: EventRecorder) -> JoinHandle<()> {
+        let dag = {
+            let dag = self.dag.read().await;
+            dag.insertion_order()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|h| dag.get(h).ok())
+                .map(HeaderRecord::from_header)
+                .collect()
+        };
+        let governor = match &self.governor {
+            Some(governor) => Some(governor.read().await.snapshot()),
+            None => None,
+        };
+        recorder.record_start(&AgentStart {
+            state: self.state,
+            local_tip: self.local_tip,
+            network_tip: self.network_tip,
+            params: self.params.clone(),
+            k_schedule: Some(self.k_schedule.clone()),
+            governor,
+            dag,
+        });
+
+        let (tap_tx, mut tap_rx) = event_channel(self.event_tx.max_capacity());
+        let event_tx = std::mem::replace(&mut self.event_tx, tap_tx);
+        tokio::spawn(async move {
+            while let Some(event) = tap_rx.recv().await {
+                recorder.record_event(&event);
+                let _ = event_tx.send(event).await;
+            }
+        })
+    }
+
+    /// Transition to a new state if the transition table allows it.
+    ///
+    /// Accepted transitions are recorded in the history and emit a
+    /// `StateChanged` event; illegal or guard-failing ones leave the state
+    /// unchanged and emit an `Error` event.  Staying in the current state
+    /// is a no-op.  Returns `true` if the agent is in `new_state` afterwards.
+    async fn transition_to(
+        &mut self,
+        new_state: AgentStateLabel,
+        reason: TransitionReason,
+        ctx: TransitionContext,
+    ) -> bool {
         let old = self.state;
         if old == new_state {
-            return;
+            return true;
         }
+        if let Err(e) = check_transition(old, new_state, reason, &ctx) {
+            warn!("Rejected state transition: {e}");
+            let _ = self
+                .event_tx
+                .send(AgentEvent::Error {
+                    message: e.to_string(),
+                })
+                .await;
+            return false;
+        }
+
         self.state = new_state;
-        info!(from = %old, to = %new_state, "State transition");
+        self.history.record(old, new_state, reason, &ctx, self.clock.unix_millis());
+        info!(from = %old, to = %new_state, %reason, "State transition");
 
         if let Err(e) = self
             .event_tx
             .send(AgentEvent::StateChanged {
                 from: old,
                 to: new_state,
+                reason,
             })
             .await
         {
             error!("Failed to send state-change event: {e}");
         }
+        true
+    }
+
+    /// Context for transitions that don't depend on a divergence depth.
+    fn context(&self) -> TransitionContext {
+        TransitionContext::new(self.params.partition_threshold())
+    }
+
+    /// Schedule a k change above the local tip.  Activations at or below
+    /// the tip would recolor history and are rejected with an error event.
+    async fn handle_update_k(&mut self, proposal: KProposal, activation_blue_score: Option<u64>) {
+        let new_k = proposal.new_k;
+        let tip_score = {
+            let dag = self.dag.read().await;
+            dag.get(&self.local_tip).map(|h| h.blue_score).unwrap_or(0)
+        };
+        let activation = activation_blue_score.unwrap_or(tip_score + 1);
+        let mut governor = match &self.governor {
+            Some(governor) => Some(governor.write().await),
+            None => None,
+        };
+        // Changes the server already approved are recorded as the latest
+        // entry of the shared governor; only check the rest.
+        let governor = governor
+            .as_deref_mut()
+            .filter(|g| !g.is_recorded(new_k, activation));
+        let from_k = self.k_schedule.latest_k();
+        let result = if activation <= tip_score {
+            Err(format!(
+                "k activation {activation} is not above the local tip blue score {tip_score}"
+            ))
+        } else if let Some(Err(e)) = governor.as_ref().map(|g| g.check(from_k, &proposal)) {
+            Err(format!("k update refused: {e}"))
+        } else {
+            self.k_schedule
+                .schedule(activation, new_k)
+                .map_err(|e| e.to_string())
+        };
+        if let (Ok(()), Some(governor)) = (&result, governor) {
+            governor.record(from_k, proposal, activation);
+        }
+
+        match result {
+            Ok(()) => {
+                info!(old_k = from_k, new_k, activation, "k change scheduled");
+                self.params.k = new_k;
+                let _ = self
+                    .event_tx
+                    .send(AgentEvent::KScheduled {
+                        new_k,
+                        activation_blue_score: activation,
+                    })
+                    .await;
+            }
+            Err(message) => {
+                warn!("Rejected k update: {message}");
+                let _ = self.event_tx.send(AgentEvent::Error { message }).await;
+            }
+        }
     }
 
     /// Handle a divergence check.
@@ -136,7 +457,16 @@ impl GhostDagAgent {
                 "Network tip not in local DAG — divergence detected"
             );
             drop(dag);
-            self.transition_to(AgentStateLabel::Drifting).await;
+            // While partitioned, an unknown tip is no evidence of healing.
+            if self.state != AgentStateLabel::Partitioned {
+                let ctx = self.context();
+                self.transition_to(
+                    AgentStateLabel::Drifting,
+                    TransitionReason::DivergenceDetected,
+                    ctx,
+                )
+                .await;
+            }
             self.emit_divergence_detected(network_tip, 0).await;
             return;
         }
@@ -152,8 +482,30 @@ impl GhostDagAgent {
 
         // Check divergence.
         if has_diverged(&self.local_tip, &network_chain) {
+            // A network chain that contradicts a checkpoint is never followed.
+            let floor = match shared_checkpoint(
+                &dag,
+                &self.local_tip,
+                &network_tip,
+                &self.params.checkpoints,
+            ) {
+                Ok(floor) => floor,
+                Err(e) => {
+                    drop(dag);
+                    warn!(%network_tip, "Ignoring network tip: {e}");
+                    let message = format!("network tip {network_tip} rejected: {e}");
+                    let _ = self.event_tx.send(AgentEvent::Error { message }).await;
+                    return;
+                }
+            };
+
             // Compute LCA.
-            let lca_result = match greedy_path_intersection(&dag, &self.local_tip, &network_chain) {
+            let lca_result = match greedy_path_intersection_bounded(
+                &dag,
+                &self.local_tip,
+                &network_chain,
+                floor.as_ref(),
+            ) {
                 Ok(r) => r,
                 Err(e) => {
                     error!("LCA computation failed: {e}");
@@ -161,15 +513,28 @@ impl GhostDagAgent {
                 }
             };
 
-            // Check if divergence exceeds 3k threshold → PARTITIONED.
-            if lca_result.divergence_depth > 3 * self.k {
+            // Check if divergence exceeds the partition threshold → PARTITIONED.
+            let threshold = self.params.partition_threshold();
+            let ctx = TransitionContext::with_depth(threshold, lca_result.divergence_depth);
+            if lca_result.divergence_depth > threshold {
                 warn!(
                     depth = lca_result.divergence_depth,
-                    threshold = 3 * self.k,
-                    "Divergence exceeds 3k — assuming network partition"
+                    threshold,
+                    "Divergence exceeds partition threshold — assuming network partition"
                 );
                 drop(dag);
-                self.transition_to(AgentStateLabel::Partitioned).await;
+                // Still PARTITIONED means the last heal failed: try again.
+                if self.state == AgentStateLabel::Partitioned
+                    || self
+                        .transition_to(
+                            AgentStateLabel::Partitioned,
+                            TransitionReason::PartitionDetected,
+                            ctx,
+                        )
+                        .await
+                {
+                    self.handle_heal_partition(network_tip).await;
+                }
             } else {
                 info!(
                     lca = %lca_result.lca,
@@ -178,67 +543,128 @@ impl GhostDagAgent {
                     "Divergence detected — switching to DRIFTING"
                 );
                 drop(dag);
-                self.transition_to(AgentStateLabel::Drifting).await;
+                let reason = if self.state == AgentStateLabel::Partitioned {
+                    TransitionReason::PartitionHealing
+                } else {
+                    TransitionReason::DivergenceDetected
+                };
+                self.transition_to(AgentStateLabel::Drifting, reason, ctx).await;
                 self.emit_divergence_detected(network_tip, lca_result.divergence_depth).await;
             }
         } else {
             // No divergence — ensure we're SYNCED.
             drop(dag);
-            self.transition_to(AgentStateLabel::Synced).await;
+            let ctx = TransitionContext::with_depth(self.params.partition_threshold(), 0);
+            self.transition_to(AgentStateLabel::Synced, TransitionReason::Converged, ctx)
+                .await;
         }
     }
 
-    /// Handle the recovery process — fetch missing blocks and re-color.
-    async fn handle_start_recovery(&mut self, _lca: BlockHash, missing_blocks: Vec<BlockHash>) {
-        self.transition_to(AgentStateLabel::Recovering).await;
-
-        let blocks_to_recover = missing_blocks.len() as u64;
+    /// Handle the recovery process — fetch missing blocks, ingest them
+    /// and re-color.  Only returns to SYNCED once every missing block is
+    /// present locally; otherwise falls back to DRIFTING with an error.
+    async fn handle_start_recovery(
+        &mut self,
+        _lca: BlockHash,
+        missing_blocks: Vec<BlockHash>,
+        session: Option<RecoveryAttempt>,
+    ) {
+        let ctx = self.context();
+        if !self
+            .transition_to(AgentStateLabel::Recovering, TransitionReason::RecoveryStarted, ctx)
+            .await
+        {
+            return;
+        }
         info!(
-            blocks = blocks_to_recover,
-            "Starting recovery — ingesting missing blocks"
+            blocks = missing_blocks.len(),
+            ?session,
+            "Starting recovery — fetching missing blocks"
         );
 
-        // In a production system, we would fetch these blocks from peers.
-        // Here we simulate: check if blocks are already present, and
-        // re-run coloring.
-        {
-            let mut dag = self.dag.write().await;
-
-            // Re-color the DAG with the current k.
-            match color_dag(&mut dag, self.k) {
-                Ok(coloring) => {
-                    info!(
-                        blue = coloring.blue_set.len(),
-                        red = coloring.red_set.len(),
-                        "DAG re-colored after recovery"
-                    );
-                }
-                Err(e) => {
-                    error!("Re-coloring failed during recovery: {e}");
-                    let _ = self.event_tx.send(AgentEvent::Error {
-                        message: format!("recovery re-coloring failed: {e}"),
-                    }).await;
-                    return;
+        let fetched = match &self.fetcher {
+            Some(fetcher) => {
+                match fetch_with_ancestors(
+                    fetcher.as_ref(),
+                    &self.dag,
+                    &missing_blocks,
+                    RECOVERY_FETCH_LIMIT,
+                )
+                .await
+                {
+                    Ok(headers) => headers,
+                    Err(e) => {
+                        self.fail_recovery(format!("block fetch failed: {e}")).await;
+                        return;
+                    }
                 }
             }
+            None => Vec::new(),
+        };
+        let fetched_count = fetched.len() as u64;
 
-            // Update local tip to the highest blue-score block.
-            if let Some(new_tip) = dag
-                .headers()
-                .max_by_key(|h| h.blue_score)
-                .map(|h| h.hash)
-            {
-                self.local_tip = new_tip;
+        let outcome = match ingest_and_recolor(
+            &self.dag,
+            fetched,
+            &self.k_schedule,
+            &mut self.orphans,
+            self.clock.now(),
+        )
+        .await
+        {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                self.fail_recovery(format!("recovery re-coloring failed: {e}")).await;
+                return;
             }
+        };
+        info!(
+            ingested = outcome.ingested,
+            blue = outcome.coloring.blue_set.len(),
+            red = outcome.coloring.red_set.len(),
+            "DAG re-colored after recovery"
+        );
+        let _ = self
+            .event_tx
+            .send(AgentEvent::RecoveryProgress {
+                session,
+                missing: missing_blocks.len() as u64,
+                fetched: fetched_count,
+                ingested: outcome.ingested,
+            })
+            .await;
+        self.request_missing_ancestors(outcome.missing_ancestors).await;
+
+        // Update local tip to the highest blue-score block.  The network
+        // chain already passed the checkpoints, so a block refused here is a
+        // stale fork, not a reason to fail.
+        if let Some((tip, e)) = self.adopt_best_tip().await {
+            warn!(%tip, "Passing over block that contradicts a checkpoint: {e}");
+        }
+        let still_missing = {
+            let dag = self.dag.read().await;
+            missing_blocks.iter().filter(|h| !dag.contains(h)).count()
+        };
+
+        if still_missing > 0 {
+            self.fail_recovery(format!(
+                "{still_missing} of {} missing blocks could not be obtained ({} ingested)",
+                missing_blocks.len(),
+                outcome.ingested
+            ))
+            .await;
+            return;
         }
 
         // Transition to SYNCED.
-        self.transition_to(AgentStateLabel::Synced).await;
+        let ctx = self.context();
+        self.transition_to(AgentStateLabel::Synced, TransitionReason::RecoveryComplete, ctx)
+            .await;
 
         if let Err(e) = self
             .event_tx
             .send(AgentEvent::RecoveryComplete {
-                blocks_recovered: blocks_to_recover,
+                blocks_recovered: outcome.ingested,
             })
             .await
         {
@@ -248,6 +674,238 @@ impl GhostDagAgent {
         info!("Recovery complete — agent is SYNCED");
     }
 
+    /// Heal a partition: gather the network side's blocks, merge them into
+    /// the DAG, recolor, and return to SYNCED on the merged DAG's bluest
+    /// tip.  On failure the agent stays PARTITIONED and the next
+    /// divergence check retries.
+    async fn handle_heal_partition(&mut self, network_tip: BlockHash) {
+        info!(local_tip = %self.local_tip, %network_tip, "Healing partition");
+
+        let fetched = match &self.fetcher {
+            Some(fetcher) => match fetch_with_ancestors(
+                fetcher.as_ref(),
+                &self.dag,
+                &[network_tip],
+                RECOVERY_FETCH_LIMIT,
+            )
+            .await
+            {
+                Ok(headers) => headers,
+                Err(e) => {
+                    self.fail_healing(format!("block fetch failed: {e}")).await;
+                    return;
+                }
+            },
+            None => Vec::new(),
+        };
+
+        let report = match heal_partition(
+            &self.dag,
+            fetched,
+            &self.k_schedule,
+            &mut self.orphans,
+            self.clock.now(),
+            &self.params.checkpoints,
+            self.local_tip,
+            network_tip,
+        )
+        .await
+        {
+            Ok(report) => report,
+            Err(e) => {
+                let missing = self.orphans.missing_ancestors(&*self.dag.read().await);
+                self.request_missing_ancestors(missing).await;
+                self.fail_healing(format!("partition merge failed: {e}")).await;
+                return;
+            }
+        };
+        info!(
+            new_tip = %report.new_tip,
+            local_red = report.local_side.red,
+            network_red = report.network_side.red,
+            reorged = report.chain_diff.removed.len(),
+            "Partition merged"
+        );
+
+        // The same choice `heal_partition` made for `report.new_tip`: the
+        // best block that agrees with the checkpoints.
+        if let Some((tip, e)) = self.adopt_best_tip().await {
+            warn!(%tip, "Passing over block that contradicts a checkpoint: {e}");
+        }
+        let ctx = self.context();
+        self.transition_to(AgentStateLabel::Synced, TransitionReason::PartitionHealed, ctx)
+            .await;
+
+        if let Err(e) = self
+            .event_tx
+            .send(AgentEvent::PartitionHealed {
+                new_tip: report.new_tip,
+                local_blocks: report.local_side.blocks,
+                local_red: report.local_side.red,
+                network_blocks: report.network_side.blocks,
+                network_red: report.network_side.red,
+                chain_diff: report.chain_diff,
+                affected_from_score: report.affected_from_score,
+                affected_to_score: report.affected_to_score,
+            })
+            .await
+        {
+            error!("Failed to send partition-healed event: {e}");
+        }
+    }
+
+    /// Report a failed heal; the agent stays PARTITIONED.
+    async fn fail_healing(&mut self, message: String) {
+        error!("Partition healing failed: {message}");
+        let _ = self.event_tx.send(AgentEvent::Error { message }).await;
+    }
+
+    /// Catch up with the fetcher's peer via headers-first IBD.  The agent
+    /// is RECOVERING for the duration and SYNCED once the peer has no
+    /// more headers to offer.
+    async fn handle_start_ibd(&mut self, batch_size: usize) {
+        let Some(fetcher) = self.fetcher.clone() else {
+            let message = "initial block download requires a block fetcher".to_string();
+            error!("{message}");
+            let _ = self.event_tx.send(AgentEvent::Error { message }).await;
+            return;
+        };
+        let ctx = self.context();
+        if !self
+            .transition_to(AgentStateLabel::Recovering, TransitionReason::IbdStarted, ctx)
+            .await
+        {
+            return;
+        }
+        info!(batch_size, "Starting initial block download");
+
+        let report = match run_ibd(
+            &self.dag,
+            fetcher.as_ref(),
+            &self.k_schedule,
+            &mut self.orphans,
+            self.clock.as_ref(),
+            &self.params.checkpoints,
+            batch_size,
+            Some(&self.event_tx),
+        )
+        .await
+        {
+            Ok(report) => report,
+            Err(e) => {
+                self.fail_recovery(format!("initial block download failed: {e}"))
+                    .await;
+                return;
+            }
+        };
+
+        // Every batch passed the checkpoints, so only a stale fork can be
+        // refused here.
+        if let Some((tip, e)) = self.adopt_best_tip().await {
+            warn!(%tip, "Passing over block that contradicts a checkpoint: {e}");
+        }
+
+        let ctx = self.context();
+        self.transition_to(AgentStateLabel::Synced, TransitionReason::IbdComplete, ctx)
+            .await;
+        let _ = self
+            .event_tx
+            .send(AgentEvent::IbdComplete {
+                blocks_ingested: report.blocks_ingested,
+            })
+            .await;
+        info!(
+            batches = report.batches,
+            ingested = report.blocks_ingested,
+            "Initial block download complete — agent is SYNCED"
+        );
+    }
+
+    /// Ingest blocks delivered by peers.  Out-of-order blocks are parked as
+    /// orphans and their missing ancestors requested.
+    async fn handle_submit_blocks(&mut self, blocks: Vec<BlockHeader>) {
+        let submitted: HashSet<BlockHash> = blocks.iter().map(|b| b.hash).collect();
+        let outcome = match ingest_and_recolor(
+            &self.dag,
+            blocks,
+            &self.k_schedule,
+            &mut self.orphans,
+            self.clock.now(),
+        )
+        .await
+        {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                error!("Block ingestion failed: {e}");
+                let _ = self
+                    .event_tx
+                    .send(AgentEvent::Error {
+                        message: format!("block ingestion failed: {e}"),
+                    })
+                    .await;
+                return;
+            }
+        };
+        info!(
+            ingested = outcome.ingested,
+            orphaned = outcome.orphaned.len(),
+            pool = self.orphans.len(),
+            "Ingested submitted blocks"
+        );
+
+        if outcome.ingested > 0 {
+            // A refused fork is reported once, when it arrives, not on
+            // every later submission it still outweighs.
+            if let Some((tip, e)) = self.adopt_best_tip().await {
+                if submitted.contains(&tip) {
+                    warn!(%tip, "Not following block past a checkpoint: {e}");
+                    let message = format!("refusing reorg: {e}");
+                    let _ = self.event_tx.send(AgentEvent::Error { message }).await;
+                }
+            }
+            let _ = self
+                .event_tx
+                .send(AgentEvent::BlocksIngested {
+                    ingested: outcome.ingested,
+                    local_tip: self.local_tip,
+                })
+                .await;
+        }
+        self.request_missing_ancestors(outcome.missing_ancestors).await;
+    }
+
+    /// Move the local tip to the highest blue-score block whose selected
+    /// chain agrees with every checkpoint (see `best_tip`).  Returns the
+    /// heaviest block passed over, and why, if any was.
+    async fn adopt_best_tip(&mut self) -> Option<(BlockHash, GhostDagError)> {
+        let best = best_tip(&*self.dag.read().await, &self.params.checkpoints);
+        if let Some(tip) = best.tip {
+            self.local_tip = tip;
+        }
+        best.refused
+    }
+
+    /// Emit a `RequestBlocks` event for the orphan pool's missing ancestors.
+    async fn request_missing_ancestors(&self, hashes: Vec<BlockHash>) {
+        if hashes.is_empty() {
+            return;
+        }
+        info!(count = hashes.len(), "Requesting missing ancestors of orphan blocks");
+        let _ = self
+            .event_tx
+            .send(AgentEvent::RequestBlocks { hashes })
+            .await;
+    }
+
+    /// Abort recovery: emit an error event and fall back to DRIFTING.
+    async fn fail_recovery(&mut self, message: String) {
+        error!("Recovery failed: {message}");
+        let _ = self.event_tx.send(AgentEvent::Error { message }).await;
+        let ctx = self.context();
+        self.transition_to(AgentStateLabel::Drifting, TransitionReason::RecoveryFailed, ctx)
+            .await;
+    }
+
     /// Emit a divergence-detected event.
     async fn emit_divergence_detected(&self, network_tip: BlockHash, depth: u64) {
         let _ = self
@@ -265,13 +923,26 @@ impl GhostDagAgent {
 mod tests {
     use super::*;
     use crate::channels::{command_channel, event_channel};
-    use argus_ghostdag::block::BlockHeader;
+    use crate::fetcher::MockBlockFetcher;
+    use crate::governance::{KChangeSource, KGovernor, KPolicy};
+    use tokio::sync::oneshot;
+    use argus_ghostdag::checkpoints::Checkpoint;
     use argus_ghostdag::coloring::color_dag;
 
     async fn setup_synced_agent() -> (
         tokio::sync::mpsc::Sender<AgentCommand>,
         tokio::sync::mpsc::Receiver<AgentEvent>,
         tokio::task::JoinHandle<()>,
+    ) {
+        setup_agent_with_fetcher(None).await
+    }
+
+    async fn setup_agent_with_fetcher(
+        fetcher: Option<Arc<dyn BlockFetcher>>,
+    ) -> (
+        tokio::sync::mpsc::Sender<AgentCommand>,
+        tokio::sync::mpsc::Receiver<AgentEvent>,
+        tokio::task::JoinHandle<()>,
     ) {
         let mut dag = DagStore::new();
         let g = BlockHash::from_byte(0);
@@ -284,7 +955,10 @@ mod tests {
         let (cmd_tx, cmd_rx) = command_channel(32);
         let (event_tx, event_rx) = event_channel(32);
 
-        let agent = GhostDagAgent::new(dag, a, 3, cmd_rx, event_tx);
+        let mut agent = GhostDagAgent::new(dag, a, ConsensusParams::devnet(), cmd_rx, event_tx);
+        if let Some(fetcher) = fetcher {
+            agent = agent.with_fetcher(fetcher);
+        }
         let handle = tokio::spawn(agent.run());
 
         (cmd_tx, event_rx, handle)
@@ -334,9 +1008,10 @@ mod tests {
         // Should have received a StateChanged event to DRIFTING.
         let event = event_rx.recv().await.unwrap();
         match event {
-            AgentEvent::StateChanged { from, to } => {
+            AgentEvent::StateChanged { from, to, reason } => {
                 assert_eq!(from, AgentStateLabel::Synced);
                 assert_eq!(to, AgentStateLabel::Drifting);
+                assert_eq!(reason, TransitionReason::DivergenceDetected);
             }
             other => panic!("Expected StateChanged, got {other:?}"),
         }
@@ -347,43 +1022,590 @@ mod tests {
 
     #[tokio::test]
     async fn test_recovery_flow() {
+        // The network has G → A → B → C; we only have G → A.
+        let fetcher = MockBlockFetcher::new();
+        let a = BlockHash::from_byte(1);
+        let b = BlockHash::from_byte(5);
+        let c = BlockHash::from_byte(6);
+        fetcher.insert(BlockHeader::new(b, vec![a], 2));
+        fetcher.insert(BlockHeader::new(c, vec![b], 3));
+        let (cmd_tx, mut event_rx, handle) =
+            setup_agent_with_fetcher(Some(Arc::new(fetcher))).await;
+
+        // Trigger recovery directly, listing only the tip as missing.
+        cmd_tx
+            .send(AgentCommand::StartRecovery {
+                lca: a,
+                missing_blocks: vec![c],
+                session: None,
+            })
+            .await
+            .unwrap();
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            events.push(event);
+        }
+
+        // The ancestor B was chased and both blocks were ingested.
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, AgentEvent::RecoveryComplete { blocks_recovered: 2 })));
+        assert!(matches!(
+            events.last(),
+            Some(AgentEvent::RecoveryComplete { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_start_ibd() {
+        // The peer has G → A → 10 more blocks; we have G → A.
+        let fetcher = MockBlockFetcher::new();
+        fetcher.insert(BlockHeader::genesis(BlockHash::from_byte(0), 0));
+        fetcher.insert(BlockHeader::new(BlockHash::from_byte(1), vec![BlockHash::from_byte(0)], 1));
+        for i in 2u8..12 {
+            fetcher.insert(BlockHeader::new(
+                BlockHash::from_byte(i),
+                vec![BlockHash::from_byte(i - 1)],
+                i as u64,
+            ));
+        }
+        let (cmd_tx, mut event_rx, handle) =
+            setup_agent_with_fetcher(Some(Arc::new(fetcher))).await;
+
+        cmd_tx
+            .send(AgentCommand::StartIbd { batch_size: Some(4) })
+            .await
+            .unwrap();
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            events.push(event);
+        }
+        let progress = events
+            .iter()
+            .filter(|e| matches!(e, AgentEvent::IbdProgress { .. }))
+            .count();
+        assert_eq!(progress, 3);
+        assert!(matches!(
+            events.last(),
+            Some(AgentEvent::IbdComplete { blocks_ingested: 10 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_submit_out_of_order_blocks() {
+        let (cmd_tx, mut event_rx, handle) = setup_synced_agent().await;
+        let a = BlockHash::from_byte(1);
+        let b = BlockHash::from_byte(2);
+        let c = BlockHash::from_byte(3);
+
+        // C arrives before its parent B: parked, and B is requested.
+        cmd_tx
+            .send(AgentCommand::SubmitBlocks {
+                blocks: vec![BlockHeader::new(c, vec![b], 3)],
+            })
+            .await
+            .unwrap();
+        // B arrives: both are inserted.
+        cmd_tx
+            .send(AgentCommand::SubmitBlocks {
+                blocks: vec![BlockHeader::new(b, vec![a], 2)],
+            })
+            .await
+            .unwrap();
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 2);
+        match &events[0] {
+            AgentEvent::RequestBlocks { hashes } => assert_eq!(hashes, &vec![b]),
+            other => panic!("Expected RequestBlocks, got {other:?}"),
+        }
+        match &events[1] {
+            AgentEvent::BlocksIngested { ingested, local_tip } => {
+                assert_eq!((*ingested, *local_tip), (2, c));
+            }
+            other => panic!("Expected BlocksIngested, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refuses_reorg_below_checkpoint() {
+        let g = BlockHash::from_byte(0);
+        let a = BlockHash::from_byte(1);
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        dag.add_block(BlockHeader::new(a, vec![g], 1)).unwrap();
+        color_dag(&mut dag, 3).unwrap();
+
+        let mut params = ConsensusParams::devnet();
+        params.checkpoints = vec![Checkpoint::new(1, a)];
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, mut event_rx) = event_channel(32);
+        let agent = GhostDagAgent::new(Arc::new(RwLock::new(dag)), a, params, cmd_rx, event_tx);
+        let handle = tokio::spawn(agent.run());
+
+        // A longer branch from genesis that skips the checkpoint block.
+        let fork: Vec<BlockHash> = (20..23).map(BlockHash::from_byte).collect();
+        let blocks = vec![
+            BlockHeader::new(fork[0], vec![g], 1),
+            BlockHeader::new(fork[1], vec![fork[0]], 2),
+            BlockHeader::new(fork[2], vec![fork[1]], 3),
+        ];
+        cmd_tx.send(AgentCommand::SubmitBlocks { blocks }).await.unwrap();
+        cmd_tx
+            .send(AgentCommand::CheckDivergence { network_tip: fork[2] })
+            .await
+            .unwrap();
+        let (reply, status) = oneshot::channel();
+        cmd_tx.send(AgentCommand::GetStatus { reply }).await.unwrap();
+        let status = status.await.unwrap();
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
+
+        assert_eq!(status.local_tip, a);
+        assert_eq!(status.state, AgentStateLabel::Synced);
+        let mut errors = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            match event {
+                AgentEvent::Error { message } => errors.push(message),
+                AgentEvent::DivergenceDetected { .. } => panic!("followed a bad network tip"),
+                _ => {}
+            }
+        }
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("refusing reorg"));
+        assert!(errors[1].contains("rejected"));
+    }
+
+    #[tokio::test]
+    async fn test_orphans_age_on_agent_clock() {
+        use crate::clock::ManualClock;
+        use std::time::Duration;
+
+        let g = BlockHash::from_byte(0);
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        color_dag(&mut dag, 3).unwrap();
+        let clock = ManualClock::default().shared();
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, _event_rx) = event_channel(32);
+        let agent = GhostDagAgent::new(
+            Arc::new(RwLock::new(dag)),
+            g,
+            ConsensusParams::devnet(),
+            cmd_rx,
+            event_tx,
+        )
+        .with_orphan_pool(OrphanPool::new(16, Duration::from_secs(60)))
+        .with_clock(clock.clone());
+        let handle = tokio::spawn(agent.run());
+        let orphans = || async {
+            let (reply, status) = oneshot::channel();
+            cmd_tx.send(AgentCommand::GetStatus { reply }).await.unwrap();
+            status.await.unwrap().orphans
+        };
+
+        let orphan = BlockHeader::new(BlockHash::from_byte(9), vec![BlockHash::from_byte(8)], 2);
+        cmd_tx
+            .send(AgentCommand::SubmitBlocks { blocks: vec![orphan] })
+            .await
+            .unwrap();
+        assert_eq!(orphans().await, 1);
+
+        // Only the agent's clock has moved past the orphan TTL.
+        clock.advance(Duration::from_secs(61));
+        let blocks = vec![BlockHeader::new(BlockHash::from_byte(1), vec![g], 1)];
+        cmd_tx.send(AgentCommand::SubmitBlocks { blocks }).await.unwrap();
+        assert_eq!(orphans().await, 0);
+
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_good_chain_advances_past_refused_fork() {
+        let g = BlockHash::from_byte(0);
+        let a = BlockHash::from_byte(1);
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        dag.add_block(BlockHeader::new(a, vec![g], 1)).unwrap();
+        color_dag(&mut dag, 3).unwrap();
+
+        let mut params = ConsensusParams::devnet();
+        params.checkpoints = vec![Checkpoint::new(1, a)];
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, mut event_rx) = event_channel(32);
+        let agent = GhostDagAgent::new(Arc::new(RwLock::new(dag)), a, params, cmd_rx, event_tx);
+        let handle = tokio::spawn(agent.run());
+        let local_tip = || async {
+            let (reply, status) = oneshot::channel();
+            cmd_tx.send(AgentCommand::GetStatus { reply }).await.unwrap();
+            status.await.unwrap().local_tip
+        };
+
+        // A heavier fork that skips the checkpoint block stays the
+        // heaviest while the good chain grows beneath it.
+        let fork: Vec<BlockHash> = (20..24).map(BlockHash::from_byte).collect();
+        let blocks = vec![
+            BlockHeader::new(fork[0], vec![g], 1),
+            BlockHeader::new(fork[1], vec![fork[0]], 2),
+            BlockHeader::new(fork[2], vec![fork[1]], 3),
+            BlockHeader::new(fork[3], vec![fork[2]], 4),
+        ];
+        cmd_tx.send(AgentCommand::SubmitBlocks { blocks }).await.unwrap();
+        assert_eq!(local_tip().await, a);
+
+        let b = BlockHash::from_byte(2);
+        let c = BlockHash::from_byte(3);
+        let blocks = vec![BlockHeader::new(b, vec![a], 2)];
+        cmd_tx.send(AgentCommand::SubmitBlocks { blocks }).await.unwrap();
+        assert_eq!(local_tip().await, b);
+        let blocks = vec![BlockHeader::new(c, vec![b], 3)];
+        cmd_tx.send(AgentCommand::SubmitBlocks { blocks }).await.unwrap();
+        assert_eq!(local_tip().await, c);
+
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
+
+        // Only the fork's own submission is reported.
+        let mut errors = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            if let AgentEvent::Error { message } = event {
+                errors.push(message);
+            }
+        }
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("refusing reorg"));
+    }
+
+    #[tokio::test]
+    async fn test_recovery_without_blocks_fails() {
         let (cmd_tx, mut event_rx, handle) = setup_synced_agent().await;
 
-        // Trigger recovery directly.
         cmd_tx
             .send(AgentCommand::StartRecovery {
                 lca: BlockHash::from_byte(0),
                 missing_blocks: vec![BlockHash::from_byte(5), BlockHash::from_byte(6)],
+                session: Some(RecoveryAttempt {
+                    session_id: 7,
+                    attempt: 2,
+                }),
             })
             .await
             .unwrap();
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
 
-        // Should see: Synced→Recovering, then Recovering→Synced, then RecoveryComplete.
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            events.push(event);
+        }
+
+        assert!(events.iter().all(|e| !matches!(e, AgentEvent::RecoveryComplete { .. })));
+        assert!(events.iter().any(|e| matches!(e, AgentEvent::Error { .. })));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            AgentEvent::RecoveryProgress {
+                session: Some(RecoveryAttempt { session_id: 7, attempt: 2 }),
+                missing: 2,
+                ingested: 0,
+                ..
+            }
+        )));
+        assert!(matches!(
+            events.last(),
+            Some(AgentEvent::StateChanged {
+                to: AgentStateLabel::Drifting,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_partition_heals_to_synced() {
+        // G → A, then a 15-block network branch and a 12-block local
+        // branch off A: deeper than the devnet threshold (9).
+        let h = BlockHash::from_u64;
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        dag.add_block(BlockHeader::new(h(1), vec![h(0)], 1)).unwrap();
+        for (base, len) in [(100, 15), (200, 12)] {
+            let mut parent = h(1);
+            for i in 1..=len {
+                dag.add_block(BlockHeader::new(h(base + i), vec![parent], 1 + i))
+                    .unwrap();
+                parent = h(base + i);
+            }
+        }
+        color_dag(&mut dag, 3).unwrap();
+
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, mut event_rx) = event_channel(32);
+        let agent = GhostDagAgent::new(
+            Arc::new(RwLock::new(dag)),
+            h(212),
+            ConsensusParams::devnet(),
+            cmd_rx,
+            event_tx,
+        );
+        let handle = tokio::spawn(agent.run());
+
+        cmd_tx
+            .send(AgentCommand::CheckDivergence {
+                network_tip: h(115),
+            })
+            .await
+            .unwrap();
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
 
         let mut events = Vec::new();
         while let Ok(event) = event_rx.try_recv() {
             events.push(event);
         }
+        let reasons: Vec<TransitionReason> = events
+            .iter()
+            .filter_map(|e| match e {
+                AgentEvent::StateChanged { reason, .. } => Some(*reason),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                TransitionReason::PartitionDetected,
+                TransitionReason::PartitionHealed
+            ]
+        );
+        match events.last() {
+            Some(AgentEvent::PartitionHealed {
+                local_blocks,
+                local_red,
+                network_blocks,
+                network_red,
+                chain_diff,
+                ..
+            }) => {
+                assert_eq!((*local_blocks, *network_blocks), (12, 15));
+                assert_eq!(*network_red, 0);
+                assert!(*local_red > 0);
+                assert_eq!(chain_diff.fork_point, h(1));
+                assert_eq!(chain_diff.removed.len(), 12);
+            }
+            other => panic!("Expected PartitionHealed, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partition_heals_onto_checkpointed_tip() {
+        // As above, plus a heavier 20-block branch off genesis that skips
+        // the checkpoint at A.
+        let h = BlockHash::from_u64;
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        dag.add_block(BlockHeader::new(h(1), vec![h(0)], 1)).unwrap();
+        for (root, base, len) in [(1, 100, 15), (1, 200, 12), (0, 300, 20)] {
+            let mut parent = h(root);
+            for i in 1..=len {
+                dag.add_block(BlockHeader::new(h(base + i), vec![parent], 1 + i))
+                    .unwrap();
+                parent = h(base + i);
+            }
+        }
+        color_dag(&mut dag, 3).unwrap();
 
-        // Verify we got a RecoveryComplete event.
-        assert!(events.iter().any(|e| matches!(e, AgentEvent::RecoveryComplete { .. })));
+        let mut params = ConsensusParams::devnet();
+        params.checkpoints = vec![Checkpoint::new(1, h(1))];
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, mut event_rx) = event_channel(32);
+        let agent =
+            GhostDagAgent::new(Arc::new(RwLock::new(dag)), h(212), params, cmd_rx, event_tx);
+        let handle = tokio::spawn(agent.run());
 
+        cmd_tx
+            .send(AgentCommand::CheckDivergence {
+                network_tip: h(115),
+            })
+            .await
+            .unwrap();
+        let (reply, status) = oneshot::channel();
+        cmd_tx.send(AgentCommand::GetStatus { reply }).await.unwrap();
+        let status = status.await.unwrap();
         cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
         handle.await.unwrap();
+
+        assert_eq!(status.state, AgentStateLabel::Synced);
+        assert_eq!(status.local_tip, h(115));
+        let mut healed = None;
+        while let Ok(event) = event_rx.try_recv() {
+            if let AgentEvent::PartitionHealed { new_tip, .. } = event {
+                healed = Some(new_tip);
+            }
+        }
+        assert_eq!(healed, Some(h(115)));
+    }
+
+    #[tokio::test]
+    async fn test_illegal_transition_is_rejected() {
+        let mut dag = DagStore::new();
+        let g = BlockHash::from_byte(0);
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, mut event_rx) = event_channel(32);
+        let mut agent = GhostDagAgent::new(
+            Arc::new(RwLock::new(dag)),
+            g,
+            ConsensusParams::devnet(),
+            cmd_rx,
+            event_tx,
+        );
+        agent.state = AgentStateLabel::Partitioned;
+        let handle = tokio::spawn(agent.run());
+
+        // PARTITIONED → RECOVERING is not in the table.
+        cmd_tx
+            .send(AgentCommand::StartRecovery {
+                lca: g,
+                missing_blocks: vec![BlockHash::from_byte(5)],
+                session: None,
+            })
+            .await
+            .unwrap();
+        let (reply, history) = oneshot::channel();
+        cmd_tx
+            .send(AgentCommand::GetHistory { limit: None, reply })
+            .await
+            .unwrap();
+        assert!(history.await.unwrap().is_empty());
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            AgentEvent::Error { message } => assert!(message.contains("illegal transition")),
+            other => panic!("Expected Error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_k_rejects_retroactive_activation() {
+        let (cmd_tx, mut event_rx, handle) = setup_synced_agent().await;
+
+        // Local tip A has blue score 1; activating at 1 would recolor it.
+        cmd_tx
+            .send(AgentCommand::UpdateK {
+                new_k: 10,
+                activation_blue_score: Some(1),
+                source: KChangeSource::Rpc,
+                reason: String::new(),
+                confidence: None,
+            })
+            .await
+            .unwrap();
+
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
+
+        let event = event_rx.recv().await.unwrap();
+        assert!(matches!(event, AgentEvent::Error { .. }));
     }
 
     #[tokio::test]
     async fn test_update_k() {
-        let (cmd_tx, _event_rx, handle) = setup_synced_agent().await;
+        let (cmd_tx, mut event_rx, handle) = setup_synced_agent().await;
 
         // Send a k update — should not error.
         cmd_tx
-            .send(AgentCommand::UpdateK { new_k: 10 })
+            .send(AgentCommand::UpdateK {
+                new_k: 10,
+                activation_blue_score: None,
+                source: KChangeSource::Rpc,
+                reason: String::new(),
+                confidence: None,
+            })
             .await
             .unwrap();
 
         cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
         handle.await.unwrap();
+
+        // Local tip A has blue score 1, so the change activates at 2.
+        assert!(matches!(
+            event_rx.recv().await.unwrap(),
+            AgentEvent::KScheduled {
+                new_k: 10,
+                activation_blue_score: 2
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_update_k_respects_governor() {
+        let mut dag = DagStore::new();
+        let g = BlockHash::from_byte(0);
+        let a = BlockHash::from_byte(1);
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        dag.add_block(BlockHeader::new(a, vec![g], 1)).unwrap();
+        color_dag(&mut dag, 3).unwrap();
+
+        let governor = KGovernor::new(KPolicy {
+            max_k: 8,
+            ..Default::default()
+        })
+        .shared();
+        let (cmd_tx, cmd_rx) = command_channel(32);
+        let (event_tx, mut event_rx) = event_channel(32);
+        let agent = GhostDagAgent::new(
+            Arc::new(RwLock::new(dag)),
+            a,
+            ConsensusParams::devnet(),
+            cmd_rx,
+            event_tx,
+        )
+        .with_governor(governor.clone());
+        let handle = tokio::spawn(agent.run());
+
+        for (new_k, source) in [(9, KChangeSource::Rpc), (6, KChangeSource::Tuner)] {
+            cmd_tx
+                .send(AgentCommand::UpdateK {
+                    new_k,
+                    activation_blue_score: None,
+                    source,
+                    reason: "test".to_string(),
+                    confidence: None,
+                })
+                .await
+                .unwrap();
+        }
+        cmd_tx.send(AgentCommand::Shutdown).await.unwrap();
+        handle.await.unwrap();
+
+        match event_rx.recv().await.unwrap() {
+            AgentEvent::Error { message } => assert!(message.contains("outside the allowed range")),
+            other => panic!("Expected Error, got {other:?}"),
+        }
+        assert!(matches!(
+            event_rx.recv().await.unwrap(),
+            AgentEvent::KScheduled { new_k: 6, .. }
+        ));
+
+        let audit = governor.read().await.audit(None);
+        assert_eq!(audit.len(), 1);
+        assert_eq!((audit[0].from_k, audit[0].to_k), (3, 6));
+        assert_eq!(audit[0].source, KChangeSource::Tuner);
     }
 }
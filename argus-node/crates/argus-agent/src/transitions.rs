@@ -0,0 +1,346 @@
+/// Argus Agent — Transition table and history.
+///
+/// Every state change the `GhostDagAgent` may make is listed in
+/// `TRANSITIONS` together with the reason that justifies it and a guard
+/// over the facts known at the time (`TransitionContext`).  Anything not
+/// in the table is illegal and rejected.
+///
+/// ```text
+///  SYNCED ──divergence──▶ DRIFTING ──recovery_started──▶ RECOVERING
+///    │  ▲                  │  ▲   ◀──recovery_failed────    │
+///    │  └──converged───────┘  │                             │
+///    │  ◀──────────recovery_complete / ibd_complete─────────┘
+///    └──partition──▶ PARTITIONED ──partition_healing──▶ DRIFTING
+///                        └──────partition_healed──────▶ SYNCED
+/// ```
+///
+/// Accepted transitions are kept in a bounded `TransitionHistory` so that
+/// operators can audit flapping.
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::channels::AgentStateLabel;
+use AgentStateLabel::{Drifting, Partitioned, Recovering, Synced};
+use TransitionReason::*;
+
+/// Default number of transitions kept in the history.
+pub const DEFAULT_HISTORY_CAPACITY: usize = 256;
+
+/// Why the agent changed state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionReason {
+    /// The network chain diverged within the partition threshold.
+    DivergenceDetected,
+    /// The divergence exceeds the partition threshold.
+    PartitionDetected,
+    /// A previously partitioned network is back within the threshold.
+    PartitionHealing,
+    /// Both sides of a partition were merged and recolored.
+    PartitionHealed,
+    /// The local tip is on the network's selected chain again.
+    Converged,
+    /// Fetching missing blocks started.
+    RecoveryStarted,
+    /// All missing blocks were ingested.
+    RecoveryComplete,
+    /// Recovery could not obtain or ingest the missing blocks.
+    RecoveryFailed,
+    /// Initial block download started.
+    IbdStarted,
+    /// Initial block download finished.
+    IbdComplete,
+}
+
+impl fmt::Display for TransitionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::DivergenceDetected => "divergence_detected",
+            Self::PartitionDetected => "partition_detected",
+            Self::PartitionHealing => "partition_healing",
+            Self::PartitionHealed => "partition_healed",
+            Self::Converged => "converged",
+            Self::RecoveryStarted => "recovery_started",
+            Self::RecoveryComplete => "recovery_complete",
+            Self::RecoveryFailed => "recovery_failed",
+            Self::IbdStarted => "ibd_started",
+            Self::IbdComplete => "ibd_complete",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Facts a guard may inspect.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransitionContext {
+    /// Divergence depth from the latest check, if one was measured.
+    pub divergence_depth: Option<u64>,
+    /// `ConsensusParams::partition_threshold()`.
+    pub partition_threshold: u64,
+}
+
+impl TransitionContext {
+    /// Context with the given threshold and no measured divergence.
+    pub fn new(partition_threshold: u64) -> Self {
+        Self {
+            divergence_depth: None,
+            partition_threshold,
+        }
+    }
+
+    /// Context carrying a measured divergence depth.
+    pub fn with_depth(partition_threshold: u64, depth: u64) -> Self {
+        Self {
+            divergence_depth: Some(depth),
+            partition_threshold,
+        }
+    }
+}
+
+/// A guard condition on a transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Guard {
+    /// No condition.
+    Always,
+    /// The measured divergence (if any) is within the partition threshold.
+    WithinThreshold,
+    /// A divergence deeper than the partition threshold was measured.
+    BeyondThreshold,
+}
+
+impl Guard {
+    /// Whether the guard holds in `ctx`.
+    pub fn check(&self, ctx: &TransitionContext) -> bool {
+        match self {
+            Self::Always => true,
+            Self::WithinThreshold => ctx
+                .divergence_depth
+                .is_none_or(|d| d <= ctx.partition_threshold),
+            Self::BeyondThreshold => ctx
+                .divergence_depth
+                .is_some_and(|d| d > ctx.partition_threshold),
+        }
+    }
+}
+
+impl fmt::Display for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Always => write!(f, "always"),
+            Self::WithinThreshold => write!(f, "divergence within partition threshold"),
+            Self::BeyondThreshold => write!(f, "divergence beyond partition threshold"),
+        }
+    }
+}
+
+/// One allowed transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionRule {
+    pub from: AgentStateLabel,
+    pub to: AgentStateLabel,
+    pub reason: TransitionReason,
+    pub guard: Guard,
+}
+
+const fn rule(
+    from: AgentStateLabel,
+    to: AgentStateLabel,
+    reason: TransitionReason,
+    guard: Guard,
+) -> TransitionRule {
+    TransitionRule {
+        from,
+        to,
+        reason,
+        guard,
+    }
+}
+
+/// The complete transition table.
+pub const TRANSITIONS: &[TransitionRule] = &[
+    rule(Synced, Drifting, DivergenceDetected, Guard::WithinThreshold),
+    rule(Synced, Partitioned, PartitionDetected, Guard::BeyondThreshold),
+    rule(Synced, Recovering, RecoveryStarted, Guard::Always),
+    rule(Synced, Recovering, IbdStarted, Guard::Always),
+    rule(Drifting, Synced, Converged, Guard::Always),
+    rule(Drifting, Partitioned, PartitionDetected, Guard::BeyondThreshold),
+    rule(Drifting, Recovering, RecoveryStarted, Guard::Always),
+    rule(Drifting, Recovering, IbdStarted, Guard::Always),
+    rule(Recovering, Synced, RecoveryComplete, Guard::Always),
+    rule(Recovering, Synced, IbdComplete, Guard::Always),
+    rule(Recovering, Drifting, RecoveryFailed, Guard::Always),
+    rule(Partitioned, Drifting, PartitionHealing, Guard::WithinThreshold),
+    rule(Partitioned, Synced, Converged, Guard::Always),
+    rule(Partitioned, Synced, PartitionHealed, Guard::Always),
+];
+
+/// Why a transition was rejected.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum TransitionError {
+    #[error("illegal transition {from} -> {to} ({reason})")]
+    NotAllowed {
+        from: AgentStateLabel,
+        to: AgentStateLabel,
+        reason: TransitionReason,
+    },
+
+    #[error("transition {from} -> {to} ({reason}) requires: {guard}")]
+    GuardFailed {
+        from: AgentStateLabel,
+        to: AgentStateLabel,
+        reason: TransitionReason,
+        guard: Guard,
+    },
+}
+
+/// Look up and guard-check a transition.
+pub fn check_transition(
+    from: AgentStateLabel,
+    to: AgentStateLabel,
+    reason: TransitionReason,
+    ctx: &TransitionContext,
+) -> Result<&'static TransitionRule, TransitionError> {
+    let rule = TRANSITIONS
+        .iter()
+        .find(|r| r.from == from && r.to == to && r.reason == reason)
+        .ok_or(TransitionError::NotAllowed { from, to, reason })?;
+    if !rule.guard.check(ctx) {
+        return Err(TransitionError::GuardFailed {
+            from,
+            to,
+            reason,
+            guard: rule.guard,
+        });
+    }
+    Ok(rule)
+}
+
+/// An accepted transition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransitionRecord {
+    pub from: AgentStateLabel,
+    pub to: AgentStateLabel,
+    pub reason: TransitionReason,
+    /// Divergence depth that justified the transition, if measured.
+    pub divergence_depth: Option<u64>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+}
+
+/// Bounded log of accepted transitions, oldest first.
+#[derive(Debug, Clone)]
+pub struct TransitionHistory {
+    records: VecDeque<TransitionRecord>,
+    capacity: usize,
+}
+
+impl TransitionHistory {
+    /// An empty history keeping at most `capacity` records.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Record a transition made at `timestamp_ms` (Unix milliseconds).
+    pub fn record(
+        &mut self,
+        from: AgentStateLabel,
+        to: AgentStateLabel,
+        reason: TransitionReason,
+        ctx: &TransitionContext,
+        timestamp_ms: u64,
+    ) -> TransitionRecord {
+        let record = TransitionRecord {
+            from,
+            to,
+            reason,
+            divergence_depth: ctx.divergence_depth,
+            timestamp_ms,
+        };
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record.clone());
+        record
+    }
+
+    /// Number of records held.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The most recent `limit` records (all if `None`), oldest first.
+    pub fn recent(&self, limit: Option<usize>) -> Vec<TransitionRecord> {
+        let skip = limit.map_or(0, |l| self.records.len().saturating_sub(l));
+        self.records.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Default for TransitionHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_has_exit_from_every_state() {
+        for state in [Synced, Drifting, Recovering, Partitioned] {
+            assert!(
+                TRANSITIONS.iter().any(|r| r.from == state),
+                "{state} has no exit"
+            );
+        }
+    }
+
+    #[test]
+    fn test_illegal_and_guarded_transitions() {
+        let ctx = TransitionContext::new(9);
+        assert!(check_transition(Synced, Recovering, RecoveryStarted, &ctx).is_ok());
+        assert!(matches!(
+            check_transition(Partitioned, Recovering, RecoveryStarted, &ctx),
+            Err(TransitionError::NotAllowed { .. })
+        ));
+        assert!(matches!(
+            check_transition(Synced, Synced, RecoveryComplete, &ctx),
+            Err(TransitionError::NotAllowed { .. })
+        ));
+
+        let deep = TransitionContext::with_depth(9, 12);
+        assert!(check_transition(Synced, Partitioned, PartitionDetected, &deep).is_ok());
+        assert!(matches!(
+            check_transition(Partitioned, Drifting, PartitionHealing, &deep),
+            Err(TransitionError::GuardFailed { .. })
+        ));
+        // Partitioning needs a measured depth.
+        assert!(check_transition(Synced, Partitioned, PartitionDetected, &ctx).is_err());
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = TransitionHistory::new(2);
+        let ctx = TransitionContext::new(9);
+        history.record(Synced, Drifting, DivergenceDetected, &ctx, 1);
+        history.record(Drifting, Recovering, RecoveryStarted, &ctx, 2);
+        history.record(Recovering, Synced, RecoveryComplete, &ctx, 3);
+
+        assert_eq!(history.len(), 2);
+        let recent = history.recent(None);
+        assert_eq!(recent[0].reason, RecoveryStarted);
+        assert_eq!(recent[1].reason, RecoveryComplete);
+        assert_eq!(history.recent(Some(1))[0].reason, RecoveryComplete);
+    }
+}
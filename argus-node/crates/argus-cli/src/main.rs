@@ -1,10 +1,19 @@
 use clap::{Parser, Subcommand};
-use tracing::{info, error};
+use tracing::info;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
 
-use argus_ghostdag::{DagStore, BlockHash, BlockHeader};
-use argus_linearizer::{start_server, ServerConfig, ServerState};
+use argus_ghostdag::{
+    load_checkpoints, recommend_k, ConsensusParams, DagStore, GhostDagError, Network,
+    OrderingRuleKind,
+};
+use argus_agent::{
+    read_recording, replay, KControllerKind, KPolicy, KTunerConfig, RecoveryConfig,
+};
+use argus_linearizer::{
+    run_node, FlowControl, NodeConfig, ServerConfig, ServerState, SlowConsumerPolicy,
+};
 
 #[derive(Parser)]
 #[command(name = "argus")]
@@ -14,6 +23,8 @@ struct Cli {
     command: Commands,
 }
 
+// Parsed once at startup; boxing the large `Start` variant buys nothing.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Start the Argus Orchestration Layer
@@ -24,9 +35,71 @@ enum Commands {
         /// Port for the WebSocket stream
         #[arg(long, default_value_t = 9292)]
         ws_port: u16,
-        /// GhostDAG k-parameter
-        #[arg(long, default_value_t = 3)]
-        k: u64,
+        /// Network preset (mainnet, testnet, devnet)
+        #[arg(long, default_value = "devnet")]
+        network: String,
+        /// Load consensus parameters from a TOML file instead of a preset
+        #[arg(long)]
+        params: Option<PathBuf>,
+        /// Add trusted checkpoints from a TOML file of [[checkpoints]] entries
+        #[arg(long)]
+        checkpoints: Option<PathBuf>,
+        /// Override the GhostDAG k-parameter from the preset or file
+        #[arg(long)]
+        k: Option<u64>,
+        /// Derive k from this expected network delay (PHANTOM bound) unless --k is given
+        #[arg(long)]
+        delay_ms: Option<f64>,
+        /// Tolerated probability of an honest anticone exceeding k (with --delay-ms)
+        #[arg(long, default_value_t = argus_ghostdag::DEFAULT_OVERFLOW_PROBABILITY)]
+        overflow_probability: f64,
+        /// Default ordering tiebreak rule (xor, hash, blue_work, timestamp)
+        #[arg(long, default_value = "xor")]
+        ordering: String,
+        /// JSON-RPC address (host:port) of a peer to sync and recover from
+        #[arg(long)]
+        peer: Option<String>,
+        /// JSON-RPC address (host:port) of another node polled for the network tip (repeatable)
+        #[arg(long = "tip-peer")]
+        tip_peers: Vec<String>,
+        /// Tune k natively with a built-in controller (pid, bandit)
+        #[arg(long)]
+        auto_k: Option<String>,
+        /// Smallest k any update may set
+        #[arg(long, default_value_t = 1)]
+        k_min: u64,
+        /// Largest k any update may set
+        #[arg(long, default_value_t = 64)]
+        k_max: u64,
+        /// Largest total k change per minute
+        #[arg(long, default_value_t = 8)]
+        k_max_step: u64,
+        /// Minimum confidence for k updates that report one
+        #[arg(long, default_value_t = 0.5)]
+        k_min_confidence: f64,
+        /// Append agent commands and events to this JSON Lines file
+        #[arg(long)]
+        event_log: Option<PathBuf>,
+        /// Outgoing message batches queued per WebSocket client
+        #[arg(long, default_value_t = argus_linearizer::DEFAULT_CLIENT_QUEUE)]
+        ws_client_queue: usize,
+        /// What to do with a WebSocket client whose queue overflows (disconnect, coalesce, resync)
+        #[arg(long, default_value = "resync")]
+        slow_consumer: String,
+    },
+    /// Replay a recorded agent event log and check it reproduces the same events
+    Replay {
+        /// JSON Lines file written with `start --event-log`
+        log: PathBuf,
+    },
+    /// Print (or save) the consensus parameters of a network preset as TOML
+    Params {
+        /// Network preset (mainnet, testnet, devnet)
+        #[arg(long, default_value = "devnet")]
+        network: String,
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
     },
     /// Check connectivity and health
     Check {
@@ -44,28 +117,153 @@ async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Start { rpc_port, ws_port, k } => {
+        Commands::Start {
+            rpc_port,
+            ws_port,
+            network,
+            params,
+            checkpoints,
+            k,
+            delay_ms,
+            overflow_probability,
+            ordering,
+            peer,
+            tip_peers,
+            auto_k,
+            k_min,
+            k_max,
+            k_max_step,
+            k_min_confidence,
+            event_log,
+            ws_client_queue,
+            slow_consumer,
+        } => {
+            let mut params = match params {
+                Some(path) => ConsensusParams::load(path)?,
+                None => ConsensusParams::preset(network.parse::<Network>()?),
+            };
+            match (k, delay_ms) {
+                (Some(k), _) => params.k = k,
+                (None, Some(delay_ms)) => {
+                    let delay = Duration::try_from_secs_f64(delay_ms / 1000.0).map_err(|_| {
+                        GhostDagError::InvalidParams(format!(
+                            "--delay-ms must be a non-negative number of ms, got {delay_ms}"
+                        ))
+                    })?;
+                    params.k = recommend_k(
+                        delay,
+                        params.block_rate(),
+                        overflow_probability,
+                    )?;
+                    info!(
+                        "k = {} recommended for {delay_ms} ms delay at {:.2} blocks/s (δ = {})",
+                        params.k,
+                        params.block_rate(),
+                        overflow_probability
+                    );
+                }
+                (None, None) => {}
+            }
+            if let Some(path) = checkpoints {
+                params.checkpoints.extend(load_checkpoints(&path)?);
+                params.checkpoints.sort_by_key(|c| c.blue_score);
+                info!("{} trusted checkpoints", params.checkpoints.len());
+            }
+            params.validate()?;
+
             info!("Starting Argus Orchestration Layer...");
-            info!("RPC Port: {}, WS Port: {}, k: {}", rpc_port, ws_port, k);
+            info!(
+                "RPC Port: {}, WS Port: {}, network: {}, k: {}",
+                rpc_port, ws_port, params.network, params.k
+            );
+
+            let dag = DagStore::from_params(&params)?;
+
+            let recovery = RecoveryConfig {
+                params: params.clone(),
+                ..Default::default()
+            };
+            let k_policy = KPolicy {
+                min_k: k_min,
+                max_k: k_max,
+                max_step: k_max_step,
+                min_confidence: k_min_confidence,
+                ..Default::default()
+            };
+            let shared_state = Arc::new(ServerState::new(dag, params).with_k_policy(k_policy));
+
+            let mut tip_peer_addrs = Vec::with_capacity(tip_peers.len());
+            for addr in &tip_peers {
+                tip_peer_addrs.push(tokio::net::lookup_host(addr).await?.next().ok_or_else(
+                    || anyhow::anyhow!("cannot resolve tip peer address {addr}"),
+                )?);
+            }
 
-            let mut dag = DagStore::new();
-            let genesis_hash = BlockHash::from_byte(0x00);
-            dag.add_genesis(BlockHeader::genesis(genesis_hash, 0))?;
-            
-            let shared_state = Arc::new(ServerState::new(dag, k));
-            
             // Perform initial coloring.
             shared_state.recolor_and_broadcast().await?;
 
-            let config = ServerConfig {
-                ws_addr: format!("0.0.0.0:{}", ws_port).parse()?,
-                rpc_addr: format!("0.0.0.0:{}", rpc_port).parse()?,
+            let config = NodeConfig {
+                server: ServerConfig {
+                    ws_addr: format!("0.0.0.0:{}", ws_port).parse()?,
+                    rpc_addr: format!("0.0.0.0:{}", rpc_port).parse()?,
+                    ordering_rule: ordering.parse::<OrderingRuleKind>()?,
+                    flow_control: FlowControl {
+                        client_queue: ws_client_queue,
+                        policy: slow_consumer
+                            .parse::<SlowConsumerPolicy>()
+                            .map_err(anyhow::Error::msg)?,
+                    },
+                },
+                recovery,
+                peer_rpc: match peer {
+                    Some(addr) => Some(tokio::net::lookup_host(&addr).await?.next().ok_or_else(
+                        || anyhow::anyhow!("cannot resolve peer address {addr}"),
+                    )?),
+                    None => None,
+                },
+                tip_peers: tip_peer_addrs,
+                tip_aggregator: Default::default(),
+                k_tuner: match auto_k {
+                    Some(kind) => Some(KTunerConfig {
+                        controller: kind.parse::<KControllerKind>().map_err(anyhow::Error::msg)?,
+                        ..Default::default()
+                    }),
+                    None => None,
+                },
+                event_log,
             };
 
             let (_shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
 
-            // Start the combined RPC + WebSocket server.
-            start_server(shared_state, config, shutdown_rx).await;
+            // Run the server together with the agent and recovery loop.
+            run_node(shared_state, config, shutdown_rx).await;
+        }
+        Commands::Replay { log } => {
+            let report = replay(read_recording(&log)?, None).await?;
+            info!(
+                "Replayed {} commands: {} events recorded, {} emitted",
+                report.commands,
+                report.expected.len(),
+                report.actual.len()
+            );
+            match report.first_mismatch {
+                None => info!("Replay matches the recording"),
+                Some(i) => anyhow::bail!(
+                    "replay diverges at event {i}: recorded {:?}, replayed {:?}",
+                    report.expected.get(i),
+                    report.actual.get(i)
+                ),
+            }
+        }
+        Commands::Params { network, out } => {
+            let params = ConsensusParams::preset(network.parse::<Network>()?);
+            match out {
+                Some(path) => {
+                    params.save(&path)?;
+                    info!("Wrote {} parameters to {}", params.network, path.display());
+                }
+                None => print!("{}", params.to_toml_string()?),
+            }
         }
         Commands::Check { endpoint } => {
             info!("Checking Argus connectivity at {}...", endpoint);
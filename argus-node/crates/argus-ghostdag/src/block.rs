@@ -6,6 +6,8 @@ use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::errors::{GhostDagError, GhostDagResult};
+
 // ---------------------------------------------------------------------------
 // BlockHash
 // ---------------------------------------------------------------------------
@@ -35,11 +37,25 @@ impl BlockHash {
         Self(h)
     }
 
+    /// Parse a 64-character hex string (as produced by `to_hex`).
+    pub fn from_hex(s: &str) -> GhostDagResult<Self> {
+        let s = s.trim_start_matches("0x");
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(GhostDagError::InvalidHash(s.to_string()));
+        }
+        let mut h = [0u8; 32];
+        for (i, byte) in h.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16)
+                .map_err(|_| GhostDagError::InvalidHash(s.to_string()))?;
+        }
+        Ok(Self(h))
+    }
+
     /// Bitwise XOR of two hashes.
     pub fn xor(&self, other: &BlockHash) -> BlockHash {
         let mut result = [0u8; 32];
-        for i in 0..32 {
-            result[i] = self.0[i] ^ other.0[i];
+        for (r, (a, b)) in result.iter_mut().zip(self.0.iter().zip(other.0.iter())) {
+            *r = a ^ b;
         }
         BlockHash(result)
     }
@@ -150,6 +166,13 @@ mod tests {
         assert!(a < b);
     }
 
+    #[test]
+    fn test_block_hash_hex_roundtrip() {
+        let a = BlockHash::from_u64(0xDEAD_BEEF);
+        assert_eq!(BlockHash::from_hex(&a.to_hex()).unwrap(), a);
+        assert!(BlockHash::from_hex("abc").is_err());
+    }
+
     #[test]
     fn test_genesis() {
         let g = BlockHeader::genesis(BlockHash::from_byte(0), 1000);
@@ -0,0 +1,224 @@
+/// Argus GhostDAG — Trusted checkpoints.
+///
+/// A checkpoint pins the block that the selected-parent chain must pass
+/// through at a given blue score.  Chains that reach the score at any other
+/// block are rejected, which caps how deep a reorg can go: nothing below
+/// the newest checkpoint on the local chain can ever change.
+///
+/// Checkpoints are trusted to lie on one chain, so `checkpoint_floor` only
+/// walks down to the newest checkpoint the tip has reached — older ones
+/// are in its past.
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::BlockHash;
+use crate::dag::DagStore;
+use crate::errors::{GhostDagError, GhostDagResult};
+use crate::params::hex_hash;
+
+/// A block the selected chain must contain at `blue_score`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Blue score of the checkpoint block.
+    pub blue_score: u64,
+    /// Checkpoint block hash (hex-encoded in TOML).
+    #[serde(with = "hex_hash")]
+    pub hash: BlockHash,
+}
+
+impl Checkpoint {
+    /// Pin `hash` at `blue_score`.
+    pub fn new(blue_score: u64, hash: BlockHash) -> Self {
+        Self { blue_score, hash }
+    }
+}
+
+/// On-disk form of a checkpoint list: `[[checkpoints]]` tables.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointFile {
+    #[serde(default)]
+    checkpoints: Vec<Checkpoint>,
+}
+
+/// Check that blue scores strictly increase.
+pub fn validate_checkpoints(checkpoints: &[Checkpoint]) -> GhostDagResult<()> {
+    for pair in checkpoints.windows(2) {
+        if pair[1].blue_score <= pair[0].blue_score {
+            return Err(GhostDagError::InvalidParams(format!(
+                "checkpoints must have strictly increasing blue scores ({} then {})",
+                pair[0].blue_score, pair[1].blue_score
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Load and validate a TOML file of `[[checkpoints]]` entries.
+pub fn load_checkpoints(path: impl AsRef<Path>) -> GhostDagResult<Vec<Checkpoint>> {
+    let path = path.as_ref();
+    let s = std::fs::read_to_string(path).map_err(|e| {
+        GhostDagError::InvalidParams(format!("cannot read {}: {e}", path.display()))
+    })?;
+    let file: CheckpointFile = toml::from_str(&s)
+        .map_err(|e| GhostDagError::InvalidParams(format!("TOML parse error: {e}")))?;
+    validate_checkpoints(&file.checkpoints)?;
+    Ok(file.checkpoints)
+}
+
+/// The newest checkpoint the selected chain of `tip` has reached, or
+/// `None` if `tip` is below every checkpoint.
+///
+/// Fails with `CheckpointMismatch` if the chain reaches that checkpoint's
+/// blue score at a different block.  `checkpoints` must be sorted by
+/// blue score (see `validate_checkpoints`).
+pub fn checkpoint_floor(
+    dag: &DagStore,
+    tip: &BlockHash,
+    checkpoints: &[Checkpoint],
+) -> GhostDagResult<Option<Checkpoint>> {
+    let mut current = dag.get(tip)?;
+    let Some(checkpoint) = checkpoints
+        .iter()
+        .rev()
+        .find(|c| c.blue_score <= current.blue_score)
+    else {
+        return Ok(None);
+    };
+
+    while current.blue_score > checkpoint.blue_score {
+        match current.selected_parent {
+            Some(sp) => current = dag.get(&sp)?,
+            None => break,
+        }
+    }
+    if current.hash != checkpoint.hash {
+        return Err(GhostDagError::CheckpointMismatch {
+            blue_score: checkpoint.blue_score,
+            expected: checkpoint.hash,
+            found: current.hash,
+        });
+    }
+    Ok(Some(*checkpoint))
+}
+
+/// Outcome of `best_tip`.
+#[derive(Debug)]
+pub struct BestTip {
+    /// The block to follow; `None` only if no block passes.
+    pub tip: Option<BlockHash>,
+    /// The heaviest block passed over, and why, if any was.
+    pub refused: Option<(BlockHash, GhostDagError)>,
+}
+
+/// The block a colored `dag` should follow: the highest blue-score block
+/// (ties broken by the larger hash) whose selected chain passes
+/// `checkpoint_floor`.
+pub fn best_tip(dag: &DagStore, checkpoints: &[Checkpoint]) -> BestTip {
+    let mut candidates: Vec<(u64, BlockHash)> =
+        dag.headers().map(|h| (h.blue_score, h.hash)).collect();
+    candidates.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut refused = None;
+    for (_, tip) in candidates {
+        match checkpoint_floor(dag, &tip, checkpoints) {
+            Ok(_) => {
+                return BestTip {
+                    tip: Some(tip),
+                    refused,
+                }
+            }
+            Err(e) => {
+                refused.get_or_insert((tip, e));
+            }
+        }
+    }
+    BestTip { tip: None, refused }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::coloring::color_dag;
+
+    fn h(v: u64) -> BlockHash {
+        BlockHash::from_u64(v)
+    }
+
+    /// Chain G → 1 → … → 6 with a fork 2 → 103 → 104 → 105 → 106 → 107.
+    fn forked_dag() -> DagStore {
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        for i in 1..=6 {
+            dag.add_block(BlockHeader::new(h(i), vec![h(i - 1)], i)).unwrap();
+        }
+        let mut prev = h(2);
+        for i in 103..=107 {
+            dag.add_block(BlockHeader::new(h(i), vec![prev], i)).unwrap();
+            prev = h(i);
+        }
+        color_dag(&mut dag, 3).unwrap();
+        dag
+    }
+
+    #[test]
+    fn test_checkpoint_floor() {
+        let dag = forked_dag();
+        let checkpoints = [Checkpoint::new(2, h(2)), Checkpoint::new(4, h(4))];
+
+        let floor = |tip: u64| checkpoint_floor(&dag, &h(tip), &checkpoints);
+        assert_eq!(floor(6).unwrap(), Some(checkpoints[1]));
+        assert_eq!(floor(3).unwrap(), Some(checkpoints[0]));
+        assert_eq!(floor(1).unwrap(), None);
+        assert_eq!(checkpoint_floor(&dag, &h(6), &[]).unwrap(), None);
+
+        // The fork passes blue score 4 at block 104.
+        match floor(107) {
+            Err(GhostDagError::CheckpointMismatch { blue_score, found, .. }) => {
+                assert_eq!((blue_score, found), (4, h(104)));
+            }
+            other => panic!("expected a checkpoint mismatch, got {other:?}"),
+        }
+        // Below the second checkpoint the fork is still acceptable.
+        assert_eq!(floor(103).unwrap(), Some(checkpoints[0]));
+    }
+
+    #[test]
+    fn test_best_tip_skips_forks_off_checkpoint() {
+        let dag = forked_dag();
+        let best = best_tip(&dag, &[]);
+        assert_eq!(best.tip, Some(h(107)));
+        assert!(best.refused.is_none());
+
+        // The heavier fork misses the checkpoint at blue score 4.
+        let best = best_tip(&dag, &[Checkpoint::new(4, h(4))]);
+        assert_eq!(best.tip, Some(h(6)));
+        assert!(matches!(
+            best.refused,
+            Some((tip, GhostDagError::CheckpointMismatch { .. })) if tip == h(107)
+        ));
+    }
+
+    #[test]
+    fn test_load_checkpoints() {
+        let path = std::env::temp_dir()
+            .join(format!("argus-checkpoints-{}.toml", std::process::id()));
+        let toml = format!(
+            "[[checkpoints]]\nblue_score = 2\nhash = \"{}\"\n\n\
+             [[checkpoints]]\nblue_score = 4\nhash = \"{}\"\n",
+            h(2).to_hex(),
+            h(4).to_hex()
+        );
+        std::fs::write(&path, toml).unwrap();
+        let loaded = load_checkpoints(&path).unwrap();
+        assert_eq!(loaded, vec![Checkpoint::new(2, h(2)), Checkpoint::new(4, h(4))]);
+
+        std::fs::write(&path, "[[checkpoints]]\nblue_score = 4\nhash = \"00\"\n").unwrap();
+        assert!(load_checkpoints(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let unsorted = [Checkpoint::new(4, h(4)), Checkpoint::new(4, h(5))];
+        assert!(validate_checkpoints(&unsorted).is_err());
+    }
+}
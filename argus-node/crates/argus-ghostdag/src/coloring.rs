@@ -10,6 +10,10 @@
 ///
 /// This module mutates `BlockHeader.blue_score`, `BlockHeader.selected_parent`,
 /// and `BlockHeader.blue_work` in-place on the `DagStore`.
+///
+/// `color_dag_scheduled` generalizes step 3 to a `KSchedule`: each block is
+/// judged with the k in force at its own blue score (which depends only on
+/// its past, so it is known before the block's color is decided).
 use std::collections::HashSet;
 
 use num_bigint::BigUint;
@@ -17,6 +21,7 @@ use num_bigint::BigUint;
 use crate::block::BlockHash;
 use crate::dag::DagStore;
 use crate::errors::{GhostDagError, GhostDagResult};
+use crate::schedule::KSchedule;
 
 /// Result of a coloring pass over the entire DAG.
 #[derive(Debug, Clone)]
@@ -25,7 +30,7 @@ pub struct ColoringOutput {
     pub blue_set: HashSet<BlockHash>,
     /// Blocks colored red.
     pub red_set: HashSet<BlockHash>,
-    /// The k parameter used.
+    /// The k parameter used (for a schedule: the latest scheduled k).
     pub k: u64,
 }
 
@@ -57,6 +62,18 @@ pub fn color_dag(dag: &mut DagStore, k: u64) -> GhostDagResult<ColoringOutput> {
     if k < 1 {
         return Err(GhostDagError::InvalidK(k));
     }
+    color_dag_scheduled(dag, &KSchedule::new(k))
+}
+
+/// Run the k-cluster coloring with the k for each block taken from
+/// `schedule` at that block's blue score.
+///
+/// With a single-entry schedule this is exactly `color_dag`.
+pub fn color_dag_scheduled(
+    dag: &mut DagStore,
+    schedule: &KSchedule,
+) -> GhostDagResult<ColoringOutput> {
+    schedule.validate()?;
     let genesis = dag.genesis().ok_or(GhostDagError::NoGenesis)?;
     let topo = dag.topological_order()?;
 
@@ -101,9 +118,20 @@ pub fn color_dag(dag: &mut DagStore, k: u64) -> GhostDagResult<ColoringOutput> {
             ac.iter().filter(|h| blue_set.contains(h)).count() as u64
         };
 
+        // -----------------------------------------------------------
+        // Step 4 (hoisted): blue_score(B) = |blue_set ∩ past(B)|.
+        // Every block in past(B) is already colored, so this does not
+        // depend on B's own color and can select the scheduled k.
+        // -----------------------------------------------------------
+        let blue_score = {
+            let past = dag.past(&block_hash)?;
+            past.iter().filter(|h| blue_set.contains(h)).count() as u64
+        };
+
         // -----------------------------------------------------------
         // Step 3: Color decision.
         // -----------------------------------------------------------
+        let k = schedule.k_at(blue_score);
         let is_blue = blue_anticone_count <= k;
         if is_blue {
             blue_set.insert(block_hash);
@@ -111,14 +139,6 @@ pub fn color_dag(dag: &mut DagStore, k: u64) -> GhostDagResult<ColoringOutput> {
             red_set.insert(block_hash);
         }
 
-        // -----------------------------------------------------------
-        // Step 4: blue_score(B) = |blue_set ∩ past(B)|
-        // -----------------------------------------------------------
-        let blue_score = {
-            let past = dag.past(&block_hash)?;
-            past.iter().filter(|h| blue_set.contains(h)).count() as u64
-        };
-
         // -----------------------------------------------------------
         // Step 5: blue_work(B)
         // -----------------------------------------------------------
@@ -136,7 +156,11 @@ pub fn color_dag(dag: &mut DagStore, k: u64) -> GhostDagResult<ColoringOutput> {
         header.blue_work = blue_work;
     }
 
-    Ok(ColoringOutput { blue_set, red_set, k })
+    Ok(ColoringOutput {
+        blue_set,
+        red_set,
+        k: schedule.latest_k(),
+    })
 }
 
 /// Compute the selected-parent chain from `tip` back to genesis.
@@ -160,14 +184,34 @@ pub fn selected_parent_chain(
     Ok(chain)
 }
 
-/// Compute `|anticone(B) ∩ blue_set|` for a given block after coloring.
-pub fn blue_anticone_size(
-    dag: &DagStore,
-    hash: &BlockHash,
-    blue_set: &HashSet<BlockHash>,
-) -> GhostDagResult<u64> {
-    let ac = dag.anticone(hash)?;
-    Ok(ac.iter().filter(|h| blue_set.contains(h)).count() as u64)
+/// Whether `hash` was colored red, read back from a colored DAG: a
+/// non-genesis block is red exactly when it added nothing to its selected
+/// parent's blue work.  Genesis is always blue.
+pub fn is_red(dag: &DagStore, hash: &BlockHash) -> GhostDagResult<bool> {
+    let header = dag.get(hash)?;
+    match header.selected_parent {
+        Some(sp) => Ok(dag.get(&sp)?.blue_work == header.blue_work),
+        None => Ok(false),
+    }
+}
+
+/// Rebuild the `ColoringOutput` of a DAG that `color_dag_scheduled` has
+/// already colored in place with `schedule`, without coloring it again.
+pub fn read_coloring(dag: &DagStore, schedule: &KSchedule) -> GhostDagResult<ColoringOutput> {
+    let mut blue_set = HashSet::new();
+    let mut red_set = HashSet::new();
+    for header in dag.headers() {
+        if is_red(dag, &header.hash)? {
+            red_set.insert(header.hash);
+        } else {
+            blue_set.insert(header.hash);
+        }
+    }
+    Ok(ColoringOutput {
+        blue_set,
+        red_set,
+        k: schedule.latest_k(),
+    })
 }
 
 #[cfg(test)]
@@ -212,6 +256,28 @@ mod tests {
         dag
     }
 
+    #[test]
+    fn test_is_red_matches_coloring() {
+        let mut dag = build_test_dag();
+        let result = color_dag(&mut dag, 1).unwrap();
+        assert!(!result.red_set.is_empty());
+        for hash in result.blue_set.iter().chain(&result.red_set) {
+            assert_eq!(is_red(&dag, hash).unwrap(), result.red_set.contains(hash));
+        }
+    }
+
+    #[test]
+    fn test_read_coloring_matches_scheduled_coloring() {
+        let mut dag = build_test_dag();
+        let mut schedule = KSchedule::new(1);
+        schedule.schedule(2, 3).unwrap();
+        let colored = color_dag_scheduled(&mut dag, &schedule).unwrap();
+        let read = read_coloring(&dag, &schedule).unwrap();
+        assert_eq!(read.blue_set, colored.blue_set);
+        assert_eq!(read.red_set, colored.red_set);
+        assert_eq!(read.k, colored.k);
+    }
+
     #[test]
     fn test_coloring_k1() {
         let mut dag = build_test_dag();
@@ -239,6 +305,43 @@ mod tests {
         assert!(result.red_set.is_empty());
     }
 
+    #[test]
+    fn test_scheduled_k_does_not_recolor_history() {
+        // Four siblings under genesis (score 1), then two merging tips
+        // at score >= 2.
+        let mut base = {
+            let mut dag = DagStore::new();
+            let g = BlockHash::from_byte(0);
+            dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+            let side: Vec<BlockHash> = (1u8..=4).map(BlockHash::from_byte).collect();
+            for h in &side {
+                dag.add_block(BlockHeader::new(*h, vec![g], 1)).unwrap();
+            }
+            dag.add_block(BlockHeader::new(BlockHash::from_byte(5), side[..2].to_vec(), 2))
+                .unwrap();
+            dag.add_block(BlockHeader::new(BlockHash::from_byte(6), side[2..].to_vec(), 2))
+                .unwrap();
+            dag
+        };
+
+        // Clone before coloring so both passes see the same topological order.
+        let mut scheduled = base.clone();
+        let before = color_dag(&mut base, 1).unwrap();
+        assert!(!before.red_set.is_empty());
+
+        // Raising k from blue score 2 on must not change the siblings
+        // at score 1.
+        let mut schedule = KSchedule::new(1);
+        schedule.schedule(2, 100).unwrap();
+        let after = color_dag_scheduled(&mut scheduled, &schedule).unwrap();
+
+        for b in 1u8..=4 {
+            let h = BlockHash::from_byte(b);
+            assert_eq!(before.blue_set.contains(&h), after.blue_set.contains(&h));
+        }
+        assert_eq!(after.k, 100);
+    }
+
     #[test]
     fn test_selected_parent_chain() {
         let mut dag = build_test_dag();
@@ -6,6 +6,7 @@ use std::collections::{HashMap, HashSet, VecDeque};
 
 use crate::block::{BlockHash, BlockHeader};
 use crate::errors::{GhostDagError, GhostDagResult};
+use crate::params::ConsensusParams;
 
 /// The in-memory DAG store.  Thread-safety is handled externally
 /// (the agent wraps this in `Arc<RwLock<…>>`).
@@ -17,6 +18,11 @@ pub struct DagStore {
     children: HashMap<BlockHash, Vec<BlockHash>>,
     /// The genesis block hash.
     genesis: Option<BlockHash>,
+    /// Memoized `past(B)` sets.  A block's past never changes once it is
+    /// inserted (parents are immutable), so entries are never invalidated.
+    past_cache: HashMap<BlockHash, HashSet<BlockHash>>,
+    /// Maximum direct parents per block (`None` = unlimited).
+    max_parents: Option<usize>,
 }
 
 impl DagStore {
@@ -26,14 +32,31 @@ impl DagStore {
             headers: HashMap::new(),
             children: HashMap::new(),
             genesis: None,
+            past_cache: HashMap::new(),
+            max_parents: None,
         }
     }
 
+    /// Create a store seeded with the network's genesis block and
+    /// enforcing its `max_parents` limit.
+    pub fn from_params(params: &ConsensusParams) -> GhostDagResult<Self> {
+        params.validate()?;
+        let mut dag = Self::new();
+        dag.max_parents = Some(params.max_parents);
+        dag.add_genesis(params.genesis.header())?;
+        Ok(dag)
+    }
+
     /// Returns the genesis hash, if set.
     pub fn genesis(&self) -> Option<BlockHash> {
         self.genesis
     }
 
+    /// Maximum direct parents per block, if enforced.
+    pub fn max_parents(&self) -> Option<usize> {
+        self.max_parents
+    }
+
     /// Returns the number of blocks in the store.
     pub fn len(&self) -> usize {
         self.headers.len()
@@ -84,6 +107,15 @@ impl DagStore {
         if header.parents.is_empty() && self.genesis.is_some() {
             return Err(GhostDagError::OrphanBlock(hash));
         }
+        if let Some(max) = self.max_parents {
+            if header.parents.len() > max {
+                return Err(GhostDagError::TooManyParents {
+                    block: hash,
+                    count: header.parents.len(),
+                    max,
+                });
+            }
+        }
         // Verify all parents exist.
         for p in &header.parents {
             if !self.headers.contains_key(p) {
@@ -254,6 +286,52 @@ impl DagStore {
         Ok(sorted)
     }
 
+    /// An order in which inserting the blocks into an empty store
+    /// reproduces every children list, and with it `topological_order`
+    /// and the tie-breaks of coloring.  Parents come before children and
+    /// siblings keep their original order.
+    pub fn insertion_order(&self) -> GhostDagResult<Vec<BlockHash>> {
+        let mut in_degree: HashMap<BlockHash, usize> = self
+            .headers
+            .values()
+            .map(|h| (h.hash, h.parents.len()))
+            .collect();
+        let mut next_sibling: HashMap<BlockHash, Vec<BlockHash>> = HashMap::new();
+        for children in self.children.values() {
+            for pair in children.windows(2) {
+                next_sibling.entry(pair[0]).or_default().push(pair[1]);
+                if let Some(deg) = in_degree.get_mut(&pair[1]) {
+                    *deg += 1;
+                }
+            }
+        }
+
+        let mut ready: Vec<BlockHash> = in_degree
+            .iter()
+            .filter_map(|(h, d)| if *d == 0 { Some(*h) } else { None })
+            .collect();
+        let mut order = Vec::with_capacity(self.headers.len());
+        while let Some(cur) = ready.pop() {
+            order.push(cur);
+            let children = self.children.get(&cur).into_iter().flatten();
+            for next in children.chain(next_sibling.get(&cur).into_iter().flatten()) {
+                if let Some(deg) = in_degree.get_mut(next) {
+                    *deg -= 1;
+                    if *deg == 0 {
+                        ready.push(*next);
+                    }
+                }
+            }
+        }
+
+        if order.len() != self.headers.len() {
+            return Err(GhostDagError::Internal(
+                "no insertion order reproduces the children lists".to_string(),
+            ));
+        }
+        Ok(order)
+    }
+
     /// Return all block hashes.
     pub fn all_hashes(&self) -> Vec<BlockHash> {
         self.headers.keys().copied().collect()
@@ -301,7 +379,7 @@ mod tests {
 
     #[test]
     fn test_past() {
-        let dag = diamond_dag();
+        let mut dag = diamond_dag();
         let c = BlockHash::from_byte(3);
         let past = dag.past(&c).unwrap();
         assert!(past.contains(&BlockHash::from_byte(0))); // G
@@ -322,7 +400,7 @@ mod tests {
 
     #[test]
     fn test_anticone() {
-        let dag = diamond_dag();
+        let mut dag = diamond_dag();
         let a = BlockHash::from_byte(1);
         let ac = dag.anticone(&a).unwrap();
         // A's anticone should be {B} — B is not an ancestor or descendant of A.
@@ -338,6 +416,23 @@ mod tests {
         assert_eq!(tips[0], BlockHash::from_byte(3));
     }
 
+    #[test]
+    fn test_from_params_enforces_max_parents() {
+        let mut params = ConsensusParams::devnet();
+        params.max_parents = 1;
+        let mut dag = DagStore::from_params(&params).unwrap();
+        let g = params.genesis.hash;
+        assert_eq!(dag.genesis(), Some(g));
+
+        let a = BlockHash::from_byte(1);
+        let b = BlockHash::from_byte(2);
+        dag.add_block(BlockHeader::new(a, vec![g], 1)).unwrap();
+        assert!(matches!(
+            dag.add_block(BlockHeader::new(b, vec![g, a], 2)),
+            Err(GhostDagError::TooManyParents { count: 2, max: 1, .. })
+        ));
+    }
+
     #[test]
     fn test_topological_order() {
         let dag = diamond_dag();
@@ -348,4 +443,30 @@ mod tests {
         // C must come last.
         assert_eq!(order[3], BlockHash::from_byte(3));
     }
+
+    #[test]
+    fn test_insertion_order_reproduces_children() {
+        // Children of G arrive as 5, 3, 4 — not in hash or BFS order.
+        let mut dag = DagStore::new();
+        let h = BlockHash::from_byte;
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        for (child, parents) in [(5, vec![0]), (3, vec![0]), (7, vec![5, 3]), (4, vec![0])] {
+            let parents = parents.into_iter().map(h).collect();
+            dag.add_block(BlockHeader::new(h(child), parents, 1)).unwrap();
+        }
+
+        let mut rebuilt = DagStore::new();
+        for hash in dag.insertion_order().unwrap() {
+            let header = dag.get(&hash).unwrap();
+            if header.is_genesis() {
+                rebuilt.add_genesis(header.clone()).unwrap();
+            } else {
+                rebuilt.add_block(header.clone()).unwrap();
+            }
+        }
+        for hash in dag.all_hashes() {
+            assert_eq!(rebuilt.children_of(&hash).unwrap(), dag.children_of(&hash).unwrap());
+        }
+        assert_eq!(rebuilt.topological_order().unwrap(), dag.topological_order().unwrap());
+    }
 }
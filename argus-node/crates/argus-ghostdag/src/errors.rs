@@ -24,6 +24,29 @@ pub enum GhostDagError {
     #[error("k parameter must be >= 1, got {0}")]
     InvalidK(u64),
 
+    #[error("block {block} has {count} parents, more than the maximum of {max}")]
+    TooManyParents {
+        block: BlockHash,
+        count: usize,
+        max: usize,
+    },
+
+    #[error("invalid consensus parameters: {0}")]
+    InvalidParams(String),
+
+    #[error("invalid k schedule: {0}")]
+    InvalidSchedule(String),
+
+    #[error("invalid block hash: {0:?}")]
+    InvalidHash(String),
+
+    #[error("selected chain reaches blue score {blue_score} at {found}, not checkpoint {expected}")]
+    CheckpointMismatch {
+        blue_score: u64,
+        expected: BlockHash,
+        found: BlockHash,
+    },
+
     #[error("ordering error: {0}")]
     OrderingError(String),
 
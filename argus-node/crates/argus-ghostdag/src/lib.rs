@@ -1,23 +1,53 @@
-/// Argus GhostDAG — Core DAG math library.
-///
-/// This crate provides the foundational GhostDAG primitives:
-///
-/// - **`block`** — `BlockHash` and `BlockHeader` types.
-/// - **`dag`** — `DagStore` with parent/child adjacency, `past(B)`,
-///   `future(B)`, `anticone(B)`, and topological sorting.
-/// - **`coloring`** — k-cluster coloring algorithm per PHANTOM §3.
-/// - **`ordering`** — PHANTOM total ordering with XOR tiebreak.
-/// - **`errors`** — Error types.
+//! Argus GhostDAG — Core DAG math library.
+//!
+//! This crate provides the foundational GhostDAG primitives:
+//!
+//! - **`block`** — `BlockHash` and `BlockHeader` types.
+//! - **`checkpoints`** — Trusted `(blue score, hash)` checkpoints and the
+//!   chain check that enforces them.
+//! - **`dag`** — `DagStore` with parent/child adjacency, `past(B)`,
+//!   `future(B)`, `anticone(B)`, and topological sorting.
+//! - **`coloring`** — k-cluster coloring algorithm per PHANTOM §3.
+//! - **`ordering`** — PHANTOM total ordering with pluggable tiebreak rules
+//!   (XOR with selected parent by default).
+//! - **`phantom`** — Exact PHANTOM solver for validating the greedy coloring
+//!   on small DAGs (tests and the `reference` feature).
+//! - **`params`** — `ConsensusParams` with network presets and TOML I/O.
+//! - **`schedule`** — `KSchedule`: k changes activated at a blue score.
+//! - **`sweep`** — Read-only multi-k coloring analysis on cloned DAGs.
+//! - **`metrics`** — Network metrics (red rate, tip velocity, propagation
+//!   delay, width) derived from a colored DAG.
+//! - **`recommend`** — PHANTOM's choice of k from delay, block rate and δ.
+//! - **`errors`** — Error types.
 
 pub mod block;
+pub mod checkpoints;
 pub mod coloring;
 pub mod dag;
 pub mod errors;
+pub mod metrics;
 pub mod ordering;
+pub mod params;
+#[cfg(any(test, feature = "reference"))]
+pub mod phantom;
+pub mod recommend;
+pub mod schedule;
+pub mod sweep;
 
 // Re-exports for convenience.
 pub use block::{BlockHash, BlockHeader};
-pub use coloring::{color_dag, selected_parent_chain, ColoringOutput};
+pub use checkpoints::{checkpoint_floor, load_checkpoints, Checkpoint};
+pub use coloring::{color_dag, color_dag_scheduled, selected_parent_chain, ColoringOutput};
 pub use dag::DagStore;
 pub use errors::{GhostDagError, GhostDagResult};
-pub use ordering::{total_order, total_order_hashes, OrderedEntry};
+pub use metrics::{dag_metrics, DagMetrics, DEFAULT_METRICS_WINDOW};
+pub use ordering::{
+    total_order, total_order_hashes, total_order_with, OrderedEntry, OrderingRule,
+    OrderingRuleKind,
+};
+pub use params::{ConsensusParams, GenesisConfig, Network};
+pub use recommend::{
+    anticone_overflow_probability, recommend_k, DEFAULT_OVERFLOW_PROBABILITY, MAX_RECOMMENDED_K,
+};
+pub use schedule::{KChange, KSchedule};
+pub use sweep::{selected_tip, sweep_k, KSweepResult};
@@ -0,0 +1,270 @@
+/// Argus GhostDAG — Network metrics derived from the DAG.
+///
+/// The shape of a colored DAG says a lot about the network that produced
+/// it, so these metrics need no external probes:
+///
+/// - **red rate** — fraction of the window's blocks that are red (orphans).
+/// - **tip count / tip velocity** — tips now versus tips of the DAG as it
+///   stood before the window, per second of block time.
+/// - **block intervals** — deltas between consecutive block timestamps.
+/// - **propagation delay** — with blocks arriving every `Δ` ms and a
+///   network delay of `D`, a block's anticone holds the blocks mined
+///   within `D` before and after it, about `2D/Δ`.  Hence
+///   `D ≈ mean_anticone · Δ / 2`.
+/// - **DAG width** — blocks sharing a height (longest path from genesis).
+///   Blocks at one height are never ancestors of each other, so the widest
+///   height is a lower bound on the largest antichain.
+///
+/// The window is the last `window` blocks of the topological order, which
+/// is always closed under parents.  Anticones are counted inside the
+/// window, so blocks at its edges read slightly low.
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::BlockHash;
+use crate::coloring::is_red;
+use crate::dag::DagStore;
+use crate::errors::{GhostDagError, GhostDagResult};
+
+/// Default number of blocks `dag_metrics` looks at.
+pub const DEFAULT_METRICS_WINDOW: usize = 256;
+
+/// Metrics over the most recent blocks of a colored DAG.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DagMetrics {
+    /// Blocks in the window.
+    pub window_blocks: u64,
+    /// Red blocks in the window.
+    pub red_blocks: u64,
+    /// `red_blocks / window_blocks`.
+    pub red_rate: f64,
+    /// Current number of tips.
+    pub tip_count: u64,
+    /// Tips of the DAG without the window's blocks.
+    pub tip_count_before: u64,
+    /// `(tip_count - tip_count_before)` per second of the window's span.
+    pub tip_velocity: f64,
+    /// Mean delta between consecutive block timestamps (ms).
+    pub mean_block_interval_ms: f64,
+    /// Median delta between consecutive block timestamps (ms).
+    pub median_block_interval_ms: f64,
+    /// Mean anticone size within the window.
+    pub mean_anticone: f64,
+    /// Largest anticone within the window.
+    pub max_anticone: u64,
+    /// `mean_anticone · mean_block_interval_ms / 2`.
+    pub estimated_propagation_delay_ms: f64,
+    /// Most blocks sharing one height in the window.
+    pub dag_width: u64,
+    /// Blocks per distinct height in the window.
+    pub mean_width: f64,
+}
+
+/// Compute `DagMetrics` over the last `window` blocks of `dag`.
+pub fn dag_metrics(dag: &DagStore, window: usize) -> GhostDagResult<DagMetrics> {
+    if window == 0 {
+        return Err(GhostDagError::Internal("metrics window must be positive".into()));
+    }
+    dag.genesis().ok_or(GhostDagError::NoGenesis)?;
+    let order = dag.topological_order()?;
+
+    let mut height: HashMap<BlockHash, u64> = HashMap::with_capacity(order.len());
+    for hash in &order {
+        let h = dag
+            .parents(hash)?
+            .iter()
+            .filter_map(|p| height.get(p))
+            .max()
+            .map_or(0, |h| h + 1);
+        height.insert(*hash, h);
+    }
+
+    let start = order.len().saturating_sub(window);
+    let blocks = &order[start..];
+    let in_window: HashSet<BlockHash> = blocks.iter().copied().collect();
+    let n = blocks.len();
+
+    let mut red_blocks = 0u64;
+    for hash in blocks {
+        if is_red(dag, hash)? {
+            red_blocks += 1;
+        }
+    }
+
+    let tip_count = dag.tips().len() as u64;
+    let tip_count_before = order[..start]
+        .iter()
+        .map(|h| dag.children_of(h).map(|ch| ch.iter().all(|c| in_window.contains(c))))
+        .collect::<GhostDagResult<Vec<bool>>>()?
+        .into_iter()
+        .filter(|is_tip| *is_tip)
+        .count() as u64;
+
+    let mut timestamps = blocks
+        .iter()
+        .map(|h| dag.get(h).map(|b| b.timestamp))
+        .collect::<GhostDagResult<Vec<u64>>>()?;
+    timestamps.sort_unstable();
+    let mut intervals: Vec<u64> = timestamps.windows(2).map(|w| w[1] - w[0]).collect();
+    intervals.sort_unstable();
+    let mean_block_interval_ms = mean(intervals.iter().map(|&d| d as f64), intervals.len());
+    let median_block_interval_ms = match intervals.len() {
+        0 => 0.0,
+        len if len % 2 == 1 => intervals[len / 2] as f64,
+        len => (intervals[len / 2 - 1] + intervals[len / 2]) as f64 / 2.0,
+    };
+    let span_secs = match (timestamps.first(), timestamps.last()) {
+        (Some(first), Some(last)) if last > first => (last - first) as f64 / 1000.0,
+        _ => 0.0,
+    };
+    let tip_velocity = if span_secs > 0.0 {
+        (tip_count as f64 - tip_count_before as f64) / span_secs
+    } else {
+        0.0
+    };
+
+    let anticones = window_anticones(dag, blocks, &in_window)?;
+    let mean_anticone = mean(anticones.iter().map(|&a| a as f64), n);
+    let max_anticone = anticones.iter().copied().max().unwrap_or(0);
+
+    let mut per_height: HashMap<u64, u64> = HashMap::new();
+    for hash in blocks {
+        *per_height.entry(height[hash]).or_insert(0) += 1;
+    }
+    let dag_width = per_height.values().copied().max().unwrap_or(0);
+
+    Ok(DagMetrics {
+        window_blocks: n as u64,
+        red_blocks,
+        red_rate: red_blocks as f64 / n as f64,
+        tip_count,
+        tip_count_before,
+        tip_velocity,
+        mean_block_interval_ms,
+        median_block_interval_ms,
+        mean_anticone,
+        max_anticone,
+        estimated_propagation_delay_ms: mean_anticone * mean_block_interval_ms / 2.0,
+        dag_width,
+        mean_width: n as f64 / per_height.len().max(1) as f64,
+    })
+}
+
+/// Anticone size of each window block, counting window blocks only.
+/// `blocks` must be in topological order.
+fn window_anticones(
+    dag: &DagStore,
+    blocks: &[BlockHash],
+    in_window: &HashSet<BlockHash>,
+) -> GhostDagResult<Vec<u64>> {
+    let mut past: HashMap<BlockHash, HashSet<BlockHash>> = HashMap::with_capacity(blocks.len());
+    let mut future_count: HashMap<BlockHash, u64> = HashMap::with_capacity(blocks.len());
+    for hash in blocks {
+        let mut ancestors = HashSet::new();
+        for parent in dag.parents(hash)?.iter().filter(|p| in_window.contains(p)) {
+            ancestors.insert(*parent);
+            ancestors.extend(past[parent].iter().copied());
+        }
+        for ancestor in &ancestors {
+            *future_count.entry(*ancestor).or_insert(0) += 1;
+        }
+        past.insert(*hash, ancestors);
+    }
+    let n = blocks.len() as u64;
+    Ok(blocks
+        .iter()
+        .map(|h| {
+            let related = past[h].len() as u64 + future_count.get(h).copied().unwrap_or(0);
+            n - 1 - related
+        })
+        .collect())
+}
+
+fn mean(values: impl Iterator<Item = f64>, len: usize) -> f64 {
+    if len == 0 {
+        0.0
+    } else {
+        values.sum::<f64>() / len as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::coloring::color_dag;
+
+    fn h(v: u64) -> BlockHash {
+        BlockHash::from_u64(v)
+    }
+
+    /// Genesis, then `levels` levels of `width` blocks each, one every
+    /// 100 ms; every block references all blocks of the previous level.
+    fn layered_dag(levels: u64, width: u64, k: u64) -> DagStore {
+        let mut dag = DagStore::new();
+        dag.add_genesis(BlockHeader::genesis(h(0), 0)).unwrap();
+        let mut prev = vec![h(0)];
+        let mut next_id = 1;
+        for _ in 0..levels {
+            let mut level = Vec::new();
+            for _ in 0..width {
+                dag.add_block(BlockHeader::new(h(next_id), prev.clone(), next_id * 100))
+                    .unwrap();
+                level.push(h(next_id));
+                next_id += 1;
+            }
+            prev = level;
+        }
+        color_dag(&mut dag, k).unwrap();
+        dag
+    }
+
+    #[test]
+    fn test_chain_metrics() {
+        let dag = layered_dag(10, 1, 3);
+        let m = dag_metrics(&dag, DEFAULT_METRICS_WINDOW).unwrap();
+        assert_eq!(m.window_blocks, 11);
+        assert_eq!(m.red_blocks, 0);
+        assert_eq!(m.tip_count, 1);
+        assert_eq!(m.mean_anticone, 0.0);
+        assert_eq!(m.estimated_propagation_delay_ms, 0.0);
+        assert_eq!(m.dag_width, 1);
+        assert_eq!(m.median_block_interval_ms, 100.0);
+    }
+
+    #[test]
+    fn test_wide_dag_metrics() {
+        // Three parallel blocks per level: each has two siblings in its
+        // anticone, and with k = 1 one of every three is red.
+        let dag = layered_dag(6, 3, 1);
+        let m = dag_metrics(&dag, 9).unwrap();
+        assert_eq!(m.window_blocks, 9);
+        assert_eq!(m.dag_width, 3);
+        assert_eq!(m.mean_width, 3.0);
+        assert_eq!(m.max_anticone, 2);
+        assert_eq!(m.mean_anticone, 2.0);
+        assert_eq!(m.red_blocks, 3);
+        assert_eq!(m.tip_count, 3);
+        // Before the window the DAG ended in the three blocks of level 3.
+        assert_eq!(m.tip_count_before, 3);
+        assert_eq!(m.tip_velocity, 0.0);
+        assert_eq!(m.mean_block_interval_ms, 100.0);
+        assert_eq!(m.estimated_propagation_delay_ms, 100.0);
+    }
+
+    #[test]
+    fn test_tip_velocity() {
+        // A chain that forks into four tips in its last 400 ms.
+        let mut dag = layered_dag(3, 1, 3);
+        for i in 0..4 {
+            dag.add_block(BlockHeader::new(h(10 + i), vec![h(3)], 400 + 100 * i))
+                .unwrap();
+        }
+        color_dag(&mut dag, 3).unwrap();
+        let m = dag_metrics(&dag, 4).unwrap();
+        assert_eq!((m.tip_count, m.tip_count_before), (4, 1));
+        assert_eq!(m.tip_velocity, 3.0 / 0.3);
+        assert!(dag_metrics(&dag, 0).is_err());
+    }
+}
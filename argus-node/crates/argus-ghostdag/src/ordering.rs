@@ -3,16 +3,149 @@
 /// Produces a deterministic linear sequence from a DAG by:
 ///
 /// 1. Sorting blocks by `blue_score(B)` ascending.
-/// 2. Breaking ties using `hash(B) XOR hash(selected_parent(B))`
-///    — lexicographic comparison of the 256-bit XOR result.
+/// 2. Breaking ties with an `OrderingRule`.  The default rule is
+///    `hash(B) XOR hash(selected_parent(B))` — lexicographic comparison
+///    of the 256-bit XOR result.  Alternative rules (plain hash, blue work,
+///    timestamp) exist for research comparisons.
+/// 3. Falling back to the block hash when the rule's keys are equal.
 ///
 /// This ordering is the foundation of the Linearization Engine (Task 2)
 /// and guarantees GNN consumers receive a deterministic, chronological stream.
 use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
-use crate::block::BlockHash;
+use serde::{Deserialize, Serialize};
+
+use crate::block::{BlockHash, BlockHeader};
 use crate::dag::DagStore;
-use crate::errors::GhostDagResult;
+use crate::errors::{GhostDagError, GhostDagResult};
+
+/// A tiebreak rule for blocks with equal blue score.
+///
+/// The rule maps each header to a 256-bit key; blocks with equal blue
+/// score are ordered by key ascending, then by hash.
+pub trait OrderingRule: Send + Sync {
+    /// Short identifier (matches `OrderingRuleKind`'s string form).
+    fn name(&self) -> &'static str;
+
+    /// The sort key among blocks with equal blue score.
+    fn tiebreak_key(&self, header: &BlockHeader) -> BlockHash;
+}
+
+/// `hash(B) XOR hash(selected_parent(B))` — the PHANTOM default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XorTiebreak;
+
+impl OrderingRule for XorTiebreak {
+    fn name(&self) -> &'static str {
+        "xor"
+    }
+
+    fn tiebreak_key(&self, header: &BlockHeader) -> BlockHash {
+        // For genesis (no selected parent), use hash XOR ZERO = hash.
+        header
+            .hash
+            .xor(&header.selected_parent.unwrap_or(BlockHash::ZERO))
+    }
+}
+
+/// Plain `hash(B)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HashTiebreak;
+
+impl OrderingRule for HashTiebreak {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn tiebreak_key(&self, header: &BlockHeader) -> BlockHash {
+        header.hash
+    }
+}
+
+/// `blue_work(B)` ascending (saturating at 256 bits).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlueWorkTiebreak;
+
+impl OrderingRule for BlueWorkTiebreak {
+    fn name(&self) -> &'static str {
+        "blue_work"
+    }
+
+    fn tiebreak_key(&self, header: &BlockHeader) -> BlockHash {
+        let bytes = header.blue_work.to_bytes_be();
+        if bytes.len() > 32 {
+            return BlockHash([0xFF; 32]);
+        }
+        let mut key = [0u8; 32];
+        key[32 - bytes.len()..].copy_from_slice(&bytes);
+        BlockHash(key)
+    }
+}
+
+/// Block timestamp ascending.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimestampTiebreak;
+
+impl OrderingRule for TimestampTiebreak {
+    fn name(&self) -> &'static str {
+        "timestamp"
+    }
+
+    fn tiebreak_key(&self, header: &BlockHeader) -> BlockHash {
+        BlockHash::from_u64(header.timestamp)
+    }
+}
+
+/// Selector for the built-in ordering rules, for configuration and
+/// request parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderingRuleKind {
+    #[default]
+    Xor,
+    Hash,
+    BlueWork,
+    Timestamp,
+}
+
+impl OrderingRuleKind {
+    /// Every built-in rule.
+    pub const ALL: [OrderingRuleKind; 4] = [
+        OrderingRuleKind::Xor,
+        OrderingRuleKind::Hash,
+        OrderingRuleKind::BlueWork,
+        OrderingRuleKind::Timestamp,
+    ];
+
+    /// The rule implementation.
+    pub fn rule(self) -> &'static dyn OrderingRule {
+        match self {
+            Self::Xor => &XorTiebreak,
+            Self::Hash => &HashTiebreak,
+            Self::BlueWork => &BlueWorkTiebreak,
+            Self::Timestamp => &TimestampTiebreak,
+        }
+    }
+}
+
+impl fmt::Display for OrderingRuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.rule().name())
+    }
+}
+
+impl FromStr for OrderingRuleKind {
+    type Err = GhostDagError;
+
+    fn from_str(s: &str) -> GhostDagResult<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|k| k.rule().name() == s)
+            .ok_or_else(|| GhostDagError::OrderingError(format!("unknown ordering rule {s:?}")))
+    }
+}
 
 /// A single entry in the linearized output, carrying the block hash
 /// and its position in the total order.
@@ -21,11 +154,13 @@ pub struct OrderedEntry {
     pub hash: BlockHash,
     pub blue_score: u64,
     pub topological_index: u64,
-    /// The XOR tiebreak value (hash XOR selected_parent_hash).
+    /// The ordering rule's tiebreak key (hash XOR selected_parent_hash
+    /// for the default rule).
     pub tiebreak_key: BlockHash,
 }
 
-/// Produce the PHANTOM total ordering over all blocks in the DAG.
+/// Produce the PHANTOM total ordering over all blocks in the DAG,
+/// using the default XOR tiebreak.
 ///
 /// The DAG **must** have been colored first (via `coloring::color_dag`).
 ///
@@ -33,30 +168,33 @@ pub struct OrderedEntry {
 ///
 /// A `Vec<OrderedEntry>` sorted by `(blue_score ASC, tiebreak_key ASC)`.
 pub fn total_order(dag: &DagStore) -> GhostDagResult<Vec<OrderedEntry>> {
-    let all_hashes = dag.all_hashes();
-    let mut entries: Vec<OrderedEntry> = Vec::with_capacity(all_hashes.len());
-
-    for hash in all_hashes {
-        let hdr = dag.get(&hash)?;
-        // XOR tiebreak: hash(B) XOR hash(selected_parent(B)).
-        // For genesis (no selected parent), use hash XOR ZERO = hash.
-        let sp_hash = hdr.selected_parent.unwrap_or(BlockHash::ZERO);
-        let tiebreak_key = hash.xor(&sp_hash);
+    total_order_with(dag, &XorTiebreak)
+}
 
-        entries.push(OrderedEntry {
-            hash,
+/// Produce the total ordering with a custom tiebreak rule.
+///
+/// Sorted by `(blue_score ASC, rule key ASC, hash ASC)`.
+pub fn total_order_with(
+    dag: &DagStore,
+    rule: &dyn OrderingRule,
+) -> GhostDagResult<Vec<OrderedEntry>> {
+    let mut entries: Vec<OrderedEntry> = dag
+        .headers()
+        .map(|hdr| OrderedEntry {
+            hash: hdr.hash,
             blue_score: hdr.blue_score,
             topological_index: 0, // will be assigned after sorting
-            tiebreak_key,
-        });
-    }
+            tiebreak_key: rule.tiebreak_key(hdr),
+        })
+        .collect();
 
     // Sort: primary key = blue_score ASC, secondary key = tiebreak_key ASC
-    // (lexicographic over 256-bit XOR value).
+    // (lexicographic over 256 bits), final key = hash.
     entries.sort_by(|a, b| {
         a.blue_score
             .cmp(&b.blue_score)
             .then_with(|| a.tiebreak_key.cmp(&b.tiebreak_key))
+            .then_with(|| a.hash.cmp(&b.hash))
     });
 
     // Assign topological indices.
@@ -75,19 +213,24 @@ pub fn total_order_hashes(dag: &DagStore) -> GhostDagResult<Vec<BlockHash>> {
 /// Compare two blocks by the PHANTOM ordering rule.
 /// Returns `Ordering::Less` if `a` comes before `b` in the total order.
 pub fn compare_blocks(dag: &DagStore, a: &BlockHash, b: &BlockHash) -> GhostDagResult<Ordering> {
+    compare_blocks_with(dag, a, b, &XorTiebreak)
+}
+
+/// Compare two blocks under a custom tiebreak rule.
+pub fn compare_blocks_with(
+    dag: &DagStore,
+    a: &BlockHash,
+    b: &BlockHash,
+    rule: &dyn OrderingRule,
+) -> GhostDagResult<Ordering> {
     let ha = dag.get(a)?;
     let hb = dag.get(b)?;
 
-    let sp_a = ha.selected_parent.unwrap_or(BlockHash::ZERO);
-    let sp_b = hb.selected_parent.unwrap_or(BlockHash::ZERO);
-
-    let xor_a = a.xor(&sp_a);
-    let xor_b = b.xor(&sp_b);
-
     Ok(ha
         .blue_score
         .cmp(&hb.blue_score)
-        .then_with(|| xor_a.cmp(&xor_b)))
+        .then_with(|| rule.tiebreak_key(ha).cmp(&rule.tiebreak_key(hb)))
+        .then_with(|| a.cmp(b)))
 }
 
 #[cfg(test)]
@@ -167,6 +310,58 @@ mod tests {
         assert_eq!(order[0].hash, BlockHash::from_byte(0));
     }
 
+    #[test]
+    fn test_rules_agree_with_compare_blocks() {
+        let mut dag = build_10_block_dag();
+        color_dag(&mut dag, 3).unwrap();
+
+        for kind in OrderingRuleKind::ALL {
+            let order = total_order_with(&dag, kind.rule()).unwrap();
+            assert_eq!(order.len(), 10);
+            assert_eq!(order[0].hash, BlockHash::from_byte(0));
+            for pair in order.windows(2) {
+                let cmp = compare_blocks_with(&dag, &pair[0].hash, &pair[1].hash, kind.rule())
+                    .unwrap();
+                assert_eq!(cmp, Ordering::Less, "rule {kind}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_timestamp_rule_orders_by_timestamp() {
+        // A and B share blue score 1; B is older but has the larger hash.
+        let mut dag = DagStore::new();
+        let g = BlockHash::from_byte(0);
+        let a = BlockHash::from_byte(1);
+        let b = BlockHash::from_byte(2);
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        dag.add_block(BlockHeader::new(a, vec![g], 20)).unwrap();
+        dag.add_block(BlockHeader::new(b, vec![g], 10)).unwrap();
+        color_dag(&mut dag, 3).unwrap();
+
+        let by_hash: Vec<BlockHash> = total_order_with(&dag, &HashTiebreak)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.hash)
+            .collect();
+        let by_time: Vec<BlockHash> = total_order_with(&dag, &TimestampTiebreak)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.hash)
+            .collect();
+        assert_eq!(by_hash, vec![g, a, b]);
+        assert_eq!(by_time, vec![g, b, a]);
+    }
+
+    #[test]
+    fn test_rule_kind_parse() {
+        for kind in OrderingRuleKind::ALL {
+            assert_eq!(kind.to_string().parse::<OrderingRuleKind>().unwrap(), kind);
+        }
+        assert!("nope".parse::<OrderingRuleKind>().is_err());
+        assert_eq!(OrderingRuleKind::default(), OrderingRuleKind::Xor);
+    }
+
     #[test]
     fn test_compare_blocks() {
         let mut dag = build_10_block_dag();
@@ -0,0 +1,309 @@
+/// Argus GhostDAG — Consensus parameters.
+///
+/// Groups every network-wide constant that used to be passed around as a
+/// bare `k: u64` or hard-coded at call sites: the k-cluster parameter,
+/// parent limits, timing targets, the finality / pruning / merge depths,
+/// the genesis header and any trusted checkpoints.
+///
+/// Named presets (`mainnet`, `testnet`, `devnet`) cover the common cases;
+/// custom networks can be loaded from and saved to TOML.
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::{BlockHash, BlockHeader};
+use crate::checkpoints::{validate_checkpoints, Checkpoint};
+use crate::errors::{GhostDagError, GhostDagResult};
+
+/// The built-in network presets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet,
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mainnet => write!(f, "mainnet"),
+            Self::Testnet => write!(f, "testnet"),
+            Self::Devnet => write!(f, "devnet"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = GhostDagError;
+
+    fn from_str(s: &str) -> GhostDagResult<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "mainnet" => Ok(Self::Mainnet),
+            "testnet" => Ok(Self::Testnet),
+            "devnet" => Ok(Self::Devnet),
+            other => Err(GhostDagError::InvalidParams(format!(
+                "unknown network preset {other:?}"
+            ))),
+        }
+    }
+}
+
+/// The genesis block of a network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisConfig {
+    /// Genesis block hash (hex-encoded in TOML).
+    #[serde(with = "hex_hash")]
+    pub hash: BlockHash,
+    /// Genesis timestamp in milliseconds since epoch.
+    pub timestamp: u64,
+}
+
+impl GenesisConfig {
+    /// Build the genesis `BlockHeader`.
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader::genesis(self.hash, self.timestamp)
+    }
+}
+
+/// Consensus parameters shared by coloring, the agent and the linearizer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsensusParams {
+    /// Human-readable network name.
+    pub network: String,
+    /// The GhostDAG k parameter (maximum blue anticone size).
+    pub k: u64,
+    /// Maximum number of direct parents a block may reference.
+    pub max_parents: usize,
+    /// Target interval between blocks, in milliseconds.
+    pub target_block_time_ms: u64,
+    /// Blue-score depth after which a block is considered final.
+    pub finality_depth: u64,
+    /// Blue-score depth below which block bodies may be pruned.
+    pub pruning_depth: u64,
+    /// Maximum blue-score depth of blocks a new block may merge.
+    pub merge_depth: u64,
+    /// Divergence deeper than `partition_k_multiplier * k` is treated as a
+    /// network partition rather than ordinary drift.
+    pub partition_k_multiplier: u64,
+    /// The genesis block.
+    pub genesis: GenesisConfig,
+    /// Trusted checkpoints, sorted by blue score.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl ConsensusParams {
+    /// Parameters for the named preset.
+    pub fn preset(network: Network) -> Self {
+        match network {
+            Network::Mainnet => Self::mainnet(),
+            Network::Testnet => Self::testnet(),
+            Network::Devnet => Self::devnet(),
+        }
+    }
+
+    /// Mainnet-like parameters: 1 block/s with a PHANTOM-sized k.
+    pub fn mainnet() -> Self {
+        Self {
+            network: Network::Mainnet.to_string(),
+            k: 18,
+            max_parents: 10,
+            target_block_time_ms: 1_000,
+            finality_depth: 86_400,
+            pruning_depth: 185_798,
+            merge_depth: 3_600,
+            partition_k_multiplier: 3,
+            genesis: GenesisConfig {
+                hash: BlockHash::from_u64(0xA126_0000_0000_0001),
+                timestamp: 1_735_689_600_000,
+            },
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Testnet parameters: mainnet timing with shorter depths.
+    pub fn testnet() -> Self {
+        Self {
+            network: Network::Testnet.to_string(),
+            k: 10,
+            max_parents: 10,
+            target_block_time_ms: 1_000,
+            finality_depth: 8_640,
+            pruning_depth: 18_580,
+            merge_depth: 360,
+            partition_k_multiplier: 3,
+            genesis: GenesisConfig {
+                hash: BlockHash::from_u64(0xA126_0000_0000_0002),
+                timestamp: 1_735_689_600_000,
+            },
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Local development parameters (the historical `argus start` defaults).
+    pub fn devnet() -> Self {
+        Self {
+            network: Network::Devnet.to_string(),
+            k: 3,
+            max_parents: 5,
+            target_block_time_ms: 1_000,
+            finality_depth: 1_000,
+            pruning_depth: 2_000,
+            merge_depth: 100,
+            partition_k_multiplier: 3,
+            genesis: GenesisConfig {
+                hash: BlockHash::from_byte(0x00),
+                timestamp: 0,
+            },
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Divergence depth above which the agent assumes a partition.
+    pub fn partition_threshold(&self) -> u64 {
+        self.partition_k_multiplier.saturating_mul(self.k)
+    }
+
+    /// Target block rate in blocks per second.
+    pub fn block_rate(&self) -> f64 {
+        1000.0 / self.target_block_time_ms.max(1) as f64
+    }
+
+    /// Check internal consistency.
+    pub fn validate(&self) -> GhostDagResult<()> {
+        if self.k < 1 {
+            return Err(GhostDagError::InvalidK(self.k));
+        }
+        if self.max_parents < 1 {
+            return Err(GhostDagError::InvalidParams(
+                "max_parents must be >= 1".to_string(),
+            ));
+        }
+        if self.target_block_time_ms == 0 {
+            return Err(GhostDagError::InvalidParams(
+                "target_block_time_ms must be > 0".to_string(),
+            ));
+        }
+        if self.merge_depth > self.finality_depth {
+            return Err(GhostDagError::InvalidParams(format!(
+                "merge_depth ({}) must not exceed finality_depth ({})",
+                self.merge_depth, self.finality_depth
+            )));
+        }
+        if self.finality_depth > self.pruning_depth {
+            return Err(GhostDagError::InvalidParams(format!(
+                "finality_depth ({}) must not exceed pruning_depth ({})",
+                self.finality_depth, self.pruning_depth
+            )));
+        }
+        validate_checkpoints(&self.checkpoints)
+    }
+
+    /// Parse and validate parameters from a TOML document.
+    pub fn from_toml_str(s: &str) -> GhostDagResult<Self> {
+        let params: Self = toml::from_str(s)
+            .map_err(|e| GhostDagError::InvalidParams(format!("TOML parse error: {e}")))?;
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Serialize to a TOML document.
+    pub fn to_toml_string(&self) -> GhostDagResult<String> {
+        toml::to_string_pretty(self)
+            .map_err(|e| GhostDagError::InvalidParams(format!("TOML encode error: {e}")))
+    }
+
+    /// Load parameters from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> GhostDagResult<Self> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).map_err(|e| {
+            GhostDagError::InvalidParams(format!("cannot read {}: {e}", path.display()))
+        })?;
+        Self::from_toml_str(&s)
+    }
+
+    /// Save parameters to a TOML file.
+    pub fn save(&self, path: impl AsRef<Path>) -> GhostDagResult<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_toml_string()?).map_err(|e| {
+            GhostDagError::InvalidParams(format!("cannot write {}: {e}", path.display()))
+        })
+    }
+}
+
+impl Default for ConsensusParams {
+    fn default() -> Self {
+        Self::devnet()
+    }
+}
+
+/// Serde adapter that encodes a `BlockHash` as a hex string.
+pub(crate) mod hex_hash {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::block::BlockHash;
+
+    pub fn serialize<S: Serializer>(hash: &BlockHash, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hash.to_hex())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<BlockHash, D::Error> {
+        let s = String::deserialize(d)?;
+        BlockHash::from_hex(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_are_valid() {
+        for net in [Network::Mainnet, Network::Testnet, Network::Devnet] {
+            let p = ConsensusParams::preset(net);
+            p.validate().unwrap();
+            assert_eq!(p.network, net.to_string());
+        }
+        assert_eq!(ConsensusParams::default(), ConsensusParams::devnet());
+    }
+
+    #[test]
+    fn test_toml_roundtrip() {
+        let p = ConsensusParams::testnet();
+        let s = p.to_toml_string().unwrap();
+        assert!(s.contains(&p.genesis.hash.to_hex()));
+        assert_eq!(ConsensusParams::from_toml_str(&s).unwrap(), p);
+    }
+
+    #[test]
+    fn test_checkpoints_roundtrip() {
+        let mut p = ConsensusParams::devnet();
+        p.checkpoints = vec![
+            Checkpoint::new(10, BlockHash::from_u64(10)),
+            Checkpoint::new(20, BlockHash::from_u64(20)),
+        ];
+        let s = p.to_toml_string().unwrap();
+        assert!(s.contains("[[checkpoints]]"));
+        assert_eq!(ConsensusParams::from_toml_str(&s).unwrap(), p);
+
+        p.checkpoints.reverse();
+        assert!(p.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_params_rejected() {
+        let mut p = ConsensusParams::devnet();
+        p.merge_depth = p.finality_depth + 1;
+        let s = p.to_toml_string().unwrap();
+        assert!(ConsensusParams::from_toml_str(&s).is_err());
+    }
+
+    #[test]
+    fn test_partition_threshold() {
+        let p = ConsensusParams::devnet();
+        assert_eq!(p.partition_threshold(), 9);
+    }
+}
@@ -0,0 +1,452 @@
+/// Argus GhostDAG — Exact PHANTOM reference solver.
+///
+/// PHANTOM defines the blue set as a *maximum k-cluster*: the largest set
+/// `S` such that every `B ∈ S` has `|anticone(B) ∩ S| ≤ k`.  Finding it is
+/// NP-hard, which is why `color_dag` uses the greedy GHOSTDAG rule.  This
+/// module solves the exact problem by branch-and-bound for small DAGs
+/// (up to `MAX_EXACT_BLOCKS`) so the greedy coloring can be validated:
+///
+/// - `max_k_cluster` — an exact maximum k-cluster.
+/// - `compare_with_greedy` — where `color_dag` picks a smaller or different
+///   blue set, or one that is not a k-cluster at all.
+/// - `random_dag` / `measure_deviation` — seeded random DAGs and aggregate
+///   deviation statistics.
+///
+/// Compiled for tests and with the `reference` feature.
+use std::collections::HashSet;
+
+use crate::block::{BlockHash, BlockHeader};
+use crate::coloring::color_dag;
+use crate::dag::DagStore;
+use crate::errors::{GhostDagError, GhostDagResult};
+
+/// Largest DAG the exact solver accepts.
+pub const MAX_EXACT_BLOCKS: usize = 32;
+
+/// Compute an exact maximum k-cluster of `dag`.
+///
+/// Ties between clusters of equal size are broken deterministically in
+/// favor of the lexicographically smallest hashes.
+pub fn max_k_cluster(dag: &DagStore, k: u64) -> GhostDagResult<HashSet<BlockHash>> {
+    let (hashes, anticones) = anticone_masks(dag)?;
+    let mut search = ClusterSearch {
+        anticones: &anticones,
+        k: k.min(u32::MAX as u64) as u32,
+        counts: vec![0; hashes.len()],
+        best: 0,
+        best_size: 0,
+    };
+    search.run(0, 0, 0);
+    Ok(mask_to_set(&hashes, search.best))
+}
+
+/// Whether `set` is a k-cluster of `dag`.
+pub fn is_k_cluster(dag: &DagStore, set: &HashSet<BlockHash>, k: u64) -> GhostDagResult<bool> {
+    let mut scratch = dag.clone();
+    for h in set {
+        let ac = scratch.anticone(h)?;
+        if ac.iter().filter(|a| set.contains(a)).count() as u64 > k {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// How the greedy GHOSTDAG coloring compares with an exact PHANTOM optimum.
+#[derive(Debug, Clone)]
+pub struct PhantomComparison {
+    /// The k parameter compared.
+    pub k: u64,
+    /// Number of blocks in the DAG.
+    pub total_blocks: usize,
+    /// Size of the exact maximum k-cluster.
+    pub optimal_size: usize,
+    /// Size of the greedy blue set.
+    pub greedy_size: usize,
+    /// Whether the greedy blue set satisfies the k-cluster property.
+    pub greedy_is_k_cluster: bool,
+    /// Blocks blue under greedy but not in the reference optimum.
+    pub only_in_greedy: Vec<BlockHash>,
+    /// Blocks in the reference optimum but red under greedy.
+    pub only_in_optimal: Vec<BlockHash>,
+}
+
+impl PhantomComparison {
+    /// How many blocks smaller the greedy blue set is (0 if not smaller).
+    pub fn size_gap(&self) -> usize {
+        self.optimal_size.saturating_sub(self.greedy_size)
+    }
+
+    /// Whether greedy found *a* maximum k-cluster (not necessarily the same
+    /// one as the reference solver).
+    pub fn greedy_is_optimal(&self) -> bool {
+        self.greedy_is_k_cluster && self.greedy_size == self.optimal_size
+    }
+}
+
+/// Color a clone of `dag` greedily and compare it with the exact optimum.
+pub fn compare_with_greedy(dag: &DagStore, k: u64) -> GhostDagResult<PhantomComparison> {
+    let optimal = max_k_cluster(dag, k)?;
+    let mut scratch = dag.clone();
+    let greedy = color_dag(&mut scratch, k)?.blue_set;
+    let greedy_is_k_cluster = is_k_cluster(dag, &greedy, k)?;
+
+    let mut only_in_greedy: Vec<BlockHash> = greedy.difference(&optimal).copied().collect();
+    let mut only_in_optimal: Vec<BlockHash> = optimal.difference(&greedy).copied().collect();
+    only_in_greedy.sort();
+    only_in_optimal.sort();
+
+    Ok(PhantomComparison {
+        k,
+        total_blocks: dag.len(),
+        optimal_size: optimal.len(),
+        greedy_size: greedy.len(),
+        greedy_is_k_cluster,
+        only_in_greedy,
+        only_in_optimal,
+    })
+}
+
+/// Aggregate deviation of greedy coloring over many random DAGs.
+#[derive(Debug, Clone, Default)]
+pub struct DeviationStats {
+    /// Number of DAGs compared.
+    pub trials: usize,
+    /// DAGs where greedy found a maximum k-cluster.
+    pub optimal: usize,
+    /// DAGs where the greedy blue set is a k-cluster smaller than the
+    /// optimum.
+    pub smaller: usize,
+    /// DAGs where the greedy blue set violates the k-cluster property.
+    /// With `optimal` and `smaller` this covers every trial exactly once.
+    pub not_k_cluster: usize,
+    /// Sum of `size_gap` over all trials.
+    pub total_gap: usize,
+    /// Largest `size_gap` observed.
+    pub max_gap: usize,
+}
+
+impl DeviationStats {
+    /// Mean blue-set size gap per DAG.
+    pub fn mean_gap(&self) -> f64 {
+        if self.trials == 0 {
+            0.0
+        } else {
+            self.total_gap as f64 / self.trials as f64
+        }
+    }
+}
+
+/// Compare greedy and exact coloring on `trials` random DAGs.
+pub fn measure_deviation(
+    seed: u64,
+    trials: usize,
+    config: &RandomDagConfig,
+    k: u64,
+) -> GhostDagResult<DeviationStats> {
+    let mut stats = DeviationStats::default();
+    for t in 0..trials {
+        let dag = random_dag(seed.wrapping_add(t as u64), config)?;
+        let cmp = compare_with_greedy(&dag, k)?;
+        stats.trials += 1;
+        if !cmp.greedy_is_k_cluster {
+            stats.not_k_cluster += 1;
+        } else if cmp.greedy_size < cmp.optimal_size {
+            stats.smaller += 1;
+        } else {
+            stats.optimal += 1;
+        }
+        stats.total_gap += cmp.size_gap();
+        stats.max_gap = stats.max_gap.max(cmp.size_gap());
+    }
+    Ok(stats)
+}
+
+/// Shape of the DAGs produced by `random_dag`.
+#[derive(Debug, Clone)]
+pub struct RandomDagConfig {
+    /// Total blocks including genesis.
+    pub blocks: usize,
+    /// Maximum propagation lag, in blocks: a new block only sees blocks
+    /// created at least `0..=max_lag` steps earlier.  Larger values give
+    /// wider DAGs.
+    pub max_lag: usize,
+    /// Maximum parents per block.
+    pub max_parents: usize,
+}
+
+impl Default for RandomDagConfig {
+    fn default() -> Self {
+        Self {
+            blocks: 16,
+            max_lag: 3,
+            max_parents: 3,
+        }
+    }
+}
+
+/// Generate a seeded random DAG.
+///
+/// Blocks are created one at a time; each sees a random-lag prefix of the
+/// earlier blocks and references (a random subset of) that prefix's tips,
+/// mimicking miners with stale views of the network.
+pub fn random_dag(seed: u64, config: &RandomDagConfig) -> GhostDagResult<DagStore> {
+    if config.blocks == 0 || config.max_parents == 0 {
+        return Err(GhostDagError::Internal(
+            "random_dag needs at least one block and one parent".to_string(),
+        ));
+    }
+    let mut rng = SplitMix64(seed);
+    let mut dag = DagStore::new();
+    let mut hashes: Vec<BlockHash> = Vec::with_capacity(config.blocks);
+    // parents_of[i] = indices of block i's parents.
+    let mut parents_of: Vec<Vec<usize>> = Vec::with_capacity(config.blocks);
+
+    let genesis = BlockHash::from_u64(rng.next() | 1);
+    dag.add_genesis(BlockHeader::genesis(genesis, 0))?;
+    hashes.push(genesis);
+    parents_of.push(Vec::new());
+
+    for i in 1..config.blocks {
+        let lag = (rng.next() % (config.max_lag as u64 + 1)) as usize;
+        let visible = i.saturating_sub(lag).max(1);
+
+        // Tips of the visible prefix: blocks with no child inside it.
+        let mut has_child = vec![false; visible];
+        for ps in &parents_of[..visible] {
+            for &p in ps {
+                has_child[p] = true;
+            }
+        }
+        let mut tips: Vec<usize> = (0..visible).filter(|&j| !has_child[j]).collect();
+
+        // Random subset of at most `max_parents` tips, at least one.
+        for j in (1..tips.len()).rev() {
+            let swap = (rng.next() % (j as u64 + 1)) as usize;
+            tips.swap(j, swap);
+        }
+        let take = 1 + (rng.next() % config.max_parents as u64) as usize;
+        tips.truncate(take.min(tips.len()));
+
+        let mut hash = BlockHash::from_u64(rng.next());
+        while dag.contains(&hash) {
+            hash = BlockHash::from_u64(rng.next());
+        }
+        let parents: Vec<BlockHash> = tips.iter().map(|&j| hashes[j]).collect();
+        dag.add_block(BlockHeader::new(hash, parents, i as u64))?;
+        hashes.push(hash);
+        parents_of.push(tips);
+    }
+    Ok(dag)
+}
+
+// ---------------------------------------------------------------------------
+// Internals
+// ---------------------------------------------------------------------------
+
+/// Hashes sorted ascending, and each block's anticone as a bitmask over
+/// those indices.
+fn anticone_masks(dag: &DagStore) -> GhostDagResult<(Vec<BlockHash>, Vec<u64>)> {
+    if dag.len() > MAX_EXACT_BLOCKS {
+        return Err(GhostDagError::Internal(format!(
+            "exact PHANTOM solver supports at most {MAX_EXACT_BLOCKS} blocks, got {}",
+            dag.len()
+        )));
+    }
+    let mut hashes = dag.all_hashes();
+    hashes.sort();
+    let index = |h: &BlockHash| hashes.iter().position(|x| x == h);
+
+    let mut scratch = dag.clone();
+    let mut anticones = Vec::with_capacity(hashes.len());
+    for h in &hashes {
+        let mut mask = 0u64;
+        for a in scratch.anticone(h)? {
+            if let Some(i) = index(&a) {
+                mask |= 1 << i;
+            }
+        }
+        anticones.push(mask);
+    }
+    Ok((hashes, anticones))
+}
+
+fn mask_to_set(hashes: &[BlockHash], mask: u64) -> HashSet<BlockHash> {
+    hashes
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| mask & (1 << i) != 0)
+        .map(|(_, h)| *h)
+        .collect()
+}
+
+/// Branch-and-bound over include/exclude decisions in index order.
+struct ClusterSearch<'a> {
+    anticones: &'a [u64],
+    k: u32,
+    /// `counts[i]` = |anticone(i) ∩ current set|.
+    counts: Vec<u32>,
+    best: u64,
+    best_size: u32,
+}
+
+impl ClusterSearch<'_> {
+    fn run(&mut self, idx: usize, set: u64, size: u32) {
+        let n = self.anticones.len();
+        if size > self.best_size {
+            self.best = set;
+            self.best_size = size;
+        }
+        if idx == n || size + (n - idx) as u32 <= self.best_size {
+            return;
+        }
+
+        // Include `idx` if it and every member of its anticone stay within k.
+        let ac = self.anticones[idx];
+        let fits = self.counts[idx] <= self.k
+            && (0..n)
+                .filter(|&j| set & ac & (1 << j) != 0)
+                .all(|j| self.counts[j] < self.k);
+        if fits {
+            for j in 0..n {
+                if ac & (1 << j) != 0 {
+                    self.counts[j] += 1;
+                }
+            }
+            self.run(idx + 1, set | (1 << idx), size + 1);
+            for j in 0..n {
+                if ac & (1 << j) != 0 {
+                    self.counts[j] -= 1;
+                }
+            }
+        }
+
+        self.run(idx + 1, set, size);
+    }
+}
+
+/// Minimal deterministic PRNG so the generator needs no dependencies.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exhaustive search over all subsets, for cross-checking tiny DAGs.
+    fn brute_force_size(dag: &DagStore, k: u64) -> usize {
+        let (_, anticones) = anticone_masks(dag).unwrap();
+        let n = anticones.len();
+        (0u64..1 << n)
+            .filter(|&s| {
+                (0..n)
+                    .filter(|&i| s & (1 << i) != 0)
+                    .all(|i| (anticones[i] & s).count_ones() as u64 <= k)
+            })
+            .map(|s| s.count_ones() as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_wide_dag_optimum() {
+        // Genesis, five parallel siblings, one merging tip.
+        let mut dag = DagStore::new();
+        let g = BlockHash::from_byte(0);
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        let side: Vec<BlockHash> = (1u8..=5).map(BlockHash::from_byte).collect();
+        for h in &side {
+            dag.add_block(BlockHeader::new(*h, vec![g], 1)).unwrap();
+        }
+        dag.add_block(BlockHeader::new(BlockHash::from_byte(6), side, 2))
+            .unwrap();
+
+        // k=1: genesis + two siblings + tip.
+        let cluster = max_k_cluster(&dag, 1).unwrap();
+        assert_eq!(cluster.len(), 4);
+        assert!(is_k_cluster(&dag, &cluster, 1).unwrap());
+
+        let cmp = compare_with_greedy(&dag, 1).unwrap();
+        assert!(cmp.greedy_is_optimal());
+    }
+
+    #[test]
+    fn test_solver_matches_brute_force() {
+        let config = RandomDagConfig {
+            blocks: 12,
+            max_lag: 4,
+            max_parents: 3,
+        };
+        for seed in 0..20 {
+            let dag = random_dag(seed, &config).unwrap();
+            for k in 1..=3 {
+                let cluster = max_k_cluster(&dag, k).unwrap();
+                assert!(is_k_cluster(&dag, &cluster, k).unwrap());
+                assert_eq!(cluster.len(), brute_force_size(&dag, k), "seed {seed} k {k}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_dag_is_deterministic() {
+        let config = RandomDagConfig::default();
+        let a = random_dag(7, &config).unwrap();
+        let b = random_dag(7, &config).unwrap();
+        let mut ha = a.all_hashes();
+        let mut hb = b.all_hashes();
+        ha.sort();
+        hb.sort();
+        assert_eq!(ha, hb);
+        assert_eq!(a.len(), config.blocks);
+    }
+
+    #[test]
+    fn test_greedy_never_beats_a_valid_optimum() {
+        let config = RandomDagConfig {
+            blocks: 24,
+            max_lag: 4,
+            max_parents: 3,
+        };
+        for seed in 42..67 {
+            let dag = random_dag(seed, &config).unwrap();
+            let cmp = compare_with_greedy(&dag, 2).unwrap();
+            if cmp.greedy_is_k_cluster {
+                assert!(cmp.greedy_size <= cmp.optimal_size);
+            }
+        }
+
+        let stats = measure_deviation(42, 25, &config, 2).unwrap();
+        assert_eq!(stats.trials, 25);
+        assert_eq!(stats.optimal + stats.smaller + stats.not_k_cluster, stats.trials);
+        // Wide DAGs: greedy's blue set is never a global k-cluster here.
+        assert_eq!(stats.not_k_cluster, 25);
+
+        // Narrower DAGs and a larger k: seed 42 gives 4 optimal trials.
+        let narrow = RandomDagConfig {
+            blocks: 12,
+            max_lag: 2,
+            max_parents: 3,
+        };
+        let stats = measure_deviation(42, 25, &narrow, 3).unwrap();
+        assert_eq!((stats.optimal, stats.smaller, stats.not_k_cluster), (4, 0, 21));
+    }
+
+    #[test]
+    fn test_rejects_large_dags() {
+        let config = RandomDagConfig {
+            blocks: MAX_EXACT_BLOCKS + 1,
+            ..Default::default()
+        };
+        let dag = random_dag(1, &config).unwrap();
+        assert!(max_k_cluster(&dag, 3).is_err());
+    }
+}
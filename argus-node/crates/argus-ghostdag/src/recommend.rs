@@ -0,0 +1,114 @@
+/// Argus GhostDAG — Choosing k from network delay and block rate.
+///
+/// PHANTOM (Sompolinsky & Zohar, §4) picks k so that an honest block's
+/// anticone rarely exceeds it.  Honest blocks created within `D` before or
+/// after a block cannot reference it or be referenced by it, so with blocks
+/// arriving as a Poisson process of rate `λ` the anticone size is
+/// `Poisson(2Dλ)`.  k is the smallest integer with
+///
+/// ```text
+///   P[Poisson(2Dλ) > k] = Σ_{j > k} e^{-2Dλ} (2Dλ)^j / j!  <  δ
+/// ```
+///
+/// A larger δ buys a smaller k (faster confirmation) at the cost of more
+/// honest blocks being colored red.
+use std::time::Duration;
+
+use crate::errors::{GhostDagError, GhostDagResult};
+
+/// Default tolerated probability of an honest anticone exceeding k.
+pub const DEFAULT_OVERFLOW_PROBABILITY: f64 = 0.01;
+
+/// Largest k `recommend_k` will search up to.
+pub const MAX_RECOMMENDED_K: u64 = 100_000;
+
+/// Probability that an honest block's anticone holds more than `k` blocks,
+/// given network delay `delay` and block rate `rate` (blocks per second).
+pub fn anticone_overflow_probability(k: u64, delay: Duration, rate: f64) -> f64 {
+    let mean = 2.0 * delay.as_secs_f64() * rate;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    let mut cdf = 0.0;
+    // Work with log terms so large means do not underflow e^{-mean}.
+    let mut log_term = -mean;
+    for j in 0..=k {
+        cdf += log_term.exp();
+        log_term += mean.ln() - ((j + 1) as f64).ln();
+    }
+    (1.0 - cdf).max(0.0)
+}
+
+/// The smallest k (at least 1) whose anticone-overflow probability is
+/// below `delta`.
+pub fn recommend_k(delay: Duration, rate: f64, delta: f64) -> GhostDagResult<u64> {
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(GhostDagError::InvalidParams(format!(
+            "block rate must be positive, got {rate}"
+        )));
+    }
+    if !(delta > 0.0 && delta < 1.0) {
+        return Err(GhostDagError::InvalidParams(format!(
+            "overflow probability must be in (0, 1), got {delta}"
+        )));
+    }
+    let mean = 2.0 * delay.as_secs_f64() * rate;
+    if !mean.is_finite() {
+        return Err(GhostDagError::InvalidParams(format!(
+            "2·delay·rate is not finite ({mean})"
+        )));
+    }
+    if mean == 0.0 {
+        return Ok(1);
+    }
+
+    let mut cdf = 0.0;
+    let mut log_term = -mean;
+    for k in 0..=MAX_RECOMMENDED_K {
+        cdf += log_term.exp();
+        if 1.0 - cdf < delta {
+            return Ok(k.max(1));
+        }
+        log_term += mean.ln() - ((k + 1) as f64).ln();
+    }
+    Err(GhostDagError::InvalidParams(format!(
+        "no k up to {MAX_RECOMMENDED_K} meets δ = {delta} for 2Dλ = {mean:.1}"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recommend_k_matches_poisson_tail() {
+        // 2Dλ = 1: P[X > 3] ≈ 0.019, P[X > 4] ≈ 0.0037.
+        let delay = Duration::from_millis(500);
+        assert_eq!(recommend_k(delay, 1.0, 0.01).unwrap(), 4);
+        assert_eq!(recommend_k(delay, 1.0, 0.05).unwrap(), 3);
+        let p = anticone_overflow_probability(4, delay, 1.0);
+        assert!((p - 0.00366).abs() < 1e-4);
+
+        // Faster blocks or longer delays need a larger k.
+        assert!(recommend_k(Duration::from_secs(2), 1.0, 0.01).unwrap() > 4);
+        assert!(recommend_k(delay, 10.0, 0.01).unwrap() > 4);
+        // No delay: every block sees all others.
+        assert_eq!(recommend_k(Duration::ZERO, 1.0, 0.01).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_recommend_k_large_mean() {
+        // 2Dλ = 1000 underflows e^{-2Dλ}; the answer is ≈ mean + 2.3σ.
+        let k = recommend_k(Duration::from_secs(50), 10.0, 0.01).unwrap();
+        assert!((1060..=1090).contains(&k), "k = {k}");
+    }
+
+    #[test]
+    fn test_recommend_k_rejects_bad_input() {
+        let delay = Duration::from_millis(500);
+        assert!(recommend_k(delay, 0.0, 0.01).is_err());
+        assert!(recommend_k(delay, 1.0, 0.0).is_err());
+        assert!(recommend_k(delay, 1.0, 1.0).is_err());
+        assert!(recommend_k(delay, f64::NAN, 0.01).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+/// Argus GhostDAG — Epoch-scheduled k.
+///
+/// A `KSchedule` records which k is in force from which blue score on.
+/// Coloring looks up the k for each block by that block's own blue score,
+/// so a k change only affects blocks at or above its activation point and
+/// the colors of existing history never change retroactively.  Two nodes
+/// that agree on the schedule agree on every block's color, regardless of
+/// when each of them learned about the change.
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{GhostDagError, GhostDagResult};
+
+/// A single scheduled k change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KChange {
+    /// First blue score at which `k` applies.
+    pub activation_blue_score: u64,
+    /// The k parameter in force from the activation point on.
+    pub k: u64,
+}
+
+/// An ordered list of k changes.  The first entry always activates at
+/// blue score 0 (genesis); deserializing checks this with `validate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawKSchedule")]
+pub struct KSchedule {
+    changes: Vec<KChange>,
+}
+
+/// `KSchedule` as written on the wire, before validation.
+#[derive(Deserialize)]
+struct RawKSchedule {
+    changes: Vec<KChange>,
+}
+
+impl TryFrom<RawKSchedule> for KSchedule {
+    type Error = GhostDagError;
+
+    fn try_from(raw: RawKSchedule) -> GhostDagResult<Self> {
+        let schedule = KSchedule {
+            changes: raw.changes,
+        };
+        schedule.validate()?;
+        Ok(schedule)
+    }
+}
+
+impl KSchedule {
+    /// A schedule with a single k in force from genesis.
+    pub fn new(initial_k: u64) -> Self {
+        Self {
+            changes: vec![KChange {
+                activation_blue_score: 0,
+                k: initial_k,
+            }],
+        }
+    }
+
+    /// The k in force for a block with the given blue score.
+    pub fn k_at(&self, blue_score: u64) -> u64 {
+        self.changes
+            .iter()
+            .rev()
+            .find(|c| c.activation_blue_score <= blue_score)
+            .map(|c| c.k)
+            .unwrap_or(self.changes[0].k)
+    }
+
+    /// The most recently scheduled k (the k new blocks will eventually use).
+    pub fn latest_k(&self) -> u64 {
+        self.changes.last().map(|c| c.k).unwrap_or(self.changes[0].k)
+    }
+
+    /// The activation point of the last scheduled change.
+    pub fn last_activation(&self) -> u64 {
+        self.changes
+            .last()
+            .map(|c| c.activation_blue_score)
+            .unwrap_or(0)
+    }
+
+    /// All scheduled changes, ordered by activation.
+    pub fn changes(&self) -> &[KChange] {
+        &self.changes
+    }
+
+    /// Schedule `k` from `activation_blue_score` on.
+    ///
+    /// Activations must not precede the last scheduled change; scheduling at
+    /// exactly the same activation replaces that change.  Callers are
+    /// responsible for rejecting activations at or below the current tip,
+    /// which would recolor history.
+    pub fn schedule(&mut self, activation_blue_score: u64, k: u64) -> GhostDagResult<()> {
+        if k < 1 {
+            return Err(GhostDagError::InvalidK(k));
+        }
+        let last = self.last_activation();
+        if activation_blue_score < last {
+            return Err(GhostDagError::InvalidSchedule(format!(
+                "activation {activation_blue_score} precedes the last scheduled change at {last}"
+            )));
+        }
+        if activation_blue_score == last {
+            if let Some(c) = self.changes.last_mut() {
+                c.k = k;
+            }
+        } else {
+            self.changes.push(KChange {
+                activation_blue_score,
+                k,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that the schedule starts at genesis, that activations
+    /// strictly increase and that every k is >= 1.
+    pub fn validate(&self) -> GhostDagResult<()> {
+        match self.changes.first() {
+            None => {
+                return Err(GhostDagError::InvalidSchedule(
+                    "schedule has no k changes".to_string(),
+                ))
+            }
+            Some(first) if first.activation_blue_score != 0 => {
+                return Err(GhostDagError::InvalidSchedule(format!(
+                    "first change activates at {} instead of 0",
+                    first.activation_blue_score
+                )))
+            }
+            Some(_) => {}
+        }
+        for pair in self.changes.windows(2) {
+            if pair[1].activation_blue_score <= pair[0].activation_blue_score {
+                return Err(GhostDagError::InvalidSchedule(format!(
+                    "activation {} does not follow {}",
+                    pair[1].activation_blue_score, pair[0].activation_blue_score
+                )));
+            }
+        }
+        for c in &self.changes {
+            if c.k < 1 {
+                return Err(GhostDagError::InvalidK(c.k));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_k_at() {
+        let mut s = KSchedule::new(3);
+        s.schedule(10, 5).unwrap();
+        s.schedule(20, 8).unwrap();
+        assert_eq!(s.k_at(0), 3);
+        assert_eq!(s.k_at(9), 3);
+        assert_eq!(s.k_at(10), 5);
+        assert_eq!(s.k_at(19), 5);
+        assert_eq!(s.k_at(1_000), 8);
+        assert_eq!(s.latest_k(), 8);
+    }
+
+    #[test]
+    fn test_schedule_rejects_out_of_order() {
+        let mut s = KSchedule::new(3);
+        s.schedule(10, 5).unwrap();
+        assert!(s.schedule(5, 4).is_err());
+        assert!(s.schedule(12, 0).is_err());
+
+        // Same activation replaces.
+        s.schedule(10, 6).unwrap();
+        assert_eq!(s.changes().len(), 2);
+        assert_eq!(s.k_at(10), 6);
+    }
+
+    #[test]
+    fn test_deserialize_validates() {
+        let parse = |json: &str| serde_json::from_str::<KSchedule>(json);
+        let s = parse(
+            r#"{"changes":[{"activation_blue_score":0,"k":3},{"activation_blue_score":9,"k":5}]}"#,
+        )
+        .unwrap();
+        assert_eq!(s.k_at(9), 5);
+
+        assert!(parse(r#"{"changes":[]}"#).is_err());
+        assert!(parse(r#"{"changes":[{"activation_blue_score":4,"k":3}]}"#).is_err());
+        assert!(parse(
+            r#"{"changes":[{"activation_blue_score":0,"k":3},{"activation_blue_score":0,"k":5}]}"#
+        )
+        .is_err());
+        assert!(parse(r#"{"changes":[{"activation_blue_score":0,"k":0}]}"#).is_err());
+    }
+}
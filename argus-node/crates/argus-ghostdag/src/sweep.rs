@@ -0,0 +1,200 @@
+/// Argus GhostDAG — Multi-k sweep analysis.
+///
+/// Colors *clones* of a DAG for a range of k values and reports how each
+/// choice of k would shape the blue set and the PHANTOM ordering, without
+/// mutating the caller's DAG.  Each k is colored on its own thread.
+///
+/// This is the read-only counterpart to hot-swapping k on a live node:
+/// the RL optimizer and operators can compare candidates side by side
+/// instead of recoloring (and re-broadcasting) for every trial.
+use std::thread;
+
+use crate::block::BlockHash;
+use crate::coloring::color_dag;
+use crate::dag::DagStore;
+use crate::errors::{GhostDagError, GhostDagResult};
+use crate::ordering::total_order_hashes;
+
+/// Summary of coloring the DAG with a single k.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KSweepResult {
+    /// The k parameter this entry was colored with.
+    pub k: u64,
+    /// Number of blue blocks.
+    pub blue_count: u64,
+    /// Number of red blocks.
+    pub red_count: u64,
+    /// `blue_count / total_blocks` (0.0 for an empty DAG).
+    pub blue_ratio: f64,
+    /// The tip with the highest blue score under this k.
+    pub selected_tip: BlockHash,
+    /// Whether `selected_tip` differs from the input DAG's selected tip.
+    pub selected_tip_changed: bool,
+    /// Fraction of positions in the total order that are identical to the
+    /// ordering of the input DAG (1.0 = the order is unchanged).
+    pub ordering_stability: f64,
+}
+
+/// Color a clone of `dag` once per entry in `ks` and summarize each run.
+///
+/// The input DAG is treated as the reference: `selected_tip_changed` and
+/// `ordering_stability` compare against the coloring it currently carries
+/// (normally the live k).  Results are returned in the order of `ks`.
+pub fn sweep_k(dag: &DagStore, ks: &[u64]) -> GhostDagResult<Vec<KSweepResult>> {
+    if let Some(&bad) = ks.iter().find(|&&k| k < 1) {
+        return Err(GhostDagError::InvalidK(bad));
+    }
+    dag.genesis().ok_or(GhostDagError::NoGenesis)?;
+
+    let reference_order = total_order_hashes(dag)?;
+    let reference_tip = selected_tip(dag)?;
+
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .max(1);
+
+    let mut results = Vec::with_capacity(ks.len());
+    for chunk in ks.chunks(workers) {
+        let chunk_results: Vec<GhostDagResult<KSweepResult>> = thread::scope(|scope| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|&k| {
+                    let reference_order = &reference_order;
+                    scope.spawn(move || sweep_one(dag, k, reference_order, reference_tip))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join().unwrap_or_else(|_| {
+                        Err(GhostDagError::Internal("k sweep worker panicked".to_string()))
+                    })
+                })
+                .collect()
+        });
+        for r in chunk_results {
+            results.push(r?);
+        }
+    }
+    Ok(results)
+}
+
+/// Color a single clone with `k` and compare it against the reference.
+fn sweep_one(
+    dag: &DagStore,
+    k: u64,
+    reference_order: &[BlockHash],
+    reference_tip: BlockHash,
+) -> GhostDagResult<KSweepResult> {
+    let mut scratch = dag.clone();
+    let coloring = color_dag(&mut scratch, k)?;
+    let order = total_order_hashes(&scratch)?;
+    let tip = selected_tip(&scratch)?;
+
+    let total = scratch.len();
+    let blue_count = coloring.blue_set.len() as u64;
+    let red_count = coloring.red_set.len() as u64;
+    let blue_ratio = if total == 0 {
+        0.0
+    } else {
+        blue_count as f64 / total as f64
+    };
+    let ordering_stability = if order.is_empty() {
+        1.0
+    } else {
+        let same = order
+            .iter()
+            .zip(reference_order)
+            .filter(|(a, b)| a == b)
+            .count();
+        same as f64 / order.len() as f64
+    };
+
+    Ok(KSweepResult {
+        k,
+        blue_count,
+        red_count,
+        blue_ratio,
+        selected_tip: tip,
+        selected_tip_changed: tip != reference_tip,
+        ordering_stability,
+    })
+}
+
+/// The tip with the highest blue score (ties broken by the larger hash,
+/// matching the selected-parent rule in `color_dag`).
+pub fn selected_tip(dag: &DagStore) -> GhostDagResult<BlockHash> {
+    dag.tips()
+        .into_iter()
+        .filter_map(|h| dag.get(&h).ok().map(|hdr| (hdr.blue_score, h)))
+        .max()
+        .map(|(_, h)| h)
+        .ok_or(GhostDagError::NoGenesis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+
+    /// A wide DAG: genesis with five parallel children merged by one tip.
+    fn build_wide_dag() -> DagStore {
+        let mut dag = DagStore::new();
+        let g = BlockHash::from_byte(0);
+        dag.add_genesis(BlockHeader::genesis(g, 0)).unwrap();
+        let side: Vec<BlockHash> = (1u8..=5).map(BlockHash::from_byte).collect();
+        for h in &side {
+            dag.add_block(BlockHeader::new(*h, vec![g], 1)).unwrap();
+        }
+        dag.add_block(BlockHeader::new(BlockHash::from_byte(6), side, 2))
+            .unwrap();
+        dag
+    }
+
+    #[test]
+    fn test_sweep_does_not_mutate_input() {
+        let mut dag = build_wide_dag();
+        color_dag(&mut dag, 10).unwrap();
+        let before: Vec<u64> = {
+            let mut v: Vec<_> = dag.headers().map(|h| h.blue_score).collect();
+            v.sort();
+            v
+        };
+
+        sweep_k(&dag, &[1, 2]).unwrap();
+
+        let mut after: Vec<u64> = dag.headers().map(|h| h.blue_score).collect();
+        after.sort();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn test_sweep_reports_per_k() {
+        let mut dag = build_wide_dag();
+        color_dag(&mut dag, 10).unwrap();
+
+        let results = sweep_k(&dag, &[1, 10]).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].k, 1);
+        assert_eq!(results[1].k, 10);
+
+        // k=1 cannot keep all five siblings blue.
+        assert!(results[0].red_count > 0);
+        assert!(results[0].blue_ratio < 1.0);
+
+        // The reference k reproduces the input ordering exactly.
+        assert_eq!(results[1].red_count, 0);
+        assert_eq!(results[1].ordering_stability, 1.0);
+        assert!(!results[1].selected_tip_changed);
+    }
+
+    #[test]
+    fn test_sweep_rejects_invalid_k() {
+        let dag = build_wide_dag();
+        assert!(matches!(
+            sweep_k(&dag, &[3, 0]),
+            Err(GhostDagError::InvalidK(0))
+        ));
+    }
+}
@@ -0,0 +1,311 @@
+/// Argus Linearizer — Per-client flow control for the WebSocket stream.
+///
+/// Every client has a bounded outgoing queue drained by its own writer
+/// task.  A client falls behind when that queue is full as a frame arrives
+/// (or when it misses frames from the hub itself); from then on its frames
+/// are dropped and counted, and `SlowConsumerPolicy` decides what happens:
+///
+/// - `Disconnect` closes the connection at once.
+/// - `Coalesce` waits for room, then sends a `gap` message and one delta
+///   merging every missed delta (latest version of each block).
+/// - `Resync` waits for room, then sends a `gap` message and a snapshot.
+///
+/// `ClientRegistry` keeps live queue and lag counters for every connected
+/// client, served by the `get_stream_clients` RPC.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::SocketAddr;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use argus_ghostdag::ordering::OrderingRuleKind;
+
+use crate::schema::{BlockDelta, BlockDeltaKind, LinearizedBlock, StreamClientMetrics};
+
+/// Default number of outgoing message batches queued per client.
+pub const DEFAULT_CLIENT_QUEUE: usize = 64;
+
+/// What to do with a client whose queue overflowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowConsumerPolicy {
+    /// Close the connection.
+    Disconnect,
+    /// Send one delta merging everything missed.
+    Coalesce,
+    /// Send a full snapshot.
+    #[default]
+    Resync,
+}
+
+impl fmt::Display for SlowConsumerPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SlowConsumerPolicy::Disconnect => "disconnect",
+            SlowConsumerPolicy::Coalesce => "coalesce",
+            SlowConsumerPolicy::Resync => "resync",
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for SlowConsumerPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disconnect" => Ok(SlowConsumerPolicy::Disconnect),
+            "coalesce" => Ok(SlowConsumerPolicy::Coalesce),
+            "resync" => Ok(SlowConsumerPolicy::Resync),
+            other => Err(format!(
+                "unknown slow-consumer policy '{other}' (expected disconnect, coalesce or resync)"
+            )),
+        }
+    }
+}
+
+/// Per-client queue size and overflow policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowControl {
+    /// Outgoing message batches queued per client before it is behind.
+    pub client_queue: usize,
+    /// What to do once it is.
+    pub policy: SlowConsumerPolicy,
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        Self {
+            client_queue: DEFAULT_CLIENT_QUEUE,
+            policy: SlowConsumerPolicy::default(),
+        }
+    }
+}
+
+/// Merge consecutive deltas into one holding the latest version of each
+/// block, in total-order sequence.  `None` if `deltas` is empty.
+pub fn coalesce_deltas<'a>(deltas: impl IntoIterator<Item = &'a BlockDelta>) -> Option<BlockDelta> {
+    let mut latest: Option<(u64, OrderingRuleKind)> = None;
+    let mut blocks: HashMap<&str, &LinearizedBlock> = HashMap::new();
+    for delta in deltas {
+        latest = Some((delta.seq, delta.ordering));
+        for block in &delta.blocks {
+            blocks.insert(block.hash.as_str(), block);
+        }
+    }
+    let (seq, ordering) = latest?;
+    let mut blocks: Vec<LinearizedBlock> = blocks.into_values().cloned().collect();
+    blocks.sort_by_key(|b| b.topological_index);
+    Some(BlockDelta {
+        kind: BlockDeltaKind::Delta,
+        seq,
+        ordering,
+        blocks,
+    })
+}
+
+/// Live counters for one connected client.
+#[derive(Debug)]
+pub struct ClientStats {
+    id: u64,
+    peer: SocketAddr,
+    ordering: OrderingRuleKind,
+    queue_capacity: usize,
+    queued: AtomicUsize,
+    max_queued: AtomicUsize,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    gaps: AtomicU64,
+    last_seq: AtomicU64,
+}
+
+impl ClientStats {
+    /// A batch was queued.
+    pub fn enqueued(&self) {
+        let depth = self.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        self.max_queued.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    /// A batch of `messages` was written to the socket.
+    pub fn written(&self, messages: usize) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        self.sent.fetch_add(messages as u64, Ordering::Relaxed);
+    }
+
+    /// `frames` were dropped while the client was behind.
+    pub fn dropped(&self, frames: u64) {
+        self.dropped.fetch_add(frames, Ordering::Relaxed);
+    }
+
+    /// A `gap` message was sent.
+    pub fn gap(&self) {
+        self.gaps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The client now has every delta up to `seq`.
+    pub fn delivered_seq(&self, seq: u64) {
+        self.last_seq.fetch_max(seq, Ordering::Relaxed);
+    }
+
+    fn metrics(&self, hub_seq: u64) -> StreamClientMetrics {
+        let last_seq = self.last_seq.load(Ordering::Relaxed);
+        StreamClientMetrics {
+            id: self.id,
+            peer: self.peer.to_string(),
+            ordering: self.ordering,
+            queued: self.queued.load(Ordering::Relaxed),
+            max_queued: self.max_queued.load(Ordering::Relaxed),
+            queue_capacity: self.queue_capacity,
+            sent: self.sent.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            gaps: self.gaps.load(Ordering::Relaxed),
+            last_seq,
+            lag: hub_seq.saturating_sub(last_seq),
+        }
+    }
+}
+
+/// Counters of every connected client.  Cloning shares the registry.
+#[derive(Debug, Clone, Default)]
+pub struct ClientRegistry {
+    next_id: Arc<AtomicU64>,
+    clients: Arc<Mutex<BTreeMap<u64, Arc<ClientStats>>>>,
+}
+
+impl ClientRegistry {
+    /// Track a new client until the returned registration is dropped.
+    pub fn register(
+        &self,
+        peer: SocketAddr,
+        ordering: OrderingRuleKind,
+        queue_capacity: usize,
+    ) -> ClientRegistration {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let stats = Arc::new(ClientStats {
+            id,
+            peer,
+            ordering,
+            queue_capacity,
+            queued: AtomicUsize::new(0),
+            max_queued: AtomicUsize::new(0),
+            sent: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            gaps: AtomicU64::new(0),
+            last_seq: AtomicU64::new(0),
+        });
+        self.clients.lock().unwrap().insert(id, stats.clone());
+        ClientRegistration {
+            clients: self.clients.clone(),
+            stats,
+        }
+    }
+
+    /// Metrics of every connected client; `hub_seq` gives the latest
+    /// sequence number of each ordering.
+    pub fn metrics(&self, hub_seq: impl Fn(OrderingRuleKind) -> u64) -> Vec<StreamClientMetrics> {
+        let clients: Vec<Arc<ClientStats>> =
+            self.clients.lock().unwrap().values().cloned().collect();
+        clients
+            .iter()
+            .map(|c| c.metrics(hub_seq(c.ordering)))
+            .collect()
+    }
+}
+
+/// RAII registration returned by `ClientRegistry::register`.
+#[derive(Debug)]
+pub struct ClientRegistration {
+    clients: Arc<Mutex<BTreeMap<u64, Arc<ClientStats>>>>,
+    stats: Arc<ClientStats>,
+}
+
+impl ClientRegistration {
+    /// A handle to the counters that outlives the registration.
+    pub fn stats(&self) -> Arc<ClientStats> {
+        self.stats.clone()
+    }
+}
+
+impl Deref for ClientRegistration {
+    type Target = ClientStats;
+
+    fn deref(&self) -> &ClientStats {
+        &self.stats
+    }
+}
+
+impl Drop for ClientRegistration {
+    fn drop(&mut self) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.remove(&self.stats.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(hash: &str, topological_index: u64, is_blue: bool) -> LinearizedBlock {
+        LinearizedBlock {
+            hash: hash.to_string(),
+            blue_score: topological_index,
+            blue_work: "0".to_string(),
+            topological_index,
+            adjacency_list: Vec::new(),
+            is_blue,
+            selected_parent: None,
+        }
+    }
+
+    #[test]
+    fn test_coalesce_keeps_latest_version() {
+        let delta = |seq, blocks| BlockDelta {
+            kind: BlockDeltaKind::Delta,
+            seq,
+            ordering: OrderingRuleKind::Xor,
+            blocks,
+        };
+        let deltas = [
+            delta(3, vec![block("aa", 1, true), block("bb", 2, true)]),
+            delta(4, vec![block("bb", 2, false)]),
+            delta(5, vec![block("cc", 0, true)]),
+        ];
+        let merged = coalesce_deltas(&deltas).unwrap();
+        assert_eq!(merged.seq, 5);
+        assert_eq!(
+            merged.blocks,
+            vec![block("cc", 0, true), block("aa", 1, true), block("bb", 2, false)]
+        );
+        assert!(coalesce_deltas(&[]).is_none());
+
+        assert_eq!("coalesce".parse(), Ok(SlowConsumerPolicy::Coalesce));
+        assert!("drop".parse::<SlowConsumerPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_registry_reports_lag() {
+        let registry = ClientRegistry::default();
+        let peer: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let client = registry.register(peer, OrderingRuleKind::Xor, 4);
+        client.enqueued();
+        client.enqueued();
+        client.written(3);
+        client.dropped(5);
+        client.gap();
+        client.delivered_seq(7);
+
+        let metrics = registry.metrics(|_| 10);
+        assert_eq!(metrics.len(), 1);
+        let m = &metrics[0];
+        assert_eq!((m.queued, m.max_queued, m.queue_capacity), (1, 2, 4));
+        assert_eq!((m.sent, m.dropped, m.gaps), (3, 5, 1));
+        assert_eq!((m.last_seq, m.lag), (7, 3));
+
+        drop(client);
+        assert!(registry.metrics(|_| 0).is_empty());
+    }
+}
@@ -1,18 +1,37 @@
-/// Argus Linearizer — Linearization Engine for GNNs.
-///
-/// This crate provides:
-///
-/// - **`schema`** — GNN-ready JSON schemas (`LinearizedBlock`, `DagSnapshot`, etc.).
-/// - **`stream`** — WebSocket producer for broadcasting linearized DAG frames.
-/// - **`server`** — JSON-RPC + WebSocket server with hot-swap k, smart submit, etc.
+//! Argus Linearizer — Linearization Engine for GNNs.
+//!
+//! This crate provides:
+//!
+//! - **`schema`** — GNN-ready JSON schemas (`LinearizedBlock`, `DagSnapshot`, etc.).
+//! - **`stream`** — `StreamHub` and the WebSocket producer that serves it to clients.
+//! - **`flow`** — Per-client flow control: bounded queues, slow-consumer policies, lag metrics.
+//! - **`subscriptions`** — Client subscription protocol: topics, filters and acks.
+//! - **`server`** — JSON-RPC + WebSocket server with hot-swap k, smart submit, etc.
+//! - **`node`** — Node runtime composing the server with the `GhostDagAgent` and recovery loop.
 
+pub mod flow;
+pub mod node;
 pub mod schema;
 pub mod server;
 pub mod stream;
+pub mod subscriptions;
 
 // Re-exports.
+pub use flow::{
+    coalesce_deltas, ClientRegistry, FlowControl, SlowConsumerPolicy, DEFAULT_CLIENT_QUEUE,
+};
 pub use schema::{
-    AgentHealth, DagSnapshot, LinearizedBlock, SmartSubmitRequest, SmartSubmitResponse,
+    AgentHealth, BlockDelta, BlockDeltaKind, ChainBlock, ChainChange, ColorFlip, DagSnapshot,
+    KAnalysis, KRecommendation, LinearizedBlock, NetworkMetrics, SmartSubmitRequest,
+    SmartSubmitResponse, StreamClientMetrics,
+};
+pub use node::{run_node, spawn_agent, AgentRuntime, NodeConfig};
+pub use server::{start_server, KUpdateError, ServerConfig, ServerState};
+pub use stream::{
+    linearize_dag, linearize_dag_with, push_snapshot, push_snapshot_with, OrderingSubscriptions,
+    StreamFrame, StreamHub, WsProducer, DEFAULT_DELTA_HISTORY, DEFAULT_STREAM_CAPACITY,
+};
+pub use subscriptions::{
+    ClientMessage, ClientRequest, ClientSubscriptions, ServerMessage, SubscriptionFilter, Topic,
+    TopicEvent, MAX_SUBSCRIPTIONS_PER_CLIENT,
 };
-pub use server::{start_server, ServerConfig, ServerState};
-pub use stream::{linearize_dag, push_snapshot, StreamFrame, WsProducer};
@@ -0,0 +1,746 @@
+/// Argus Linearizer — Node runtime.
+///
+/// Composes the JSON-RPC/WebSocket server with the `GhostDagAgent` and its
+/// `RecoveryLoop`, all sharing the server's `Arc<RwLock<DagStore>>`:
+///
+/// - `AgentEvent::StateChanged` drives `ServerState.agent_state`, so
+///   `get_health` reports the agent's real state.
+/// - Every agent event is published on `ServerState.stream` (topic
+///   `agent_events`; `{"type": "agent_event", "event": …}` on the legacy
+///   stream), along with a `health` update on each state change.
+/// - Newly ingested blocks and recovery, IBD and partition-healing
+///   completions refresh the server's coloring cache and publish a new
+///   snapshot.
+/// - k changes the agent schedules itself (`KScheduled`, e.g. from the
+///   optional `KTuner`) are mirrored into the server's k schedule.
+/// - The agent checks k changes against the server's `KGovernor`, so both
+///   share one policy and one audit log.
+/// - The agent, its recovery loop, its recorder, the k tuner and the tip
+///   aggregator all read the time from `ServerState.clock`.
+/// - Every network tip the agent learns is written to
+///   `AgentRuntime::network_tip`, which the recovery loop checks against.
+/// - A `TipAggregator` polls `get_tips` on the peer and every
+///   `NodeConfig::tip_peers` node, and feeds the agent the tip most of
+///   them agree on.
+/// - With `NodeConfig::event_log` set, the agent appends its commands and
+///   events to a JSON Lines file that `argus replay` can re-run.
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use argus_agent::channels::{command_channel, event_channel, AgentEvent, EventRx, EventTx};
+use argus_agent::fetcher::{BlockFetcher, JsonRpcBlockFetcher};
+use argus_agent::handle::{AgentHandle, DEFAULT_QUERY_TIMEOUT};
+use argus_agent::k_controller::KTunerConfig;
+use argus_agent::network_tip::{JsonRpcTipProvider, PeerInfo, TipAggregator, TipAggregatorConfig};
+use argus_agent::recorder::EventRecorder;
+use argus_agent::recovery::{RecoveryConfig, RecoveryLoop};
+use argus_agent::state_machine::GhostDagAgent;
+use argus_ghostdag::block::BlockHash;
+
+use crate::server::{start_server, ServerConfig, ServerState};
+use crate::stream::StreamFrame;
+use crate::subscriptions::TopicEvent;
+
+/// Buffer size of the agent command and event channels.
+const AGENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Configuration for a full node.
+#[derive(Debug, Clone, Default)]
+pub struct NodeConfig {
+    /// Server addresses and defaults.
+    pub server: ServerConfig,
+    /// Recovery loop settings; `params` should match `ServerState.params`.
+    pub recovery: RecoveryConfig,
+    /// JSON-RPC address of a peer to fetch missing blocks from.  When set,
+    /// the node also runs an initial block download from it at startup.
+    pub peer_rpc: Option<SocketAddr>,
+    /// JSON-RPC addresses of further nodes polled for the network tip,
+    /// alongside `peer_rpc`.
+    pub tip_peers: Vec<SocketAddr>,
+    /// How the network tip is derived from the polled nodes.
+    pub tip_aggregator: TipAggregatorConfig,
+    /// Run a native k controller against the agent.
+    pub k_tuner: Option<KTunerConfig>,
+    /// Append the agent's commands and events to this JSON Lines file.
+    pub event_log: Option<PathBuf>,
+}
+
+/// The agent side of a running node.
+pub struct AgentRuntime {
+    /// Handle to the running agent.
+    pub handle: AgentHandle,
+    /// Network tip fed to the recovery loop.
+    pub network_tip: Arc<RwLock<Option<BlockHash>>>,
+    event_tx: EventTx,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl AgentRuntime {
+    /// Wait for the agent, recovery loop and event pump to exit.
+    pub async fn join(self) {
+        // The event pump runs until every sender is gone.
+        let AgentRuntime {
+            event_tx, tasks, ..
+        } = self;
+        drop(event_tx);
+        for task in tasks {
+            let _ = task.await;
+        }
+    }
+}
+
+/// Spawn the agent, its recovery loop and the event pump over `state`'s
+/// DAG, and register the agent handle with `state`.
+///
+/// The tasks stop once `shutdown` fires and the agent receives `Shutdown`.
+pub async fn spawn_agent(
+    state: &Arc<ServerState>,
+    recovery: RecoveryConfig,
+    fetcher: Option<Arc<dyn BlockFetcher>>,
+    recorder: Option<EventRecorder>,
+    shutdown: tokio::sync::watch::Receiver<bool>,
+) -> AgentRuntime {
+    let local_tip = {
+        let dag = state.dag.read().await;
+        dag.headers()
+            .max_by_key(|h| h.blue_score)
+            .map(|h| h.hash)
+            .or_else(|| dag.genesis())
+            .unwrap_or(state.params.genesis.hash)
+    };
+
+    let (cmd_tx, cmd_rx) = command_channel(AGENT_CHANNEL_CAPACITY);
+    let (event_tx, event_rx) = event_channel(AGENT_CHANNEL_CAPACITY);
+    let network_tip = Arc::new(RwLock::new(None));
+
+    let mut agent = GhostDagAgent::new(
+        state.dag.clone(),
+        local_tip,
+        state.params.clone(),
+        cmd_rx,
+        event_tx.clone(),
+    )
+    .with_governor(state.k_governor.clone())
+    .with_clock(state.clock.clone())
+    .with_shared_network_tip(network_tip.clone());
+    if let Some(fetcher) = fetcher {
+        agent = agent.with_fetcher(fetcher);
+    }
+    if let Some(recorder) = recorder {
+        agent = agent.with_recorder(recorder.with_clock(state.clock.clone()));
+    }
+    let handle = AgentHandle::new(cmd_tx.clone());
+
+    let recovery_loop = RecoveryLoop::new(
+        state.dag.clone(),
+        recovery,
+        cmd_tx,
+        event_tx.clone(),
+        network_tip.clone(),
+        shutdown,
+    )
+    .with_clock(state.clock.clone());
+
+    let tasks = vec![
+        tokio::spawn(agent.run()),
+        tokio::spawn(recovery_loop.run()),
+        tokio::spawn(forward_agent_events(state.clone(), event_rx)),
+    ];
+
+    if let Ok(agent_state) = handle.state().await {
+        *state.agent_state.write().await = agent_state.to_string();
+    }
+    // RPC queries give up rather than wait behind a long sync.
+    *state.agent.write().await = Some(
+        handle
+            .clone()
+            .with_clock(state.clock.clone())
+            .with_query_timeout(DEFAULT_QUERY_TIMEOUT),
+    );
+
+    AgentRuntime {
+        handle,
+        network_tip,
+        event_tx,
+        tasks,
+    }
+}
+
+/// Apply agent events to the server state and forward them to WebSocket
+/// subscribers.  Runs until every event sender is dropped.
+async fn forward_agent_events(state: Arc<ServerState>, mut event_rx: EventRx) {
+    while let Some(event) = event_rx.recv().await {
+        match &event {
+            AgentEvent::StateChanged { to, .. } => {
+                *state.agent_state.write().await = to.to_string();
+                state.publish_health().await;
+            }
+            AgentEvent::KScheduled {
+                new_k,
+                activation_blue_score,
+            } => {
+                if let Err(e) = state
+                    .apply_agent_k_change(*new_k, *activation_blue_score)
+                    .await
+                {
+                    error!("Failed to mirror agent k change: {e}");
+                }
+            }
+            AgentEvent::BlocksIngested { .. }
+            | AgentEvent::RecoveryComplete { .. }
+            | AgentEvent::IbdComplete { .. }
+            | AgentEvent::PartitionHealed { .. } => {
+                // The agent has already colored the shared DAG.
+                if let Err(e) = state.republish_agent_coloring().await {
+                    error!("Failed to publish coloring after agent sync: {e}");
+                }
+            }
+            _ => {}
+        }
+
+        state.stream.publish(StreamFrame::new(TopicEvent::Agent(event)));
+    }
+}
+
+/// Run a full node: server, agent and recovery loop, until `shutdown`.
+pub async fn run_node(
+    state: Arc<ServerState>,
+    config: NodeConfig,
+    shutdown: tokio::sync::watch::Receiver<bool>,
+) {
+    let fetcher = config
+        .peer_rpc
+        .map(|addr| Arc::new(JsonRpcBlockFetcher::new(addr)) as Arc<dyn BlockFetcher>);
+    let start_ibd = fetcher.is_some();
+
+    let recorder = match &config.event_log {
+        Some(path) => match EventRecorder::create(path) {
+            Ok(recorder) => {
+                info!(path = %path.display(), "Recording agent commands and events");
+                Some(recorder)
+            }
+            Err(e) => {
+                error!("Cannot open event log {}: {e}", path.display());
+                None
+            }
+        },
+        None => None,
+    };
+
+    let mut runtime = spawn_agent(
+        &state,
+        config.recovery,
+        fetcher,
+        recorder,
+        shutdown.clone(),
+    )
+    .await;
+    if let Some(tuner) = &config.k_tuner {
+        info!(controller = ?tuner.controller, "Starting native k tuner");
+        runtime.tasks.push(tokio::spawn(tuner.build().with_clock(state.clock.clone()).run(
+            state.dag.clone(),
+            runtime.handle.clone(),
+            tuner.interval,
+            shutdown.clone(),
+        )));
+    }
+    let mut tip_peers: Vec<SocketAddr> = config.peer_rpc.into_iter().collect();
+    for addr in &config.tip_peers {
+        if !tip_peers.contains(addr) {
+            tip_peers.push(*addr);
+        }
+    }
+    if !tip_peers.is_empty() {
+        info!(peers = tip_peers.len(), "Starting network tip aggregator");
+        let mut aggregator =
+            TipAggregator::new(config.tip_aggregator.clone()).with_clock(state.clock.clone());
+        for addr in tip_peers {
+            let peer = PeerInfo::new(addr.to_string(), addr.ip().to_string());
+            aggregator = aggregator.with_provider(Arc::new(JsonRpcTipProvider::new(peer, addr)));
+        }
+        runtime.tasks.push(tokio::spawn(aggregator.run(
+            runtime.handle.clone(),
+            runtime.network_tip.clone(),
+            runtime.event_tx.clone(),
+            shutdown.clone(),
+        )));
+    }
+    if start_ibd {
+        info!(peer = ?config.peer_rpc, "Starting initial block download from peer");
+        let _ = runtime.handle.start_ibd(None).await;
+    }
+
+    start_server(state, config.server, shutdown).await;
+
+    let _ = runtime.handle.shutdown().await;
+    runtime.join().await;
+    info!("Argus node stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use argus_agent::channels::AgentStateLabel;
+    use argus_agent::transitions::TransitionReason;
+    use argus_ghostdag::block::BlockHeader;
+    use argus_ghostdag::dag::DagStore;
+    use argus_ghostdag::params::ConsensusParams;
+
+    async fn node_state() -> Arc<ServerState> {
+        let params = ConsensusParams::devnet();
+        let dag = DagStore::from_params(&params).unwrap();
+        let state = Arc::new(ServerState::new(dag, params));
+        state.recolor_and_broadcast().await.unwrap();
+        state
+    }
+
+    #[tokio::test]
+    async fn test_agent_drives_agent_state() {
+        let state = node_state().await;
+        let mut frames = state.stream.subscribe();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+
+        let runtime = spawn_agent(&state, RecoveryConfig::default(), None, None, shutdown_rx).await;
+        assert_eq!(*state.agent_state.read().await, "SYNCED");
+        assert!(state.agent.read().await.is_some());
+
+        // An unknown network tip makes the agent drift.
+        let status = runtime
+            .handle
+            .check_divergence(BlockHash::from_byte(0xAB))
+            .await
+            .unwrap();
+        assert_eq!(status.state, AgentStateLabel::Drifting);
+
+        // The health update published alongside has no legacy payload.
+        let frame = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            loop {
+                let frame = frames.recv().await.unwrap();
+                if frame.json.is_some() {
+                    break frame;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(frame.ordering.is_none());
+        let json: serde_json::Value =
+            serde_json::from_str(frame.json.as_deref().unwrap()).unwrap();
+        assert_eq!(json["type"], "agent_event");
+        assert!(json["event"]["StateChanged"].is_object());
+
+        // The pump applies the event before forwarding it.
+        assert_eq!(*state.agent_state.read().await, "DRIFTING");
+        assert_eq!(state.get_health().await.agent_state, "DRIFTING");
+
+        let history = state.get_agent_history(None).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].to, AgentStateLabel::Drifting);
+        assert_eq!(history[0].reason, TransitionReason::DivergenceDetected);
+
+        shutdown_tx.send(true).unwrap();
+        runtime.handle.shutdown().await.unwrap();
+        runtime.join().await;
+    }
+
+    #[tokio::test]
+    async fn test_network_tip_starts_recovery_session() {
+        let state = node_state().await;
+        let h = BlockHash::from_byte;
+        let g = state.params.genesis.hash;
+        {
+            // G → A is the network chain; our bluest tip Y sits on G → X → Y.
+            let mut dag = state.dag.write().await;
+            dag.add_block(BlockHeader::new(h(1), vec![g], 1)).unwrap();
+            dag.add_block(BlockHeader::new(h(2), vec![g], 1)).unwrap();
+            dag.add_block(BlockHeader::new(h(3), vec![h(2)], 2)).unwrap();
+        }
+        state.recolor_and_broadcast().await.unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let recovery = RecoveryConfig {
+            check_interval_ms: 5,
+            ..Default::default()
+        };
+        let runtime = spawn_agent(&state, recovery, None, None, shutdown_rx).await;
+
+        runtime.handle.update_network_tip(h(1)).await.unwrap();
+        // Commands run in order: once this is answered, the tip is shared.
+        assert_eq!(runtime.handle.network_tip().await.unwrap(), Some(h(1)));
+        assert_eq!(*runtime.network_tip.read().await, Some(h(1)));
+
+        // The recovery loop sees the divergence and starts a session.
+        tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            loop {
+                let history = state.get_agent_history(None).await.unwrap();
+                if history.iter().any(|t| t.reason == TransitionReason::RecoveryStarted) {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        shutdown_tx.send(true).unwrap();
+        runtime.handle.shutdown().await.unwrap();
+        runtime.join().await;
+    }
+
+    fn free_addr() -> SocketAddr {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    type WsClient = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    /// Connect to a server that may still be binding.
+    async fn connect(url: &str) -> WsClient {
+        tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            loop {
+                match tokio_tungstenite::connect_async(url).await {
+                    Ok((client, _)) => break client,
+                    Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+                }
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    /// The next text message from the server, as JSON.
+    async fn next_json(client: &mut WsClient) -> serde_json::Value {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::Message;
+
+        loop {
+            let msg = tokio::time::timeout(std::time::Duration::from_secs(2), client.next())
+                .await
+                .expect("no message reached the client")
+                .unwrap()
+                .unwrap();
+            if let Message::Text(text) = msg {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ws_client_receives_blocks_and_agent_events() {
+        use argus_ghostdag::block::BlockHeader;
+
+        let state = node_state().await;
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let config = ServerConfig {
+            ws_addr: free_addr(),
+            rpc_addr: free_addr(),
+            ..Default::default()
+        };
+        let url = format!("ws://{}/", config.ws_addr);
+        let server = tokio::spawn(start_server(state.clone(), config, shutdown_rx.clone()));
+
+        let mut client = connect(&url).await;
+        let snapshot = next_json(&mut client).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["blocks"].as_array().unwrap().len(), 1);
+        let seq = snapshot["seq"].as_u64().unwrap();
+
+        let runtime = spawn_agent(&state, RecoveryConfig::default(), None, None, shutdown_rx).await;
+        let block = BlockHash::from_byte(0x11);
+        runtime
+            .handle
+            .submit_blocks(vec![BlockHeader::new(
+                block,
+                vec![state.params.genesis.hash],
+                1_000,
+            )])
+            .await
+            .unwrap();
+
+        // The new block reaches the client both as the next delta and as an
+        // agent event, in whichever order the hub published them.
+        let (mut saw_delta, mut saw_event) = (false, false);
+        while !(saw_delta && saw_event) {
+            let json = next_json(&mut client).await;
+            if json["type"] == "delta" {
+                assert_eq!(json["seq"].as_u64(), Some(seq + 1));
+                let blocks = json["blocks"].as_array().unwrap();
+                assert_eq!(blocks.len(), 1);
+                saw_delta |= blocks[0]["hash"] == block.to_hex();
+            } else if json["type"] == "agent_event" {
+                saw_event |= json["event"]["BlocksIngested"]["ingested"] == 1;
+            }
+        }
+
+        // A client reconnecting from the old snapshot only gets the delta.
+        let mut resumed = connect(&format!("{url}?resume_from={seq}")).await;
+        let replayed = next_json(&mut resumed).await;
+        assert_eq!(replayed["type"], "delta");
+        assert_eq!(replayed["seq"].as_u64(), Some(seq + 1));
+
+        shutdown_tx.send(true).unwrap();
+        runtime.handle.shutdown().await.unwrap();
+        runtime.join().await;
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ws_subscriptions_are_acked_and_filtered() {
+        use argus_ghostdag::block::BlockHeader;
+        use futures_util::SinkExt;
+        use tokio_tungstenite::tungstenite::Message;
+
+        let state = node_state().await;
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let config = ServerConfig {
+            ws_addr: free_addr(),
+            rpc_addr: free_addr(),
+            ..Default::default()
+        };
+        let url = format!("ws://{}/", config.ws_addr);
+        let server = tokio::spawn(start_server(state.clone(), config, shutdown_rx.clone()));
+        let mut client = connect(&url).await;
+        assert_eq!(next_json(&mut client).await["type"], "snapshot");
+
+        let genesis = state.params.genesis.hash;
+        let (a, b) = (BlockHash::from_byte(0x21), BlockHash::from_byte(0x22));
+        for (id, mut request) in [
+            (1, serde_json::json!({ "op": "subscribe", "topic": "chain_changes" })),
+            (
+                2,
+                serde_json::json!({
+                    "op": "subscribe",
+                    "topic": "blocks",
+                    "filter": { "hashes": [b.to_hex()] },
+                }),
+            ),
+        ] {
+            request["request_id"] = id.into();
+            client.send(Message::Text(request.to_string())).await.unwrap();
+            let ack = next_json(&mut client).await;
+            assert_eq!(ack["type"], "subscribed");
+            assert_eq!(ack["request_id"].as_u64(), Some(id));
+            assert_eq!(ack["subscription"].as_u64(), Some(id));
+        }
+        // A blocks subscription starts from a snapshot, filtered like the rest.
+        let snapshot = next_json(&mut client).await;
+        assert_eq!(snapshot["data"]["type"], "snapshot");
+        assert!(snapshot["data"]["blocks"].as_array().unwrap().is_empty());
+
+        // Blocks are ingested directly, then published by a recolor.
+        {
+            let mut dag = state.dag.write().await;
+            dag.add_block(BlockHeader::new(a, vec![genesis], 1_000)).unwrap();
+            dag.add_block(BlockHeader::new(b, vec![a], 2_000)).unwrap();
+        }
+        state.recolor_and_broadcast().await.unwrap();
+
+        // The delta is published before the chain change.
+        let blocks = next_json(&mut client).await;
+        assert_eq!(blocks["type"], "event");
+        assert_eq!(blocks["subscription"].as_u64(), Some(2));
+        assert_eq!(blocks["data"]["seq"], snapshot["data"]["seq"].as_u64().unwrap() + 1);
+        let data = blocks["data"]["blocks"].as_array().unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0]["hash"], b.to_hex());
+
+        let chain = next_json(&mut client).await;
+        assert_eq!(chain["topic"], "chain_changes");
+        assert_eq!(chain["data"]["fork_point"], genesis.to_hex());
+        let added: Vec<&str> = chain["data"]["added"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["hash"].as_str().unwrap())
+            .collect();
+        assert_eq!(added, vec![a.to_hex(), b.to_hex()]);
+
+        client
+            .send(Message::Text(r#"{"op": "unsubscribe", "subscription": 9}"#.into()))
+            .await
+            .unwrap();
+        assert_eq!(next_json(&mut client).await["type"], "error");
+
+        shutdown_tx.send(true).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_k_reaches_agent() {
+        let state = node_state().await;
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let runtime = spawn_agent(&state, RecoveryConfig::default(), None, None, shutdown_rx).await;
+
+        state.schedule_k(7, None).await.unwrap();
+        assert_eq!(runtime.handle.status().await.unwrap().scheduled_k, 7);
+
+        // A change the agent makes itself reaches the server.
+        runtime.handle.update_k(9, Some(50)).await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            while *state.k.read().await != 9 {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let changes = state.get_k_schedule().await.changes().to_vec();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[2].activation_blue_score, 50);
+
+        shutdown_tx.send(true).unwrap();
+        runtime.handle.shutdown().await.unwrap();
+        runtime.join().await;
+    }
+
+    #[tokio::test]
+    async fn test_node_follows_tip_peer() {
+        // The peer already has block A on top of genesis.
+        let peer_state = node_state().await;
+        let a = BlockHash::from_byte(0xA1);
+        let g = peer_state.params.genesis.hash;
+        peer_state
+            .dag
+            .write()
+            .await
+            .add_block(BlockHeader::new(a, vec![g], 1))
+            .unwrap();
+        peer_state.recolor_and_broadcast().await.unwrap();
+        let peer_rpc = free_addr();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let peer_config = ServerConfig {
+            ws_addr: free_addr(),
+            rpc_addr: peer_rpc,
+            ..Default::default()
+        };
+        let peer = tokio::spawn(start_server(peer_state, peer_config, shutdown_rx.clone()));
+
+        let state = node_state().await;
+        let config = NodeConfig {
+            server: ServerConfig {
+                ws_addr: free_addr(),
+                rpc_addr: free_addr(),
+                ..Default::default()
+            },
+            tip_peers: vec![peer_rpc],
+            tip_aggregator: TipAggregatorConfig {
+                poll_interval: std::time::Duration::from_millis(10),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let node = tokio::spawn(run_node(state.clone(), config, shutdown_rx));
+
+        tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            loop {
+                let handle = state.agent.read().await.clone();
+                if let Some(handle) = handle {
+                    if handle.network_tip().await.unwrap() == Some(a) {
+                        break;
+                    }
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        shutdown_tx.send(true).unwrap();
+        node.await.unwrap();
+        peer.await.unwrap();
+    }
+
+    /// Add a chain of `len` blocks on top of `parent`, hashes from `first`.
+    async fn add_chain(state: &ServerState, parent: BlockHash, first: u8, len: u8) -> BlockHash {
+        let mut dag = state.dag.write().await;
+        let mut tip = parent;
+        for i in 0..len {
+            let hash = BlockHash::from_byte(first + i);
+            dag.add_block(BlockHeader::new(hash, vec![tip], 1_000 + u64::from(i)))
+                .unwrap();
+            tip = hash;
+        }
+        tip
+    }
+
+    #[tokio::test]
+    async fn test_node_heals_deep_fork_reported_by_tip_peer() {
+        // Both sides of the fork share C.  Our node saw both and follows the
+        // heavier 15-block side; the peer only has the 12-block side, deeper
+        // than devnet's partition threshold of 9.
+        let peer_state = node_state().await;
+        let g = peer_state.params.genesis.hash;
+        let c = add_chain(&peer_state, g, 0x10, 1).await;
+        let peer_tip = add_chain(&peer_state, c, 0x60, 12).await;
+        peer_state.recolor_and_broadcast().await.unwrap();
+        let peer_rpc = free_addr();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let peer_config = ServerConfig {
+            ws_addr: free_addr(),
+            rpc_addr: peer_rpc,
+            ..Default::default()
+        };
+        let peer = tokio::spawn(start_server(peer_state, peer_config, shutdown_rx.clone()));
+
+        let state = node_state().await;
+        add_chain(&state, g, 0x10, 1).await;
+        let local_tip = add_chain(&state, c, 0x30, 15).await;
+        add_chain(&state, c, 0x60, 12).await;
+        state.recolor_and_broadcast().await.unwrap();
+        let mut frames = state.stream.subscribe();
+        let config = NodeConfig {
+            server: ServerConfig {
+                ws_addr: free_addr(),
+                rpc_addr: free_addr(),
+                ..Default::default()
+            },
+            tip_peers: vec![peer_rpc],
+            tip_aggregator: TipAggregatorConfig {
+                poll_interval: std::time::Duration::from_millis(10),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let node = tokio::spawn(run_node(state.clone(), config, shutdown_rx));
+
+        // The aggregator's check declares the partition and the agent heals it.
+        let healed = tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            loop {
+                let frame = frames.recv().await.unwrap();
+                let Some(json) = frame.json.as_deref() else {
+                    continue;
+                };
+                let json: serde_json::Value = serde_json::from_str(json).unwrap();
+                if json["event"]["PartitionHealed"].is_object() {
+                    break json;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        let new_tip = &healed["event"]["PartitionHealed"]["new_tip"];
+        assert_eq!(*new_tip, serde_json::to_value(local_tip).unwrap());
+
+        let history = state.get_agent_history(None).await.unwrap();
+        assert!(history.len() >= 2);
+        assert_eq!(history[0].from, AgentStateLabel::Synced);
+        assert_eq!(history[0].to, AgentStateLabel::Partitioned);
+        assert_eq!(history[0].reason, TransitionReason::PartitionDetected);
+        assert_eq!(history[1].to, AgentStateLabel::Synced);
+        assert_eq!(history[1].reason, TransitionReason::PartitionHealed);
+        let handle = state.agent.read().await.clone().unwrap();
+        assert_eq!(handle.network_tip().await.unwrap(), Some(peer_tip));
+        assert_eq!(handle.local_tip().await.unwrap(), local_tip);
+
+        shutdown_tx.send(true).unwrap();
+        node.await.unwrap();
+        peer.await.unwrap();
+    }
+}
@@ -4,11 +4,14 @@
 /// via WebSocket and returned by JSON-RPC.
 use serde::{Deserialize, Serialize};
 
+use argus_ghostdag::metrics::DagMetrics;
+use argus_ghostdag::ordering::OrderingRuleKind;
+
 /// A single linearized block in the GNN-ready format.
 ///
 /// This is the canonical schema sent over the WebSocket stream and
 /// returned by all API endpoints.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LinearizedBlock {
     /// Block hash (hex-encoded).
     pub hash: String,
@@ -41,7 +44,7 @@ pub struct DagSnapshot {
     /// Total number of blocks in the snapshot.
     pub total_blocks: u64,
 
-    /// The k parameter used for coloring.
+    /// The k in force at the tip (the coloring k of its blue score).
     pub k: u64,
 
     /// Tip hash (hex-encoded).
@@ -54,7 +57,7 @@ pub struct DagSnapshot {
 /// Health status for the /agent/health endpoint.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentHealth {
-    /// Current GhostDAG k parameter.
+    /// The k in force at the tip blue score.
     pub current_k: u64,
 
     /// The RL agent's confidence score (0.0 to 1.0).
@@ -76,6 +79,70 @@ pub struct AgentHealth {
     pub red_count: u64,
 }
 
+/// One entry of the `analyze_k` response: what the DAG would look like
+/// if it were colored with `k`.  Computed on a clone; live state is untouched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KAnalysis {
+    /// The candidate k.
+    pub k: u64,
+
+    /// Fraction of blocks colored blue.
+    pub blue_ratio: f64,
+
+    /// Number of red blocks.
+    pub red_count: u64,
+
+    /// Selected tip (hex-encoded) under this k.
+    pub selected_tip: String,
+
+    /// Whether the selected tip differs from the live one.
+    pub selected_tip_changed: bool,
+
+    /// Fraction of total-order positions unchanged versus the live ordering.
+    pub ordering_stability: f64,
+}
+
+/// Response of `get_network_metrics`: metrics the node derives from its
+/// own DAG, plus the k in force.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkMetrics {
+    /// The k in force at the tip blue score.
+    pub current_k: u64,
+
+    /// Red rate, tip velocity, block intervals, propagation delay, width.
+    #[serde(flatten)]
+    pub dag: DagMetrics,
+}
+
+/// Response of `recommend_k`: PHANTOM's k for the given (or measured)
+/// delay and block rate, next to the live metrics it was derived from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KRecommendation {
+    /// Smallest k whose anticone-overflow probability is below `delta`.
+    pub recommended_k: u64,
+
+    /// The k in force at the tip blue score.
+    pub current_k: u64,
+
+    /// Network delay used (ms).
+    pub delay_ms: f64,
+
+    /// Block rate used (blocks per second).
+    pub block_rate: f64,
+
+    /// Tolerated anticone-overflow probability.
+    pub delta: f64,
+
+    /// Overflow probability at `current_k` under the same delay and rate.
+    pub current_overflow_probability: f64,
+
+    /// Whether the delay or the rate was measured from the DAG.
+    pub measured: bool,
+
+    /// Live metrics over the default window.
+    pub metrics: DagMetrics,
+}
+
 /// Response for the POST /tx/submit-smart endpoint.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SmartSubmitResponse {
@@ -107,6 +174,88 @@ fn default_parent_count() -> usize {
     3
 }
 
+/// Whether a `BlockDelta` holds every block or only changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockDeltaKind {
+    /// Every block, as of `seq`.
+    Snapshot,
+    /// Blocks appended or changed since `seq - 1`.
+    Delta,
+}
+
+/// Linearized blocks at a point in one ordering's stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockDelta {
+    /// Snapshot or delta.
+    #[serde(rename = "type")]
+    pub kind: BlockDeltaKind,
+    /// Sequence number, increasing by one per delta of this ordering.
+    pub seq: u64,
+    /// The ordering the blocks were linearized with.
+    pub ordering: OrderingRuleKind,
+    /// The blocks, in total-order sequence.
+    pub blocks: Vec<LinearizedBlock>,
+}
+
+/// Queue and lag counters of one WebSocket client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamClientMetrics {
+    /// Connection ID, unique for the server's lifetime.
+    pub id: u64,
+    /// Remote address.
+    pub peer: String,
+    /// Ordering the client streams.
+    pub ordering: OrderingRuleKind,
+    /// Message batches waiting to be written.
+    pub queued: usize,
+    /// Most batches ever waiting at once.
+    pub max_queued: usize,
+    /// Batches the client may have waiting before it is behind.
+    pub queue_capacity: usize,
+    /// Messages written to the socket.
+    pub sent: u64,
+    /// Frames dropped while the client was behind.
+    pub dropped: u64,
+    /// `gap` messages sent.
+    pub gaps: u64,
+    /// Latest sequence number queued for the client.
+    pub last_seq: u64,
+    /// Deltas of its ordering the client has not been sent yet.
+    pub lag: u64,
+}
+
+/// A block on the selected-parent chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainBlock {
+    /// Block hash (hex-encoded).
+    pub hash: String,
+    /// Blue score of the block.
+    pub blue_score: u64,
+}
+
+/// How the selected-parent chain moved after a recolor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainChange {
+    /// Last block the old and new chains share (hex-encoded).
+    pub fork_point: String,
+    /// Blocks that left the chain, oldest first.
+    pub removed: Vec<ChainBlock>,
+    /// Blocks that joined the chain, oldest first.
+    pub added: Vec<ChainBlock>,
+}
+
+/// A block whose color changed after a recolor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColorFlip {
+    /// Block hash (hex-encoded).
+    pub hash: String,
+    /// Blue score of the block.
+    pub blue_score: u64,
+    /// The block's new color.
+    pub is_blue: bool,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3,30 +3,100 @@
 /// Exposes two endpoints:
 /// - **WebSocket** on port 9292: streams linearized blocks.
 /// - **JSON-RPC** on port 9293: request/response queries over the DAG.
+///   One request per connection; the request ends where its JSON value
+///   ends (or where the client shuts down its write half), up to
+///   `MAX_RPC_REQUEST_BYTES`.
 ///
 /// JSON-RPC methods:
-/// - `linearize_range(from_score, to_score)` — returns blocks in a blue-score range.
-/// - `get_tip_order()` — returns the full PHANTOM total ordering.
-/// - `get_tips()` — returns current DAG tips with blue scores.
-/// - `get_snapshot(n)` — returns the last N blocks (GNN sub-graph).
+/// - `linearize_range(from_score, to_score, ordering?)` — returns blocks in a blue-score range.
+/// - `get_tip_order(ordering?)` — returns the full PHANTOM total ordering.
+/// - `get_tips()` — returns current DAG tips with blue scores and blue work.
+/// - `get_snapshot(n, ordering?)` — returns the last N blocks (GNN sub-graph).
 /// - `get_health()` — returns agent health info.
-/// - `update_k(new_k)` — hot-swaps the k parameter.
+/// - `update_k(new_k, activation_blue_score?, source?, reason?, confidence?)` —
+///   schedules a k change from a blue score above the current tip (never
+///   recolors existing blocks), subject to the node's `KPolicy`.  The change
+///   is checked at the node's `rl_confidence`; `confidence` can only lower it.
+/// - `revert_k(reason?)` — rolls back the most recent k change not yet reverted.
+/// - `get_k_audit(limit?)` — every accepted k change with its source and reason.
+/// - `get_k_schedule()` — returns every scheduled k change.
+/// - `get_consensus_params()` — returns the node's consensus parameters.
+/// - `analyze_k(ks)` — colors a clone of the DAG for each candidate k (read-only).
+/// - `get_headers(hashes)` — returns the known headers among `hashes`
+///   (served to peers' `JsonRpcBlockFetcher` during recovery).
+/// - `get_headers_after(locator, cursor?, max?)` — serves initial block
+///   download: headers the requester lacks, in sync order.
+/// - `get_network_metrics(window?)` — red rate, tip velocity, block intervals,
+///   estimated propagation delay and width over the last `window` blocks.
+/// - `recommend_k(delay_ms?, rate?, delta?)` — PHANTOM's k for the delay and
+///   block rate (measured from the DAG when omitted), with the live metrics.
+/// - `get_agent_history(limit?)` — the agent's recent state transitions with
+///   reasons and timestamps, oldest first.
+/// - `get_stream_clients()` — queue depth, dropped frames, gaps and lag of
+///   each connected WebSocket client.
+///
+/// `ordering` selects the tiebreak rule (`xor`, `hash`, `blue_work`,
+/// `timestamp`); it defaults to `ServerConfig::ordering_rule`.
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
-use tokio::sync::{broadcast, RwLock};
-use tracing::{error, info};
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
 
+use argus_agent::clock::{system_clock, SharedClock};
+use argus_agent::fetcher::HeaderRecord;
+use argus_agent::governance::{
+    KAuditRecord, KChangeSource, KGovernanceError, KGovernor, KPolicy, KProposal,
+    SharedKGovernor,
+};
+use argus_agent::handle::{AgentHandle, AgentHandleError};
+use argus_agent::healing::diff_chains;
+use argus_agent::ibd::headers_after;
+use argus_agent::transitions::TransitionRecord;
 use argus_ghostdag::block::BlockHash;
-use argus_ghostdag::coloring::{color_dag, ColoringOutput};
+use argus_ghostdag::coloring::{
+    color_dag_scheduled, read_coloring, selected_parent_chain, ColoringOutput,
+};
 use argus_ghostdag::dag::DagStore;
-use argus_ghostdag::ordering::total_order;
+use argus_ghostdag::metrics::{dag_metrics, DEFAULT_METRICS_WINDOW};
+use argus_ghostdag::ordering::OrderingRuleKind;
+use argus_ghostdag::params::ConsensusParams;
+use argus_ghostdag::recommend::{
+    anticone_overflow_probability, recommend_k, DEFAULT_OVERFLOW_PROBABILITY,
+};
+use argus_ghostdag::schedule::KSchedule;
+use argus_ghostdag::sweep::{selected_tip, sweep_k};
 
+use crate::flow::FlowControl;
 use crate::schema::{
-    AgentHealth, DagSnapshot, LinearizedBlock, SmartSubmitRequest, SmartSubmitResponse,
+    AgentHealth, ChainBlock, ChainChange, ColorFlip, DagSnapshot, KAnalysis, KRecommendation,
+    LinearizedBlock, NetworkMetrics, SmartSubmitRequest, SmartSubmitResponse,
 };
-use crate::stream::{linearize_dag, push_snapshot, StreamFrame, WsProducer};
+use crate::stream::{linearize_dag_with, push_snapshot_with, StreamFrame, StreamHub, WsProducer};
+use crate::subscriptions::TopicEvent;
+
+/// Upper bound on the number of candidate k values per `analyze_k` call.
+pub const MAX_ANALYZE_K: usize = 64;
+
+/// Upper bound on the number of hashes per `get_headers` call.
+pub const MAX_GET_HEADERS: usize = 512;
+
+/// Upper bound on the `get_network_metrics` window (anticones are quadratic).
+pub const MAX_METRICS_WINDOW: usize = 2048;
+
+/// Upper bound on the size of one JSON-RPC request.
+pub const MAX_RPC_REQUEST_BYTES: usize = 1 << 20;
+
+/// Why a k change was not applied.
+#[derive(Debug, Error)]
+pub enum KUpdateError {
+    #[error(transparent)]
+    Governance(#[from] KGovernanceError),
+    #[error(transparent)]
+    GhostDag(#[from] argus_ghostdag::GhostDagError),
+}
 
 /// Shared state for the JSON-RPC + WebSocket server.
 pub struct ServerState {
@@ -34,50 +104,157 @@ pub struct ServerState {
     pub dag: Arc<RwLock<DagStore>>,
     /// Current coloring result.
     pub coloring: Arc<RwLock<Option<ColoringOutput>>>,
-    /// Current k parameter.
+    /// Consensus parameters the node was started with.
+    pub params: ConsensusParams,
+    /// Latest scheduled k (starts at `params.k`); it may not be active
+    /// yet, see `current_k`.
     pub k: Arc<RwLock<u64>>,
+    /// Blue-score-activated k changes; coloring uses the k in force at
+    /// each block's blue score.
+    pub k_schedule: Arc<RwLock<KSchedule>>,
     /// Agent state label (string).
     pub agent_state: Arc<RwLock<String>>,
     /// RL confidence score.
     pub rl_confidence: Arc<RwLock<f64>>,
-    /// WebSocket broadcast sender.
-    pub ws_tx: broadcast::Sender<StreamFrame>,
+    /// k policy and audit log, shared with the agent.
+    pub k_governor: SharedKGovernor,
+    /// Default ordering rule for linearized output.
+    pub ordering_rule: Arc<RwLock<OrderingRuleKind>>,
+    /// Hub every stream frame is published into; `WsProducer` serves it.
+    pub stream: StreamHub,
+    /// Selected-parent chain as of the last recolor, genesis first.
+    pub chain: Arc<RwLock<Vec<BlockHash>>>,
+    /// Handle to the running `GhostDagAgent`, once the node has spawned it.
+    /// Its queries time out, so RPC calls never hang on a busy agent.
+    pub agent: Arc<RwLock<Option<AgentHandle>>>,
+    /// Time source for timestamps, shared with the agent and the governor.
+    pub clock: SharedClock,
 }
 
 impl ServerState {
-    /// Create a new server state with initial DAG and k.
-    pub fn new(dag: DagStore, k: u64) -> Self {
-        let (ws_tx, _) = broadcast::channel(1024);
+    /// Create a new server state with an initial DAG and consensus parameters.
+    pub fn new(dag: DagStore, params: ConsensusParams) -> Self {
         Self {
             dag: Arc::new(RwLock::new(dag)),
             coloring: Arc::new(RwLock::new(None)),
-            k: Arc::new(RwLock::new(k)),
+            k: Arc::new(RwLock::new(params.k)),
+            k_schedule: Arc::new(RwLock::new(KSchedule::new(params.k))),
+            params,
             agent_state: Arc::new(RwLock::new("INIT".to_string())),
             rl_confidence: Arc::new(RwLock::new(1.0)),
-            ws_tx,
+            k_governor: KGovernor::default().shared(),
+            ordering_rule: Arc::new(RwLock::new(OrderingRuleKind::default())),
+            stream: StreamHub::default(),
+            chain: Arc::new(RwLock::new(Vec::new())),
+            agent: Arc::new(RwLock::new(None)),
+            clock: system_clock(),
         }
     }
 
-    /// Re-color the DAG and push a new snapshot to WebSocket clients.
+    /// Govern k changes with `policy` instead of `KPolicy::default()`.
+    pub fn with_k_policy(mut self, policy: KPolicy) -> Self {
+        self.k_governor = KGovernor::new(policy).with_clock(self.clock.clone()).shared();
+        self
+    }
+
+    /// Read the time from `clock` instead of the system clock.  Call before
+    /// the agent is spawned; the k governor is rebuilt around it.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        let policy = self
+            .k_governor
+            .try_read()
+            .map(|g| g.policy().clone())
+            .unwrap_or_default();
+        self.k_governor = KGovernor::new(policy).with_clock(clock.clone()).shared();
+        self.clock = clock;
+        self
+    }
+
+    /// Resolve an optional requested ordering to the server default.
+    async fn resolve_ordering(&self, ordering: Option<OrderingRuleKind>) -> OrderingRuleKind {
+        match ordering {
+            Some(o) => o,
+            None => *self.ordering_rule.read().await,
+        }
+    }
+
+    /// Linearize the whole DAG with the given (or default) ordering.
+    pub async fn linearize(
+        &self,
+        ordering: Option<OrderingRuleKind>,
+    ) -> Result<Vec<LinearizedBlock>, argus_ghostdag::GhostDagError> {
+        let rule = self.resolve_ordering(ordering).await;
+        let dag = self.dag.read().await;
+        let coloring_guard = self.coloring.read().await;
+        let coloring = coloring_guard
+            .as_ref()
+            .ok_or_else(|| argus_ghostdag::GhostDagError::Internal("DAG not colored yet".into()))?;
+        linearize_dag_with(&dag, coloring, rule.rule())
+    }
+
+    /// Re-color the DAG and publish a new snapshot, the color flips and
+    /// chain change it caused, and the new health.
     pub async fn recolor_and_broadcast(&self) -> Result<(), argus_ghostdag::GhostDagError> {
-        let k = *self.k.read().await;
+        let schedule = self.k_schedule.read().await.clone();
         let coloring = {
             let mut dag = self.dag.write().await;
-            let c = color_dag(&mut dag, k)?;
-            c
+            color_dag_scheduled(&mut dag, &schedule)?
         };
+        self.broadcast_coloring(coloring).await
+    }
+
+    /// Publish the coloring the agent already wrote into the shared DAG
+    /// (after ingesting, recovering or syncing blocks) without coloring
+    /// it again.  Otherwise like `recolor_and_broadcast`.
+    pub async fn republish_agent_coloring(&self) -> Result<(), argus_ghostdag::GhostDagError> {
+        let schedule = self.k_schedule.read().await.clone();
+        let coloring = read_coloring(&*self.dag.read().await, &schedule)?;
+        self.broadcast_coloring(coloring).await
+    }
 
-        // Push snapshot.
+    async fn broadcast_coloring(
+        &self,
+        coloring: ColoringOutput,
+    ) -> Result<(), argus_ghostdag::GhostDagError> {
+        // Push one snapshot per ordering in use.
         {
+            let default_rule = *self.ordering_rule.read().await;
+            let mut rules = self.stream.orderings().active();
+            if !rules.contains(&default_rule) {
+                rules.push(default_rule);
+            }
             let dag = self.dag.read().await;
-            let _ = push_snapshot(&self.ws_tx, &dag, &coloring);
+            for rule in rules {
+                let _ = push_snapshot_with(&self.stream, &dag, &coloring, rule);
+            }
+
+            if let Some(previous) = self.coloring.read().await.as_ref() {
+                let flips = color_flips(&dag, previous, &coloring);
+                if !flips.is_empty() {
+                    self.stream.publish(StreamFrame::new(TopicEvent::ColorFlips(flips)));
+                }
+            }
+
+            let chain = selected_parent_chain(&dag, &selected_tip(&dag)?)?;
+            let mut previous = self.chain.write().await;
+            if let Some(change) = chain_change(&dag, &previous, &chain) {
+                self.stream.publish(StreamFrame::new(TopicEvent::ChainChanged(change)));
+            }
+            *previous = chain;
         }
 
         // Store coloring.
         *self.coloring.write().await = Some(coloring);
+        self.publish_health().await;
         Ok(())
     }
 
+    /// Publish the current health on the stream's `health` topic.
+    pub async fn publish_health(&self) {
+        let health = self.get_health().await;
+        self.stream.publish(StreamFrame::new(TopicEvent::Health(health)));
+    }
+
     /// Get all tips sorted by blue score descending.
     pub async fn get_bluest_tips(&self, count: usize) -> Vec<(BlockHash, u64)> {
         let dag = self.dag.read().await;
@@ -86,44 +263,39 @@ impl ServerState {
             .into_iter()
             .filter_map(|h| dag.get(&h).ok().map(|hdr| (h, hdr.blue_score)))
             .collect();
-        tips.sort_by(|a, b| b.1.cmp(&a.1));
+        tips.sort_by_key(|t| std::cmp::Reverse(t.1));
         tips.truncate(count);
         tips
     }
 
     /// Get a snapshot of the last N blocks.
-    pub async fn get_snapshot(&self, n: usize) -> Result<DagSnapshot, argus_ghostdag::GhostDagError> {
+    pub async fn get_snapshot(
+        &self,
+        n: usize,
+        ordering: Option<OrderingRuleKind>,
+    ) -> Result<DagSnapshot, argus_ghostdag::GhostDagError> {
+        let all_blocks = self.linearize(ordering).await?;
         let dag = self.dag.read().await;
-        let coloring_guard = self.coloring.read().await;
-        let coloring = coloring_guard
-            .as_ref()
-            .ok_or_else(|| argus_ghostdag::GhostDagError::Internal("DAG not colored yet".into()))?;
-
-        let all_blocks = linearize_dag(&dag, coloring)?;
         let total = all_blocks.len();
-        let start = if total > n { total - n } else { 0 };
+        let start = total.saturating_sub(n);
         let blocks = all_blocks[start..].to_vec();
 
-        let tip = dag
+        let (tip, tip_score) = dag
             .tips()
             .into_iter()
             .filter_map(|h| dag.get(&h).ok().map(|hdr| (h, hdr.blue_score)))
             .max_by_key(|(_, score)| *score)
-            .map(|(h, _)| h.to_hex())
+            .map(|(h, score)| (h.to_hex(), score))
             .unwrap_or_default();
 
-        let k = *self.k.read().await;
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
+        let k = self.k_schedule.read().await.k_at(tip_score);
 
         Ok(DagSnapshot {
             blocks,
             total_blocks: total as u64,
             k,
             tip,
-            generated_at: now,
+            generated_at: self.clock.unix_millis(),
         })
     }
 
@@ -131,7 +303,6 @@ impl ServerState {
     pub async fn get_health(&self) -> AgentHealth {
         let dag = self.dag.read().await;
         let coloring_guard = self.coloring.read().await;
-        let k = *self.k.read().await;
         let confidence = *self.rl_confidence.read().await;
         let state = self.agent_state.read().await.clone();
 
@@ -147,7 +318,7 @@ impl ServerState {
             .unwrap_or(0);
 
         AgentHealth {
-            current_k: k,
+            current_k: self.k_schedule.read().await.k_at(tip_blue_score),
             rl_confidence: confidence,
             agent_state: state,
             tip_blue_score,
@@ -165,16 +336,11 @@ impl ServerState {
         let selected_parents: Vec<String> = tips.iter().map(|(h, _)| h.to_hex()).collect();
         let parent_blue_scores: Vec<u64> = tips.iter().map(|(_, s)| *s).collect();
 
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
-
         SmartSubmitResponse {
             accepted: !selected_parents.is_empty(),
             selected_parents,
             parent_blue_scores,
-            suggested_timestamp: now,
+            suggested_timestamp: self.clock.unix_millis(),
         }
     }
 
@@ -183,14 +349,9 @@ impl ServerState {
         &self,
         from_score: u64,
         to_score: u64,
+        ordering: Option<OrderingRuleKind>,
     ) -> Result<Vec<LinearizedBlock>, argus_ghostdag::GhostDagError> {
-        let dag = self.dag.read().await;
-        let coloring_guard = self.coloring.read().await;
-        let coloring = coloring_guard
-            .as_ref()
-            .ok_or_else(|| argus_ghostdag::GhostDagError::Internal("DAG not colored yet".into()))?;
-
-        let all = linearize_dag(&dag, coloring)?;
+        let all = self.linearize(ordering).await?;
         let filtered: Vec<LinearizedBlock> = all
             .into_iter()
             .filter(|b| b.blue_score >= from_score && b.blue_score <= to_score)
@@ -198,14 +359,312 @@ impl ServerState {
         Ok(filtered)
     }
 
-    /// Hot-swap the k parameter and re-color.
-    pub async fn update_k(&self, new_k: u64) -> Result<(), argus_ghostdag::GhostDagError> {
+    /// Switch to `new_k` for every block above the current tip.
+    pub async fn update_k(&self, new_k: u64) -> Result<(), KUpdateError> {
+        self.schedule_k(new_k, None).await.map(|_| ())
+    }
+
+    /// Schedule `new_k` from JSON-RPC at the current `rl_confidence` (see
+    /// `propose_k`).  Returns the activation point.
+    pub async fn schedule_k(
+        &self,
+        new_k: u64,
+        activation_blue_score: Option<u64>,
+    ) -> Result<u64, KUpdateError> {
+        let confidence = *self.rl_confidence.read().await;
+        let proposal = KProposal::new(new_k, KChangeSource::Rpc).with_confidence(confidence);
+        self.propose_k(proposal, activation_blue_score)
+            .await
+            .map(|record| record.activation_blue_score)
+    }
+
+    /// Check `proposal` against the k policy, schedule it from
+    /// `activation_blue_score` on (default: one above the current tip),
+    /// record it and re-color.
+    ///
+    /// Activations at or below the tip are rejected because they would
+    /// change the colors of blocks that already exist.  Nodes that must
+    /// agree on colors should pass the same explicit activation point.
+    pub async fn propose_k(
+        &self,
+        proposal: KProposal,
+        activation_blue_score: Option<u64>,
+    ) -> Result<KAuditRecord, KUpdateError> {
+        let new_k = proposal.new_k;
         if new_k < 1 {
-            return Err(argus_ghostdag::GhostDagError::InvalidK(new_k));
+            return Err(argus_ghostdag::GhostDagError::InvalidK(new_k).into());
+        }
+        let record = {
+            let mut governor = self.k_governor.write().await;
+            let tip_score = {
+                let dag = self.dag.read().await;
+                dag.headers().map(|h| h.blue_score).max().unwrap_or(0)
+            };
+            let activation = activation_blue_score.unwrap_or(tip_score + 1);
+            if activation <= tip_score {
+                return Err(argus_ghostdag::GhostDagError::InvalidSchedule(format!(
+                    "activation {activation} is not above the tip blue score {tip_score}"
+                ))
+                .into());
+            }
+            let mut schedule = self.k_schedule.write().await;
+            let from_k = schedule.latest_k();
+            governor.check(from_k, &proposal)?;
+            schedule.schedule(activation, new_k)?;
+            governor.record(from_k, proposal, activation)
+        };
+        info!(
+            from_k = record.from_k,
+            new_k,
+            source = %record.source,
+            reason = %record.reason,
+            "k change accepted"
+        );
+        *self.k.write().await = new_k;
+        // Keep the agent's schedule identical so both color the same way.
+        // The change is already recorded, so the agent will not re-check it.
+        if let Some(agent) = self.agent.read().await.as_ref() {
+            let forwarded = KProposal {
+                new_k,
+                source: record.source,
+                reason: record.reason.clone(),
+                confidence: record.confidence,
+                reverts: record.reverts,
+            };
+            if agent
+                .propose_k(forwarded, Some(record.activation_blue_score))
+                .await
+                .is_err()
+            {
+                warn!("Agent is not running; k change not forwarded");
+            }
+        }
+        self.recolor_and_broadcast().await?;
+        Ok(record)
+    }
+
+    /// Roll back the most recent k change that has not been reverted yet,
+    /// from one above the current tip.
+    pub async fn revert_k(&self, reason: &str) -> Result<KAuditRecord, KUpdateError> {
+        let proposal = self.k_governor.read().await.revert_proposal(reason)?;
+        self.propose_k(proposal, None).await
+    }
+
+    /// The most recent `limit` accepted k changes (all if `None`), oldest first.
+    pub async fn get_k_audit(&self, limit: Option<usize>) -> Vec<KAuditRecord> {
+        self.k_governor.read().await.audit(limit)
+    }
+
+    /// Mirror a k change the agent scheduled on its own (e.g. from its k
+    /// tuner).  Changes the server already has are ignored.
+    pub async fn apply_agent_k_change(
+        &self,
+        new_k: u64,
+        activation_blue_score: u64,
+    ) -> Result<(), argus_ghostdag::GhostDagError> {
+        {
+            let mut schedule = self.k_schedule.write().await;
+            let known = schedule.changes().last().is_some_and(|c| {
+                c.activation_blue_score == activation_blue_score && c.k == new_k
+            });
+            if known {
+                return Ok(());
+            }
+            schedule.schedule(activation_blue_score, new_k)?;
         }
         *self.k.write().await = new_k;
         self.recolor_and_broadcast().await
     }
+
+    /// The k in force at the highest blue score in the DAG.  Changes
+    /// scheduled above it are reported by `k` and `get_k_schedule` only.
+    pub async fn current_k(&self) -> u64 {
+        let tip_score = {
+            let dag = self.dag.read().await;
+            dag.headers().map(|h| h.blue_score).max().unwrap_or(0)
+        };
+        self.k_schedule.read().await.k_at(tip_score)
+    }
+
+    /// The current k schedule.
+    pub async fn get_k_schedule(&self) -> KSchedule {
+        self.k_schedule.read().await.clone()
+    }
+
+    /// Wire records for the requested headers; unknown hashes are skipped.
+    pub async fn get_headers(&self, hashes: &[BlockHash]) -> Vec<HeaderRecord> {
+        let dag = self.dag.read().await;
+        hashes
+            .iter()
+            .filter_map(|h| dag.get(h).ok())
+            .map(HeaderRecord::from_header)
+            .collect()
+    }
+
+    /// Serve an initial-block-download request (see `ibd::headers_after`).
+    pub async fn get_headers_after(
+        &self,
+        locator: &[BlockHash],
+        cursor: Option<&BlockHash>,
+        max: usize,
+    ) -> Result<Vec<HeaderRecord>, argus_ghostdag::GhostDagError> {
+        let dag = self.dag.read().await;
+        let headers = headers_after(&dag, locator, cursor, max.min(MAX_GET_HEADERS))?;
+        Ok(headers.iter().map(HeaderRecord::from_header).collect())
+    }
+
+    /// Metrics over the last `window` blocks (default
+    /// `DEFAULT_METRICS_WINDOW`, at most `MAX_METRICS_WINDOW`).
+    pub async fn get_network_metrics(
+        &self,
+        window: Option<usize>,
+    ) -> Result<NetworkMetrics, argus_ghostdag::GhostDagError> {
+        let window = window
+            .unwrap_or(DEFAULT_METRICS_WINDOW)
+            .min(MAX_METRICS_WINDOW);
+        let dag = dag_metrics(&*self.dag.read().await, window)?;
+        Ok(NetworkMetrics {
+            current_k: self.current_k().await,
+            dag,
+        })
+    }
+
+    /// Recommend k from `delay_ms` and `rate` (blocks per second), each
+    /// defaulting to the value measured over the last
+    /// `DEFAULT_METRICS_WINDOW` blocks; the rate falls back to the
+    /// consensus target when the DAG has no block intervals yet.
+    pub async fn recommend_k(
+        &self,
+        delay_ms: Option<f64>,
+        rate: Option<f64>,
+        delta: Option<f64>,
+    ) -> Result<KRecommendation, argus_ghostdag::GhostDagError> {
+        let live = self.get_network_metrics(None).await?;
+        let measured = delay_ms.is_none() || rate.is_none();
+        let delay_ms = delay_ms.unwrap_or(live.dag.estimated_propagation_delay_ms);
+        let block_rate = rate.unwrap_or(if live.dag.mean_block_interval_ms > 0.0 {
+            1000.0 / live.dag.mean_block_interval_ms
+        } else {
+            self.params.block_rate()
+        });
+        let delta = delta.unwrap_or(DEFAULT_OVERFLOW_PROBABILITY);
+        let delay = Duration::try_from_secs_f64(delay_ms / 1000.0).map_err(|_| {
+            argus_ghostdag::GhostDagError::InvalidParams(format!(
+                "delay must be a non-negative number of ms, got {delay_ms}"
+            ))
+        })?;
+
+        Ok(KRecommendation {
+            recommended_k: recommend_k(delay, block_rate, delta)?,
+            current_k: live.current_k,
+            delay_ms,
+            block_rate,
+            delta,
+            current_overflow_probability: anticone_overflow_probability(
+                live.current_k,
+                delay,
+                block_rate,
+            ),
+            measured,
+            metrics: live.dag,
+        })
+    }
+
+    /// The agent's most recent state transitions, oldest first.
+    pub async fn get_agent_history(
+        &self,
+        limit: Option<usize>,
+    ) -> Result<Vec<TransitionRecord>, AgentHandleError> {
+        let agent = self.agent.read().await.clone();
+        match agent {
+            Some(agent) => agent.history(limit).await,
+            None => Err(AgentHandleError::Stopped),
+        }
+    }
+
+    /// Evaluate candidate k values on a clone of the DAG.
+    ///
+    /// Never touches the live DAG, coloring, or k, and never broadcasts.
+    /// Coloring runs on a blocking thread so the RPC runtime stays responsive.
+    pub async fn analyze_k(
+        &self,
+        ks: Vec<u64>,
+    ) -> Result<Vec<KAnalysis>, argus_ghostdag::GhostDagError> {
+        if ks.is_empty() || ks.len() > MAX_ANALYZE_K {
+            return Err(argus_ghostdag::GhostDagError::Internal(format!(
+                "analyze_k accepts 1..={MAX_ANALYZE_K} k values, got {}",
+                ks.len()
+            )));
+        }
+        let snapshot = self.dag.read().await.clone();
+        let results = tokio::task::spawn_blocking(move || sweep_k(&snapshot, &ks))
+            .await
+            .map_err(|e| argus_ghostdag::GhostDagError::Internal(format!("k sweep failed: {e}")))??;
+
+        Ok(results
+            .into_iter()
+            .map(|r| KAnalysis {
+                k: r.k,
+                blue_ratio: r.blue_ratio,
+                red_count: r.red_count,
+                selected_tip: r.selected_tip.to_hex(),
+                selected_tip_changed: r.selected_tip_changed,
+                ordering_stability: r.ordering_stability,
+            })
+            .collect())
+    }
+}
+
+/// Blocks colored in both `previous` and `current` whose color differs,
+/// by blue score then hash.
+fn color_flips(
+    dag: &DagStore,
+    previous: &ColoringOutput,
+    current: &ColoringOutput,
+) -> Vec<ColorFlip> {
+    let to_blue = current.blue_set.iter().filter(|h| previous.red_set.contains(h));
+    let to_red = current.red_set.iter().filter(|h| previous.blue_set.contains(h));
+    let mut flips: Vec<(u64, BlockHash, bool)> = to_blue
+        .map(|h| (*h, true))
+        .chain(to_red.map(|h| (*h, false)))
+        .filter_map(|(h, is_blue)| dag.get(&h).ok().map(|hdr| (hdr.blue_score, h, is_blue)))
+        .collect();
+    flips.sort();
+    flips
+        .into_iter()
+        .map(|(blue_score, hash, is_blue)| ColorFlip {
+            hash: hash.to_hex(),
+            blue_score,
+            is_blue,
+        })
+        .collect()
+}
+
+/// How the selected chain moved from `previous` to `current`; `None` if
+/// it did not, or if there was no previous chain.
+fn chain_change(
+    dag: &DagStore,
+    previous: &[BlockHash],
+    current: &[BlockHash],
+) -> Option<ChainChange> {
+    let diff = diff_chains(previous, current).ok()?;
+    if diff.removed.is_empty() && diff.added.is_empty() {
+        return None;
+    }
+    let blocks = |hashes: &[BlockHash]| -> Vec<ChainBlock> {
+        hashes
+            .iter()
+            .map(|h| ChainBlock {
+                hash: h.to_hex(),
+                blue_score: dag.get(h).map(|hdr| hdr.blue_score).unwrap_or(0),
+            })
+            .collect()
+    };
+    Some(ChainChange {
+        fork_point: diff.fork_point.to_hex(),
+        removed: blocks(&diff.removed),
+        added: blocks(&diff.added),
+    })
 }
 
 /// Configuration for the server.
@@ -213,6 +672,10 @@ impl ServerState {
 pub struct ServerConfig {
     pub ws_addr: SocketAddr,
     pub rpc_addr: SocketAddr,
+    /// Default tiebreak rule for RPC and stream output.
+    pub ordering_rule: OrderingRuleKind,
+    /// Per-client queue size and slow-consumer policy of the stream.
+    pub flow_control: FlowControl,
 }
 
 impl Default for ServerConfig {
@@ -220,6 +683,8 @@ impl Default for ServerConfig {
         Self {
             ws_addr: "0.0.0.0:9292".parse().unwrap(),
             rpc_addr: "0.0.0.0:9293".parse().unwrap(),
+            ordering_rule: OrderingRuleKind::default(),
+            flow_control: FlowControl::default(),
         }
     }
 }
@@ -239,8 +704,12 @@ pub async fn start_server(
         "Starting Argus Linearizer server"
     );
 
+    *state.ordering_rule.write().await = config.ordering_rule;
+
     // Start WebSocket producer.
-    let (ws_producer, _ws_tx) = WsProducer::new(config.ws_addr);
+    let ws_producer = WsProducer::new(config.ws_addr, state.stream.clone())
+        .with_default_ordering(config.ordering_rule)
+        .with_flow_control(config.flow_control);
     let ws_shutdown = shutdown.clone();
     let ws_handle = tokio::spawn(async move {
         ws_producer.run(ws_shutdown).await;
@@ -266,7 +735,7 @@ async fn run_rpc_server(
     addr: SocketAddr,
     mut shutdown: tokio::sync::watch::Receiver<bool>,
 ) {
-    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::io::AsyncWriteExt;
     use tokio::net::TcpListener;
 
     let listener = match TcpListener::bind(addr).await {
@@ -284,18 +753,18 @@ async fn run_rpc_server(
         tokio::select! {
             result = listener.accept() => {
                 match result {
-                    Ok((mut stream, peer)) => {
+                    Ok((mut stream, _peer)) => {
                         let state = state.clone();
                         tokio::spawn(async move {
-                            let mut buf = vec![0u8; 4096];
-                            match stream.read(&mut buf).await {
-                                Ok(n) if n > 0 => {
-                                    let request = String::from_utf8_lossy(&buf[..n]).to_string();
-                                    let response = handle_rpc_request(&state, &request).await;
-                                    let _ = stream.write_all(response.as_bytes()).await;
+                            let response = match read_rpc_request(&mut stream).await {
+                                Ok(Some(request)) => {
+                                    let request = String::from_utf8_lossy(&request).to_string();
+                                    handle_rpc_request(&state, &request).await
                                 }
-                                _ => {}
-                            }
+                                Ok(None) => return,
+                                Err(e) => rpc_error(-32600, &format!("Invalid request: {e}")),
+                            };
+                            let _ = stream.write_all(response.as_bytes()).await;
                         });
                     }
                     Err(e) => {
@@ -313,6 +782,48 @@ async fn run_rpc_server(
     }
 }
 
+/// Read one request off `stream`: until the bytes read so far form a
+/// complete JSON value, the client shuts down its write half, or the
+/// request exceeds `MAX_RPC_REQUEST_BYTES` (an error).  Returns `None`
+/// if the client sent nothing.
+async fn read_rpc_request<R: tokio::io::AsyncRead + Unpin>(
+    stream: &mut R,
+) -> std::io::Result<Option<Vec<u8>>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut buf = Vec::new();
+    let mut chunk = vec![0u8; 16 * 1024];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok((!buf.is_empty()).then_some(buf));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.len() > MAX_RPC_REQUEST_BYTES {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("request exceeds {MAX_RPC_REQUEST_BYTES} bytes"),
+            ));
+        }
+        match serde_json::from_slice::<serde::de::IgnoredAny>(&buf) {
+            // Incomplete: wait for more.
+            Err(e) if e.is_eof() => {}
+            // Complete, or malformed (answered with a parse error).
+            _ => return Ok(Some(buf)),
+        }
+    }
+}
+
+/// A JSON-RPC error response with no id.
+fn rpc_error(code: i64, message: &str) -> String {
+    serde_json::to_string(&serde_json::json!({
+        "jsonrpc": "2.0",
+        "error": {"code": code, "message": message},
+        "id": null
+    }))
+    .unwrap_or_default()
+}
+
 /// Handle a single JSON-RPC request string and return a response string.
 async fn handle_rpc_request(state: &Arc<ServerState>, request: &str) -> String {
     // Parse minimal JSON-RPC 2.0.
@@ -335,36 +846,64 @@ async fn handle_rpc_request(state: &Arc<ServerState>, request: &str) -> String {
 
     let parsed: RpcRequest = match serde_json::from_str(request) {
         Ok(r) => r,
-        Err(e) => {
-            return serde_json::to_string(&serde_json::json!({
-                "jsonrpc": "2.0",
-                "error": {"code": -32700, "message": format!("Parse error: {e}")},
-                "id": null
-            }))
-            .unwrap_or_default();
-        }
+        Err(e) => return rpc_error(-32700, &format!("Parse error: {e}")),
     };
 
     let id = parsed.id.unwrap_or(serde_json::Value::Null);
     let params = parsed.params.unwrap_or(serde_json::Value::Null);
 
-    let result: Result<serde_json::Value, String> = match parsed.method.as_str() {
+    let result = dispatch_rpc(state, &parsed.method, params).await;
+
+    let resp = match result {
+        Ok(value) => RpcResponse {
+            jsonrpc: "2.0",
+            result: Some(value),
+            error: None,
+            id,
+        },
+        Err(msg) => RpcResponse {
+            jsonrpc: "2.0",
+            result: None,
+            error: Some(serde_json::json!({"code": -32603, "message": msg})),
+            id,
+        },
+    };
+
+    serde_json::to_string(&resp).unwrap_or_default()
+}
+
+
+/// Parse the optional `ordering` parameter.
+fn ordering_param(params: &serde_json::Value) -> Result<Option<OrderingRuleKind>, String> {
+    match params.get("ordering").and_then(|v| v.as_str()) {
+        Some(s) => s.parse().map(Some).map_err(|e: argus_ghostdag::GhostDagError| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Execute a single JSON-RPC method.
+async fn dispatch_rpc(
+    state: &Arc<ServerState>,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    match method {
         "get_tip_order" => {
-            let dag = state.dag.read().await;
-            let coloring_guard = state.coloring.read().await;
-            match coloring_guard.as_ref() {
-                Some(coloring) => match linearize_dag(&dag, coloring) {
-                    Ok(blocks) => Ok(serde_json::to_value(blocks).unwrap()),
-                    Err(e) => Err(e.to_string()),
-                },
-                None => Err("DAG not colored yet".to_string()),
+            let ordering = ordering_param(&params)?;
+            match state.linearize(ordering).await {
+                Ok(blocks) => Ok(serde_json::to_value(blocks).unwrap()),
+                Err(e) => Err(e.to_string()),
             }
         }
         "get_tips" => {
             let tips = state.get_bluest_tips(10).await;
+            let dag = state.dag.read().await;
             let tip_data: Vec<serde_json::Value> = tips
                 .into_iter()
-                .map(|(h, s)| serde_json::json!({"hash": h.to_hex(), "blue_score": s}))
+                .map(|(h, s)| {
+                    let blue_work = dag.get(&h).map(|hdr| hdr.blue_work.to_string()).ok();
+                    serde_json::json!({"hash": h.to_hex(), "blue_score": s, "blue_work": blue_work})
+                })
                 .collect();
             Ok(serde_json::Value::Array(tip_data))
         }
@@ -373,63 +912,179 @@ async fn handle_rpc_request(state: &Arc<ServerState>, request: &str) -> String {
                 .get("n")
                 .and_then(|v| v.as_u64())
                 .unwrap_or(100) as usize;
-            match state.get_snapshot(n).await {
+            let ordering = ordering_param(&params)?;
+            match state.get_snapshot(n, ordering).await {
                 Ok(snapshot) => Ok(serde_json::to_value(snapshot).unwrap()),
                 Err(e) => Err(e.to_string()),
             }
         }
+        "get_consensus_params" => Ok(serde_json::to_value(&state.params).unwrap()),
         "get_health" => {
             let health = state.get_health().await;
             Ok(serde_json::to_value(health).unwrap())
         }
+        "get_stream_clients" => Ok(serde_json::to_value(state.stream.client_metrics()).unwrap()),
         "linearize_range" => {
             let from = params.get("from_score").and_then(|v| v.as_u64()).unwrap_or(0);
             let to = params.get("to_score").and_then(|v| v.as_u64()).unwrap_or(u64::MAX);
-            match state.linearize_range(from, to).await {
+            let ordering = ordering_param(&params)?;
+            match state.linearize_range(from, to, ordering).await {
                 Ok(blocks) => Ok(serde_json::to_value(blocks).unwrap()),
                 Err(e) => Err(e.to_string()),
             }
         }
-        "update_k" => {
-            let new_k = params
-                .get("new_k")
+        "update_k" => match params.get("new_k").and_then(|v| v.as_u64()) {
+            Some(new_k) => {
+                let activation = params.get("activation_blue_score").and_then(|v| v.as_u64());
+                let source = match params.get("source").and_then(|v| v.as_str()) {
+                    Some(s) => match s.parse()? {
+                        source @ (KChangeSource::Rpc | KChangeSource::Rl) => source,
+                        other => return Err(format!("source '{other}' is not allowed over RPC")),
+                    },
+                    None => KChangeSource::Rpc,
+                };
+                // A caller may admit doubt, but not vouch for more than the
+                // node's own confidence.
+                let node_confidence = *state.rl_confidence.read().await;
+                let confidence = match params.get("confidence").and_then(|v| v.as_f64()) {
+                    Some(c) => c.min(node_confidence),
+                    None => node_confidence,
+                };
+                let reason = params.get("reason").and_then(|v| v.as_str()).unwrap_or_default();
+                let proposal = KProposal::new(new_k, source)
+                    .with_reason(reason)
+                    .with_confidence(confidence);
+                match state.propose_k(proposal, activation).await {
+                    Ok(record) => Ok(serde_json::json!({
+                        "updated_k": new_k,
+                        "activation_blue_score": record.activation_blue_score,
+                        "seq": record.seq,
+                    })),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            None => Err("missing 'new_k' parameter".to_string()),
+        },
+        "revert_k" => {
+            let reason = params.get("reason").and_then(|v| v.as_str()).unwrap_or("revert_k");
+            match state.revert_k(reason).await {
+                Ok(record) => Ok(serde_json::to_value(record).unwrap()),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        "get_k_audit" => {
+            let limit = params.get("limit").and_then(|v| v.as_u64()).map(|l| l as usize);
+            Ok(serde_json::to_value(state.get_k_audit(limit).await).unwrap())
+        }
+        "get_k_schedule" => {
+            let schedule = state.get_k_schedule().await;
+            Ok(serde_json::to_value(schedule.changes()).unwrap())
+        }
+        "analyze_k" => {
+            let ks: Option<Vec<u64>> = match params.get("ks") {
+                Some(v) => serde_json::from_value(v.clone()).ok(),
+                None => match (
+                    params.get("from_k").and_then(|v| v.as_u64()),
+                    params.get("to_k").and_then(|v| v.as_u64()),
+                ) {
+                    (Some(from), Some(to)) if from <= to => {
+                        if to - from >= MAX_ANALYZE_K as u64 {
+                            return Err(format!(
+                                "analyze_k accepts at most {MAX_ANALYZE_K} k values"
+                            ));
+                        }
+                        Some((from..=to).collect())
+                    }
+                    _ => None,
+                },
+            };
+            match ks {
+                Some(ks) => match state.analyze_k(ks).await {
+                    Ok(entries) => Ok(serde_json::to_value(entries).unwrap()),
+                    Err(e) => Err(e.to_string()),
+                },
+                None => Err("expected 'ks' array or 'from_k'/'to_k' range".to_string()),
+            }
+        }
+        "get_headers" => {
+            let hashes: Vec<String> = params
+                .get("hashes")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .ok_or_else(|| "expected 'hashes' array".to_string())?;
+            if hashes.len() > MAX_GET_HEADERS {
+                return Err(format!("at most {MAX_GET_HEADERS} hashes per request"));
+            }
+            let hashes = hashes
+                .iter()
+                .map(|h| BlockHash::from_hex(h))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            Ok(serde_json::to_value(state.get_headers(&hashes).await).unwrap())
+        }
+        "get_headers_after" => {
+            let locator: Vec<String> = params
+                .get("locator")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .ok_or_else(|| "expected 'locator' array".to_string())?;
+            if locator.len() > MAX_GET_HEADERS {
+                return Err(format!("at most {MAX_GET_HEADERS} locator entries"));
+            }
+            let locator = locator
+                .iter()
+                .map(|h| BlockHash::from_hex(h))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            let cursor = match params.get("cursor").and_then(|v| v.as_str()) {
+                Some(h) => Some(BlockHash::from_hex(h).map_err(|e| e.to_string())?),
+                None => None,
+            };
+            let max = params
+                .get("max")
                 .and_then(|v| v.as_u64())
-                .ok_or_else(|| "missing 'new_k' parameter".to_string())?;
-            match state.update_k(new_k).await {
-                Ok(()) => Ok(serde_json::json!({"updated_k": new_k})),
+                .unwrap_or(MAX_GET_HEADERS as u64) as usize;
+            match state.get_headers_after(&locator, cursor.as_ref(), max).await {
+                Ok(records) => Ok(serde_json::to_value(records).unwrap()),
                 Err(e) => Err(e.to_string()),
             }
         }
-        "smart_submit" => {
-            let req: SmartSubmitRequest = serde_json::from_value(params)
-                .map_err(|e| format!("invalid request: {e}"))?;
-            let resp = state.smart_submit(req).await;
-            Ok(serde_json::to_value(resp).unwrap())
+        "get_network_metrics" => {
+            let window = params.get("window").and_then(|v| v.as_u64()).map(|w| w as usize);
+            match state.get_network_metrics(window).await {
+                Ok(metrics) => Ok(serde_json::to_value(metrics).unwrap()),
+                Err(e) => Err(e.to_string()),
+            }
         }
-        other => Err(format!("unknown method: {other}")),
-    };
-
-    let resp = match result {
-        Ok(value) => RpcResponse {
-            jsonrpc: "2.0",
-            result: Some(value),
-            error: None,
-            id,
-        },
-        Err(msg) => RpcResponse {
-            jsonrpc: "2.0",
-            result: None,
-            error: Some(serde_json::json!({"code": -32603, "message": msg})),
-            id,
+        "recommend_k" => {
+            let delay_ms = params.get("delay_ms").and_then(|v| v.as_f64());
+            let rate = params.get("rate").and_then(|v| v.as_f64());
+            let delta = params.get("delta").and_then(|v| v.as_f64());
+            match state.recommend_k(delay_ms, rate, delta).await {
+                Ok(recommendation) => Ok(serde_json::to_value(recommendation).unwrap()),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        "get_agent_history" => {
+            let limit = params.get("limit").and_then(|v| v.as_u64()).map(|l| l as usize);
+            match state.get_agent_history(limit).await {
+                Ok(records) => Ok(serde_json::to_value(records).unwrap()),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        "smart_submit" => match serde_json::from_value::<SmartSubmitRequest>(params) {
+            Ok(req) => {
+                let resp = state.smart_submit(req).await;
+                Ok(serde_json::to_value(resp).unwrap())
+            }
+            Err(e) => Err(format!("invalid request: {e}")),
         },
-    };
-
-    serde_json::to_string(&resp).unwrap_or_default()
+        other => Err(format!("unknown method: {other}")),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use argus_agent::clock::ManualClock;
     use argus_ghostdag::block::{BlockHash, BlockHeader};
     use argus_ghostdag::coloring::color_dag;
 
@@ -446,22 +1101,27 @@ mod tests {
         dag.add_block(BlockHeader::new(c, vec![a, b], 2)).unwrap();
 
         let coloring = color_dag(&mut dag, 3).unwrap();
-        let (ws_tx, _) = broadcast::channel(16);
-
         Arc::new(ServerState {
             dag: Arc::new(RwLock::new(dag)),
             coloring: Arc::new(RwLock::new(Some(coloring))),
+            params: ConsensusParams::devnet(),
             k: Arc::new(RwLock::new(3)),
+            k_schedule: Arc::new(RwLock::new(KSchedule::new(3))),
             agent_state: Arc::new(RwLock::new("SYNCED".to_string())),
             rl_confidence: Arc::new(RwLock::new(0.95)),
-            ws_tx,
+            k_governor: KGovernor::default().shared(),
+            ordering_rule: Arc::new(RwLock::new(OrderingRuleKind::default())),
+            stream: StreamHub::new(16),
+            chain: Arc::new(RwLock::new(Vec::new())),
+            agent: Arc::new(RwLock::new(None)),
+            clock: system_clock(),
         })
     }
 
     #[tokio::test]
     async fn test_get_snapshot() {
         let state = build_test_state().await;
-        let snapshot = state.get_snapshot(10).await.unwrap();
+        let snapshot = state.get_snapshot(10, None).await.unwrap();
         assert_eq!(snapshot.blocks.len(), 4);
         assert_eq!(snapshot.k, 3);
     }
@@ -491,7 +1151,7 @@ mod tests {
     #[tokio::test]
     async fn test_linearize_range() {
         let state = build_test_state().await;
-        let blocks = state.linearize_range(0, 100).await.unwrap();
+        let blocks = state.linearize_range(0, 100, None).await.unwrap();
         assert_eq!(blocks.len(), 4);
     }
 
@@ -502,6 +1162,243 @@ mod tests {
         assert_eq!(*state.k.read().await, 5);
     }
 
+    #[tokio::test]
+    async fn test_update_k_is_scheduled_above_tip() {
+        let state = build_test_state().await;
+        let tip_score = state.get_health().await.tip_blue_score;
+
+        let activation = state.schedule_k(1, None).await.unwrap();
+        assert_eq!(activation, tip_score + 1);
+        // Existing blocks keep their k=3 colors.
+        assert_eq!(state.get_health().await.red_count, 0);
+
+        // Retroactive activations are refused.
+        assert!(state.schedule_k(2, Some(tip_score)).await.is_err());
+
+        let schedule = state.get_k_schedule().await;
+        assert_eq!(schedule.changes().len(), 2);
+        assert_eq!(schedule.k_at(tip_score), 3);
+        assert_eq!(schedule.k_at(activation), 1);
+
+        // Reports show the k in force at the tip until a block reaches the
+        // activation.
+        assert_eq!(*state.k.read().await, 1);
+        assert_eq!(state.get_health().await.current_k, 3);
+        assert_eq!(state.get_network_metrics(None).await.unwrap().current_k, 3);
+        let tip = state.get_snapshot(1, None).await.unwrap().tip;
+        let tip = BlockHash::from_hex(&tip).unwrap();
+        state
+            .dag
+            .write()
+            .await
+            .add_block(BlockHeader::new(BlockHash::from_byte(9), vec![tip], 9_000))
+            .unwrap();
+        state.recolor_and_broadcast().await.unwrap();
+        assert_eq!(state.get_health().await.tip_blue_score, activation);
+        assert_eq!(state.current_k().await, 1);
+        assert_eq!(state.recommend_k(Some(100.0), Some(1.0), None).await.unwrap().current_k, 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_k_policy_and_audit() {
+        let state = build_test_state().await;
+
+        // Out of range, too large a step, and too little confidence.
+        assert!(matches!(
+            state.update_k(65).await,
+            Err(KUpdateError::Governance(KGovernanceError::OutOfRange { .. }))
+        ));
+        assert!(state.update_k(12).await.is_err());
+        let unsure = KProposal::new(4, KChangeSource::Rl).with_confidence(0.1);
+        assert!(state.propose_k(unsure, None).await.is_err());
+        assert_eq!(*state.k.read().await, 3);
+        assert!(state.get_k_audit(None).await.is_empty());
+
+        let sure = KProposal::new(5, KChangeSource::Rl)
+            .with_reason("blue ratio dropped")
+            .with_confidence(0.9);
+        let record = state.propose_k(sure, None).await.unwrap();
+        assert_eq!((record.from_k, record.to_k), (3, 5));
+
+        let audit = state.get_k_audit(None).await;
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].source, KChangeSource::Rl);
+        assert_eq!(audit[0].reason, "blue ratio dropped");
+        assert!(audit[0].timestamp_ms > 0);
+    }
+
+    #[tokio::test]
+    async fn test_server_reads_time_from_clock() {
+        let mut dag = DagStore::from_params(&ConsensusParams::devnet()).unwrap();
+        let coloring = color_dag(&mut dag, 3).unwrap();
+        let clock = ManualClock::new(5_000).shared();
+        let state = ServerState::new(dag, ConsensusParams::devnet())
+            .with_k_policy(KPolicy::default())
+            .with_clock(clock.clone());
+        *state.coloring.write().await = Some(coloring);
+
+        assert_eq!(state.get_snapshot(10, None).await.unwrap().generated_at, 5_000);
+        let request = SmartSubmitRequest {
+            payload: String::new(),
+            parent_count: 3,
+        };
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(state.smart_submit(request).await.suggested_timestamp, 5_250);
+
+        // The governor's step window follows the same clock.
+        state.update_k(8).await.unwrap();
+        assert!(state.update_k(12).await.is_err());
+        clock.advance(Duration::from_secs(60));
+        state.update_k(12).await.unwrap();
+        let audit = state.get_k_audit(None).await;
+        assert_eq!(audit[0].timestamp_ms, 5_250);
+        assert_eq!(audit[1].timestamp_ms, 65_250);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_revert_k() {
+        let state = build_test_state().await;
+        let call = |method: &str, params: &str| {
+            format!(r#"{{"jsonrpc":"2.0","method":"{method}","params":{params},"id":1}}"#)
+        };
+
+        let response: serde_json::Value = serde_json::from_str(
+            &handle_rpc_request(&state, &call("revert_k", "{}")).await,
+        )
+        .unwrap();
+        assert!(response["error"].is_object());
+
+        let response: serde_json::Value = serde_json::from_str(
+            &handle_rpc_request(&state, &call("update_k", r#"{"new_k":6,"reason":"test"}"#))
+                .await,
+        )
+        .unwrap();
+        assert_eq!(response["result"]["seq"], 1);
+
+        let response: serde_json::Value = serde_json::from_str(
+            &handle_rpc_request(&state, &call("revert_k", r#"{"reason":"too high"}"#)).await,
+        )
+        .unwrap();
+        assert_eq!(response["result"]["to_k"], 3);
+        assert_eq!(response["result"]["reverts"], 1);
+        assert_eq!(*state.k.read().await, 3);
+
+        let response: serde_json::Value = serde_json::from_str(
+            &handle_rpc_request(&state, &call("get_k_audit", "{}")).await,
+        )
+        .unwrap();
+        assert_eq!(response["result"].as_array().unwrap().len(), 2);
+        assert_eq!(response["result"][1]["source"], "revert");
+    }
+
+    #[tokio::test]
+    async fn test_rpc_update_k_cannot_raise_confidence() {
+        let state = build_test_state().await;
+        *state.rl_confidence.write().await = 0.2;
+        let call = |params: &str| {
+            format!(r#"{{"jsonrpc":"2.0","method":"update_k","params":{params},"id":1}}"#)
+        };
+
+        // The node is unsure, so a forged high confidence is refused.
+        let response: serde_json::Value = serde_json::from_str(
+            &handle_rpc_request(&state, &call(r#"{"new_k":4,"source":"rl","confidence":1.0}"#))
+                .await,
+        )
+        .unwrap();
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("confidence"));
+        assert_eq!(*state.k.read().await, 3);
+        assert!(state.get_k_audit(None).await.is_empty());
+
+        // A caller can still lower it below a confident node's.
+        *state.rl_confidence.write().await = 0.9;
+        let response: serde_json::Value = serde_json::from_str(
+            &handle_rpc_request(&state, &call(r#"{"new_k":4,"confidence":0.1}"#)).await,
+        )
+        .unwrap();
+        assert!(response["error"].is_object());
+        let response: serde_json::Value = serde_json::from_str(
+            &handle_rpc_request(&state, &call(r#"{"new_k":4}"#)).await,
+        )
+        .unwrap();
+        assert_eq!(response["result"]["updated_k"], 4);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_k_leaves_live_state_untouched() {
+        let state = build_test_state().await;
+        let mut ws_rx = state.stream.subscribe();
+
+        let entries = state.analyze_k(vec![1, 3]).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].k, 3);
+        assert_eq!(entries[1].ordering_stability, 1.0);
+
+        assert_eq!(*state.k.read().await, 3);
+        assert!(ws_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rpc_analyze_k_range() {
+        let state = build_test_state().await;
+        let request = r#"{"jsonrpc":"2.0","method":"analyze_k","params":{"from_k":1,"to_k":4},"id":1}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_rpc_request(&state, request).await).unwrap();
+        assert_eq!(response["result"].as_array().unwrap().len(), 4);
+
+        // Rejected before the range is materialized.
+        let request = r#"{"jsonrpc":"2.0","method":"analyze_k","params":{"from_k":1,"to_k":18446744073709551615},"id":2}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_rpc_request(&state, request).await).unwrap();
+        assert!(response["error"]["message"].as_str().unwrap().contains("at most"));
+    }
+
+    #[tokio::test]
+    async fn test_rpc_network_metrics() {
+        let state = build_test_state().await;
+        let request = r#"{"jsonrpc":"2.0","method":"get_network_metrics","params":{"window":3},"id":1}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_rpc_request(&state, request).await).unwrap();
+        let metrics = &response["result"];
+        assert_eq!(metrics["current_k"], 3);
+        assert_eq!(metrics["window_blocks"], 3);
+        // A and B are parallel children of genesis.
+        assert_eq!(metrics["dag_width"], 2);
+        assert_eq!(metrics["max_anticone"], 1);
+        assert_eq!(metrics["tip_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_recommend_k() {
+        let state = build_test_state().await;
+
+        let r = state.recommend_k(Some(500.0), Some(1.0), None).await.unwrap();
+        assert_eq!(r.recommended_k, 4);
+        assert_eq!(r.current_k, 3);
+        assert!(!r.measured);
+        assert!(r.current_overflow_probability > r.delta);
+
+        // Measured: blocks A and B are concurrent, so the delay is non-zero.
+        let r = state.recommend_k(None, None, Some(0.05)).await.unwrap();
+        assert!(r.measured);
+        assert!(r.delay_ms > 0.0);
+        assert!(r.recommended_k >= 1);
+        assert_eq!(r.metrics.window_blocks, 4);
+
+        assert!(state.recommend_k(Some(-1.0), None, None).await.is_err());
+        // Too large for a `Duration`: refused rather than a panic.
+        assert!(matches!(
+            state.recommend_k(Some(1e30), None, None).await,
+            Err(argus_ghostdag::GhostDagError::InvalidParams(_))
+        ));
+        let request = r#"{"jsonrpc":"2.0","method":"recommend_k","params":{"delay_ms":1e30},"id":1}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_rpc_request(&state, request).await).unwrap();
+        assert!(response["error"]["message"].as_str().unwrap().contains("delay"));
+    }
+
     #[tokio::test]
     async fn test_rpc_handler() {
         let state = build_test_state().await;
@@ -510,6 +1407,173 @@ mod tests {
         assert!(response.contains("current_k"));
     }
 
+    #[tokio::test]
+    async fn test_json_rpc_fetcher_against_server() {
+        use argus_agent::fetcher::{BlockFetcher, JsonRpcBlockFetcher};
+
+        let state = build_test_state().await;
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let server = tokio::spawn(run_rpc_server(state, addr, shutdown_rx));
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let fetcher = JsonRpcBlockFetcher::new(addr);
+        let headers = fetcher
+            .fetch_headers(&[BlockHash::from_byte(3), BlockHash::from_byte(42)])
+            .await
+            .unwrap();
+        assert_eq!(headers.len(), 1);
+
+        // A request far larger than one read still arrives whole.
+        let mut many: Vec<BlockHash> = (0..500).map(|i| BlockHash::from_u64(1_000 + i)).collect();
+        many.push(BlockHash::from_byte(2));
+        let found = fetcher.fetch_headers(&many).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].hash, BlockHash::from_byte(2));
+        assert_eq!(headers[0].hash, BlockHash::from_byte(3));
+        assert_eq!(
+            headers[0].parents,
+            vec![BlockHash::from_byte(1), BlockHash::from_byte(2)]
+        );
+
+        shutdown_tx.send(true).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_rpc_request_framing() {
+        use tokio::io::AsyncWriteExt;
+
+        // The client keeps its write half open: the request ends with the
+        // JSON value, even when it spans several reads.
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let pad = "x".repeat(10_000);
+        let request = format!(r#"{{"method":"get_headers","params":{{"pad":"{pad}"}}}}"#);
+        let writer = tokio::spawn({
+            let request = request.clone();
+            async move {
+                client.write_all(request.as_bytes()).await.unwrap();
+                client
+            }
+        });
+        let read = read_rpc_request(&mut server).await.unwrap().unwrap();
+        assert_eq!(read, request.as_bytes());
+        drop(writer.await.unwrap());
+        assert_eq!(read_rpc_request(&mut server).await.unwrap(), None);
+
+        // Oversized requests are refused.
+        let mut huge = format!(r#"{{"pad":"{}"#, "x".repeat(MAX_RPC_REQUEST_BYTES)).into_bytes();
+        huge.extend_from_slice(br#""}"#);
+        assert!(read_rpc_request(&mut huge.as_slice()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ibd_from_server() {
+        use argus_agent::fetcher::JsonRpcBlockFetcher;
+        use argus_agent::ibd::run_ibd;
+
+        let state = build_test_state().await;
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let server = tokio::spawn(run_rpc_server(state, addr, shutdown_rx));
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let mut fresh = DagStore::new();
+        fresh
+            .add_genesis(BlockHeader::genesis(BlockHash::from_byte(0), 0))
+            .unwrap();
+        color_dag(&mut fresh, 3).unwrap();
+        let fresh = Arc::new(RwLock::new(fresh));
+
+        let fetcher = JsonRpcBlockFetcher::new(addr);
+        let mut orphans = argus_agent::orphans::OrphanPool::default();
+        let clock = argus_agent::clock::SystemClock;
+        let schedule = KSchedule::new(3);
+        let report = run_ibd(&fresh, &fetcher, &schedule, &mut orphans, &clock, &[], 2, None)
+            .await
+            .unwrap();
+        assert_eq!(report.blocks_ingested, 3);
+        assert!(fresh.read().await.contains(&BlockHash::from_byte(3)));
+
+        shutdown_tx.send(true).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rpc_get_consensus_params() {
+        let state = build_test_state().await;
+        let request = r#"{"jsonrpc":"2.0","method":"get_consensus_params","id":1}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_rpc_request(&state, request).await).unwrap();
+        assert_eq!(response["result"]["network"], "devnet");
+        assert_eq!(response["result"]["genesis"]["hash"], BlockHash::from_byte(0).to_hex());
+    }
+
+    #[tokio::test]
+    async fn test_rpc_ordering_param() {
+        let state = build_test_state().await;
+        let request = r#"{"jsonrpc":"2.0","method":"get_tip_order","params":{"ordering":"timestamp"},"id":1}"#;
+        let response: serde_json::Value =
+            serde_json::from_str(&handle_rpc_request(&state, request).await).unwrap();
+        assert_eq!(response["result"].as_array().unwrap().len(), 4);
+
+        let request = r#"{"jsonrpc":"2.0","method":"get_tip_order","params":{"ordering":"bogus"},"id":1}"#;
+        let response = handle_rpc_request(&state, request).await;
+        assert!(response.contains("unknown ordering rule"));
+    }
+
+    #[tokio::test]
+    async fn test_recolor_publishes_requested_orderings() {
+        let state = build_test_state().await;
+        let mut rx = state.stream.subscribe();
+        let _sub = state.stream.orderings().subscribe(OrderingRuleKind::BlueWork);
+
+        state.recolor_and_broadcast().await.unwrap();
+
+        let mut rules = vec![
+            rx.try_recv().unwrap().ordering.unwrap(),
+            rx.try_recv().unwrap().ordering.unwrap(),
+        ];
+        rules.sort_by_key(|r| r.to_string());
+        assert_eq!(rules, vec![OrderingRuleKind::BlueWork, OrderingRuleKind::Xor]);
+    }
+
+    #[tokio::test]
+    async fn test_republish_uses_agent_coloring() {
+        let state = build_test_state().await;
+        let mut rx = state.stream.subscribe();
+
+        // The agent ingests D and colors the shared DAG itself.
+        let d = BlockHash::from_byte(4);
+        let colored = {
+            let mut dag = state.dag.write().await;
+            dag.add_block(BlockHeader::new(d, vec![BlockHash::from_byte(3)], 3)).unwrap();
+            color_dag(&mut dag, 3).unwrap()
+        };
+
+        state.republish_agent_coloring().await.unwrap();
+        let published = state.coloring.read().await.clone().unwrap();
+        assert_eq!(published.blue_set, colored.blue_set);
+        assert_eq!(published.red_set, colored.red_set);
+        assert!(published.blue_set.contains(&d));
+        assert!(rx.try_recv().is_ok());
+        assert_eq!(state.chain.read().await.last(), Some(&d));
+    }
+
+    #[tokio::test]
+    async fn test_rpc_agent_history_without_agent() {
+        let state = build_test_state().await;
+        let request = r#"{"jsonrpc":"2.0","method":"get_agent_history","id":1}"#;
+        let response = handle_rpc_request(&state, request).await;
+        assert!(response.contains("agent is not running"));
+    }
+
     #[tokio::test]
     async fn test_rpc_unknown_method() {
         let state = build_test_state().await;
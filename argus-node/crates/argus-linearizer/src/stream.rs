@@ -3,35 +3,328 @@
 /// Flattens the 3D Web-DAG into a 1D JSON stream of `LinearizedBlock`
 /// frames, pushed to all connected WebSocket clients whenever new blocks
 /// are added to the DAG.
-use std::collections::HashSet;
+///
+/// Each ordering has its own numbered stream: a client gets a full
+/// `BlockDelta` snapshot on connect, then deltas holding only the blocks
+/// appended or changed since the previous `seq`.  The hub keeps the last
+/// `delta_history` deltas per ordering, so a client reconnecting with
+/// `?resume_from=<last seq seen>` replays what it missed instead of
+/// reloading everything; older positions fall back to a snapshot.
+///
+/// Every producer — recolors, agent events, newly ingested blocks —
+/// publishes into one `StreamHub`, and `WsProducer` subscribes each client
+/// to that same hub.
+///
+/// Clients choose the tiebreak rule with a query parameter on the
+/// handshake URL, e.g. `ws://host:9292/?ordering=timestamp`; frames are
+/// only produced for rules that have at least one connected client
+/// (plus the server default).
+///
+/// Messages a client sends follow the subscription protocol in
+/// `subscriptions`; until its first subscribe it gets the default stream.
+///
+/// Each client is written through a bounded queue; one that cannot keep up
+/// is handled per `FlowControl` (see `flow`) instead of silently losing
+/// frames.
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use futures_util::{SinkExt, StreamExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
 use tokio_tungstenite::tungstenite::Message;
 use tracing::{error, info, warn};
 
 use argus_ghostdag::coloring::ColoringOutput;
 use argus_ghostdag::dag::DagStore;
-use argus_ghostdag::ordering::total_order;
+use argus_ghostdag::ordering::{total_order_with, OrderingRule, OrderingRuleKind, XorTiebreak};
 
-use crate::schema::LinearizedBlock;
+use crate::flow::{coalesce_deltas, ClientRegistry, ClientStats, FlowControl, SlowConsumerPolicy};
+use crate::schema::{BlockDelta, BlockDeltaKind, LinearizedBlock, StreamClientMetrics};
+use crate::subscriptions::{ClientSubscriptions, ServerMessage, Topic, TopicEvent};
 
-/// A message broadcast to all WebSocket clients.
+/// A message broadcast to WebSocket clients.
 #[derive(Debug, Clone)]
 pub struct StreamFrame {
-    /// JSON-encoded `Vec<LinearizedBlock>`.
-    pub json: String,
+    /// Default-stream payload: a `BlockDelta` or an agent event.  `None`
+    /// for topics only subscribers receive.
+    pub json: Option<String>,
+    /// The ordering rule the blocks were linearized with; `None` for
+    /// frames that go to every client regardless of ordering.
+    pub ordering: Option<OrderingRuleKind>,
+    /// The event, filtered per subscription.
+    pub event: Arc<TopicEvent>,
+}
+
+impl StreamFrame {
+    /// A frame for `event`.  Blocks frames only reach clients of their
+    /// ordering; every other frame reaches all clients.
+    pub fn new(event: TopicEvent) -> Self {
+        let json = match &event {
+            TopicEvent::Agent(e) => {
+                Some(serde_json::json!({ "type": "agent_event", "event": e }).to_string())
+            }
+            TopicEvent::Blocks(delta) => serde_json::to_string(delta).ok(),
+            _ => None,
+        };
+        let ordering = match &event {
+            TopicEvent::Blocks(delta) => Some(delta.ordering),
+            _ => None,
+        };
+        Self {
+            json,
+            ordering,
+            event: Arc::new(event),
+        }
+    }
+
+    /// The sequence number of a blocks frame.
+    pub fn seq(&self) -> Option<u64> {
+        self.event.seq()
+    }
+}
+
+/// Reference counts of the ordering rules requested by connected
+/// WebSocket clients.  Cloning shares the same counts.
+#[derive(Debug, Clone, Default)]
+pub struct OrderingSubscriptions {
+    counts: Arc<Mutex<HashMap<OrderingRuleKind, usize>>>,
+}
+
+impl OrderingSubscriptions {
+    /// Register a client for `kind`; the registration ends when the
+    /// returned guard is dropped.
+    pub fn subscribe(&self, kind: OrderingRuleKind) -> OrderingSubscription {
+        *self.counts.lock().unwrap().entry(kind).or_insert(0) += 1;
+        OrderingSubscription {
+            counts: self.counts.clone(),
+            kind,
+        }
+    }
+
+    /// Rules with at least one connected client.
+    pub fn active(&self) -> Vec<OrderingRuleKind> {
+        let counts = self.counts.lock().unwrap();
+        OrderingRuleKind::ALL
+            .into_iter()
+            .filter(|k| counts.get(k).copied().unwrap_or(0) > 0)
+            .collect()
+    }
+}
+
+/// RAII registration returned by `OrderingSubscriptions::subscribe`.
+#[derive(Debug)]
+pub struct OrderingSubscription {
+    counts: Arc<Mutex<HashMap<OrderingRuleKind, usize>>>,
+    kind: OrderingRuleKind,
 }
 
-/// Convert the current DAG state into a vector of `LinearizedBlock`.
+impl Drop for OrderingSubscription {
+    fn drop(&mut self) {
+        if let Ok(mut counts) = self.counts.lock() {
+            if let Some(c) = counts.get_mut(&self.kind) {
+                *c = c.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Default number of frames the hub buffers for each client.
+pub const DEFAULT_STREAM_CAPACITY: usize = 1024;
+
+/// Default number of deltas kept per ordering for `resume_from`.
+pub const DEFAULT_DELTA_HISTORY: usize = 256;
+
+/// One ordering's numbered block stream.
+#[derive(Debug, Default)]
+struct DeltaLog {
+    /// Sequence number of the latest delta; 0 before the first.
+    seq: u64,
+    /// Every block as of `seq`.
+    blocks: Vec<LinearizedBlock>,
+    /// The most recent deltas, oldest first.
+    history: VecDeque<StreamFrame>,
+}
+
+/// The single fan-out point between frame producers and WebSocket
+/// clients.  Cloning shares the channel, the ordering registrations and
+/// the delta logs.
+#[derive(Debug, Clone)]
+pub struct StreamHub {
+    tx: broadcast::Sender<StreamFrame>,
+    orderings: OrderingSubscriptions,
+    logs: Arc<Mutex<HashMap<OrderingRuleKind, DeltaLog>>>,
+    delta_history: usize,
+    clients: ClientRegistry,
+}
+
+impl StreamHub {
+    /// A hub buffering up to `capacity` frames per client.
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self {
+            tx,
+            orderings: OrderingSubscriptions::default(),
+            logs: Arc::default(),
+            delta_history: DEFAULT_DELTA_HISTORY,
+            clients: ClientRegistry::default(),
+        }
+    }
+
+    /// Keep the last `deltas` deltas per ordering for resuming clients.
+    pub fn with_delta_history(mut self, deltas: usize) -> Self {
+        self.delta_history = deltas;
+        self
+    }
+
+    /// Publish the blocks of `ordering` that were appended or changed since
+    /// the last call, as the next delta.  Returns the number of receivers,
+    /// or 0 without publishing if nothing changed.
+    pub fn publish_blocks(
+        &self,
+        ordering: OrderingRuleKind,
+        blocks: Vec<LinearizedBlock>,
+    ) -> usize {
+        let mut logs = self.logs.lock().unwrap();
+        let log = logs.entry(ordering).or_default();
+        let changed = changed_blocks(&log.blocks, &blocks);
+        if changed.is_empty() {
+            return 0;
+        }
+        log.seq += 1;
+        log.blocks = blocks;
+        let frame = StreamFrame::new(TopicEvent::Blocks(BlockDelta {
+            kind: BlockDeltaKind::Delta,
+            seq: log.seq,
+            ordering,
+            blocks: changed,
+        }));
+        log.history.push_back(frame.clone());
+        while log.history.len() > self.delta_history {
+            log.history.pop_front();
+        }
+        // Sent under the lock, so `catch_up` never misses a delta.
+        self.publish(frame)
+    }
+
+    /// What a client of `ordering` needs before live deltas: the buffered
+    /// deltas after `resume_from`, or a snapshot when those are gone (or
+    /// `resume_from` is `None`).  Pair with a receiver taken beforehand and
+    /// drop live frames at or below the last `seq` returned.
+    pub fn catch_up(
+        &self,
+        ordering: OrderingRuleKind,
+        resume_from: Option<u64>,
+    ) -> Vec<StreamFrame> {
+        let logs = self.logs.lock().unwrap();
+        let Some(log) = logs.get(&ordering) else {
+            return vec![snapshot_frame(ordering, 0, Vec::new())];
+        };
+        if let Some(from) = resume_from {
+            let oldest = log.history.front().and_then(StreamFrame::seq).unwrap_or(log.seq + 1);
+            if from <= log.seq && from + 1 >= oldest {
+                return log
+                    .history
+                    .iter()
+                    .filter(|f| f.seq().is_some_and(|seq| seq > from))
+                    .cloned()
+                    .collect();
+            }
+        }
+        vec![snapshot_frame(ordering, log.seq, log.blocks.clone())]
+    }
+
+    /// Latest sequence number of `ordering`'s stream.
+    pub fn seq(&self, ordering: OrderingRuleKind) -> u64 {
+        self.logs.lock().unwrap().get(&ordering).map_or(0, |log| log.seq)
+    }
+
+    /// Send `frame` to every subscriber.  Returns how many there are.
+    pub fn publish(&self, frame: StreamFrame) -> usize {
+        self.tx.send(frame).unwrap_or(0)
+    }
+
+    /// Receive every frame published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<StreamFrame> {
+        self.tx.subscribe()
+    }
+
+    /// Number of current subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// Ordering rules requested by connected clients.
+    pub fn orderings(&self) -> &OrderingSubscriptions {
+        &self.orderings
+    }
+
+    /// Counters of connected WebSocket clients.
+    pub fn clients(&self) -> &ClientRegistry {
+        &self.clients
+    }
+
+    /// Queue and lag metrics of every connected WebSocket client.
+    pub fn client_metrics(&self) -> Vec<StreamClientMetrics> {
+        self.clients.metrics(|ordering| self.seq(ordering))
+    }
+}
+
+impl Default for StreamHub {
+    fn default() -> Self {
+        Self::new(DEFAULT_STREAM_CAPACITY)
+    }
+}
+
+/// A snapshot frame of `blocks` at `seq`.
+fn snapshot_frame(
+    ordering: OrderingRuleKind,
+    seq: u64,
+    blocks: Vec<LinearizedBlock>,
+) -> StreamFrame {
+    StreamFrame::new(TopicEvent::Blocks(BlockDelta {
+        kind: BlockDeltaKind::Snapshot,
+        seq,
+        ordering,
+        blocks,
+    }))
+}
+
+/// Blocks in `current` that are new or differ from `previous`.
+fn changed_blocks(
+    previous: &[LinearizedBlock],
+    current: &[LinearizedBlock],
+) -> Vec<LinearizedBlock> {
+    let previous: HashMap<&str, &LinearizedBlock> =
+        previous.iter().map(|b| (b.hash.as_str(), b)).collect();
+    current
+        .iter()
+        .filter(|b| previous.get(b.hash.as_str()) != Some(b))
+        .cloned()
+        .collect()
+}
+
+/// Convert the current DAG state into a vector of `LinearizedBlock`,
+/// using the default XOR tiebreak.
 pub fn linearize_dag(
     dag: &DagStore,
     coloring: &ColoringOutput,
 ) -> Result<Vec<LinearizedBlock>, argus_ghostdag::GhostDagError> {
-    let ordered = total_order(dag)?;
+    linearize_dag_with(dag, coloring, &XorTiebreak)
+}
+
+/// Convert the current DAG state into a vector of `LinearizedBlock`
+/// ordered by the given tiebreak rule.
+pub fn linearize_dag_with(
+    dag: &DagStore,
+    coloring: &ColoringOutput,
+    rule: &dyn OrderingRule,
+) -> Result<Vec<LinearizedBlock>, argus_ghostdag::GhostDagError> {
+    let ordered = total_order_with(dag, rule)?;
 
     let blocks: Vec<LinearizedBlock> = ordered
         .into_iter()
@@ -54,26 +347,40 @@ pub fn linearize_dag(
 
 /// The WebSocket stream producer.
 ///
-/// Accepts incoming WebSocket connections and broadcasts linearized
-/// DAG updates to all connected clients.
+/// Accepts incoming WebSocket connections and subscribes each client to
+/// the frames published on its `StreamHub`.
 pub struct WsProducer {
     /// Address to bind.
     addr: SocketAddr,
-    /// Broadcast sender for stream frames.
-    broadcast_tx: broadcast::Sender<StreamFrame>,
+    /// Where frames come from.
+    hub: StreamHub,
+    /// Ordering used for clients that don't request one.
+    default_ordering: OrderingRuleKind,
+    /// Per-client queue size and slow-consumer policy.
+    flow: FlowControl,
 }
 
 impl WsProducer {
-    /// Create a new producer bound to the given address.
-    /// Returns the producer and a broadcast sender that can be used
-    /// to push new frames.
-    pub fn new(addr: SocketAddr) -> (Self, broadcast::Sender<StreamFrame>) {
-        let (tx, _) = broadcast::channel::<StreamFrame>(256);
-        let producer = Self {
+    /// Create a producer serving `hub` on the given address.
+    pub fn new(addr: SocketAddr, hub: StreamHub) -> Self {
+        Self {
             addr,
-            broadcast_tx: tx.clone(),
-        };
-        (producer, tx)
+            hub,
+            default_ordering: OrderingRuleKind::default(),
+            flow: FlowControl::default(),
+        }
+    }
+
+    /// Use `ordering` for clients that don't request one.
+    pub fn with_default_ordering(mut self, ordering: OrderingRuleKind) -> Self {
+        self.default_ordering = ordering;
+        self
+    }
+
+    /// Queue size and policy for clients that fall behind.
+    pub fn with_flow_control(mut self, flow: FlowControl) -> Self {
+        self.flow = flow;
+        self
     }
 
     /// Start accepting WebSocket connections.
@@ -98,8 +405,13 @@ impl WsProducer {
                     match result {
                         Ok((stream, peer)) => {
                             info!(peer = %peer, "New WebSocket connection");
-                            let rx = self.broadcast_tx.subscribe();
-                            tokio::spawn(handle_ws_client(stream, peer, rx));
+                            tokio::spawn(handle_ws_client(
+                                stream,
+                                peer,
+                                self.hub.clone(),
+                                self.default_ordering,
+                                self.flow,
+                            ));
                         }
                         Err(e) => {
                             error!("Failed to accept connection: {e}");
@@ -117,76 +429,328 @@ impl WsProducer {
     }
 }
 
+/// Parameters a client can put on the handshake URL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct StreamQuery {
+    ordering: Option<OrderingRuleKind>,
+    resume_from: Option<u64>,
+}
+
+/// Parse `ordering` and `resume_from` from a handshake URL query.
+fn parse_stream_query(query: Option<&str>) -> Result<StreamQuery, String> {
+    let mut parsed = StreamQuery::default();
+    for pair in query.unwrap_or_default().split('&') {
+        if let Some(value) = pair.strip_prefix("ordering=") {
+            let ordering = value.parse::<OrderingRuleKind>().map_err(|e| e.to_string())?;
+            parsed.ordering = Some(ordering);
+        } else if let Some(value) = pair.strip_prefix("resume_from=") {
+            let seq = value.parse().map_err(|e| format!("invalid resume_from: {e}"))?;
+            parsed.resume_from = Some(seq);
+        }
+    }
+    Ok(parsed)
+}
+
+/// Per-connection delivery state.
+struct ClientState {
+    ordering: OrderingRuleKind,
+    subscriptions: ClientSubscriptions,
+    /// Highest blocks `seq` queued for the client.
+    last_seq: Option<u64>,
+    stats: Arc<ClientStats>,
+}
+
+impl ClientState {
+    /// Whether dropping `frame` loses anything for this client.
+    fn wants(&self, frame: &StreamFrame) -> bool {
+        !frame.ordering.is_some_and(|o| o != self.ordering)
+            && (!self.subscriptions.is_legacy() || frame.json.is_some())
+    }
+
+    /// The text messages `frame` becomes for this client.
+    fn messages_for(&mut self, frame: &StreamFrame) -> Vec<String> {
+        if frame.ordering.is_some_and(|o| o != self.ordering) {
+            return Vec::new();
+        }
+        let seq = frame.seq();
+        let fresh = match (seq, self.last_seq) {
+            (Some(seq), Some(last)) => seq > last,
+            _ => true,
+        };
+        if let Some(seq) = seq.filter(|_| fresh) {
+            self.last_seq = Some(seq);
+            self.stats.delivered_seq(seq);
+        }
+        if !self.subscriptions.is_legacy() {
+            // Subscriptions dedupe by `seq` on their own.
+            return self
+                .subscriptions
+                .deliver(&frame.event)
+                .iter()
+                .filter_map(|m| serde_json::to_string(m).ok())
+                .collect();
+        }
+        if !fresh {
+            return Vec::new();
+        }
+        frame.json.iter().cloned().collect()
+    }
+
+    /// Apply a request from the client; a new `blocks` subscription is
+    /// followed by its snapshot or replayed deltas.
+    fn handle_text(&mut self, text: &str, hub: &StreamHub) -> Vec<String> {
+        let reply = self.subscriptions.handle_text(text);
+        let mut messages = vec![reply.clone()];
+        if let ServerMessage::Subscribed {
+            subscription,
+            topic: Topic::Blocks,
+            ..
+        } = reply
+        {
+            let resume_from = self.subscriptions.resume_from(subscription);
+            for frame in hub.catch_up(self.ordering, resume_from) {
+                messages.extend(self.subscriptions.deliver_to(subscription, &frame.event));
+            }
+        }
+        messages
+            .iter()
+            .filter_map(|m| serde_json::to_string(m).ok())
+            .collect()
+    }
+
+    /// The `gap` message for `dropped` lost frames, followed by what brings
+    /// the client back up to date under `policy`: one merged delta, or a
+    /// snapshot if the deltas are gone or `policy` is `Resync`.
+    fn recover(
+        &mut self,
+        hub: &StreamHub,
+        policy: SlowConsumerPolicy,
+        dropped: u64,
+    ) -> Vec<String> {
+        let frames = match policy {
+            SlowConsumerPolicy::Coalesce => {
+                coalesce_frames(hub.catch_up(self.ordering, self.last_seq))
+            }
+            _ => hub.catch_up(self.ordering, None),
+        };
+        let from_seq = self.last_seq.map_or(1, |last| last + 1);
+        let to_seq = frames.iter().filter_map(StreamFrame::seq).max().filter(|&to| to >= from_seq);
+        let gap = ServerMessage::Gap {
+            from_seq: to_seq.map(|_| from_seq),
+            to_seq,
+            dropped,
+            recovery: policy,
+        };
+        let mut messages: Vec<String> = serde_json::to_string(&gap).into_iter().collect();
+        for frame in &frames {
+            messages.extend(self.messages_for(frame));
+        }
+        messages
+    }
+}
+
+/// Replace consecutive deltas with one merged delta; a snapshot is kept.
+fn coalesce_frames(frames: Vec<StreamFrame>) -> Vec<StreamFrame> {
+    let deltas: Vec<&BlockDelta> = frames
+        .iter()
+        .filter_map(|frame| match frame.event.as_ref() {
+            TopicEvent::Blocks(delta) if delta.kind == BlockDeltaKind::Delta => Some(delta),
+            _ => None,
+        })
+        .collect();
+    if deltas.len() < frames.len() {
+        return frames;
+    }
+    coalesce_deltas(deltas)
+        .map(|delta| vec![StreamFrame::new(TopicEvent::Blocks(delta))])
+        .unwrap_or_default()
+}
+
 /// Handle a single WebSocket client connection.
 async fn handle_ws_client(
     stream: TcpStream,
     peer: SocketAddr,
-    mut rx: broadcast::Receiver<StreamFrame>,
+    hub: StreamHub,
+    default_ordering: OrderingRuleKind,
+    flow: FlowControl,
 ) {
-    let ws_stream = match tokio_tungstenite::accept_async(stream).await {
+    let mut query = StreamQuery::default();
+    // The callback signature (and its large error type) is fixed by tungstenite.
+    #[allow(clippy::result_large_err)]
+    let callback = |req: &Request, resp: Response| -> Result<Response, ErrorResponse> {
+        match parse_stream_query(req.uri().query()) {
+            Ok(parsed) => {
+                query = parsed;
+                Ok(resp)
+            }
+            Err(msg) => {
+                let mut err = ErrorResponse::new(Some(msg));
+                *err.status_mut() = StatusCode::BAD_REQUEST;
+                Err(err)
+            }
+        }
+    };
+    let ws_stream = match tokio_tungstenite::accept_hdr_async(stream, callback).await {
         Ok(ws) => ws,
         Err(e) => {
             error!(peer = %peer, "WebSocket handshake failed: {e}");
             return;
         }
     };
+    let ordering = query.ordering.unwrap_or(default_ordering);
+    let _registration = hub.orderings().subscribe(ordering);
+    let stats = hub.clients().register(peer, ordering, flow.client_queue);
+    info!(peer = %peer, %ordering, resume_from = ?query.resume_from, "WebSocket client subscribed");
 
-    let (mut write, mut read) = ws_stream.split();
+    // Subscribe before catching up, so nothing falls in between.
+    let mut rx = hub.subscribe();
+    let mut client = ClientState {
+        ordering,
+        subscriptions: ClientSubscriptions::new(),
+        last_seq: None,
+        stats: stats.stats(),
+    };
+    let (write, mut read) = ws_stream.split();
+    let (queue, pending) = mpsc::channel(flow.client_queue.max(1));
+    let mut writer = tokio::spawn(write_queued(write, pending, stats.stats(), peer));
 
-    // Spawn a reader task to handle client pings/close frames.
-    let reader_handle = tokio::spawn(async move {
-        while let Some(msg) = read.next().await {
-            match msg {
-                Ok(Message::Close(_)) => break,
-                Ok(Message::Ping(data)) => {
-                    // Pong is handled automatically by tungstenite in most cases.
-                    let _ = data;
+    let initial: Vec<String> = hub
+        .catch_up(ordering, query.resume_from)
+        .iter()
+        .flat_map(|frame| client.messages_for(frame))
+        .collect();
+    if let Ok(permit) = queue.try_reserve() {
+        send_queued(permit, initial, &stats);
+    }
+    // Frames dropped since the client fell behind.
+    let mut behind: Option<u64> = None;
+    loop {
+        tokio::select! {
+            msg = read.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let replies = client.handle_text(&text, &hub);
+                    // Replies wait for room instead of being dropped.
+                    let Ok(permit) = queue.reserve().await else { break };
+                    send_queued(permit, replies, &stats);
                 }
-                Err(e) => {
+                Some(Ok(Message::Close(_))) | None => break,
+                // Pings are answered by tungstenite on the next write.
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
                     warn!(peer = %peer, "WebSocket read error: {e}");
                     break;
                 }
-                _ => {}
-            }
-        }
-    });
-
-    // Push frames to the client.
-    loop {
-        match rx.recv().await {
-            Ok(frame) => {
-                if let Err(e) = write.send(Message::Text(frame.json)).await {
-                    warn!(peer = %peer, "WebSocket send error: {e}");
+            },
+            frame = rx.recv() => {
+                let dropped = match frame {
+                    Ok(frame) if !client.wants(&frame) => continue,
+                    Ok(frame) => {
+                        if behind.is_none() {
+                            match queue.try_reserve() {
+                                Ok(permit) => {
+                                    send_queued(permit, client.messages_for(&frame), &stats);
+                                    continue;
+                                }
+                                Err(TrySendError::Closed(())) => break,
+                                Err(TrySendError::Full(())) => {}
+                            }
+                        }
+                        1
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => n,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        info!(peer = %peer, "Broadcast channel closed — disconnecting client");
+                        break;
+                    }
+                };
+                stats.dropped(dropped);
+                if flow.policy == SlowConsumerPolicy::Disconnect {
+                    warn!(peer = %peer, dropped, "Client fell behind — disconnecting");
                     break;
                 }
-            }
-            Err(broadcast::error::RecvError::Lagged(n)) => {
-                warn!(peer = %peer, lagged = n, "Client lagged behind — dropping frames");
-            }
-            Err(broadcast::error::RecvError::Closed) => {
-                info!(peer = %peer, "Broadcast channel closed — disconnecting client");
-                break;
-            }
+                if behind.is_none() {
+                    let policy = flow.policy;
+                    warn!(peer = %peer, %policy, "Client fell behind — dropping frames");
+                }
+                *behind.get_or_insert(0) += dropped;
+            },
+            permit = queue.reserve(), if behind.is_some() => {
+                let Ok(permit) = permit else { break };
+                let dropped = behind.take().unwrap_or_default();
+                let policy = flow.policy;
+                info!(peer = %peer, dropped, %policy, "Client caught up — sending gap");
+                stats.gap();
+                send_queued(permit, client.recover(&hub, flow.policy, dropped), &stats);
+            },
+            // The writer only stops on a send error.
+            _ = &mut writer => break,
         }
     }
 
-    reader_handle.abort();
+    writer.abort();
     info!(peer = %peer, "WebSocket client disconnected");
 }
 
-/// Push a new linearized snapshot to all connected WebSocket clients.
+/// Hand `messages` to the writer through a reserved queue slot.
+fn send_queued(permit: mpsc::Permit<'_, Vec<String>>, messages: Vec<String>, stats: &ClientStats) {
+    if !messages.is_empty() {
+        stats.enqueued();
+        permit.send(messages);
+    }
+}
+
+/// Write queued batches to the socket until the queue closes or a send
+/// fails.
+async fn write_queued<S>(
+    mut write: S,
+    mut pending: mpsc::Receiver<Vec<String>>,
+    stats: Arc<ClientStats>,
+    peer: SocketAddr,
+) where
+    S: futures_util::Sink<Message> + Unpin,
+    S::Error: std::fmt::Display,
+{
+    while let Some(messages) = pending.recv().await {
+        let count = messages.len();
+        if let Err(e) = send_all(&mut write, messages).await {
+            warn!(peer = %peer, "WebSocket send error: {e}");
+            return;
+        }
+        stats.written(count);
+    }
+}
+
+/// Send each text message in order.
+async fn send_all<S>(write: &mut S, messages: Vec<String>) -> Result<(), S::Error>
+where
+    S: futures_util::Sink<Message> + Unpin,
+{
+    for text in messages {
+        write.send(Message::Text(text)).await?;
+    }
+    Ok(())
+}
+
+/// Publish the blocks of the default ordering that changed since the
+/// last push.
 pub fn push_snapshot(
-    tx: &broadcast::Sender<StreamFrame>,
+    hub: &StreamHub,
     dag: &DagStore,
     coloring: &ColoringOutput,
 ) -> Result<usize, argus_ghostdag::GhostDagError> {
-    let blocks = linearize_dag(dag, coloring)?;
-    let json = serde_json::to_string(&blocks).map_err(|e| {
-        argus_ghostdag::GhostDagError::Internal(format!("JSON serialization failed: {e}"))
-    })?;
-    let frame = StreamFrame { json };
-    let receivers = tx.send(frame).unwrap_or(0);
-    Ok(receivers)
+    push_snapshot_with(hub, dag, coloring, OrderingRuleKind::default())
+}
+
+/// Linearize with `ordering` and publish the blocks that changed since the
+/// last push to the clients that requested that ordering.
+pub fn push_snapshot_with(
+    hub: &StreamHub,
+    dag: &DagStore,
+    coloring: &ColoringOutput,
+    ordering: OrderingRuleKind,
+) -> Result<usize, argus_ghostdag::GhostDagError> {
+    let blocks = linearize_dag_with(dag, coloring, ordering.rule())?;
+    Ok(hub.publish_blocks(ordering, blocks))
 }
 
 #[cfg(test)]
@@ -226,12 +790,146 @@ mod tests {
     }
 
     #[test]
-    fn test_push_snapshot_no_receivers() {
-        let (dag, coloring) = build_test_dag();
-        let (tx, _) = broadcast::channel::<StreamFrame>(16);
+    fn test_push_snapshot_sends_deltas() {
+        let (mut dag, coloring) = build_test_dag();
+        let hub = StreamHub::new(16);
         // No receivers subscribed — should still succeed.
-        let count = push_snapshot(&tx, &dag, &coloring).unwrap();
+        let count = push_snapshot(&hub, &dag, &coloring).unwrap();
         assert_eq!(count, 0);
+        assert_eq!(hub.seq(OrderingRuleKind::default()), 1);
+
+        // Nothing changed: nothing is sent.
+        let mut rx = hub.subscribe();
+        assert_eq!(push_snapshot(&hub, &dag, &coloring).unwrap(), 0);
+
+        let d = BlockHash::from_byte(4);
+        dag.add_block(BlockHeader::new(d, vec![BlockHash::from_byte(3)], 3)).unwrap();
+        let coloring = color_dag(&mut dag, 3).unwrap();
+        assert_eq!(push_snapshot(&hub, &dag, &coloring).unwrap(), 1);
+        let frame = rx.try_recv().unwrap();
+        assert_eq!(frame.ordering, Some(OrderingRuleKind::default()));
+        assert_eq!(frame.seq(), Some(2));
+        let TopicEvent::Blocks(delta) = frame.event.as_ref() else {
+            panic!("expected a blocks frame");
+        };
+        assert_eq!(delta.kind, BlockDeltaKind::Delta);
+        assert_eq!(delta.blocks.len(), 1);
+        assert_eq!(delta.blocks[0].hash, d.to_hex());
+    }
+
+    #[test]
+    fn test_catch_up_replays_or_snapshots() {
+        let hub = StreamHub::new(16).with_delta_history(2);
+        let ordering = OrderingRuleKind::default();
+        let (dag, coloring) = build_test_dag();
+        let all = linearize_dag(&dag, &coloring).unwrap();
+
+        let kinds = |frames: &[StreamFrame]| -> Vec<(BlockDeltaKind, u64)> {
+            frames
+                .iter()
+                .map(|f| match f.event.as_ref() {
+                    TopicEvent::Blocks(d) => (d.kind, d.seq),
+                    other => panic!("expected a blocks frame, got {other:?}"),
+                })
+                .collect()
+        };
+        assert_eq!(kinds(&hub.catch_up(ordering, None)), vec![(BlockDeltaKind::Snapshot, 0)]);
+
+        // Deltas 1..=4 publish one more block each; only 3 and 4 are kept.
+        for n in 1..=all.len() {
+            hub.publish_blocks(ordering, all[..n].to_vec());
+        }
+        assert_eq!(hub.seq(ordering), 4);
+        let snapshot = hub.catch_up(ordering, None);
+        assert_eq!(kinds(&snapshot), vec![(BlockDeltaKind::Snapshot, 4)]);
+        let TopicEvent::Blocks(delta) = snapshot[0].event.as_ref() else { unreachable!() };
+        assert_eq!(delta.blocks, all);
+
+        let replay = hub.catch_up(ordering, Some(2));
+        assert_eq!(kinds(&replay), vec![(BlockDeltaKind::Delta, 3), (BlockDeltaKind::Delta, 4)]);
+        assert!(hub.catch_up(ordering, Some(4)).is_empty());
+        // Too old, or from the future (e.g. before a restart): snapshot.
+        assert_eq!(kinds(&hub.catch_up(ordering, Some(1))), vec![(BlockDeltaKind::Snapshot, 4)]);
+        assert_eq!(kinds(&hub.catch_up(ordering, Some(9))), vec![(BlockDeltaKind::Snapshot, 4)]);
+    }
+
+    #[test]
+    fn test_recover_sends_gap_then_delta_or_snapshot() {
+        let hub = StreamHub::new(16).with_delta_history(2);
+        let ordering = OrderingRuleKind::default();
+        let (dag, coloring) = build_test_dag();
+        let all = linearize_dag(&dag, &coloring).unwrap();
+        for n in 1..=all.len() {
+            hub.publish_blocks(ordering, all[..n].to_vec());
+        }
+        let peer: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let registration = hub.clients().register(peer, ordering, 1);
+        let client = |last_seq| ClientState {
+            ordering,
+            subscriptions: ClientSubscriptions::new(),
+            last_seq: Some(last_seq),
+            stats: registration.stats(),
+        };
+        let parse = |messages: Vec<String>| -> Vec<serde_json::Value> {
+            messages.iter().map(|m| serde_json::from_str(m).unwrap()).collect()
+        };
+
+        // Deltas 3 and 4 are still buffered: merged into one.
+        let mut coalescing = client(2);
+        let messages = parse(coalescing.recover(&hub, SlowConsumerPolicy::Coalesce, 5));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["type"], "gap");
+        assert_eq!(messages[0]["from_seq"], 3);
+        assert_eq!(messages[0]["to_seq"], 4);
+        assert_eq!(messages[0]["dropped"], 5);
+        assert_eq!(messages[0]["recovery"], "coalesce");
+        assert_eq!(messages[1]["type"], "delta");
+        assert_eq!(messages[1]["seq"], 4);
+        assert_eq!(messages[1]["blocks"].as_array().unwrap().len(), 2);
+        assert_eq!(coalescing.last_seq, Some(4));
+
+        // Delta 2 is gone: coalescing falls back to a snapshot, as resync does.
+        for policy in [SlowConsumerPolicy::Coalesce, SlowConsumerPolicy::Resync] {
+            let messages = parse(client(1).recover(&hub, policy, 3));
+            assert_eq!(messages[0]["from_seq"], 2);
+            assert_eq!(messages[1]["type"], "snapshot");
+            assert_eq!(messages[1]["blocks"].as_array().unwrap().len(), all.len());
+        }
+
+        // Nothing missed on this ordering: only the gap.
+        let messages = parse(client(4).recover(&hub, SlowConsumerPolicy::Resync, 1));
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0]["from_seq"].is_null());
+
+        let metrics = hub.client_metrics();
+        assert_eq!((metrics[0].last_seq, metrics[0].lag), (4, 0));
+    }
+
+    #[test]
+    fn test_parse_stream_query() {
+        assert_eq!(parse_stream_query(None).unwrap(), StreamQuery::default());
+        assert_eq!(parse_stream_query(Some("foo=1")).unwrap(), StreamQuery::default());
+        assert_eq!(
+            parse_stream_query(Some("foo=1&ordering=blue_work&resume_from=12")).unwrap(),
+            StreamQuery {
+                ordering: Some(OrderingRuleKind::BlueWork),
+                resume_from: Some(12),
+            }
+        );
+        assert!(parse_stream_query(Some("ordering=bogus")).is_err());
+        assert!(parse_stream_query(Some("resume_from=-1")).is_err());
+    }
+
+    #[test]
+    fn test_ordering_subscriptions_refcount() {
+        let subs = OrderingSubscriptions::default();
+        let a = subs.subscribe(OrderingRuleKind::Timestamp);
+        let b = subs.subscribe(OrderingRuleKind::Timestamp);
+        assert_eq!(subs.active(), vec![OrderingRuleKind::Timestamp]);
+        drop(a);
+        assert_eq!(subs.active(), vec![OrderingRuleKind::Timestamp]);
+        drop(b);
+        assert!(subs.active().is_empty());
     }
 
     #[test]
@@ -0,0 +1,570 @@
+/// Argus Linearizer — WebSocket subscription protocol.
+///
+/// Clients send JSON requests over the stream socket:
+///
+/// - `{"op": "subscribe", "topic": "blocks", "filter": {…}, "request_id": 1}`
+/// - `{"op": "unsubscribe", "subscription": 3, "request_id": 2}`
+///
+/// Each request is answered with `{"type": "subscribed", …}`,
+/// `{"type": "unsubscribed", …}` or `{"type": "error", …}`, echoing the
+/// optional `request_id`.  Subscribed topics then arrive as
+/// `{"type": "event", "subscription": 3, "topic": "blocks", "data": …}`.
+///
+/// Topics:
+/// - `blocks` — a `BlockDelta` snapshot on subscribe, then deltas, in the
+///   connection's ordering.  With `"resume_from": seq` the subscription
+///   instead replays the deltas after `seq` while the server still buffers
+///   them.
+/// - `chain_changes` — blocks leaving and joining the selected chain.
+/// - `color_flips` — blocks whose color changed on a recolor.
+/// - `agent_events` — every `AgentEvent`.
+/// - `health` — `AgentHealth` after each recolor and agent state change.
+///
+/// Filters (`blue_only`, `min_blue_score`, `hashes`) apply per block on the
+/// first three topics; an event is skipped when no block passes.  Chain
+/// blocks are blue, so `blue_only` does not narrow `chain_changes`.
+///
+/// A connection that has never subscribed keeps the default stream:
+/// `BlockDelta` frames (`{"type": "snapshot" | "delta", "seq": …}`) and
+/// `{"type": "agent_event", …}` frames.
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use argus_agent::channels::AgentEvent;
+use argus_ghostdag::block::BlockHash;
+
+use crate::flow::SlowConsumerPolicy;
+use crate::schema::{
+    AgentHealth, BlockDelta, BlockDeltaKind, ChainBlock, ChainChange, ColorFlip, LinearizedBlock,
+};
+
+/// Most subscriptions one connection may hold at once.
+pub const MAX_SUBSCRIPTIONS_PER_CLIENT: usize = 64;
+
+/// A stream clients can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    Blocks,
+    ChainChanges,
+    ColorFlips,
+    AgentEvents,
+    Health,
+}
+
+impl Topic {
+    /// Whether a `SubscriptionFilter` can narrow this topic.
+    pub fn accepts_filter(self) -> bool {
+        matches!(self, Topic::Blocks | Topic::ChainChanges | Topic::ColorFlips)
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Topic::Blocks => "blocks",
+            Topic::ChainChanges => "chain_changes",
+            Topic::ColorFlips => "color_flips",
+            Topic::AgentEvents => "agent_events",
+            Topic::Health => "health",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Narrows a subscription to matching blocks.  Every set field must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubscriptionFilter {
+    /// Only blue blocks (for `color_flips`: only flips to blue).
+    pub blue_only: bool,
+    /// Only blocks at or above this blue score.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_blue_score: Option<u64>,
+    /// Only these blocks (hex-encoded hashes).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<Vec<String>>,
+}
+
+impl SubscriptionFilter {
+    /// Whether the filter lets everything through.
+    pub fn is_empty(&self) -> bool {
+        !self.blue_only && self.min_blue_score.is_none() && self.hashes.is_none()
+    }
+
+    /// Validate the hashes and normalize them to the stream's hex form.
+    fn compile(&self) -> Result<CompiledFilter, String> {
+        let hashes = match &self.hashes {
+            Some(hashes) => Some(
+                hashes
+                    .iter()
+                    .map(|h| {
+                        BlockHash::from_hex(h)
+                            .map(|h| h.to_hex())
+                            .map_err(|e| format!("invalid hash {h:?}: {e}"))
+                    })
+                    .collect::<Result<HashSet<_>, _>>()?,
+            ),
+            None => None,
+        };
+        Ok(CompiledFilter {
+            blue_only: self.blue_only,
+            min_blue_score: self.min_blue_score.unwrap_or(0),
+            hashes,
+        })
+    }
+}
+
+/// A `SubscriptionFilter` ready for matching.
+#[derive(Debug, Clone)]
+struct CompiledFilter {
+    blue_only: bool,
+    min_blue_score: u64,
+    hashes: Option<HashSet<String>>,
+}
+
+impl CompiledFilter {
+    fn matches(&self, hash: &str, blue_score: u64, is_blue: bool) -> bool {
+        (is_blue || !self.blue_only)
+            && blue_score >= self.min_blue_score
+            && self.hashes.as_ref().is_none_or(|h| h.contains(hash))
+    }
+}
+
+/// A request sent by a client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ClientRequest {
+    /// Start receiving `topic`, narrowed by `filter`.  For `blocks`,
+    /// `resume_from` asks for the deltas after that sequence number instead
+    /// of a snapshot.
+    Subscribe {
+        topic: Topic,
+        #[serde(default)]
+        filter: SubscriptionFilter,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        resume_from: Option<u64>,
+    },
+    /// Stop a subscription.
+    Unsubscribe { subscription: u64 },
+}
+
+/// A client request with an optional ID echoed in the reply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<u64>,
+    #[serde(flatten)]
+    pub request: ClientRequest,
+}
+
+/// A message sent to a subscribing client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// A subscription was created.
+    Subscribed {
+        request_id: Option<u64>,
+        subscription: u64,
+        topic: Topic,
+    },
+    /// A subscription was removed.
+    Unsubscribed {
+        request_id: Option<u64>,
+        subscription: u64,
+    },
+    /// A request was rejected.
+    Error {
+        request_id: Option<u64>,
+        message: String,
+    },
+    /// Data for one subscription.
+    Event {
+        subscription: u64,
+        topic: Topic,
+        data: Value,
+    },
+    /// The connection fell behind and frames were dropped; the recovery
+    /// (a merged delta or a snapshot) follows.  `from_seq..=to_seq` is the
+    /// blocks range missed, absent if no blocks were.
+    Gap {
+        from_seq: Option<u64>,
+        to_seq: Option<u64>,
+        dropped: u64,
+        recovery: SlowConsumerPolicy,
+    },
+}
+
+/// An event published on the stream, before per-subscription filtering.
+#[derive(Debug, Clone)]
+pub enum TopicEvent {
+    /// A snapshot of, or changes to, one ordering's linearized blocks.
+    Blocks(BlockDelta),
+    /// The selected chain moved.
+    ChainChanged(ChainChange),
+    /// Blocks changed color.
+    ColorFlips(Vec<ColorFlip>),
+    /// An event from the agent.
+    Agent(AgentEvent),
+    /// Current node health.
+    Health(AgentHealth),
+}
+
+impl TopicEvent {
+    /// The topic this event belongs to.
+    pub fn topic(&self) -> Topic {
+        match self {
+            TopicEvent::Blocks(_) => Topic::Blocks,
+            TopicEvent::ChainChanged(_) => Topic::ChainChanges,
+            TopicEvent::ColorFlips(_) => Topic::ColorFlips,
+            TopicEvent::Agent(_) => Topic::AgentEvents,
+            TopicEvent::Health(_) => Topic::Health,
+        }
+    }
+
+    /// The stream sequence number of a `Blocks` event.
+    pub fn seq(&self) -> Option<u64> {
+        match self {
+            TopicEvent::Blocks(delta) => Some(delta.seq),
+            _ => None,
+        }
+    }
+
+    /// The part of the event that passes `filter`, or `None` if no block
+    /// does.  Snapshots are always kept so the client learns their `seq`.
+    fn filtered(&self, filter: &CompiledFilter) -> Option<Value> {
+        let value = match self {
+            TopicEvent::Blocks(delta) => {
+                let blocks: Vec<LinearizedBlock> = delta
+                    .blocks
+                    .iter()
+                    .filter(|b| filter.matches(&b.hash, b.blue_score, b.is_blue))
+                    .cloned()
+                    .collect();
+                if blocks.is_empty() && delta.kind == BlockDeltaKind::Delta {
+                    return None;
+                }
+                serde_json::to_value(BlockDelta {
+                    blocks,
+                    ..*delta
+                })
+            }
+            TopicEvent::ChainChanged(change) => {
+                let keep = |blocks: &[ChainBlock]| -> Vec<ChainBlock> {
+                    blocks
+                        .iter()
+                        .filter(|b| filter.matches(&b.hash, b.blue_score, true))
+                        .cloned()
+                        .collect()
+                };
+                let change = ChainChange {
+                    fork_point: change.fork_point.clone(),
+                    removed: keep(&change.removed),
+                    added: keep(&change.added),
+                };
+                if change.removed.is_empty() && change.added.is_empty() {
+                    return None;
+                }
+                serde_json::to_value(change)
+            }
+            TopicEvent::ColorFlips(flips) => {
+                let kept: Vec<&ColorFlip> = flips
+                    .iter()
+                    .filter(|f| filter.matches(&f.hash, f.blue_score, f.is_blue))
+                    .collect();
+                if kept.is_empty() {
+                    return None;
+                }
+                serde_json::to_value(kept)
+            }
+            TopicEvent::Agent(event) => serde_json::to_value(event),
+            TopicEvent::Health(health) => serde_json::to_value(health),
+        };
+        value.ok()
+    }
+}
+
+/// One active subscription.
+#[derive(Debug)]
+struct Subscription {
+    topic: Topic,
+    filter: CompiledFilter,
+    resume_from: Option<u64>,
+    /// Highest `seq` delivered, so catch-up and live frames never repeat.
+    last_seq: Option<u64>,
+}
+
+/// The subscriptions held by one WebSocket connection.
+#[derive(Debug, Default)]
+pub struct ClientSubscriptions {
+    next_id: u64,
+    active: BTreeMap<u64, Subscription>,
+    subscribed: bool,
+}
+
+impl ClientSubscriptions {
+    /// A connection with no subscriptions, on the legacy stream.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the connection still gets the legacy stream, i.e. has never
+    /// subscribed.
+    pub fn is_legacy(&self) -> bool {
+        !self.subscribed
+    }
+
+    /// Number of active subscriptions.
+    pub fn len(&self) -> usize {
+        self.active.len()
+    }
+
+    /// Whether there are no active subscriptions.
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Parse and apply a text message from the client; returns the reply.
+    pub fn handle_text(&mut self, text: &str) -> ServerMessage {
+        match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => self.apply(message),
+            Err(e) => ServerMessage::Error {
+                request_id: serde_json::from_str::<Value>(text)
+                    .ok()
+                    .and_then(|v| v.get("request_id")?.as_u64()),
+                message: format!("invalid request: {e}"),
+            },
+        }
+    }
+
+    /// Apply a client request; returns the reply.
+    pub fn apply(&mut self, message: ClientMessage) -> ServerMessage {
+        let request_id = message.request_id;
+        let error = |message: String| ServerMessage::Error {
+            request_id,
+            message,
+        };
+        match message.request {
+            ClientRequest::Subscribe {
+                topic,
+                filter,
+                resume_from,
+            } => {
+                if !topic.accepts_filter() && !filter.is_empty() {
+                    return error(format!("topic {topic} does not take a filter"));
+                }
+                if topic != Topic::Blocks && resume_from.is_some() {
+                    return error(format!("topic {topic} cannot resume"));
+                }
+                if self.active.len() >= MAX_SUBSCRIPTIONS_PER_CLIENT {
+                    return error(format!(
+                        "at most {MAX_SUBSCRIPTIONS_PER_CLIENT} subscriptions per connection"
+                    ));
+                }
+                let filter = match filter.compile() {
+                    Ok(filter) => filter,
+                    Err(e) => return error(e),
+                };
+                self.next_id += 1;
+                let subscription = Subscription {
+                    topic,
+                    filter,
+                    resume_from,
+                    last_seq: None,
+                };
+                self.active.insert(self.next_id, subscription);
+                self.subscribed = true;
+                ServerMessage::Subscribed {
+                    request_id,
+                    subscription: self.next_id,
+                    topic,
+                }
+            }
+            ClientRequest::Unsubscribe { subscription } => {
+                if self.active.remove(&subscription).is_none() {
+                    return error(format!("unknown subscription {subscription}"));
+                }
+                ServerMessage::Unsubscribed {
+                    request_id,
+                    subscription,
+                }
+            }
+        }
+    }
+
+    /// The `resume_from` a subscription was created with.
+    pub fn resume_from(&self, subscription: u64) -> Option<u64> {
+        self.active.get(&subscription)?.resume_from
+    }
+
+    /// One `Event` message per subscription that `event` matches, in
+    /// subscription order.
+    pub fn deliver(&mut self, event: &TopicEvent) -> Vec<ServerMessage> {
+        let ids: Vec<u64> = self.active.keys().copied().collect();
+        ids.into_iter()
+            .filter_map(|id| self.deliver_to(id, event))
+            .collect()
+    }
+
+    /// The `Event` message for `event` on one subscription, if it matches.
+    /// `Blocks` events at or below the last delivered `seq` are dropped.
+    pub fn deliver_to(&mut self, subscription: u64, event: &TopicEvent) -> Option<ServerMessage> {
+        let sub = self.active.get_mut(&subscription)?;
+        let topic = event.topic();
+        if sub.topic != topic {
+            return None;
+        }
+        if let Some(seq) = event.seq() {
+            if sub.last_seq.is_some_and(|last| seq <= last) {
+                return None;
+            }
+            sub.last_seq = Some(seq);
+        }
+        event.filtered(&sub.filter).map(|data| ServerMessage::Event {
+            subscription,
+            topic,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(byte: u8, blue_score: u64, is_blue: bool) -> LinearizedBlock {
+        LinearizedBlock {
+            hash: BlockHash::from_byte(byte).to_hex(),
+            blue_score,
+            blue_work: blue_score.to_string(),
+            topological_index: blue_score,
+            adjacency_list: Vec::new(),
+            is_blue,
+            selected_parent: None,
+        }
+    }
+
+    fn delta(kind: BlockDeltaKind, seq: u64, blocks: Vec<LinearizedBlock>) -> TopicEvent {
+        TopicEvent::Blocks(BlockDelta {
+            kind,
+            seq,
+            ordering: Default::default(),
+            blocks,
+        })
+    }
+
+    fn subscribe(subs: &mut ClientSubscriptions, text: &str) -> u64 {
+        match subs.handle_text(text) {
+            ServerMessage::Subscribed { subscription, .. } => subscription,
+            other => panic!("expected an ack, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_subscribe_ack_and_unsubscribe() {
+        let mut subs = ClientSubscriptions::new();
+        assert!(subs.is_legacy());
+
+        let reply = subs.handle_text(r#"{"op": "subscribe", "topic": "health", "request_id": 7}"#);
+        assert_eq!(
+            reply,
+            ServerMessage::Subscribed {
+                request_id: Some(7),
+                subscription: 1,
+                topic: Topic::Health,
+            }
+        );
+        assert_eq!(subscribe(&mut subs, r#"{"op": "subscribe", "topic": "blocks"}"#), 2);
+        assert!(!subs.is_legacy());
+
+        let reply = subs.handle_text(r#"{"op": "unsubscribe", "subscription": 1}"#);
+        assert!(matches!(reply, ServerMessage::Unsubscribed { subscription: 1, .. }));
+        assert_eq!(subs.len(), 1);
+
+        // Unknown IDs, unknown topics, and filters on unfiltered topics fail.
+        for bad in [
+            r#"{"op": "unsubscribe", "subscription": 1, "request_id": 3}"#,
+            r#"{"op": "subscribe", "topic": "mempool", "request_id": 3}"#,
+            r#"{"op": "subscribe", "topic": "health", "filter": {"blue_only": true},
+                "request_id": 3}"#,
+            r#"{"op": "subscribe", "topic": "blocks", "filter": {"hashes": ["zz"]},
+                "request_id": 3}"#,
+            r#"{"op": "subscribe", "topic": "health", "resume_from": 4, "request_id": 3}"#,
+        ] {
+            match subs.handle_text(bad) {
+                ServerMessage::Error { request_id, .. } => assert_eq!(request_id, Some(3)),
+                other => panic!("expected an error for {bad}, got {other:?}"),
+            }
+        }
+        // Dropping every subscription does not bring back the legacy stream.
+        subs.handle_text(r#"{"op": "unsubscribe", "subscription": 2}"#);
+        assert!(subs.is_empty() && !subs.is_legacy());
+    }
+
+    #[test]
+    fn test_filters_narrow_blocks_and_flips() {
+        let mut subs = ClientSubscriptions::new();
+        let all = subscribe(&mut subs, r#"{"op": "subscribe", "topic": "blocks"}"#);
+        let blue = subscribe(
+            &mut subs,
+            r#"{"op": "subscribe", "topic": "blocks",
+                "filter": {"blue_only": true, "min_blue_score": 2}}"#,
+        );
+        let wanted = BlockHash::from_byte(9).to_hex();
+        let one = subscribe(
+            &mut subs,
+            &format!(
+                r#"{{"op": "subscribe", "topic": "color_flips",
+                     "filter": {{"hashes": ["{}"]}}}}"#,
+                wanted.to_uppercase()
+            ),
+        );
+
+        let blocks = vec![block(1, 1, true), block(2, 2, false), block(3, 3, true)];
+        let first = delta(BlockDeltaKind::Delta, 5, blocks);
+        let messages = subs.deliver(&first);
+        assert_eq!(messages.len(), 2);
+        let subscription = |m: &ServerMessage| match m {
+            ServerMessage::Event { subscription, .. } => *subscription,
+            other => panic!("expected an event, got {other:?}"),
+        };
+        let hashes = |m: &ServerMessage| match m {
+            ServerMessage::Event { data, .. } => data
+                .get("blocks")
+                .unwrap_or(data)
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|b| b["hash"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>(),
+            other => panic!("expected an event, got {other:?}"),
+        };
+        assert_eq!(subscription(&messages[0]), all);
+        assert_eq!(hashes(&messages[0]).len(), 3);
+        assert_eq!(subscription(&messages[1]), blue);
+        assert_eq!(hashes(&messages[1]), vec![BlockHash::from_byte(3).to_hex()]);
+
+        // A repeated seq is dropped; an empty filtered delta is skipped, but
+        // an empty filtered snapshot is still sent.
+        assert!(subs.deliver(&first).is_empty());
+        let red = vec![block(4, 4, false)];
+        assert_eq!(subs.deliver(&delta(BlockDeltaKind::Delta, 6, red.clone())).len(), 1);
+        let messages = subs.deliver(&delta(BlockDeltaKind::Snapshot, 7, red));
+        assert_eq!(messages.len(), 2);
+        assert!(hashes(&messages[1]).is_empty());
+
+        let flip = |byte: u8| ColorFlip {
+            hash: BlockHash::from_byte(byte).to_hex(),
+            blue_score: 4,
+            is_blue: false,
+        };
+        assert!(subs.deliver(&TopicEvent::ColorFlips(vec![flip(8)])).is_empty());
+        let messages = subs.deliver(&TopicEvent::ColorFlips(vec![flip(8), flip(9)]));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(subscription(&messages[0]), one);
+        assert_eq!(hashes(&messages[0]), vec![wanted]);
+    }
+}
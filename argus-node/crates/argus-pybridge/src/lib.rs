@@ -1,10 +1,11 @@
+// pyo3 0.20 macros expand to impls that newer rustc flags as non-local.
+#![allow(non_local_definitions)]
+
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
-use std::collections::HashSet;
 use std::sync::{Arc, RwLock};
 
 use argus_ghostdag::{BlockHash, BlockHeader, DagStore, color_dag, total_order_hashes};
-use argus_linearizer::schema::LinearizedBlock;
 
 /// A thread-safe wrapper around the GhostDAG store for Python.
 #[pyclass]
@@ -83,6 +84,12 @@ impl PyDagStore {
     }
 }
 
+impl Default for PyDagStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// The Argus PyBridge module.
 #[pymodule]
 fn argus_pybridge(_py: Python, m: &PyModule) -> PyResult<()> {